@@ -0,0 +1,42 @@
+use borderless::__private::storage_keys::{
+    BASE_KEY_METADATA, META_SUB_KEY_PARTICIPANTS, META_SUB_KEY_SINKS,
+};
+use borderless::__private::write_field;
+use borderless::common::Participant;
+use borderless::events::Sink;
+use borderless::{BorderlessId, ContractId};
+use flipper_contract::flipper::client;
+
+#[test]
+fn build_call_with_flipper_client() -> borderless::Result<()> {
+    let target = ContractId::generate();
+    let writer = BorderlessId::generate();
+    write_field(
+        BASE_KEY_METADATA,
+        META_SUB_KEY_SINKS,
+        &vec![Sink::new(
+            target,
+            "flipper".to_string(),
+            "writer".to_string(),
+        )],
+    );
+    write_field(
+        BASE_KEY_METADATA,
+        META_SUB_KEY_PARTICIPANTS,
+        &vec![Participant {
+            id: writer,
+            alias: "writer".to_string(),
+            roles: Vec::new(),
+        }],
+    );
+
+    let call = client::set_switch(target, true)?;
+    assert_eq!(call.contract_id, target);
+    assert_eq!(call.writer, writer);
+    assert_eq!(call.action.method_name(), Some("set_switch"));
+    assert_eq!(
+        call.action.params,
+        borderless::serialize::json!({ "switch": true })
+    );
+    Ok(())
+}
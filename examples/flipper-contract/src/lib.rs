@@ -1,4 +1,4 @@
-#[borderless::contract]
+#[borderless::contract(client_feature = "client")]
 pub mod flipper {
     use borderless::collections::lazyvec::LazyVec;
     use borderless::contracts::env;
@@ -20,9 +20,11 @@ pub mod flipper {
     }
 
     impl Flipper {
+        /// Flips the switch and returns its new value
         #[action]
-        fn flip_switch(&mut self) {
+        fn flip_switch(&mut self) -> Output<bool> {
             self.set_switch(!self.switch);
+            Output::new(self.switch)
         }
 
         #[action(web_api = true, roles = "Flipper")]
@@ -35,6 +37,18 @@ pub mod flipper {
             self.switch = switch;
         }
 
+        /// Returns, how often the switch has been set
+        #[action]
+        fn counter(&self) -> u32 {
+            self.counter
+        }
+
+        /// Returns the number of recorded switch changes
+        #[action]
+        fn history_len(&self) -> Result<usize> {
+            Ok(self.history.len())
+        }
+
         #[action]
         fn issue_msg(&self, topic: String) -> Message {
             message(topic).with_value(json!({}))
@@ -42,11 +56,7 @@ pub mod flipper {
 
         #[action(web_api = true, roles = "Flipper")]
         pub fn set_other(&self, switch: bool) -> Result<ContractCall> {
-            let call = env::sink("flipper")?
-                .call_method("set_switch")
-                .with_value(value!({ "switch": switch }))
-                .build()?;
-            Ok(call)
+            client::set_switch(env::sink("flipper")?, switch)
         }
 
         #[action(web_api = true, roles = "Flipper")]
@@ -56,11 +66,36 @@ pub mod flipper {
             target: borderless::ContractId,
         ) -> Result<ContractCall> {
             // Bypass the sink usage by directly specifying the target contract
-            let call = target
-                .call_method("set_switch")
-                .with_value(value!({ "switch": switch }))
-                .build()?;
-            Ok(call)
+            client::set_switch(target, switch)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::flipper::__derived;
+    use borderless::__private::registers::{REGISTER_INPUT, REGISTER_OUTPUT_VALUE};
+    use borderless::__private::{read_register, write_register};
+    use borderless::events::CallAction;
+    use borderless::serialize::json;
+
+    /// Executes an action and returns its output value
+    fn exec(method: &str) -> Option<Vec<u8>> {
+        let action = CallAction::by_method(method, json!({}));
+        write_register(REGISTER_INPUT, action.to_bytes().unwrap());
+        __derived::exec_txn().unwrap();
+        read_register(REGISTER_OUTPUT_VALUE)
+    }
+
+    #[test]
+    fn actions_return_their_values() {
+        let state = json!({ "switch": false, "counter": 0, "history": [] });
+        write_register(REGISTER_INPUT, state.to_string());
+        __derived::exec_introduction().unwrap();
+
+        // Plain values and outputs are both returned to the caller
+        assert_eq!(exec("flip_switch").as_deref(), Some(b"true".as_slice()));
+        assert_eq!(exec("counter").as_deref(), Some(b"1".as_slice()));
+        assert_eq!(exec("history_len").as_deref(), Some(b"1".as_slice()));
+    }
+}
@@ -8,14 +8,17 @@ use std::{
 
 use anyhow::{Context, Result};
 use borderless::{
-    common::{Introduction, IntroductionDto, Revocation},
+    common::{Id, Introduction, IntroductionDto, Revocation},
     contracts::TxCtx,
     events::CallAction,
     hash::Hash256,
     pkg::{SourceType, WasmPkg},
     AgentId, BlockIdentifier, BorderlessId, ContractId, TxIdentifier,
 };
-use borderless_kv_store::{backend::lmdb::Lmdb, Db};
+use borderless_kv_store::{
+    backend::{hooks::HookedDb, lmdb::Lmdb, memdb::MemDb},
+    CursorOp, Db, KvHandle, ToCursorOp,
+};
 use borderless_runtime::{
     agent::{
         tasks::{handle_schedules, handle_ws_connection},
@@ -24,12 +27,19 @@ use borderless_runtime::{
     contract::{MutLock as ContractLock, Runtime as ContractRuntime},
     db::{
         action_log::ActionLog,
+        archive::Archive,
+        cdc::ChangeFeed,
         controller::Controller,
+        fx::FxRates,
+        history::StateHistory,
+        ledger::{generate_node_key, NodeKey},
         logger::{print_log_line, Logger},
+        schema::Migrator,
     },
+    replay::verify_replay,
     CodeStore,
 };
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use reqwest::blocking::Client;
 
 use log::{info, warn};
@@ -41,22 +51,68 @@ mod server;
 #[command(version, about, long_about = None)]
 struct Cli {
     /// Path to the database directory (global)
-    #[arg(short, long)]
-    db: PathBuf,
+    #[arg(short, long, required_unless_present = "ephemeral")]
+    db: Option<PathBuf>,
+
+    /// Keep all state in memory instead of using a database directory (nothing is persisted)
+    #[arg(long, conflicts_with = "db")]
+    ephemeral: bool,
 
     /// Borderless-ID of the writer (and executor)
     #[arg(long)]
     writer: Option<BorderlessId>,
 
+    #[command(flatten)]
+    api: ApiArgs,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Options of the api server
+#[derive(Args, Debug)]
+struct ApiArgs {
+    /// Path to the node key, that signs off verified ledger checkpoints (generated, if the file does not exist)
+    #[arg(long)]
+    node_key: Option<PathBuf>,
+
+    /// Token for the admin routes under '/v0/admin' (the routes are disabled without a token)
+    #[arg(long)]
+    admin_token: Option<String>,
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Contract related commands
     Contract(ContractCommand),
     Agent(AgentCommand),
+    /// Imports exchange rates for base-currency reporting from a csv file (date,from,to,rate)
+    ImportFxRates {
+        /// Input file containing the exchange rates
+        #[arg(short, long)]
+        file: PathBuf,
+    },
+    /// Copies the database into a new directory, while it is in use
+    Backup {
+        /// Output directory for the copy (must not contain a database yet)
+        output: PathBuf,
+
+        /// Omit free pages while copying, which makes the copy smaller but takes longer
+        #[arg(long)]
+        compact: bool,
+    },
+    /// Prints the size of the database and the number of entries per sub-database
+    Stats,
+    /// Prints the schema version of all sub-databases
+    Schema {
+        /// Applies all pending migrations
+        #[arg(long)]
+        upgrade: bool,
+
+        /// Only prints the migrations, that would be applied by '--upgrade'
+        #[arg(long, requires = "upgrade")]
+        dry_run: bool,
+    },
 }
 
 #[derive(Parser, Debug)]
@@ -85,6 +141,14 @@ enum ContractAction {
     Introduce {
         /// Input file containing introduction data
         introduction: PathBuf,
+
+        /// Number of log lines, that are kept in the log buffer (overrides the size from the package metadata)
+        #[arg(long)]
+        log_buffer_size: Option<u64>,
+
+        /// Number of actions, for which the state history is kept (keeps the full history if not set)
+        #[arg(long)]
+        history_retention: Option<u64>,
     },
     /// Execute the given action on the contract
     Process {
@@ -102,6 +166,21 @@ enum ContractAction {
     /// Prints out all logs for this contract
     Logs,
 
+    /// Exports the contract into a portable archive
+    Export {
+        /// Output file for the archive
+        output: PathBuf,
+    },
+
+    /// Imports a contract from an archive (the contract-id is taken from the archive)
+    Import {
+        /// Input file containing the archive
+        archive: PathBuf,
+    },
+
+    /// Replays the contract history in a scratch database and compares it with the current state
+    Verify,
+
     // TODO: Make this also a top-level command maybe ?
     /// Start a webserver which exposes the contract-api
     Api,
@@ -113,6 +192,10 @@ enum AgentAction {
     Introduce {
         /// Input file containing introduction data
         introduction: PathBuf,
+
+        /// Number of log lines, that are kept in the log buffer (overrides the size from the package metadata)
+        #[arg(long)]
+        log_buffer_size: Option<u64>,
     },
     /// Execute the given action on the agent
     Process {
@@ -131,6 +214,18 @@ enum AgentAction {
     /// Prints out all logs for this agent
     Logs,
 
+    /// Exports the agent into a portable archive
+    Export {
+        /// Output file for the archive
+        output: PathBuf,
+    },
+
+    /// Imports an agent from an archive (the agent-id is taken from the archive)
+    Import {
+        /// Input file containing the archive
+        archive: PathBuf,
+    },
+
     // TODO: Make this also a top-level command maybe ?
     /// Only provides API access but does not spin up the agent
     Api,
@@ -143,11 +238,40 @@ async fn main() -> Result<()> {
 
     let args = Cli::parse();
     // Setup the DB connection, etc.
-    let db = Lmdb::new(&args.db, 16).context("failed to open database")?;
+    if args.ephemeral {
+        info!("Using an ephemeral in-memory database");
+        run(args.command, MemDb::new(), args.writer, args.api).await
+    } else {
+        let path = args.db.context("missing database directory")?;
+        let db = Lmdb::new(&path, 16).context("failed to open database")?;
+        match args.command {
+            Commands::Backup { output, compact } => backup(&db, output, compact),
+            Commands::Stats => print_stats(&db),
+            Commands::Schema { upgrade, dry_run } => schema(&db, upgrade, dry_run),
+            command => run(command, db, args.writer, args.api).await,
+        }
+    }
+}
 
-    match args.command {
-        Commands::Contract(cmd) => contract(cmd, db, args.writer).await?,
-        Commands::Agent(cmd) => sw_agent(cmd, db, args.writer).await?,
+async fn run<S>(command: Commands, db: S, writer: Option<BorderlessId>, api: ApiArgs) -> Result<()>
+where
+    S: Db<Handle = <S as Db>::DB> + 'static,
+    S::DB: KvHandle<S::DB>,
+    CursorOp: ToCursorOp<S::DB>,
+{
+    match command {
+        Commands::Contract(cmd) => {
+            // Publish the state changes of all contracts, so they can be streamed via the api
+            let changes = ChangeFeed::new();
+            let db = HookedDb::new(db);
+            db.add_hook(changes.clone());
+            contract(cmd, db, writer, changes, api).await?
+        }
+        Commands::Agent(cmd) => sw_agent(cmd, db, writer).await?,
+        Commands::ImportFxRates { file } => import_fx_rates(&db, file)?,
+        Commands::Backup { .. } | Commands::Stats | Commands::Schema { .. } => {
+            anyhow::bail!("this command requires a database directory")
+        }
     }
     Ok(())
 }
@@ -177,7 +301,159 @@ pub fn generate_tx_ctx(
     Ok(tx_ctx)
 }
 
-async fn contract(command: ContractCommand, db: Lmdb, writer: Option<BorderlessId>) -> Result<()> {
+/// Exports a contract or agent into an archive file
+fn export_archive(db: &impl Db, id: Id, output: PathBuf) -> Result<()> {
+    let archive = Archive::export(db, id)?;
+    std::fs::write(&output, archive.to_bytes()?)?;
+    for section in &archive.manifest.sections {
+        info!(
+            "{}: {} entries, checksum={}",
+            section.name, section.entries, section.checksum
+        );
+    }
+    info!("Exported {id} to {}", output.display());
+    Ok(())
+}
+
+/// Imports a contract or agent from an archive file
+fn import_archive(db: &impl Db, archive: PathBuf) -> Result<()> {
+    let bytes = std::fs::read(archive)?;
+    let archive = Archive::from_bytes(&bytes)?;
+    archive.import(db)?;
+    info!("Imported {}", archive.manifest.id);
+    Ok(())
+}
+
+/// Imports exchange rates from a csv file
+fn import_fx_rates(db: &impl Db, file: PathBuf) -> Result<()> {
+    let reader = std::io::BufReader::new(std::fs::File::open(&file)?);
+    let n_rates = FxRates::new(db).import_csv(reader)?;
+    info!("Imported {n_rates} exchange rates from {}", file.display());
+    Ok(())
+}
+
+/// Copies the database into the output directory
+fn backup(db: &Lmdb, output: PathBuf, compact: bool) -> Result<()> {
+    let start = Instant::now();
+    db.backup(&output, compact)?;
+    info!(
+        "Copied database to {}. Time elapsed: {:?}",
+        output.display(),
+        start.elapsed()
+    );
+    Ok(())
+}
+
+/// Prints the size of the database and all of its sub-databases
+fn print_stats(db: &Lmdb) -> Result<()> {
+    const MIB: f64 = 1024.0 * 1024.0;
+    let stats = db.stats()?;
+    info!(
+        "map-size: {:.1} MiB, file: {:.1} MiB, used: {:.1} MiB ({} pages of {} bytes, {} free)",
+        stats.map_size as f64 / MIB,
+        stats.file_bytes() as f64 / MIB,
+        stats.used_bytes() as f64 / MIB,
+        stats.pages,
+        stats.page_size,
+        stats.free_pages
+    );
+    info!(
+        "readers: {} of {} slots used",
+        stats.num_readers, stats.max_readers
+    );
+    for sub_db in &stats.sub_dbs {
+        info!(
+            "{}: {} entries, {:.1} MiB, depth={}",
+            sub_db.name,
+            sub_db.entries,
+            (sub_db.pages() * stats.page_size as usize) as f64 / MIB,
+            sub_db.depth
+        );
+    }
+    Ok(())
+}
+
+/// Prints the schema versions, or upgrades the database to the current schema
+fn schema(db: &impl Db, upgrade: bool, dry_run: bool) -> Result<()> {
+    let migrator = Migrator::new(db);
+    if !upgrade {
+        for schema in migrator.status()? {
+            let stored = schema
+                .stored
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            info!(
+                "{}: version {} (stored: {stored}, current: {})",
+                schema.sub_db,
+                schema.version(),
+                schema.current
+            );
+        }
+        return Ok(());
+    }
+    if dry_run {
+        let pending = migrator.pending()?;
+        for m in &pending {
+            info!(
+                "{}: would migrate to version {} - {}",
+                m.sub_db, m.version, m.description
+            );
+        }
+        info!("{} pending migrations", pending.len());
+        return Ok(());
+    }
+    let start = Instant::now();
+    let applied = migrator.upgrade()?;
+    info!(
+        "Applied {} migrations. Time elapsed: {:?}",
+        applied.len(),
+        start.elapsed()
+    );
+    Ok(())
+}
+
+/// Replays the history of a contract in an in-memory database and reports the first divergence
+/// Loads the node key from the given file, or generates a new one, if the file does not exist
+fn load_node_key(path: Option<PathBuf>) -> Result<NodeKey> {
+    let Some(path) = path else {
+        warn!("No node key given - signing off checkpoints with an ephemeral key");
+        return Ok(generate_node_key());
+    };
+    if path.exists() {
+        let bytes = std::fs::read(&path).context("failed to read node key")?;
+        let secret: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("node key must be exactly 32 bytes"))?;
+        return Ok(NodeKey::from_bytes(&secret));
+    }
+    let key = generate_node_key();
+    std::fs::write(&path, key.to_bytes()).context("failed to write node key")?;
+    info!("Generated new node key in {}", path.display());
+    Ok(key)
+}
+
+fn verify_contract(db: &impl Db, cid: ContractId) -> Result<()> {
+    let start = Instant::now();
+    let report = verify_replay(db, &MemDb::new(), cid)?;
+    let elapsed = start.elapsed();
+    info!(
+        "Replayed {} actions, compared {} states. Time elapsed: {elapsed:?}",
+        report.replayed, report.compared
+    );
+    match report.divergence {
+        Some(divergence) => anyhow::bail!("replay of contract {cid} diverged: {divergence}"),
+        None => info!("Replay of contract {cid} matches the current state"),
+    }
+    Ok(())
+}
+
+async fn contract<S: Db + 'static>(
+    command: ContractCommand,
+    db: S,
+    writer: Option<BorderlessId>,
+    changes: ChangeFeed,
+    api: ApiArgs,
+) -> Result<()> {
     // Create runtime
     let code_store = CodeStore::new(&db)?;
 
@@ -202,7 +478,11 @@ async fn contract(command: ContractCommand, db: Lmdb, writer: Option<BorderlessI
 
     // Parse command
     match command.action {
-        ContractAction::Introduce { introduction } => {
+        ContractAction::Introduce {
+            introduction,
+            log_buffer_size,
+            history_retention,
+        } => {
             // Parse introduction
             let data = read_to_string(introduction)?;
             let introduction: Introduction = IntroductionDto::from_str(&data)?.try_into()?;
@@ -249,6 +529,13 @@ async fn contract(command: ContractCommand, db: Lmdb, writer: Option<BorderlessI
                 }
             }
 
+            if let Some(size) = log_buffer_size {
+                Logger::new(&db, cid).set_capacity(size)?;
+            }
+            if let Some(retention) = history_retention {
+                StateHistory::new(&db, cid).set_retention(Some(retention))?;
+            }
+
             let tx_ctx = generate_tx_ctx(&mut rt, &cid)?;
             info!("Introduce contract {cid}");
             let start = Instant::now();
@@ -301,15 +588,31 @@ async fn contract(command: ContractCommand, db: Lmdb, writer: Option<BorderlessI
             let log = Logger::new(&db, cid).get_full_log()?;
             log.into_iter().for_each(print_log_line);
         }
+        ContractAction::Export { output } => export_archive(&db, Id::contract(cid), output)?,
+        ContractAction::Import { archive } => import_archive(&db, archive)?,
+        ContractAction::Verify => verify_contract(&db, cid)?,
         ContractAction::Api => {
-            start_contract_server(db, rt.into_shared(), writer).await?;
+            let node_key = load_node_key(api.node_key)?;
+            start_contract_server(
+                db,
+                rt.into_shared(),
+                writer,
+                changes,
+                node_key,
+                api.admin_token,
+            )
+            .await?;
         }
     }
     Ok(())
 }
 
 #[allow(unused)]
-async fn sw_agent(command: AgentCommand, db: Lmdb, writer: Option<BorderlessId>) -> Result<()> {
+async fn sw_agent<S: Db + 'static>(
+    command: AgentCommand,
+    db: S,
+    writer: Option<BorderlessId>,
+) -> Result<()> {
     // Create runtime
     let code_store = CodeStore::new(&db)?;
     let lock = AgentLock::default();
@@ -333,7 +636,10 @@ async fn sw_agent(command: AgentCommand, db: Lmdb, writer: Option<BorderlessId>)
 
     // Parse command
     match command.action {
-        AgentAction::Introduce { introduction } => {
+        AgentAction::Introduce {
+            introduction,
+            log_buffer_size,
+        } => {
             // Parse introduction
             let data = read_to_string(introduction)?;
             let introduction = Introduction::from_str(&data)?;
@@ -367,6 +673,10 @@ async fn sw_agent(command: AgentCommand, db: Lmdb, writer: Option<BorderlessId>)
                 }
             }
 
+            if let Some(size) = log_buffer_size {
+                Logger::new(&db, aid).set_capacity(size)?;
+            }
+
             info!("Introduce agent {aid}");
             let start = Instant::now();
             rt.process_introduction(introduction).await?;
@@ -410,6 +720,8 @@ async fn sw_agent(command: AgentCommand, db: Lmdb, writer: Option<BorderlessId>)
             let log = Logger::new(&db, aid).get_full_log()?;
             log.into_iter().for_each(print_log_line);
         }
+        AgentAction::Export { output } => export_archive(&db, Id::agent(aid), output)?,
+        AgentAction::Import { archive } => import_archive(&db, archive)?,
         AgentAction::Api => {
             start_agent_server(db, rt.into_shared(), writer).await?;
         }
@@ -3,24 +3,39 @@ use std::{convert::Infallible, future::Future};
 use anyhow::Result;
 use axum::{
     body::{to_bytes, Body},
-    extract::State,
-    http::{Request, Response},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, RawQuery, State,
+    },
+    http::{header::CONTENT_TYPE, Request, Response, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
     routing::method_routing,
     Router,
 };
-use borderless::{events::CallAction, hash::Hash256, BorderlessId, ContractId};
+use borderless::{events::CallAction, hash::Hash256, AgentId, BorderlessId, ContractId};
 use borderless_kv_store::Db;
 use borderless_runtime::{
     agent::SharedRuntime as SharedAgentRuntime,
+    db::{
+        cdc::{ChangeFeed, ChangeStream},
+        controller::Controller,
+        ledger::NodeKey,
+        logger::{LogFilter, LogStream},
+    },
     http::{
+        admin::AdminService,
         agent::{EventHandler, RecursiveEventHandler, SwAgentService},
         contract::{ActionWriter, ContractService},
-        ledger::LedgerService,
+        ledger::{parse_export_query, LedgerService},
         Service,
     },
     SharedContractRuntime,
 };
-use log::info;
+use futures_util::StreamExt;
+use log::{info, warn};
 
 use crate::generate_tx_ctx;
 
@@ -60,6 +75,34 @@ async fn ledger_handler(
     wrap_service(state, req).await
 }
 
+/// Wraps the admin service
+async fn admin_handler(
+    state: State<AdminService<impl Db + 'static>>,
+    req: Request<Body>,
+) -> Response<Body> {
+    wrap_service(state, req).await
+}
+
+/// Streams the export of a ledger
+async fn ledger_export(
+    State(srv): State<LedgerService<impl Db + 'static>>,
+    Path(ledger_id): Path<u64>,
+    RawQuery(query): RawQuery,
+) -> axum::response::Response {
+    let (format, filter) = match parse_export_query(query.as_deref()) {
+        Ok(q) => q,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match srv.export_stream(ledger_id, filter, format) {
+        Ok(stream) => (
+            [(CONTENT_TYPE, format.content_type())],
+            Body::from_stream(stream),
+        )
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
 /// Wraps the agent service
 async fn agent_handler<E, S>(
     state: State<SwAgentService<E, S>>,
@@ -72,6 +115,100 @@ where
     wrap_service(state, req).await
 }
 
+/// Turns a stream of log lines into server-sent events
+fn sse_response(result: borderless_runtime::Result<Option<LogStream>>) -> axum::response::Response {
+    match result {
+        Ok(Some(stream)) => {
+            let events = stream.map(|line| Event::default().json_data(line));
+            Sse::new(events)
+                .keep_alive(KeepAlive::default())
+                .into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Streams the live log output of a contract
+async fn contract_log_stream(
+    State(srv): State<ContractService<impl ActionWriter, impl Db + 'static>>,
+    Path(cid): Path<ContractId>,
+    RawQuery(query): RawQuery,
+) -> axum::response::Response {
+    let filter = match LogFilter::from_query(query.as_deref()) {
+        Ok(f) => f,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    sse_response(srv.log_stream(cid, filter))
+}
+
+/// Streams the live log output of a sw-agent
+async fn agent_log_stream<E, S>(
+    State(srv): State<SwAgentService<E, S>>,
+    Path(aid): Path<AgentId>,
+    RawQuery(query): RawQuery,
+) -> axum::response::Response
+where
+    E: EventHandler + 'static,
+    S: Db + 'static,
+{
+    let filter = match LogFilter::from_query(query.as_deref()) {
+        Ok(f) => f,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    sse_response(srv.log_stream(aid, filter).await)
+}
+
+/// State of the change stream route
+#[derive(Clone)]
+struct ChangeState<S: Db> {
+    db: S,
+    feed: ChangeFeed,
+}
+
+/// Streams the state changes of a contract over a websocket
+///
+/// Every committed transaction, that changes the contract state, is sent as a json text message.
+async fn contract_change_stream<S: Db + 'static>(
+    State(state): State<ChangeState<S>>,
+    Path(cid): Path<ContractId>,
+    ws: WebSocketUpgrade,
+) -> axum::response::Response {
+    match Controller::new(&state.db).contract_exists(&cid) {
+        Ok(true) => {
+            let changes = state.feed.stream(cid);
+            ws.on_upgrade(move |socket| forward_changes(socket, changes))
+        }
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn forward_changes(mut socket: WebSocket, mut changes: ChangeStream) {
+    loop {
+        tokio::select! {
+            event = changes.next() => {
+                let Some(event) = event else { break };
+                let json = match serde_json::to_string(&*event) {
+                    Ok(json) => json,
+                    Err(e) => {
+                        warn!("failed to encode change event: {e}");
+                        continue;
+                    }
+                };
+                if socket.send(Message::Text(json.into())).await.is_err() {
+                    break;
+                }
+            }
+            // We do not expect any messages from the client - we only have to notice, when it disconnects
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            }
+        }
+    }
+}
+
 /// A dummy action-writer, that instantly applies the actions to the runtime
 #[derive(Clone)]
 struct ActionApplier<S: Db> {
@@ -120,6 +257,9 @@ pub async fn start_contract_server<DB: Db + 'static>(
     db: DB,
     rt: SharedContractRuntime<DB>,
     writer: BorderlessId,
+    changes: ChangeFeed,
+    node_key: NodeKey,
+    admin_token: Option<String>,
 ) -> Result<()> {
     rt.lock().set_executor(writer)?;
     let action_writer = ActionApplier {
@@ -127,23 +267,51 @@ pub async fn start_contract_server<DB: Db + 'static>(
         writer,
     };
     let contract_srv = ContractService::with_shared(db.clone(), rt, action_writer, writer);
-    let ledger_srv = LedgerService::new(db);
+    let change_state = ChangeState {
+        db: db.clone(),
+        feed: changes,
+    };
+    let ledger_srv = LedgerService::with_node_key(db.clone(), node_key);
 
     // Create a router and attach the custom service to a route
     let contract = Router::new()
         .route("/", method_routing::any(contract_handler))
-        .route("/{*any}", method_routing::any(contract_handler))
-        .with_state(contract_srv);
+        .route(
+            "/{cid}/logs/stream",
+            method_routing::get(contract_log_stream),
+        )
+        .fallback(contract_handler)
+        .with_state(contract_srv)
+        .merge(
+            Router::new()
+                .route(
+                    "/{cid}/changes",
+                    method_routing::get(contract_change_stream),
+                )
+                .with_state(change_state),
+        );
 
     let ledger = Router::new()
         .route("/", method_routing::any(ledger_handler))
+        .route("/{id}/export", method_routing::get(ledger_export))
         .route("/{*any}", method_routing::any(ledger_handler))
         .with_state(ledger_srv);
 
-    let app = Router::new()
+    let mut app = Router::new()
         .nest("/v0/contract", contract)
         .nest("/v0/ledger", ledger);
 
+    // The admin routes are only mounted, if there is a token to check the requests against
+    match admin_token.filter(|t| !t.is_empty()) {
+        Some(token) => {
+            let admin = Router::new()
+                .fallback(admin_handler)
+                .with_state(AdminService::new(db, token));
+            app = app.nest("/v0/admin", admin);
+        }
+        None => info!("No admin token given - admin routes are disabled"),
+    }
+
     let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
     info!("Listening on {}", listener.local_addr()?);
 
@@ -161,7 +329,10 @@ pub async fn start_agent_server<DB: Db + 'static>(
     let srv = SwAgentService::with_shared(db, rt, event_handler, writer);
 
     // Create a router and attach the custom service to a route
-    let contract = Router::new().fallback(agent_handler).with_state(srv);
+    let contract = Router::new()
+        .route("/{aid}/logs/stream", method_routing::get(agent_log_stream))
+        .fallback(agent_handler)
+        .with_state(srv);
 
     let app = Router::new().nest("/v0/agent", contract);
 
@@ -1,5 +1,13 @@
 pub mod action_log;
+pub mod archive;
+#[cfg(feature = "code-store")]
+pub mod cdc;
 pub mod controller;
+pub mod fx;
+pub mod history;
 pub mod ledger;
 pub mod logger;
+pub mod netting;
+pub mod schema;
+pub mod state_tree;
 pub mod subscriptions;
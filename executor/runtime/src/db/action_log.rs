@@ -1,7 +1,13 @@
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::str::FromStr;
+
+use borderless::events::MethodOrId;
+use borderless::hash::Hash256;
 use borderless::http::queries::Pagination;
 use borderless::http::{PaginatedElements, TxAction};
-use borderless::ContractId;
-use borderless_kv_store::{Db, RawRead, Tx};
+use borderless::{BorderlessId, ContractId};
+use borderless_kv_store::{Db, RawRead, RoCursor, RoTx, Tx};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
@@ -12,9 +18,10 @@ use borderless::__private::storage_keys::{StorageKey, BASE_KEY_ACTION_LOG};
 #[cfg(any(feature = "contracts", feature = "agents"))]
 use borderless::events::CallAction;
 
+use super::controller::{system_sub_key, Controller};
 #[allow(unused_imports)]
 use crate::log_shim::*;
-use crate::{Result, CONTRACT_SUB_DB};
+use crate::{Result, ACTION_INDEX_SUB_DB, CONTRACT_SUB_DB};
 
 /// Sub-Key where the length of the action-log is stored
 pub const SUB_KEY_LOG_LEN: u64 = u64::MAX;
@@ -64,6 +71,141 @@ pub struct ActionRecord {
 
     /// Timestamp (as milliseconds since unix-epoch), when the action was commited.
     pub commited: u64,
+
+    /// Writer of the transaction, that contained the action.
+    ///
+    /// This is `None` for records, that were written before the writer was tracked.
+    pub writer: Option<BorderlessId>,
+
+    /// Root of the [`StateTree`](super::state_tree::StateTree) after the action has been applied.
+    ///
+    /// This is `None` for records, that were written before the state-root was tracked.
+    pub state_root: Option<Hash256>,
+
+    /// Return value of the action as raw json-bytes.
+    ///
+    /// This is `None` for actions without a return value, and for records that were written before return values existed.
+    #[serde(with = "serde_bytes")]
+    pub output: Option<Vec<u8>>,
+}
+
+/// Layout of action records before the return value was tracked
+#[derive(Deserialize)]
+struct ActionRecordV2 {
+    tx_ctx: TxCtx,
+    #[serde(with = "serde_bytes")]
+    value: Vec<u8>,
+    commited: u64,
+    writer: Option<BorderlessId>,
+    state_root: Option<Hash256>,
+}
+
+/// Layout of action records before the state-root was tracked
+#[derive(Deserialize)]
+struct ActionRecordV1 {
+    tx_ctx: TxCtx,
+    #[serde(with = "serde_bytes")]
+    value: Vec<u8>,
+    commited: u64,
+    writer: Option<BorderlessId>,
+}
+
+/// Layout of action records before the writer was tracked
+#[derive(Deserialize)]
+struct LegacyActionRecord {
+    tx_ctx: TxCtx,
+    #[serde(with = "serde_bytes")]
+    value: Vec<u8>,
+    commited: u64,
+}
+
+impl ActionRecord {
+    /// Decodes a stored action record
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        Ok(postcard::from_bytes(bytes)?)
+    }
+
+    /// Decodes an action record, that uses one of the legacy layouts (without output, state-root or writer)
+    ///
+    /// Returns `None`, if the record already uses the current layout.
+    /// Only used to rewrite the records of older versions, see [`upgrade_action_records`].
+    fn upgrade(bytes: &[u8]) -> Result<Option<Self>> {
+        let e = match postcard::from_bytes::<ActionRecord>(bytes) {
+            Ok(_) => return Ok(None),
+            Err(e) => e,
+        };
+        if let Ok(v2) = postcard::from_bytes::<ActionRecordV2>(bytes) {
+            return Ok(Some(ActionRecord {
+                tx_ctx: v2.tx_ctx,
+                value: v2.value,
+                commited: v2.commited,
+                writer: v2.writer,
+                state_root: v2.state_root,
+                output: None,
+            }));
+        }
+        if let Ok(v1) = postcard::from_bytes::<ActionRecordV1>(bytes) {
+            return Ok(Some(ActionRecord {
+                tx_ctx: v1.tx_ctx,
+                value: v1.value,
+                commited: v1.commited,
+                writer: v1.writer,
+                state_root: None,
+                output: None,
+            }));
+        }
+        match postcard::from_bytes::<LegacyActionRecord>(bytes) {
+            Ok(legacy) => Ok(Some(ActionRecord {
+                tx_ctx: legacy.tx_ctx,
+                value: legacy.value,
+                commited: legacy.commited,
+                writer: None,
+                state_root: None,
+                output: None,
+            })),
+            Err(_) => Err(e.into()),
+        }
+    }
+}
+
+/// Re-encodes an entry of the contract sub-db, if it is an action record in one of the legacy layouts
+///
+/// Returns `None` for all other entries and for records, that already use the current layout.
+pub(crate) fn upgrade_action_record(key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+    match system_sub_key(key, BASE_KEY_ACTION_LOG) {
+        Some(sub_key) if sub_key != SUB_KEY_LOG_LEN => ActionRecord::upgrade(value)?
+            .map(|record| Ok(postcard::to_allocvec(&record)?))
+            .transpose(),
+        _ => Ok(None),
+    }
+}
+
+/// Rewrites all action records, that were stored in one of the legacy layouts
+///
+/// Registered as migration of the [`CONTRACT_SUB_DB`], see [`super::schema`].
+pub(crate) fn upgrade_action_records<S: Db>(db: &S) -> Result<()> {
+    use borderless_kv_store::RawWrite;
+
+    let db_ptr = db.open_sub_db(CONTRACT_SUB_DB)?;
+    let mut upgraded = Vec::new();
+    {
+        let txn = db.begin_ro_txn()?;
+        let mut cursor = txn.ro_cursor(&db_ptr)?;
+        for (key, value) in cursor.iter() {
+            if let Some(record) = upgrade_action_record(key, value)? {
+                upgraded.push((key.to_vec(), record));
+            }
+        }
+        drop(cursor);
+        txn.commit()?;
+    }
+    let mut txn = db.begin_rw_txn()?;
+    for (key, value) in &upgraded {
+        txn.write(&db_ptr, key, value)?;
+    }
+    txn.commit()?;
+    debug!("upgraded {} action records", upgraded.len());
+    Ok(())
 }
 
 impl TryFrom<ActionRecord> for TxAction {
@@ -73,10 +215,17 @@ impl TryFrom<ActionRecord> for TxAction {
         // Hm, I thought we could get around the additional parsing step here..
         // I still haven't given up ! TODO maybe construct the raw json value here, and see if this is faster.
         let action = serde_json::from_slice(&record.value)?;
+        let output = record
+            .output
+            .map(|bytes| serde_json::from_slice(&bytes))
+            .transpose()?;
         Ok(Self {
             tx_id: record.tx_ctx.tx_id,
             action,
             commited: record.commited,
+            writer: record.writer,
+            state_root: record.state_root,
+            output,
         })
     }
 }
@@ -122,6 +271,105 @@ impl RelTxAction {
     }
 }
 
+/// Filter for queries on the [`ActionLog`]
+///
+/// Can be parsed from a query string like `method=approve&writer=<borderless-id>&from=<ts>&to=<ts>`,
+/// where `from` and `to` are the commit timestamps in milliseconds since unix-epoch.
+/// Methods that are called by their id can be queried with `method_id=<id>`.
+#[derive(Debug, Clone, Default)]
+pub struct ActionQuery {
+    /// Name or id of the called method
+    pub method: Option<MethodOrId>,
+    /// Writer of the transaction
+    pub writer: Option<BorderlessId>,
+    /// Lower bound for the commit timestamp (inclusive)
+    pub from: Option<u64>,
+    /// Upper bound for the commit timestamp (inclusive)
+    pub to: Option<u64>,
+}
+
+impl ActionQuery {
+    /// Extracts a query from some query string.
+    ///
+    /// Unknown keys are ignored, but invalid values for known keys result in an error.
+    pub fn from_query(query: Option<&str>) -> Result<Self> {
+        let mut out = ActionQuery::default();
+        let query = match query {
+            Some(q) => q,
+            None => return Ok(out),
+        };
+        let invalid =
+            |key: &str, value: &str| crate::Error::msg(format!("invalid {key} '{value}'"));
+        for piece in query.split('&') {
+            let (key, value) = match piece.split_once('=') {
+                Some((k, v)) if !v.is_empty() => (k, v),
+                _ => continue,
+            };
+            match key {
+                "method" => {
+                    out.method = Some(MethodOrId::ByName {
+                        method: value.to_string(),
+                    })
+                }
+                "method_id" => {
+                    let method_id = u32::from_str(value).map_err(|_| invalid(key, value))?;
+                    out.method = Some(MethodOrId::ById { method_id });
+                }
+                "writer" => {
+                    out.writer = Some(value.parse().map_err(|_| invalid(key, value))?);
+                }
+                "from" => out.from = Some(u64::from_str(value).map_err(|_| invalid(key, value))?),
+                "to" => out.to = Some(u64::from_str(value).map_err(|_| invalid(key, value))?),
+                _ => (),
+            }
+        }
+        Ok(out)
+    }
+
+    /// Returns `true` if the query would match every action
+    pub fn is_empty(&self) -> bool {
+        self.method.is_none() && self.writer.is_none() && self.from.is_none() && self.to.is_none()
+    }
+}
+
+// NOTE: The secondary indices live in their own sub-db, and relate a method or writer to the action-index:
+// - Method: contract-id | 'm' | method-name | 0x00 | action-index
+// - Method-ID: contract-id | 'i' | method-id | action-index
+// - Writer: contract-id | 'w' | writer | action-index
+//
+// All integers are encoded in big-endian, so the cursor returns the actions of each prefix in commit order.
+const INDEX_TAG_METHOD: u8 = b'm';
+const INDEX_TAG_METHOD_ID: u8 = b'i';
+const INDEX_TAG_WRITER: u8 = b'w';
+
+fn method_index_prefix(cid: &ContractId, method: &MethodOrId) -> Vec<u8> {
+    let mut key = cid.into_bytes().to_vec();
+    match method {
+        MethodOrId::ByName { method } => {
+            key.push(INDEX_TAG_METHOD);
+            key.extend_from_slice(method.as_bytes());
+            key.push(0);
+        }
+        MethodOrId::ById { method_id } => {
+            key.push(INDEX_TAG_METHOD_ID);
+            key.extend_from_slice(&method_id.to_be_bytes());
+        }
+    }
+    key
+}
+
+fn writer_index_prefix(cid: &ContractId, writer: &BorderlessId) -> Vec<u8> {
+    let mut key = cid.into_bytes().to_vec();
+    key.push(INDEX_TAG_WRITER);
+    key.extend_from_slice(&writer.into_bytes());
+    key
+}
+
+fn index_key(mut prefix: Vec<u8>, action_idx: u64) -> Vec<u8> {
+    prefix.extend_from_slice(&action_idx.to_be_bytes());
+    prefix
+}
+
 impl<'a, S: Db> ActionLog<'a, S> {
     /// Opens (or creates) the action log
     pub fn new(db: &'a S, cid: ContractId) -> Self {
@@ -129,12 +377,16 @@ impl<'a, S: Db> ActionLog<'a, S> {
     }
 
     #[cfg(any(feature = "contracts", feature = "agents"))]
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn commit(
         self,
         db_ptr: &S::Handle,
         txn: &mut <S as Db>::RwTx<'_>,
         action: &CallAction,
+        writer: BorderlessId,
         tx_ctx: TxCtx,
+        state_root: Option<Hash256>,
+        output: Option<&[u8]>,
     ) -> Result<()> {
         use borderless_kv_store::RawWrite;
 
@@ -167,6 +419,9 @@ impl<'a, S: Db> ActionLog<'a, S> {
             tx_ctx,
             value: action.to_bytes()?,
             commited: timestamp,
+            writer: Some(writer),
+            state_root,
+            output: output.map(<[u8]>::to_vec),
         };
         write_system_value::<S, _, _>(
             db_ptr,
@@ -194,6 +449,13 @@ impl<'a, S: Db> ActionLog<'a, S> {
         };
         txn.write(&rel_db, &tx_id_bytes, &relationship.into_bytes())?;
 
+        // Update secondary indices
+        let index_db = self.db.open_sub_db(ACTION_INDEX_SUB_DB)?;
+        let method_key = index_key(method_index_prefix(&self.cid, &action.method), sub_key);
+        txn.write(&index_db, &method_key, &[])?;
+        let writer_key = index_key(writer_index_prefix(&self.cid, &writer), sub_key);
+        txn.write(&index_db, &writer_key, &[])?;
+
         debug!("Commited action to log. len={full_len}");
         Ok(())
     }
@@ -204,43 +466,123 @@ impl<'a, S: Db> ActionLog<'a, S> {
         let len_commited = self.len()?;
         debug_assert!(idx < SUB_KEY_LOG_LEN);
         if idx < len_commited {
-            self.read_value(BASE_KEY_ACTION_LOG, idx)
+            self.read_record(idx)
         } else {
             Ok(None)
         }
     }
 
-    // TODO: Add 'reverse' option
     pub fn get_tx_action_paginated(
         &self,
         pagination: Pagination,
     ) -> Result<Option<PaginatedElements<TxAction>>> {
-        // Get actions
-        let n_actions = self.len()?;
+        self.query(&ActionQuery::default(), pagination).map(Some)
+    }
 
-        let mut elements = Vec::new();
-        for idx in pagination.to_range() {
-            // TODO: We can utilize the action log here !
-            match self.read_value::<ActionRecord>(BASE_KEY_ACTION_LOG, idx as u64)? {
-                Some(record) => {
-                    let action = TxAction::try_from(record)?;
-                    elements.push(action);
+    /// Queries the actions, that match the given [`ActionQuery`]
+    ///
+    /// The method and writer are resolved via the secondary indices and the time range
+    /// via binary search over the commit timestamps, so no full scan of the log is required.
+    /// The number of total elements refers to the number of matching actions.
+    pub fn query(
+        &self,
+        query: &ActionQuery,
+        pagination: Pagination,
+    ) -> Result<PaginatedElements<TxAction>> {
+        let range = self.time_range(query.from, query.to)?;
+
+        // Collect the matching action indices from the secondary indices
+        let mut matches: Option<Vec<u64>> = None;
+        if let Some(method) = &query.method {
+            matches = Some(self.index_lookup(method_index_prefix(&self.cid, method), &range)?);
+        }
+        if let Some(writer) = &query.writer {
+            let by_writer = self.index_lookup(writer_index_prefix(&self.cid, writer), &range)?;
+            matches = Some(match matches {
+                Some(by_method) => {
+                    let by_writer: BTreeSet<u64> = by_writer.into_iter().collect();
+                    by_method
+                        .into_iter()
+                        .filter(|idx| by_writer.contains(idx))
+                        .collect()
                 }
-                None => break,
+                None => by_writer,
+            });
+        }
+
+        let total_elements = match &matches {
+            Some(m) => m.len(),
+            None => (range.end - range.start) as usize,
+        };
+        let nth = |n: usize| -> u64 {
+            let n = if pagination.reverse {
+                total_elements - 1 - n
+            } else {
+                n
+            };
+            match &matches {
+                Some(m) => m[n],
+                None => range.start + n as u64,
+            }
+        };
+
+        let page = pagination.to_range();
+        let mut elements = Vec::new();
+        for n in page.start..page.end.min(total_elements) {
+            if let Some(record) = self.read_record(nth(n))? {
+                elements.push(TxAction::try_from(record)?);
             }
         }
-        let paginated = PaginatedElements {
+        Ok(PaginatedElements {
             elements,
-            total_elements: n_actions as usize,
+            total_elements,
             pagination,
-        };
-        Ok(Some(paginated))
+        })
+    }
+
+    /// Rebuilds the method index for all actions in the log
+    ///
+    /// Only required for logs, that were written before the secondary indices existed.
+    /// The writer index can only be restored for records that contain the writer.
+    pub fn rebuild_index(&self) -> Result<()> {
+        use borderless_kv_store::RawWrite;
+
+        let index_db = self.db.create_sub_db(ACTION_INDEX_SUB_DB)?;
+        let records = self
+            .iter()
+            .enumerate()
+            .map(|(idx, r)| r.map(|r| (idx as u64, r)))
+            .collect::<Result<Vec<_>>>()?;
+        let mut txn = self.db.begin_rw_txn()?;
+        for (idx, record) in records {
+            let action = borderless::events::CallAction::from_bytes(&record.value)?;
+            let key = index_key(method_index_prefix(&self.cid, &action.method), idx);
+            txn.write(&index_db, &key, &[])?;
+            if let Some(writer) = &record.writer {
+                let key = index_key(writer_index_prefix(&self.cid, writer), idx);
+                txn.write(&index_db, &key, &[])?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Rebuilds the indices of all action logs in the database
+    ///
+    /// Registered as migration of the [`ACTION_INDEX_SUB_DB`], see [`super::schema`].
+    pub fn rebuild_all_indices(db: &'a S) -> Result<()> {
+        let ids = Controller::new(db).contract_ids()?;
+        for cid in &ids {
+            ActionLog::new(db, *cid).rebuild_index()?;
+        }
+        debug!("rebuilt action indices of {} contracts", ids.len());
+        Ok(())
     }
 
     /// Retrieves the last action record
     pub fn last(&self) -> Result<Option<ActionRecord>> {
         let len_commited = self.len()?;
-        self.read_value(BASE_KEY_ACTION_LOG, len_commited.saturating_sub(1))
+        self.read_record(len_commited.saturating_sub(1))
     }
 
     pub fn len(&self) -> Result<u64> {
@@ -253,6 +595,85 @@ impl<'a, S: Db> ActionLog<'a, S> {
         Ok(self.len()? == 0)
     }
 
+    fn read_record(&self, idx: u64) -> Result<Option<ActionRecord>> {
+        let db_ptr = self.db.open_sub_db(CONTRACT_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let key = StorageKey::system_key(self.cid, BASE_KEY_ACTION_LOG, idx);
+        let result = match txn.read(&db_ptr, &key)? {
+            Some(bytes) => Some(ActionRecord::decode(bytes)?),
+            None => None,
+        };
+        txn.commit()?;
+        Ok(result)
+    }
+
+    /// Returns the range of action indices, whose commit timestamp lies within the given bounds
+    ///
+    /// Since the log is append-only, the timestamps are ordered and we can use a binary search.
+    fn time_range(&self, from: Option<u64>, to: Option<u64>) -> Result<Range<u64>> {
+        let len = self.len()?;
+        let start = match from {
+            Some(from) => self.partition_point(len, |r| r.commited < from)?,
+            None => 0,
+        };
+        let end = match to {
+            Some(to) => self.partition_point(len, |r| r.commited <= to)?,
+            None => len,
+        };
+        Ok(start..end.max(start))
+    }
+
+    /// Returns the number of actions, that were part of a block with a number lower or equal to the given block number
+    pub fn count_until_block(&self, block_number: u64) -> Result<u64> {
+        let len = self.len()?;
+        self.partition_point(len, |r| r.tx_ctx.tx_id.number <= block_number)
+    }
+
+    /// Returns the index of the first record, for which the predicate is `false`
+    ///
+    /// Since the log is append-only, this works for all predicates that are monotonic in the commit order.
+    fn partition_point(&self, len: u64, pred: impl Fn(&ActionRecord) -> bool) -> Result<u64> {
+        let (mut lo, mut hi) = (0, len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let matches = self.read_record(mid)?.is_some_and(|r| pred(&r));
+            if matches {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        Ok(lo)
+    }
+
+    /// Returns all action indices behind the index prefix, that lie within the given range
+    fn index_lookup(&self, prefix: Vec<u8>, range: &Range<u64>) -> Result<Vec<u64>> {
+        // The index does not exist, if no action has been written yet
+        let index_db = match self.db.open_sub_db(ACTION_INDEX_SUB_DB) {
+            Ok(db_ptr) => db_ptr,
+            Err(borderless_kv_store::Error::DbNotFound(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let txn = self.db.begin_ro_txn()?;
+        let mut cursor = txn.ro_cursor(&index_db)?;
+
+        let mut out = Vec::new();
+        let start = index_key(prefix.clone(), range.start);
+        let end = index_key(prefix.clone(), range.end);
+        for (key, _) in cursor.iter_range(start..end) {
+            // Stop iterating when the key does not belong to the index
+            if key.len() != prefix.len() + 8 {
+                break;
+            }
+            let mut idx_bytes = [0u8; 8];
+            idx_bytes.copy_from_slice(&key[prefix.len()..]);
+            out.push(u64::from_be_bytes(idx_bytes));
+        }
+        // Free up resources
+        drop(cursor);
+        Ok(out)
+    }
+
     fn read_value<D: DeserializeOwned>(&self, base_key: u64, sub_key: u64) -> Result<Option<D>> {
         let db_ptr = self.db.open_sub_db(CONTRACT_SUB_DB)?;
         let txn = self.db.begin_ro_txn()?;
@@ -287,3 +708,253 @@ impl<'a, S: Db> Iterator for Iter<'a, S> {
         self.log.get(idx).transpose()
     }
 }
+
+#[cfg(all(test, feature = "contracts"))]
+mod tests {
+    use super::*;
+    use crate::ACTION_TX_REL_SUB_DB;
+    use borderless::{events::CallAction, hash::Hash256, TxIdentifier};
+    use borderless_kv_store::backend::lmdb::Lmdb;
+    use tempfile::tempdir;
+
+    fn open_tmp_lmdb() -> Lmdb {
+        let tmp_dir = tempdir().unwrap();
+        let env = Lmdb::new(tmp_dir.path(), 4).unwrap();
+        env.create_sub_db(CONTRACT_SUB_DB).unwrap();
+        env.create_sub_db(ACTION_TX_REL_SUB_DB).unwrap();
+        env.create_sub_db(ACTION_INDEX_SUB_DB).unwrap();
+        env
+    }
+
+    fn commit(db: &Lmdb, cid: ContractId, action: CallAction, writer: BorderlessId, n: u64) {
+        let db_ptr = db.open_sub_db(CONTRACT_SUB_DB).unwrap();
+        let mut txn = db.begin_rw_txn().unwrap();
+        let tx_ctx = TxCtx {
+            tx_id: TxIdentifier::new(1, n, Hash256::empty()),
+            index: 0,
+        };
+        ActionLog::new(db, cid)
+            .commit(&db_ptr, &mut txn, &action, writer, tx_ctx, None, None)
+            .unwrap();
+        txn.commit().unwrap();
+    }
+
+    fn page(per_page: usize, reverse: bool) -> Pagination {
+        Pagination {
+            page: 1,
+            per_page,
+            reverse,
+        }
+    }
+
+    #[test]
+    fn query_by_method_and_writer() -> Result<()> {
+        let db = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let alice = BorderlessId::generate();
+        let bob = BorderlessId::generate();
+        let calls = [
+            ("approve", alice),
+            ("transfer", bob),
+            ("approve", bob),
+            ("transfer", alice),
+            ("approve", alice),
+        ];
+        for (n, (method, writer)) in calls.iter().enumerate() {
+            let action = CallAction::by_method(method, serde_json::Value::Null);
+            commit(&db, cid, action, *writer, n as u64);
+        }
+        commit(
+            &db,
+            cid,
+            CallAction::by_method_id(7, serde_json::Value::Null),
+            bob,
+            5,
+        );
+
+        let log = ActionLog::new(&db, cid);
+        let all = log.query(&ActionQuery::default(), page(100, false))?;
+        assert_eq!(all.total_elements, 6);
+        assert_eq!(all.elements[0].writer, Some(alice));
+
+        let query = ActionQuery::from_query(Some("method=approve"))?;
+        let result = log.query(&query, page(100, false))?;
+        assert_eq!(result.total_elements, 3);
+
+        let query = ActionQuery::from_query(Some(&format!("method=approve&writer={alice}")))?;
+        let result = log.query(&query, page(100, false))?;
+        assert_eq!(result.total_elements, 2);
+        assert!(result.elements.iter().all(|a| a.writer == Some(alice)));
+
+        let query = ActionQuery::from_query(Some(&format!("writer={bob}")))?;
+        let result = log.query(&query, page(1, true))?;
+        assert_eq!(result.total_elements, 3);
+        assert_eq!(result.elements.len(), 1);
+        assert_eq!(result.elements[0].tx_id.number, 5);
+
+        let query = ActionQuery::from_query(Some("method_id=7"))?;
+        assert_eq!(log.query(&query, page(100, false))?.total_elements, 1);
+
+        let all_rev = log.query(&ActionQuery::default(), page(2, true))?;
+        assert_eq!(all_rev.elements[0].tx_id.number, 5);
+        assert_eq!(all_rev.elements.len(), 2);
+
+        assert!(ActionQuery::from_query(Some("writer=nobody")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn query_by_time_range() -> Result<()> {
+        let db = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let writer = BorderlessId::generate();
+        for n in 0..4 {
+            commit(
+                &db,
+                cid,
+                CallAction::by_method("tick", serde_json::Value::Null),
+                writer,
+                n,
+            );
+            std::thread::sleep(std::time::Duration::from_millis(3));
+        }
+        let log = ActionLog::new(&db, cid);
+        let ts: Vec<u64> = log.iter().map(|r| r.unwrap().commited).collect();
+
+        let query = ActionQuery {
+            from: Some(ts[1]),
+            to: Some(ts[2]),
+            ..Default::default()
+        };
+        let result = log.query(&query, page(100, false))?;
+        assert_eq!(result.total_elements, 2);
+        assert_eq!(result.elements[0].tx_id.number, 1);
+
+        let query = ActionQuery {
+            method: Some(MethodOrId::ByName {
+                method: "tick".to_string(),
+            }),
+            from: Some(ts[3] + 1),
+            ..Default::default()
+        };
+        assert_eq!(log.query(&query, page(100, false))?.total_elements, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn rebuild_missing_indices() -> Result<()> {
+        use crate::db::controller::write_system_value;
+        use borderless::__private::storage_keys::{BASE_KEY_METADATA, META_SUB_KEY_ID};
+        use borderless_kv_store::RawWrite;
+
+        let db = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let writer = BorderlessId::generate();
+        for (n, method) in ["approve", "transfer", "approve"].iter().enumerate() {
+            let action = CallAction::by_method(method, serde_json::Value::Null);
+            commit(&db, cid, action, writer, n as u64);
+        }
+        let db_ptr = db.open_sub_db(CONTRACT_SUB_DB)?;
+        let index_db = db.open_sub_db(ACTION_INDEX_SUB_DB)?;
+        // Drop the index, like for logs that were written before the index existed
+        let keys: Vec<Vec<u8>> = {
+            let txn = db.begin_ro_txn()?;
+            let mut cursor = txn.ro_cursor(&index_db)?;
+            cursor.iter().map(|(k, _)| k.to_vec()).collect()
+        };
+        let mut txn = db.begin_rw_txn()?;
+        write_system_value::<Lmdb, _, _>(
+            &db_ptr,
+            &mut txn,
+            &cid,
+            BASE_KEY_METADATA,
+            META_SUB_KEY_ID,
+            &cid,
+        )?;
+        for key in keys {
+            txn.delete(&index_db, &key)?;
+        }
+        txn.commit()?;
+
+        let log = ActionLog::new(&db, cid);
+        let query = ActionQuery::from_query(Some("method=approve"))?;
+        assert_eq!(log.query(&query, page(100, false))?.total_elements, 0);
+        ActionLog::rebuild_all_indices(&db)?;
+        assert_eq!(log.query(&query, page(100, false))?.total_elements, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn query_without_index_db() -> Result<()> {
+        let tmp_dir = tempdir().unwrap();
+        let db = Lmdb::new(tmp_dir.path(), 4).unwrap();
+        db.create_sub_db(CONTRACT_SUB_DB)?;
+        let log = ActionLog::new(&db, ContractId::generate());
+        let query = ActionQuery::from_query(Some("method=approve"))?;
+        assert_eq!(log.query(&query, page(100, false))?.total_elements, 0);
+        // Reading must not create the sub-db
+        assert!(db.open_sub_db(ACTION_INDEX_SUB_DB).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_records_are_upgraded_by_migration() -> Result<()> {
+        use borderless_kv_store::RawWrite;
+
+        let db = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let writer = BorderlessId::generate();
+        for n in 0..4 {
+            commit(
+                &db,
+                cid,
+                CallAction::by_method("set", serde_json::Value::Null),
+                writer,
+                n,
+            );
+        }
+
+        // Older layouts end before the optional fields ( `None` is encoded as a single zero byte )
+        let db_ptr = db.open_sub_db(CONTRACT_SUB_DB)?;
+        let mut txn = db.begin_rw_txn()?;
+        for (idx, cut) in [(1, 1), (2, 2), (3, 3)] {
+            let key = StorageKey::system_key(cid, BASE_KEY_ACTION_LOG, idx);
+            let mut record: ActionRecord = postcard::from_bytes(txn.read(&db_ptr, &key)?.unwrap())?;
+            record.writer = None;
+            let bytes = postcard::to_allocvec(&record)?;
+            txn.write(&db_ptr, &key, &&bytes[..bytes.len() - cut])?;
+        }
+        txn.commit()?;
+        let log = ActionLog::new(&db, cid);
+        assert!(log.get(1).is_err());
+
+        upgrade_action_records(&db)?;
+        // Running the migration again does not touch upgraded records
+        upgrade_action_records(&db)?;
+        let records = log.iter().collect::<Result<Vec<_>>>()?;
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].writer, Some(writer));
+        for (n, record) in records.iter().enumerate() {
+            assert_eq!(record.tx_ctx.tx_id.number, n as u64);
+            assert!(record.output.is_none());
+        }
+        assert!(records[1..].iter().all(|r| r.writer.is_none()));
+        Ok(())
+    }
+
+    #[test]
+    fn output_is_part_of_tx_action() -> Result<()> {
+        let record = ActionRecord {
+            tx_ctx: TxCtx::dummy(),
+            value: CallAction::by_method("order", serde_json::Value::Null).to_bytes()?,
+            commited: 42,
+            writer: None,
+            state_root: None,
+            output: Some(br#"{"order_no":7}"#.to_vec()),
+        };
+        let record = ActionRecord::decode(&postcard::to_allocvec(&record)?)?;
+        let tx_action = TxAction::try_from(record)?;
+        assert_eq!(tx_action.output, Some(serde_json::json!({ "order_no": 7 })));
+        Ok(())
+    }
+}
@@ -0,0 +1,840 @@
+//! Portable export and import of contracts and agents
+//!
+//! An [`Archive`] bundles everything that belongs to a single contract or agent into a self-contained file,
+//! which can be used as a backup or to move the entity to another node.
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use borderless::__private::storage_keys::{StorageKey, BASE_KEY_METADATA, META_SUB_KEY_ID};
+use borderless::contracts::{
+    ledger::{Currency, EntryType, LedgerEntry},
+    TxCtx,
+};
+use borderless::hash::Hash256;
+use borderless::prelude::Id;
+use borderless::BorderlessId;
+use borderless_kv_store::*;
+use serde::{Deserialize, Serialize};
+
+use super::action_log::{upgrade_action_record, RelTxAction};
+use super::controller::{is_pkg_def_key, upgrade_pkg_def, Controller};
+use super::ledger::Ledger;
+use super::logger::upgrade_log_line;
+use super::subscriptions::SubscriptionHandler;
+#[allow(unused_imports)]
+use crate::log_shim::*;
+use crate::{
+    Error, Result, ACTION_INDEX_SUB_DB, ACTION_TX_REL_SUB_DB, AGENT_SUB_DB, CONTRACT_SUB_DB,
+    LEDGER_SUB_DB, STATE_HISTORY_SUB_DB, STATE_TREE_SUB_DB, SUBSCRIPTION_REL_SUB_DB,
+    WASM_CODE_SUB_DB,
+};
+
+/// Current version of the archive format
+///
+/// Version 2 added the payment terms to the ledger entries.
+/// Version 3 added the log buffer size to the package definition and the reversed line to the ledger entries.
+pub const ARCHIVE_FORMAT_VERSION: u32 = 3;
+
+/// Oldest archive format version, that can still be imported
+const MIN_ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+/// Magic bytes at the beginning of every archive
+const ARCHIVE_MAGIC: &[u8; 4] = b"BLSA";
+
+/// Manifest of an [`Archive`]
+///
+/// Describes the exported entity and contains a checksum for every section of the archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Version of the archive format
+    pub format_version: u32,
+    /// Contract or agent, that has been exported
+    pub id: Id,
+    /// Timestamp (as milliseconds since unix-epoch), when the archive was created
+    pub created: u64,
+    /// Information about all sections of the archive
+    pub sections: Vec<SectionInfo>,
+}
+
+/// Information about a single [`Section`] of the archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SectionInfo {
+    /// Name of the section (this is the name of the sub-db, where the entries belong to)
+    pub name: String,
+    /// Number of entries in the section
+    pub entries: u64,
+    /// Hash over all entries of the section
+    pub checksum: Hash256,
+}
+
+/// Raw key-value pairs of a sub-db, that belong to the exported entity
+///
+/// The ledger is the only exception, as ledgers are shared between contracts.
+/// Its section contains the postcard encoded ledger-entries (and their tx-context) of the contract,
+/// which are re-commited on import. The keys consist of the ledger-id and the original line number.
+#[derive(Serialize, Deserialize)]
+struct Section {
+    name: String,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Section {
+    fn info(&self) -> Result<SectionInfo> {
+        Ok(SectionInfo {
+            name: self.name.clone(),
+            entries: self.entries.len() as u64,
+            checksum: Hash256::digest(&postcard::to_allocvec(&self.entries)?),
+        })
+    }
+}
+
+/// Self-contained archive of a contract or agent
+///
+/// The archive covers the introduction and metadata, package and wasm code, user-space storage,
+/// action log (including its indices and state history), logs, ledger lines and subscriptions.
+///
+/// The encoded archive consists of the magic bytes, the length of the manifest (u32 big-endian),
+/// the json-encoded manifest and the postcard-encoded sections.
+///
+/// Note: The wasm code is stored as compiled module, so the importing node should use the same runtime version.
+/// The package source is part of the metadata, in case the module has to be recompiled.
+pub struct Archive {
+    pub manifest: Manifest,
+    sections: Vec<Section>,
+}
+
+impl Archive {
+    /// Exports the contract or agent from the database
+    pub fn export<S: Db>(db: &S, id: Id) -> Result<Self> {
+        let controller = Controller::new(db);
+        let exists = match id {
+            Id::Contract { contract_id } => controller.contract_exists(&contract_id)?,
+            Id::Agent { agent_id } => controller.agent_exists(&agent_id)?,
+        };
+        if !exists {
+            return Err(Error::msg(format!("{id} does not exist")));
+        }
+        let id_bytes = id.as_ref().to_vec();
+
+        let mut sections = vec![
+            export_section(db, entity_db(&id), &id_bytes)?,
+            export_section(db, WASM_CODE_SUB_DB, &id_bytes)?,
+        ];
+
+        if let Id::Contract { contract_id } = id {
+            // The relations are keyed by the tx-id, so we take them from the action log of the contract
+            let entries = Controller::new(db)
+                .actions(contract_id)
+                .iter()
+                .enumerate()
+                .map(|(idx, record)| {
+                    let relationship = RelTxAction {
+                        cid: contract_id,
+                        action_idx: idx as u64,
+                    };
+                    Ok((
+                        record?.tx_ctx.tx_id.to_bytes().to_vec(),
+                        relationship.into_bytes().to_vec(),
+                    ))
+                })
+                .collect::<Result<_>>()?;
+            sections.push(Section {
+                name: ACTION_TX_REL_SUB_DB.to_string(),
+                entries,
+            });
+            sections.push(export_section(db, ACTION_INDEX_SUB_DB, &id_bytes)?);
+            sections.push(export_section(db, STATE_HISTORY_SUB_DB, &id_bytes)?);
+            sections.push(export_section(db, STATE_TREE_SUB_DB, &id_bytes)?);
+
+            let entries = Ledger::new(db)
+                .contract_entries(contract_id)?
+                .into_iter()
+                .map(|line| {
+                    let mut key = line.ledger_id.to_be_bytes().to_vec();
+                    key.extend_from_slice(&line.line.to_be_bytes());
+                    let value = postcard::to_allocvec(&(line.entry, line.tx_ctx, line.commited))?;
+                    Ok((key, value))
+                })
+                .collect::<Result<_>>()?;
+            sections.push(Section {
+                name: LEDGER_SUB_DB.to_string(),
+                entries,
+            });
+        }
+
+        // NOTE: The archive only contains the subscriptions, not the index by subscriber (it is rebuilt on import)
+        sections.push(Section {
+            name: SUBSCRIPTION_REL_SUB_DB.to_string(),
+            entries: SubscriptionHandler::new(db).export_entries(id)?,
+        });
+
+        let manifest = Manifest {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            id,
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("timestamp < 1970")
+                .as_millis()
+                .try_into()
+                .expect("u64 should fit for 584942417 years"),
+            sections: sections.iter().map(Section::info).collect::<Result<_>>()?,
+        };
+        Ok(Archive { manifest, sections })
+    }
+
+    /// Encodes the archive
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let manifest = serde_json::to_vec(&self.manifest)?;
+        let manifest_len = u32::try_from(manifest.len())
+            .map_err(|_| Error::msg("manifest exceeds the maximum size"))?;
+        let mut out = ARCHIVE_MAGIC.to_vec();
+        out.extend_from_slice(&manifest_len.to_be_bytes());
+        out.extend_from_slice(&manifest);
+        out.extend_from_slice(&postcard::to_allocvec(&self.sections)?);
+        Ok(out)
+    }
+
+    /// Decodes and verifies an archive
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let invalid = || Error::msg("not a borderless archive");
+        let body = bytes.strip_prefix(ARCHIVE_MAGIC).ok_or_else(invalid)?;
+        let (len_bytes, body) = body.split_first_chunk::<4>().ok_or_else(invalid)?;
+        let manifest_len = u32::from_be_bytes(*len_bytes) as usize;
+        if body.len() < manifest_len {
+            return Err(invalid());
+        }
+        let (manifest, sections) = body.split_at(manifest_len);
+        let archive = Archive {
+            manifest: serde_json::from_slice(manifest)?,
+            sections: postcard::from_bytes(sections)?,
+        };
+        archive.verify()?;
+        Ok(archive)
+    }
+
+    /// Verifies, that the content of the archive matches its manifest
+    pub fn verify(&self) -> Result<()> {
+        if !(MIN_ARCHIVE_FORMAT_VERSION..=ARCHIVE_FORMAT_VERSION)
+            .contains(&self.manifest.format_version)
+        {
+            return Err(Error::msg(format!(
+                "unsupported archive format version {}",
+                self.manifest.format_version
+            )));
+        }
+        if self.manifest.sections.len() != self.sections.len() {
+            return Err(Error::msg("number of sections does not match the manifest"));
+        }
+        for (expected, section) in self.manifest.sections.iter().zip(&self.sections) {
+            let actual = section.info()?;
+            if expected.name != actual.name
+                || expected.entries != actual.entries
+                || expected.checksum != actual.checksum
+            {
+                return Err(Error::msg(format!(
+                    "section '{}' does not match the manifest",
+                    expected.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifies the archive and restores the entity into the given database
+    ///
+    /// Returns an error, if the entity already exists in the database.
+    pub fn import<S: Db>(&self, db: &S) -> Result<()> {
+        self.verify()?;
+        let id = self.manifest.id;
+        let entity_ptr = db.create_sub_db(entity_db(&id))?;
+        let controller = Controller::new(db);
+        let exists = match id {
+            Id::Contract { contract_id } => controller.contract_exists(&contract_id)?,
+            Id::Agent { agent_id } => controller.agent_exists(&agent_id)?,
+        };
+        if exists {
+            return Err(Error::msg(format!("{id} already exists")));
+        }
+
+        let handles = self
+            .sections
+            .iter()
+            .map(|s| db.create_sub_db(&s.name))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let ledger = Ledger::new(db);
+        ledger.ensure_index()?;
+        let mut txn = db.begin_rw_txn()?;
+        for (section, db_ptr) in self.sections.iter().zip(&handles) {
+            if section.name == LEDGER_SUB_DB {
+                let cid = id
+                    .as_cid()
+                    .ok_or_else(|| Error::msg("agents have no ledger"))?;
+                // The lines get new numbers, so reversals have to point to the new line of the original entry
+                let mut lines = HashMap::new();
+                for (key, value) in &section.entries {
+                    let (mut entry, tx_ctx, commited) =
+                        decode_ledger_line(self.manifest.format_version, value)?;
+                    // Archives of older versions only contain a running number as key
+                    let (ledger_id, old_line) = match key.len() {
+                        16 => (
+                            u64::from_be_bytes(key[..8].try_into().expect("Slice length error")),
+                            Some(u64::from_be_bytes(
+                                key[8..].try_into().expect("Slice length error"),
+                            )),
+                        ),
+                        _ => (entry.creditor.merge_compact(&entry.debitor), None),
+                    };
+                    if let Some(original) = entry.reverses {
+                        entry.reverses =
+                            Some(*lines.get(&(ledger_id, original)).ok_or_else(|| {
+                                Error::msg(format!(
+                                    "reversed line {original} is not part of the archive"
+                                ))
+                            })?);
+                    }
+                    let line = ledger.commit_entry(&mut txn, &entry, cid, &tx_ctx, commited)?;
+                    if let Some(old_line) = old_line {
+                        lines.insert((ledger_id, old_line), line);
+                    }
+                }
+                continue;
+            }
+            if section.name == SUBSCRIPTION_REL_SUB_DB {
+                for (key, value) in &section.entries {
+                    SubscriptionHandler::<S>::write_entry(&mut txn, db_ptr, key, value)?;
+                }
+                continue;
+            }
+            let is_entity_db = section.name == entity_db(&id);
+            let upgrade_pkg = is_entity_db && self.manifest.format_version < 3;
+            for (key, value) in &section.entries {
+                if upgrade_pkg && is_pkg_def_key(key) {
+                    txn.write(db_ptr, key, &upgrade_pkg_def(value)?)?;
+                    continue;
+                }
+                // Action records and log lines of older versions are rewritten, like the migrations do
+                let upgraded = if is_entity_db {
+                    match upgrade_action_record(key, value)? {
+                        Some(record) => Some(record),
+                        None => upgrade_log_line(key, value)?,
+                    }
+                } else {
+                    None
+                };
+                txn.write(db_ptr, key, &upgraded.as_deref().unwrap_or(value))?;
+            }
+        }
+
+        // Sanity check - the archive must contain the entity itself
+        let key = StorageKey::system_key(id, BASE_KEY_METADATA, META_SUB_KEY_ID);
+        if txn.read(&entity_ptr, &key)?.is_none() {
+            txn.abort();
+            return Err(Error::msg(format!("archive does not contain {id}")));
+        }
+        txn.commit()?;
+        info!("Imported {id} from archive");
+        Ok(())
+    }
+}
+
+/// Returns the sub-db, where the data of the entity is stored
+fn entity_db(id: &Id) -> &'static str {
+    match id {
+        Id::Contract { .. } => CONTRACT_SUB_DB,
+        Id::Agent { .. } => AGENT_SUB_DB,
+    }
+}
+
+/// Collects all entries of a sub-db, whose keys start with the given prefix
+fn export_section<S: Db>(db: &S, name: &str, prefix: &[u8]) -> Result<Section> {
+    let db_ptr = db.create_sub_db(name)?;
+    let txn = db.begin_ro_txn()?;
+    let mut cursor = txn.ro_cursor(&db_ptr)?;
+    let entries = cursor
+        .iter_prefix(&prefix)
+        .map(|(k, v)| (k.to_vec(), v.to_vec()))
+        .collect();
+    drop(cursor);
+    txn.commit()?;
+    Ok(Section {
+        name: name.to_string(),
+        entries,
+    })
+}
+
+/// Ledger entry layout of archive format version 1 (before payment terms were added)
+#[derive(Deserialize)]
+struct LedgerEntryV1 {
+    creditor: BorderlessId,
+    debitor: BorderlessId,
+    amount_milli: i64,
+    tax_milli: i64,
+    currency: Currency,
+    kind: EntryType,
+    tag: String,
+}
+
+impl From<LedgerEntryV1> for LedgerEntry {
+    fn from(v1: LedgerEntryV1) -> Self {
+        LedgerEntry {
+            creditor: v1.creditor,
+            debitor: v1.debitor,
+            amount_milli: v1.amount_milli,
+            tax_milli: v1.tax_milli,
+            currency: v1.currency,
+            kind: v1.kind,
+            tag: v1.tag,
+            due_date: None,
+            reference: None,
+            payment_terms: None,
+            reverses: None,
+        }
+    }
+}
+
+/// Ledger entry layout of archive format version 2 (before reversals were added)
+#[derive(Deserialize)]
+struct LedgerEntryV2 {
+    creditor: BorderlessId,
+    debitor: BorderlessId,
+    amount_milli: i64,
+    tax_milli: i64,
+    currency: Currency,
+    kind: EntryType,
+    tag: String,
+    due_date: Option<u64>,
+    reference: Option<String>,
+    payment_terms: Option<u32>,
+}
+
+impl From<LedgerEntryV2> for LedgerEntry {
+    fn from(v2: LedgerEntryV2) -> Self {
+        LedgerEntry {
+            creditor: v2.creditor,
+            debitor: v2.debitor,
+            amount_milli: v2.amount_milli,
+            tax_milli: v2.tax_milli,
+            currency: v2.currency,
+            kind: v2.kind,
+            tag: v2.tag,
+            due_date: v2.due_date,
+            reference: v2.reference,
+            payment_terms: v2.payment_terms,
+            reverses: None,
+        }
+    }
+}
+
+/// Decodes a line of the ledger section
+fn decode_ledger_line(version: u32, value: &[u8]) -> Result<(LedgerEntry, TxCtx, Option<u64>)> {
+    match version {
+        1 => {
+            let (entry, tx_ctx, commited): (LedgerEntryV1, TxCtx, Option<u64>) =
+                postcard::from_bytes(value)?;
+            Ok((entry.into(), tx_ctx, commited))
+        }
+        2 => {
+            let (entry, tx_ctx, commited): (LedgerEntryV2, TxCtx, Option<u64>) =
+                postcard::from_bytes(value)?;
+            Ok((entry.into(), tx_ctx, commited))
+        }
+        _ => Ok(postcard::from_bytes(value)?),
+    }
+}
+
+#[cfg(all(test, feature = "contracts"))]
+mod tests {
+    use super::*;
+    use crate::db::action_log::ActionLog;
+    use crate::db::controller::write_system_value;
+    use crate::LEDGER_INDEX_SUB_DB;
+    use borderless::__private::storage_keys::META_SUB_KEY_PACKAGE_DEF;
+    use borderless::contracts::ledger::{Currency, EntryType};
+    use borderless::pkg::{Author, Capabilities, PkgType};
+    use borderless::{events::CallAction, BorderlessId, ContractId, TxIdentifier};
+    use borderless_kv_store::backend::lmdb::Lmdb;
+    use tempfile::{tempdir, TempDir};
+
+    fn open_tmp_lmdb() -> (Lmdb, TempDir) {
+        let tmp_dir = tempdir().unwrap();
+        let env = Lmdb::new(tmp_dir.path(), 16).unwrap();
+        (env, tmp_dir)
+    }
+
+    fn tx_ctx(number: u64) -> TxCtx {
+        TxCtx {
+            tx_id: TxIdentifier::new(1, number, Hash256::digest(&number.to_be_bytes())),
+            index: 0,
+        }
+    }
+
+    /// Writes a minimal contract with some state, an action and a ledger line
+    fn setup_contract(db: &Lmdb, cid: ContractId, tx_number: u64) {
+        let db_ptr = db.create_sub_db(CONTRACT_SUB_DB).unwrap();
+        for name in [
+            ACTION_TX_REL_SUB_DB,
+            ACTION_INDEX_SUB_DB,
+            LEDGER_SUB_DB,
+            LEDGER_INDEX_SUB_DB,
+        ] {
+            db.create_sub_db(name).unwrap();
+        }
+        let mut txn = db.begin_rw_txn().unwrap();
+        write_system_value::<Lmdb, _, _>(
+            &db_ptr,
+            &mut txn,
+            &cid,
+            BASE_KEY_METADATA,
+            META_SUB_KEY_ID,
+            &cid,
+        )
+        .unwrap();
+        txn.write(&db_ptr, &StorageKey::user_key(cid, 1, 0), b"state")
+            .unwrap();
+        let action = CallAction::by_method("set", serde_json::Value::Null);
+        ActionLog::new(db, cid)
+            .commit(
+                &db_ptr,
+                &mut txn,
+                &action,
+                BorderlessId::generate(),
+                tx_ctx(tx_number),
+                None,
+                None,
+            )
+            .unwrap();
+        let entry = LedgerEntry {
+            creditor: BorderlessId::generate(),
+            debitor: BorderlessId::generate(),
+            amount_milli: 1000,
+            tax_milli: 0,
+            currency: Currency::EUR,
+            kind: EntryType::CREATE,
+            tag: "invoice".to_string(),
+            due_date: Some(1_700_000_000_000),
+            reference: Some("INV-1".to_string()),
+            payment_terms: None,
+            reverses: None,
+        };
+        Ledger::new(db)
+            .commit_entry(&mut txn, &entry, cid, &tx_ctx(tx_number), None)
+            .unwrap();
+        txn.commit().unwrap();
+    }
+
+    #[test]
+    fn export_import_roundtrip() -> Result<()> {
+        let (src, _src_dir) = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let other = ContractId::generate();
+        setup_contract(&src, cid, 1);
+        setup_contract(&src, other, 2);
+
+        let archive = Archive::export(&src, Id::contract(cid))?;
+        let bytes = archive.to_bytes()?;
+
+        let (dst, _dst_dir) = open_tmp_lmdb();
+        let archive = Archive::from_bytes(&bytes)?;
+        archive.import(&dst)?;
+
+        let controller = Controller::new(&dst);
+        assert!(controller.contract_exists(&cid)?);
+        assert!(!controller.contract_exists(&other)?);
+        assert_eq!(controller.actions(cid).len()?, 1);
+        assert!(controller.query_action(&tx_ctx(1).tx_id)?.is_some());
+        assert!(controller.query_action(&tx_ctx(2).tx_id)?.is_none());
+        let entries = Ledger::new(&dst).contract_entries(cid)?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].entry.reference.as_deref(), Some("INV-1"));
+        assert_eq!(entries[0].entry.due_date, Some(1_700_000_000_000));
+
+        let db_ptr = dst.open_sub_db(CONTRACT_SUB_DB)?;
+        let txn = dst.begin_ro_txn()?;
+        let value = txn.read(&db_ptr, &StorageKey::user_key(cid, 1, 0))?;
+        assert_eq!(value, Some(b"state".as_slice()));
+        txn.commit()?;
+
+        // The contract cannot be imported twice
+        assert!(archive.import(&dst).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn subscriptions_are_indexed_on_import() -> Result<()> {
+        use borderless::events::Topic;
+        use borderless::AgentId;
+
+        let (src, _src_dir) = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        setup_contract(&src, cid, 1);
+        src.create_sub_db(SUBSCRIPTION_REL_SUB_DB)?;
+        let subscriber = AgentId::generate();
+        let topic = Topic::new(Id::contract(cid), "paid", "on_paid");
+        SubscriptionHandler::new(&src).subscribe(subscriber, topic.clone())?;
+        let other = Topic::new(Id::contract(ContractId::generate()), "paid", "on_paid");
+        SubscriptionHandler::new(&src).subscribe(subscriber, other)?;
+
+        let archive = Archive::export(&src, Id::contract(cid))?;
+        let (dst, _dst_dir) = open_tmp_lmdb();
+        Archive::from_bytes(&archive.to_bytes()?)?.import(&dst)?;
+
+        let handler = SubscriptionHandler::new(&dst);
+        assert_eq!(
+            handler.get_topic_subscribers(Id::contract(cid), "paid".to_string())?,
+            vec![(subscriber, "on_paid".to_string())]
+        );
+        assert_eq!(handler.get_subscriptions(subscriber)?, vec![topic]);
+        Ok(())
+    }
+
+    #[test]
+    fn reject_corrupted_archive() -> Result<()> {
+        let (db, _dir) = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        setup_contract(&db, cid, 1);
+        let mut bytes = Archive::export(&db, Id::contract(cid))?.to_bytes()?;
+
+        // Flip a byte of the last section
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        assert!(Archive::from_bytes(&bytes).is_err());
+        assert!(Archive::from_bytes(b"not an archive").is_err());
+        assert!(Archive::export(&db, Id::contract(ContractId::generate())).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn upgrade_v2_package_definition() -> Result<()> {
+        let (src, _src_dir) = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        setup_contract(&src, cid, 1);
+        // Package definition without the log buffer size
+        let pkg_def = (
+            "flipper",
+            None::<String>,
+            None::<String>,
+            None::<Capabilities>,
+            PkgType::Contract,
+            (
+                Vec::<Author>::new(),
+                Some("flips a switch"),
+                None::<String>,
+                None::<String>,
+                None::<String>,
+            ),
+        );
+        let db_ptr = src.open_sub_db(CONTRACT_SUB_DB)?;
+        let mut txn = src.begin_rw_txn()?;
+        write_system_value::<Lmdb, _, _>(
+            &db_ptr,
+            &mut txn,
+            &cid,
+            BASE_KEY_METADATA,
+            META_SUB_KEY_PACKAGE_DEF,
+            &pkg_def,
+        )?;
+        txn.commit()?;
+        let mut archive = Archive::export(&src, Id::contract(cid))?;
+        archive.manifest.format_version = 2;
+        // The ledger lines are encoded in the current format
+        archive.sections.retain(|s| s.name != LEDGER_SUB_DB);
+        archive
+            .manifest
+            .sections
+            .retain(|s| s.name != LEDGER_SUB_DB);
+
+        let (dst, _dst_dir) = open_tmp_lmdb();
+        archive.import(&dst)?;
+        let pkg_def = Controller::new(&dst).contract_pkg_def(&cid)?.unwrap();
+        assert_eq!(pkg_def.name, "flipper");
+        assert_eq!(pkg_def.meta.description.as_deref(), Some("flips a switch"));
+        assert!(pkg_def.meta.log_buffer_size.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_v1_ledger_lines() -> Result<()> {
+        let entry = (
+            BorderlessId::generate(),
+            BorderlessId::generate(),
+            1000i64,
+            0i64,
+            Currency::EUR,
+            EntryType::CREATE,
+            "invoice".to_string(),
+        );
+        let line = postcard::to_allocvec(&(&entry, tx_ctx(1), Some(42u64)))?;
+        let (decoded, tx, commited) = decode_ledger_line(1, &line)?;
+        assert_eq!(decoded.amount_milli, 1000);
+        assert_eq!(decoded.tag, "invoice");
+        assert_eq!(tx.tx_id, tx_ctx(1).tx_id);
+        assert_eq!(commited, Some(42));
+        assert!(decoded.reference.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_v2_ledger_lines() -> Result<()> {
+        let entry = (
+            BorderlessId::generate(),
+            BorderlessId::generate(),
+            1000i64,
+            0i64,
+            Currency::EUR,
+            EntryType::CREATE,
+            "invoice".to_string(),
+            Some(7u64),
+            Some("INV-1".to_string()),
+            Some(30u32),
+        );
+        let line = postcard::to_allocvec(&(&entry, tx_ctx(1), Some(42u64)))?;
+        let (decoded, tx, commited) = decode_ledger_line(2, &line)?;
+        assert_eq!(decoded.due_date, Some(7));
+        assert_eq!(decoded.reference.as_deref(), Some("INV-1"));
+        assert_eq!(decoded.payment_terms, Some(30));
+        assert!(decoded.reverses.is_none());
+        assert_eq!(tx.tx_id, tx_ctx(1).tx_id);
+        assert_eq!(commited, Some(42));
+        // The current layout is not compatible with version 2
+        assert!(decode_ledger_line(ARCHIVE_FORMAT_VERSION, &line).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn import_v2_ledger_lines() -> Result<()> {
+        let (src, _src_dir) = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        setup_contract(&src, cid, 1);
+        let mut archive = Archive::export(&src, Id::contract(cid))?;
+
+        // Re-encode the ledger lines without the reversed line
+        let idx = archive
+            .sections
+            .iter()
+            .position(|s| s.name == LEDGER_SUB_DB)
+            .unwrap();
+        let section = &mut archive.sections[idx];
+        assert!(!section.entries.is_empty());
+        for (_, value) in section.entries.iter_mut() {
+            let (e, tx_ctx, commited): (LedgerEntry, TxCtx, Option<u64>) =
+                postcard::from_bytes(value)?;
+            let v2 = (
+                e.creditor,
+                e.debitor,
+                e.amount_milli,
+                e.tax_milli,
+                e.currency,
+                e.kind,
+                e.tag,
+                e.due_date,
+                e.reference,
+                e.payment_terms,
+            );
+            *value = postcard::to_allocvec(&(v2, tx_ctx, commited))?;
+        }
+        archive.manifest.sections[idx] = section.info()?;
+        archive.manifest.format_version = 2;
+
+        let (dst, _dst_dir) = open_tmp_lmdb();
+        Archive::from_bytes(&archive.to_bytes()?)?.import(&dst)?;
+        let entries = Ledger::new(&dst).contract_entries(cid)?;
+        assert_eq!(entries.len(), archive.sections[idx].entries.len());
+        for line in entries {
+            assert_eq!(line.entry.due_date, Some(1_700_000_000_000));
+            assert_eq!(line.entry.reference.as_deref(), Some("INV-1"));
+            assert!(line.entry.reverses.is_none());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn import_legacy_action_records() -> Result<()> {
+        use crate::db::action_log::ActionRecord;
+        use borderless::__private::storage_keys::BASE_KEY_ACTION_LOG;
+
+        let (src, _src_dir) = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        setup_contract(&src, cid, 1);
+        let mut archive = Archive::export(&src, Id::contract(cid))?;
+
+        // Re-encode the action record in the layout before the writer was tracked
+        let idx = archive
+            .sections
+            .iter()
+            .position(|s| s.name == CONTRACT_SUB_DB)
+            .unwrap();
+        let section = &mut archive.sections[idx];
+        let record_key = StorageKey::system_key(cid, BASE_KEY_ACTION_LOG, 0);
+        let (_, value) = section
+            .entries
+            .iter_mut()
+            .find(|(key, _)| key.as_slice() == record_key.as_ref())
+            .unwrap();
+        let record: ActionRecord = postcard::from_bytes(value)?;
+        *value = postcard::to_allocvec(&(
+            record.tx_ctx,
+            serde_bytes::Bytes::new(&record.value),
+            record.commited,
+        ))?;
+        archive.manifest.sections[idx] = section.info()?;
+
+        let (dst, _dst_dir) = open_tmp_lmdb();
+        Archive::from_bytes(&archive.to_bytes()?)?.import(&dst)?;
+        let record = Controller::new(&dst).actions(cid).get(0)?.unwrap();
+        assert_eq!(record.tx_ctx.tx_id, tx_ctx(1).tx_id);
+        assert!(record.writer.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn reversals_point_to_imported_lines() -> Result<()> {
+        let [alice, bob] = [(); 2].map(|_| BorderlessId::generate());
+        let commit = |db: &Lmdb, cid, kind, reverses| {
+            let entry = LedgerEntry {
+                creditor: alice,
+                debitor: bob,
+                amount_milli: 1000,
+                tax_milli: 0,
+                currency: Currency::EUR,
+                kind,
+                tag: String::new(),
+                due_date: None,
+                reference: None,
+                payment_terms: None,
+                reverses,
+            };
+            let mut txn = db.begin_rw_txn().unwrap();
+            Ledger::new(db)
+                .commit_entry(&mut txn, &entry, cid, &tx_ctx(1), None)
+                .unwrap();
+            txn.commit().unwrap();
+        };
+        let (src, _src_dir) = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        setup_contract(&src, cid, 1);
+        commit(&src, cid, EntryType::CREATE, None);
+        commit(&src, cid, EntryType::REVERSAL, Some(0));
+        let archive = Archive::export(&src, Id::contract(cid))?;
+
+        // The ledger between alice and bob already has a line on the target
+        let (dst, _dst_dir) = open_tmp_lmdb();
+        setup_contract(&dst, ContractId::generate(), 2);
+        commit(&dst, ContractId::generate(), EntryType::CREATE, None);
+        Archive::from_bytes(&archive.to_bytes()?)?.import(&dst)?;
+
+        let entries = Ledger::new(&dst).contract_entries(cid)?;
+        let reversal = entries
+            .iter()
+            .find(|l| l.entry.kind == EntryType::REVERSAL)
+            .unwrap();
+        assert_eq!(reversal.entry.reverses, Some(1));
+        let meta = Ledger::new(&dst)
+            .open(alice, bob)
+            .meta()?
+            .unwrap()
+            .into_dto();
+        assert_eq!(meta.balances[&Currency::EUR], 1.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+//! Change-data-capture of the contract state
+//!
+//! The [`ChangeFeed`] is a [`CommitHook`], that has to be registered at a [`HookedDb`](borderless_kv_store::backend::hooks::HookedDb).
+//! It extracts all changes of user-space keys (which make up the state of a contract) from the committed transactions,
+//! and broadcasts them per contract to all subscribers.
+//! Changes of system-keys (like the action-log or metadata) and of other sub-databases are not part of the feed.
+
+use std::{collections::BTreeMap, pin::Pin, sync::Arc};
+
+use borderless::{__private::storage_keys::StorageKey, ContractId};
+use borderless_kv_store::backend::hooks::{ChangeSet, CommitHook};
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::log_shim::warn;
+use crate::CONTRACT_SUB_DB;
+
+/// Number of committed transactions that can be buffered, before a slow subscriber starts to lag behind
+const FEED_CHANNEL_SIZE: usize = 1024;
+
+/// Change of a single user-space key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateChange {
+    pub base_key: u64,
+    pub sub_key: u64,
+    /// New value of the key - `None` if the key was removed
+    #[serde(with = "serde_bytes")]
+    pub value: Option<Vec<u8>>,
+}
+
+/// All state changes of a contract within a single committed transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    pub cid: ContractId,
+    pub changes: Vec<StateChange>,
+}
+
+/// Stream of the change events of a single contract
+pub type ChangeStream = Pin<Box<dyn Stream<Item = Arc<ChangeEvent>> + Send>>;
+
+/// Broadcasts the state changes of all committed transactions
+#[derive(Clone)]
+pub struct ChangeFeed {
+    tx: broadcast::Sender<Arc<ChangeEvent>>,
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChangeFeed {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(FEED_CHANNEL_SIZE);
+        Self { tx }
+    }
+
+    /// Subscribes to the change events of all contracts
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<ChangeEvent>> {
+        self.tx.subscribe()
+    }
+
+    /// Subscribes to the change events of the given contract
+    ///
+    /// The stream ends, once the feed is dropped.
+    pub fn stream(&self, cid: ContractId) -> ChangeStream {
+        let rx = self.tx.subscribe();
+        let stream = futures_util::stream::unfold(rx, move |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) if event.cid == cid => return Some((event, rx)),
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(n)) => {
+                        warn!("change stream for {cid} lagged behind, skipped {n} events")
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        Box::pin(stream)
+    }
+}
+
+impl CommitHook for ChangeFeed {
+    fn on_commit(&self, changes: &ChangeSet) {
+        // Avoid the work, if nobody is listening
+        if self.tx.receiver_count() == 0 {
+            return;
+        }
+        let mut per_contract: BTreeMap<ContractId, Vec<StateChange>> = BTreeMap::new();
+        for change in changes.sub_db(CONTRACT_SUB_DB) {
+            let Ok(key) = StorageKey::try_from(change.key()) else {
+                continue;
+            };
+            let Some(cid) = key.contract_id() else {
+                continue;
+            };
+            if !key.is_user_key() {
+                continue;
+            }
+            per_contract.entry(cid).or_default().push(StateChange {
+                base_key: key.base_key(),
+                sub_key: key.sub_key(),
+                value: change.value().map(<[u8]>::to_vec),
+            });
+        }
+        for (cid, changes) in per_contract {
+            // NOTE: This only fails, if there are no receivers
+            let _ = self.tx.send(Arc::new(ChangeEvent { cid, changes }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borderless::__private::storage_keys::BASE_KEY_ACTION_LOG;
+    use borderless_kv_store::backend::{hooks::HookedDb, memdb::MemDb};
+    use borderless_kv_store::*;
+    use futures_util::{FutureExt, StreamExt};
+
+    #[test]
+    fn publishes_user_changes_per_contract() -> crate::Result<()> {
+        let db = HookedDb::new(MemDb::new());
+        let feed = ChangeFeed::new();
+        db.add_hook(feed.clone());
+        let db_ptr = db.create_sub_db(CONTRACT_SUB_DB)?;
+        let (cid, other) = (ContractId::generate(), ContractId::generate());
+        let mut all = feed.subscribe();
+        let mut stream = feed.stream(cid);
+
+        let mut txn = db.begin_rw_txn()?;
+        txn.write(&db_ptr, &StorageKey::user_key(cid, 1, 2), &[1, 2, 3])?;
+        txn.write(&db_ptr, &StorageKey::user_key(other, 1, 0), &[4])?;
+        txn.delete(&db_ptr, &StorageKey::user_key(cid, 5, 0))?;
+        // System keys are not part of the feed
+        txn.write(
+            &db_ptr,
+            &StorageKey::system_key(cid, BASE_KEY_ACTION_LOG, 0),
+            &[0],
+        )?;
+        txn.commit()?;
+
+        // NOTE: The events are already buffered in the channel, so the stream is ready immediately
+        let event = stream
+            .next()
+            .now_or_never()
+            .flatten()
+            .expect("event is ready");
+        assert_eq!(event.cid, cid);
+        assert_eq!(event.changes.len(), 2);
+        let key = StorageKey::user_key(cid, 1, 2);
+        assert_eq!(event.changes[0].base_key, key.base_key());
+        assert_eq!(event.changes[0].value.as_deref(), Some(&[1, 2, 3][..]));
+        assert_eq!(event.changes[1].value, None);
+
+        let mut cids = vec![all.try_recv().unwrap().cid, all.try_recv().unwrap().cid];
+        cids.sort();
+        let mut expected = vec![cid, other];
+        expected.sort();
+        assert_eq!(cids, expected);
+
+        assert!(stream.next().now_or_never().is_none());
+
+        let json = serde_json::to_value(&*event)?;
+        assert_eq!(json["changes"][0]["value"], serde_json::json!([1, 2, 3]));
+        Ok(())
+    }
+}
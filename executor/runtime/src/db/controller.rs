@@ -1,27 +1,30 @@
 use super::{
     action_log::{ActionLog, ActionRecord, RelTxAction},
+    history::StateHistory,
     ledger::Ledger,
     logger::Logger,
+    state_tree::StateTree,
     subscriptions::SubscriptionHandler,
 };
-use crate::log_shim::warn;
+use crate::log_shim::{debug, warn};
 use crate::{Result, ACTION_TX_REL_SUB_DB, AGENT_SUB_DB, CONTRACT_SUB_DB};
 use borderless::common::Participant;
 use borderless::events::{Events, Topic};
 use borderless::{
-    common::{Description, Metadata, Revocation},
-    contracts::Info,
-    ContractId,
     __private::storage_keys::*,
+    common::{Description, Introduction, Metadata, Revocation},
+    contracts::Info,
     events::Sink,
     hash::Hash256,
     http::{AgentInfo, ContractInfo},
-    pkg::{Source, SourceFlattened, WasmPkg, WasmPkgNoSource},
+    pkg::{
+        Author, Capabilities, PkgMeta, PkgType, Source, SourceFlattened, WasmPkg, WasmPkgNoSource,
+    },
     prelude::{Id, TxCtx},
-    AgentId, TxIdentifier,
+    AgentId, ContractId, TxIdentifier,
 };
 use borderless_kv_store::*;
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Deserialize};
 
 /// Model-controller to retrieve information about a contract from the key-value storage.
 pub struct Controller<'a, S: Db> {
@@ -33,11 +36,44 @@ impl<'a, S: Db> Controller<'a, S> {
         Self { db }
     }
 
+    /// Returns the ids of all contracts in the database
+    pub fn contract_ids(&self) -> Result<Vec<ContractId>> {
+        let db_ptr = match self.db.open_sub_db(CONTRACT_SUB_DB) {
+            Ok(db_ptr) => db_ptr,
+            Err(Error::DbNotFound(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let txn = self.db.begin_ro_txn()?;
+        let mut cursor = txn.ro_cursor(&db_ptr)?;
+        let ids = cursor
+            .iter()
+            .filter(|(key, _)| matches_system_key(key, BASE_KEY_METADATA, META_SUB_KEY_ID))
+            .map(|(key, _)| {
+                let mut id = [0u8; 16];
+                id.copy_from_slice(&key[..16]);
+                ContractId::from_bytes(id)
+            })
+            .collect();
+        drop(cursor);
+        txn.commit()?;
+        Ok(ids)
+    }
+
     /// Returns the [`ActionLog`] of the contract
     pub fn actions(&self, cid: ContractId) -> ActionLog<'a, S> {
         ActionLog::new(self.db, cid)
     }
 
+    /// Returns the [`StateHistory`] of the contract
+    pub fn history(&self, cid: ContractId) -> StateHistory<'a, S> {
+        StateHistory::new(self.db, cid)
+    }
+
+    /// Returns the [`StateTree`] of the contract
+    pub fn state_tree(&self, cid: ContractId) -> StateTree<'a, S> {
+        StateTree::new(self.db, cid)
+    }
+
     /// Returns the [`Logger`] of the contract or agent
     pub fn logs(&self, id: impl Into<Id>) -> Logger<'a, S> {
         Logger::new(self.db, id)
@@ -185,6 +221,53 @@ impl<'a, S: Db> Controller<'a, S> {
         }))
     }
 
+    /// Returns the initial state of the contract
+    ///
+    /// Returns `None` for contracts, whose initial state was written in the legacy (non-decodable) format.
+    pub fn contract_initial_state(&self, cid: &ContractId) -> Result<Option<serde_json::Value>> {
+        let state: Option<String> = self
+            .read_value(
+                &Id::contract(*cid),
+                BASE_KEY_METADATA,
+                META_SUB_KEY_INIT_STATE,
+            )
+            .unwrap_or_default();
+        Ok(state.and_then(|s| serde_json::from_str(&s).ok()))
+    }
+
+    /// Reconstructs the [`Introduction`] of the contract from the stored metadata
+    ///
+    /// Returns `None`, if the contract does not exist or parts of the introduction are not available.
+    pub fn contract_introduction(&self, cid: &ContractId) -> Result<Option<Introduction>> {
+        let id = Id::contract(*cid);
+        let participants = self.read_value(&id, BASE_KEY_METADATA, META_SUB_KEY_PARTICIPANTS)?;
+        let sinks = self.read_value(&id, BASE_KEY_METADATA, META_SUB_KEY_SINKS)?;
+        let initial_state = self.contract_initial_state(cid)?;
+        let desc = self.contract_desc(cid)?;
+        let meta = self.contract_meta(cid)?;
+        let package = self.contract_pkg_full(cid)?;
+        match (participants, sinks, initial_state, desc, meta, package) {
+            (
+                Some(participants),
+                Some(sinks),
+                Some(initial_state),
+                Some(desc),
+                Some(meta),
+                Some(package),
+            ) => Ok(Some(Introduction {
+                id,
+                participants,
+                initial_state,
+                sinks,
+                subscriptions: Vec::new(),
+                desc,
+                meta,
+                package,
+            })),
+            _ => Ok(None),
+        }
+    }
+
     /// Returns the [`Revocation`] of the contract, if any.
     pub fn contract_revocation(&self, cid: &ContractId) -> Result<Option<Revocation>> {
         self.read_value(
@@ -465,16 +548,21 @@ pub(crate) fn write_introduction<S: Db>(
         &introduction.meta,
     )?;
 
-    // Write initial state
+    // Write initial state (as json string, because postcard cannot decode a `serde_json::Value`)
     write_system_value::<S, _, _>(
         db_ptr,
         txn,
         &id,
         BASE_KEY_METADATA,
         META_SUB_KEY_INIT_STATE,
-        &introduction.initial_state,
+        &introduction.initial_state.to_string(),
     )?;
 
+    // Apply the log buffer size of the package
+    if let Some(capacity) = introduction.package.meta.log_buffer_size {
+        super::logger::init_capacity::<S>(db_ptr, txn, id, capacity)?;
+    }
+
     // Write package and source (flattened, because postcard does not support untagged enums)
     let (pkg_def, pkg_source) = introduction.package.into_def_and_source();
     let pkg_source = pkg_source.flatten();
@@ -545,3 +633,100 @@ pub(crate) fn write_revocation<S: Db>(
     )?;
     Ok(())
 }
+
+/// Layout of [`PkgMeta`] before the log buffer size was added
+#[derive(Deserialize)]
+struct PkgMetaV1 {
+    authors: Vec<Author>,
+    description: Option<String>,
+    documentation: Option<String>,
+    license: Option<String>,
+    repository: Option<String>,
+}
+
+/// Layout of [`WasmPkgNoSource`] before the log buffer size was added to the package metadata
+#[derive(Deserialize)]
+struct WasmPkgNoSourceV1 {
+    name: String,
+    app_name: Option<String>,
+    app_module: Option<String>,
+    capabilities: Option<Capabilities>,
+    pkg_type: PkgType,
+    meta: PkgMetaV1,
+}
+
+impl From<WasmPkgNoSourceV1> for WasmPkgNoSource {
+    fn from(value: WasmPkgNoSourceV1) -> Self {
+        Self {
+            name: value.name,
+            app_name: value.app_name,
+            app_module: value.app_module,
+            capabilities: value.capabilities,
+            pkg_type: value.pkg_type,
+            meta: PkgMeta {
+                authors: value.meta.authors,
+                description: value.meta.description,
+                documentation: value.meta.documentation,
+                license: value.meta.license,
+                repository: value.meta.repository,
+                log_buffer_size: None,
+            },
+        }
+    }
+}
+
+/// Rewrites all package definitions in the sub-db, that were stored before the log buffer size was added
+///
+/// Definitions, that can already be decoded with the current layout, are left untouched.
+pub(crate) fn upgrade_pkg_defs<S: Db>(db: &S, sub_db: &str) -> Result<()> {
+    let db_ptr = db.open_sub_db(sub_db)?;
+    let mut upgraded = Vec::new();
+    {
+        let txn = db.begin_ro_txn()?;
+        let mut cursor = txn.ro_cursor(&db_ptr)?;
+        for (key, value) in cursor.iter() {
+            if !is_pkg_def_key(key) || postcard::from_bytes::<WasmPkgNoSource>(value).is_ok() {
+                continue;
+            }
+            upgraded.push((key.to_vec(), upgrade_pkg_def(value)?));
+        }
+        drop(cursor);
+        txn.commit()?;
+    }
+    let mut txn = db.begin_rw_txn()?;
+    for (key, value) in &upgraded {
+        txn.write(&db_ptr, key, value)?;
+    }
+    txn.commit()?;
+    debug!(
+        "upgraded {} package definitions in '{sub_db}'",
+        upgraded.len()
+    );
+    Ok(())
+}
+
+/// Re-encodes a package definition, that was stored before the log buffer size was added
+pub(crate) fn upgrade_pkg_def(bytes: &[u8]) -> Result<Vec<u8>> {
+    let pkg_def: WasmPkgNoSource = postcard::from_bytes::<WasmPkgNoSourceV1>(bytes)?.into();
+    Ok(postcard::to_allocvec(&pkg_def)?)
+}
+
+/// Returns `true` if the key is the system-key of a package definition (for any contract or agent)
+pub(crate) fn is_pkg_def_key(key: &[u8]) -> bool {
+    matches_system_key(key, BASE_KEY_METADATA, META_SUB_KEY_PACKAGE_DEF)
+}
+
+/// Returns `true` if the key is the system-key with the given base- and sub-key (for any contract or agent)
+fn matches_system_key(key: &[u8], base_key: u64, sub_key: u64) -> bool {
+    system_sub_key(key, base_key) == Some(sub_key)
+}
+
+/// Returns the sub-key, if the key is a system-key with the given base-key (for any contract or agent)
+pub(crate) fn system_sub_key(key: &[u8], base_key: u64) -> Option<u64> {
+    if key.len() != 32 || key[16..24] != base_key.to_be_bytes() {
+        return None;
+    }
+    Some(u64::from_be_bytes(
+        key[24..].try_into().expect("Slice length error"),
+    ))
+}
@@ -0,0 +1,508 @@
+//! Exchange rates for base-currency reporting
+//!
+//! The rates are supplied by the host (either imported from a file or set via the ledger api),
+//! and each rate is valid from a given date until it is superseded by a newer rate.
+//!
+//! All conversions are done with exact fixed-point arithmetic on milli-units:
+//! rates have nine fractional digits, and results are rounded half away from zero.
+
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use borderless::contracts::ledger::{Currency, Money};
+use borderless_kv_store::codec::{KeyCodec, ValueCodec};
+use borderless_kv_store::table::Table;
+use borderless_kv_store::{self as kv, Db, Tx};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Error, Result, FX_RATES_SUB_DB};
+
+/// Number of fractional digits of an [`FxRate`]
+const RATE_DECIMALS: u32 = 9;
+
+/// Scale of an [`FxRate`] ( 1.0 = 10^9 )
+const RATE_SCALE: i128 = 10i128.pow(RATE_DECIMALS);
+
+/// Milliseconds per day
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+/// Exchange rate as fixed-point decimal number with nine fractional digits
+///
+/// The rate describes how many units of the target currency are equal to one unit of the source currency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FxRate(u64);
+
+impl FxRate {
+    /// A rate of exactly one
+    pub const ONE: FxRate = FxRate(RATE_SCALE as u64);
+
+    /// Converts an amount in milli-units (rounded half away from zero)
+    ///
+    /// Returns an error, if the converted amount does not fit into an `i64`.
+    pub fn convert(&self, amount_milli: i64) -> Result<i64> {
+        let product = amount_milli as i128 * self.0 as i128;
+        let half = RATE_SCALE / 2;
+        let rounded = if product >= 0 {
+            (product + half) / RATE_SCALE
+        } else {
+            (product - half) / RATE_SCALE
+        };
+        i64::try_from(rounded).map_err(|_| Error::msg("overflow while converting balances"))
+    }
+
+    /// Returns the inverse rate (rounded to nine fractional digits)
+    ///
+    /// Returns an error, if the inverse is too small to be represented.
+    pub fn inverse(&self) -> Result<FxRate> {
+        let scale_sq = RATE_SCALE * RATE_SCALE;
+        let rate = self.0 as i128;
+        match ((scale_sq + rate / 2) / rate) as u64 {
+            0 => Err(Error::msg(format!(
+                "exchange rate {self} has no representable inverse"
+            ))),
+            inverse => Ok(FxRate(inverse)),
+        }
+    }
+}
+
+impl FromStr for FxRate {
+    type Err = Error;
+
+    /// Parses a decimal number like `1.0845` without going through floating point numbers
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::msg(format!("invalid exchange rate '{s}' - {reason}"));
+        let (integral, fraction) = s.trim().split_once('.').unwrap_or((s.trim(), ""));
+        if integral.is_empty() && fraction.is_empty() {
+            return Err(invalid("empty value"));
+        }
+        let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if !all_digits(integral) || !all_digits(fraction) {
+            return Err(invalid("expected a positive decimal number"));
+        }
+        if fraction.len() > RATE_DECIMALS as usize {
+            return Err(invalid("too many fractional digits"));
+        }
+        let integral: u64 = match integral {
+            "" => 0,
+            i => i.parse().map_err(|_| invalid("value too large"))?,
+        };
+        let fraction: u64 = format!("{fraction:0<9}")
+            .parse()
+            .expect("fraction consists of nine digits");
+        let rate = integral
+            .checked_mul(RATE_SCALE as u64)
+            .and_then(|i| i.checked_add(fraction))
+            .ok_or_else(|| invalid("value too large"))?;
+        if rate == 0 {
+            return Err(invalid("rate must not be zero"));
+        }
+        Ok(FxRate(rate))
+    }
+}
+
+impl fmt::Display for FxRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scale = RATE_SCALE as u64;
+        let fraction = format!("{:09}", self.0 % scale);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            write!(f, "{}", self.0 / scale)
+        } else {
+            write!(f, "{}.{fraction}", self.0 / scale)
+        }
+    }
+}
+
+impl Serialize for FxRate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FxRate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A calendar date (UTC), which is stored as number of days since unix-epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FxDate(u32);
+
+impl FxDate {
+    /// Returns the date of the given timestamp (milliseconds since unix-epoch)
+    pub fn from_millis(timestamp: u64) -> Self {
+        FxDate((timestamp / DAY_MILLIS) as u32)
+    }
+}
+
+impl FromStr for FxDate {
+    type Err = Error;
+
+    /// Parses a date in the format `YYYY-MM-DD`
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::msg(format!("invalid date '{s}' - expected YYYY-MM-DD"));
+        let mut parts = s.trim().splitn(3, '-');
+        let mut next = || -> Result<i64> {
+            parts
+                .next()
+                .and_then(|p| p.parse().ok())
+                .ok_or_else(invalid)
+        };
+        let (year, month, day) = (next()?, next()?, next()?);
+        if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return Err(invalid());
+        }
+        // Days from civil date (see http://howardhinnant.github.io/date_algorithms.html)
+        let y = if month <= 2 { year - 1 } else { year };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let mp = (month + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146097 + doe - 719468;
+        u32::try_from(days).map(FxDate).map_err(|_| invalid())
+    }
+}
+
+impl fmt::Display for FxDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Civil date from days (see http://howardhinnant.github.io/date_algorithms.html)
+        let z = self.0 as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        write!(f, "{year:04}-{month:02}-{day:02}")
+    }
+}
+
+impl Serialize for FxDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FxDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Exchange rate from one currency to another, that is valid from the given date
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateEntry {
+    pub from: Currency,
+    pub to: Currency,
+    pub valid_from: FxDate,
+    pub rate: FxRate,
+}
+
+/// Total of all balances converted into a base currency
+#[derive(Debug, Clone, Serialize)]
+pub struct BaseTotal {
+    pub currency: Currency,
+    /// Converted total (formatted)
+    pub total: String,
+    /// Converted total in milli-units
+    pub total_milli: i64,
+    /// Date of the exchange rates, that were used for the conversion
+    pub date: FxDate,
+    /// Currencies without exchange rate (these are not part of the total)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing_rates: Vec<Currency>,
+}
+
+/// Exchange-rate table of the runtime
+pub struct FxRates<'a, S: Db> {
+    db: &'a S,
+}
+
+/// Key of a rate: `[ from | to | valid-from ]` (currencies are stored by their numeric code)
+type RateKey = (u32, u32, FxDate);
+
+type RateTable<S> = Table<S, RateKey, FxRate>;
+
+impl KeyCodec for FxDate {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        self.0.encode_key(out);
+    }
+
+    fn decode_key(input: &[u8]) -> std::result::Result<(Self, &[u8]), kv::Error> {
+        let (days, rest) = u32::decode_key(input)?;
+        Ok((FxDate(days), rest))
+    }
+}
+
+impl ValueCodec for FxRate {
+    fn encode_value(&self) -> std::result::Result<Vec<u8>, kv::Error> {
+        self.0.encode_value()
+    }
+
+    fn decode_value(bytes: &[u8]) -> std::result::Result<Self, kv::Error> {
+        u64::decode_value(bytes).map(FxRate)
+    }
+}
+
+fn rate_key(from: Currency, to: Currency, valid_from: FxDate) -> RateKey {
+    (from as u32, to as u32, valid_from)
+}
+
+impl<'a, S: Db> FxRates<'a, S> {
+    pub fn new(db: &'a S) -> Self {
+        Self { db }
+    }
+
+    /// Inserts or replaces the given rates
+    pub fn set(&self, rates: &[RateEntry]) -> Result<()> {
+        let table = RateTable::create(self.db, FX_RATES_SUB_DB)?;
+        let mut txn = self.db.begin_rw_txn()?;
+        for entry in rates {
+            if entry.from == entry.to {
+                txn.abort();
+                return Err(Error::msg(format!(
+                    "exchange rate from {:?} to itself is not allowed",
+                    entry.from
+                )));
+            }
+            let key = rate_key(entry.from, entry.to, entry.valid_from);
+            table.put(&mut txn, &key, &entry.rate)?;
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Imports rates from a csv file with the columns `date,from,to,rate` (e.g. `2024-01-31,USD,EUR,0.9245`)
+    ///
+    /// Empty lines, comments (starting with `#`) and a header line are skipped.
+    /// Returns the number of imported rates.
+    pub fn import_csv(&self, reader: impl BufRead) -> Result<usize> {
+        let mut rates = Vec::new();
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("date") {
+                continue;
+            }
+            let entry =
+                parse_csv_line(line).map_err(|e| Error::msg(format!("line {}: {e}", idx + 1)))?;
+            rates.push(entry);
+        }
+        self.set(&rates)?;
+        Ok(rates.len())
+    }
+
+    /// Returns all rates of the table
+    pub fn all(&self) -> Result<Vec<RateEntry>> {
+        let table = RateTable::create(self.db, FX_RATES_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let mut out = Vec::new();
+        for item in table.iter(&txn)? {
+            let ((from, to, valid_from), rate) = item?;
+            // Skip rates of currencies, that are unknown to this version
+            let (Some(from), Some(to)) = (currency(from), currency(to)) else {
+                continue;
+            };
+            out.push(RateEntry {
+                from,
+                to,
+                valid_from,
+                rate,
+            });
+        }
+        txn.commit()?;
+        Ok(out)
+    }
+
+    /// Returns the rate from one currency to another, that is valid at the given date
+    ///
+    /// If there is no direct rate, the inverse of the opposite rate is used.
+    pub fn rate(&self, from: Currency, to: Currency, date: FxDate) -> Result<Option<FxRate>> {
+        if from == to {
+            return Ok(Some(FxRate::ONE));
+        }
+        let table = RateTable::create(self.db, FX_RATES_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let direct = lookup(&table, &txn, from, to, date)?;
+        let rate = match direct {
+            Some(rate) => Some(rate),
+            None => lookup(&table, &txn, to, from, date)?
+                .map(|r| r.inverse())
+                .transpose()?,
+        };
+        txn.commit()?;
+        Ok(rate)
+    }
+
+    /// Converts all balances (in milli-units) into the base currency and sums them up
+    pub fn total<'b>(
+        &self,
+        balances: impl IntoIterator<Item = (&'b Currency, &'b i64)>,
+        base: Currency,
+        date: FxDate,
+    ) -> Result<BaseTotal> {
+        let mut total_milli: i64 = 0;
+        let mut missing_rates = Vec::new();
+        for (currency, amount) in balances {
+            match self.rate(*currency, base, date)? {
+                Some(rate) => {
+                    total_milli = total_milli
+                        .checked_add(rate.convert(*amount)?)
+                        .ok_or_else(|| Error::msg("overflow while converting balances"))?;
+                }
+                None => missing_rates.push(*currency),
+            }
+        }
+        missing_rates.sort_by_key(|c| *c as u32);
+        Ok(BaseTotal {
+            currency: base,
+            total: Money::from_milli(base, total_milli).to_string(),
+            total_milli,
+            date,
+            missing_rates,
+        })
+    }
+}
+
+/// Finds the latest rate for the pair, that is valid at the given date
+fn lookup<S: Db>(
+    table: &RateTable<S>,
+    txn: &<S as Db>::RoTx<'_>,
+    from: Currency,
+    to: Currency,
+    date: FxDate,
+) -> Result<Option<FxRate>> {
+    let range = rate_key(from, to, FxDate(0))..=rate_key(from, to, date);
+    let rate = table
+        .range_rev(txn, range)?
+        .next()
+        .transpose()?
+        .map(|(_, rate)| rate);
+    Ok(rate)
+}
+
+fn currency(code: u32) -> Option<Currency> {
+    Currency::from_be_bytes(&code.to_be_bytes())
+}
+
+fn parse_csv_line(line: &str) -> Result<RateEntry> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let [date, from, to, rate] = fields.as_slice() else {
+        return Err(Error::msg("expected the columns date,from,to,rate"));
+    };
+    Ok(RateEntry {
+        from: parse_currency(from)?,
+        to: parse_currency(to)?,
+        valid_from: date.parse()?,
+        rate: rate.parse()?,
+    })
+}
+
+/// Parses a currency by its ISO 4217 code
+pub fn parse_currency(code: &str) -> Result<Currency> {
+    // Currencies use their code as variant name in serde
+    let value = serde_json::Value::String(code.to_ascii_uppercase());
+    serde_json::from_value(value).map_err(|_| Error::msg(format!("invalid currency '{code}'")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borderless_kv_store::backend::lmdb::Lmdb;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    #[test]
+    fn parse_and_display_rates() -> Result<()> {
+        assert_eq!("1.0845".parse::<FxRate>()?, FxRate(1_084_500_000));
+        assert_eq!(".5".parse::<FxRate>()?.to_string(), "0.5");
+        assert_eq!("2".parse::<FxRate>()?.to_string(), "2");
+        assert_eq!("0.000000001".parse::<FxRate>()?, FxRate(1));
+        assert!("0.0000000001".parse::<FxRate>().is_err());
+        assert!("-1.2".parse::<FxRate>().is_err());
+        assert!("0".parse::<FxRate>().is_err());
+        assert!("1e3".parse::<FxRate>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn exact_conversion() -> Result<()> {
+        let rate: FxRate = "0.1".parse()?;
+        // 0.1 is not representable as float, but the conversion is exact
+        assert_eq!(rate.convert(3_000)?, 300);
+        assert_eq!(rate.convert(5)?, 1);
+        assert_eq!(rate.convert(-5)?, -1);
+        assert_eq!(rate.convert(4)?, 0);
+        assert_eq!("1.25".parse::<FxRate>()?.inverse()?, "0.8".parse()?);
+        let big = FxRate(u64::MAX);
+        assert_eq!(
+            big.convert(1_000)?,
+            (u64::MAX as i128 * 1000 / RATE_SCALE) as i64 + 1
+        );
+        // Overflows are reported instead of truncated
+        assert!(big.convert(i64::MAX).is_err());
+        assert!(big.convert(i64::MIN).is_err());
+        // The inverse of very large rates would be zero
+        assert!(big.inverse().is_err());
+        assert_eq!("2000000000".parse::<FxRate>()?.inverse()?, FxRate(1));
+        Ok(())
+    }
+
+    #[test]
+    fn dates() -> Result<()> {
+        for date in ["1970-01-01", "2000-02-29", "2024-12-31", "2100-03-01"] {
+            assert_eq!(date.parse::<FxDate>()?.to_string(), date);
+        }
+        assert_eq!("1970-01-02".parse::<FxDate>()?, FxDate(1));
+        assert_eq!(FxDate::from_millis(DAY_MILLIS * 3 + 1), FxDate(3));
+        assert!("2024-13-01".parse::<FxDate>().is_err());
+        assert!("yesterday".parse::<FxDate>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rates_are_valid_per_date() -> Result<()> {
+        let tmp_dir = tempdir().unwrap();
+        let db = Lmdb::new(tmp_dir.path(), 2)?;
+        let fx = FxRates::new(&db);
+        let csv = "date,from,to,rate\n\
+                   # rates of january\n\
+                   2024-01-01,USD,EUR,0.9\n\
+                   2024-02-01,USD,EUR,0.92\n\
+                   2024-01-01,CHF,EUR,1.05\n";
+        assert_eq!(fx.import_csv(csv.as_bytes())?, 3);
+        assert!(fx.import_csv("2024-01-01,USD,EUR".as_bytes()).is_err());
+        assert_eq!(fx.all()?.len(), 3);
+
+        let (usd, eur) = (Currency::USD, Currency::EUR);
+        let date = |s: &str| s.parse::<FxDate>().unwrap();
+        assert_eq!(fx.rate(usd, eur, date("2023-12-31"))?, None);
+        assert_eq!(fx.rate(usd, eur, date("2024-01-15"))?, Some("0.9".parse()?));
+        assert_eq!(
+            fx.rate(usd, eur, date("2024-03-01"))?,
+            Some("0.92".parse()?)
+        );
+        assert_eq!(
+            fx.rate(eur, usd, date("2024-02-01"))?,
+            Some("0.92".parse::<FxRate>()?.inverse()?)
+        );
+        assert_eq!(fx.rate(eur, eur, date("2000-01-01"))?, Some(FxRate::ONE));
+
+        let mut balances = HashMap::new();
+        balances.insert(usd, 100_000);
+        balances.insert(Currency::CHF, -10_000);
+        balances.insert(eur, 1_500);
+        balances.insert(Currency::GBP, 7_000);
+        let total = fx.total(&balances, eur, date("2024-02-15"))?;
+        assert_eq!(total.total_milli, 92_000 - 10_500 + 1_500);
+        assert_eq!(total.missing_rates, [Currency::GBP]);
+        Ok(())
+    }
+}
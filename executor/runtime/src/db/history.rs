@@ -0,0 +1,768 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use borderless::__private::storage_keys::{StorageKey, BASE_KEY_STATE_HISTORY};
+use borderless::{hash::Hash256, ContractId, TxIdentifier};
+use borderless_kv_store::*;
+use serde::de::DeserializeOwned;
+
+use super::action_log::{ActionLog, RelTxAction};
+#[allow(unused_imports)]
+use crate::log_shim::*;
+use crate::{Error, Result, ACTION_TX_REL_SUB_DB, CONTRACT_SUB_DB, STATE_HISTORY_SUB_DB};
+
+/// Sub-Key where the retention of the state-history is stored
+pub const SUB_KEY_RETENTION: u64 = 0;
+
+/// Sub-Key where the version is stored, up to which the history has been pruned
+pub const SUB_KEY_PRUNED: u64 = 1;
+
+/// Sub-Key where the version is stored, at which the recording of the history started
+///
+/// Contracts that were introduced before the state history existed have no history for their older versions.
+pub const SUB_KEY_BASELINE: u64 = 2;
+
+// NOTE: The history lives in its own sub-db and relates every user-key to the versions, in which it was changed:
+// - Key: storage-key | version
+// - Value: HISTORY_TAG_WRITE | value or HISTORY_TAG_REMOVE
+//
+// The version is the number of actions, that have been applied to the contract.
+// Changes from the introduction are stored with version `0`, changes from the action with index `n` with version `n + 1`.
+// Since the version is encoded in big-endian, the cursor returns the changes of each key in commit order.
+const HISTORY_TAG_REMOVE: u8 = 0;
+const HISTORY_TAG_WRITE: u8 = 1;
+
+fn history_key(key: &StorageKey, version: u64) -> [u8; 40] {
+    history_key_raw(key.as_ref(), version)
+}
+
+fn history_key_raw(key: &[u8], version: u64) -> [u8; 40] {
+    let mut out = [0u8; 40];
+    out[..32].copy_from_slice(key);
+    out[32..].copy_from_slice(&version.to_be_bytes());
+    out
+}
+
+/// Splits a history key into the raw storage key and version
+fn split_history_key(key: &[u8]) -> Option<(&[u8], u64)> {
+    if key.len() != 40 {
+        return None;
+    }
+    let mut version = [0u8; 8];
+    version.copy_from_slice(&key[32..]);
+    Some((&key[..32], u64::from_be_bytes(version)))
+}
+
+/// Decodes a history value into the value of the key (`None` if the key was removed)
+fn decode_value(value: &[u8]) -> Option<Vec<u8>> {
+    match value.split_first() {
+        Some((&HISTORY_TAG_WRITE, rest)) => Some(rest.to_vec()),
+        _ => None,
+    }
+}
+
+/// Operation on a user-key, that is recorded in the [`StateHistory`]
+pub(crate) enum HistoryOp<'b> {
+    Write(&'b StorageKey, &'b [u8]),
+    Remove(&'b StorageKey),
+}
+
+/// The `StateHistory` records every change of the contract state together with the version that produced it.
+///
+/// This allows to query the state of a contract at any given action index or block,
+/// as long as the requested version is still covered by the configured retention.
+/// By default the full history is kept.
+pub struct StateHistory<'a, S: Db> {
+    db: &'a S,
+    cid: ContractId,
+}
+
+impl<'a, S: Db> StateHistory<'a, S> {
+    pub fn new(db: &'a S, cid: ContractId) -> Self {
+        Self { db, cid }
+    }
+
+    /// Returns the number of versions, that are kept in the history (`None` means unlimited)
+    pub fn retention(&self) -> Result<Option<u64>> {
+        self.read_value(SUB_KEY_RETENTION)
+    }
+
+    /// Sets the number of versions, that are kept in the history (`None` means unlimited)
+    ///
+    /// Older changes are pruned, so that only the state at the oldest retained version can be restored.
+    /// Pruned versions remain unavailable, even if the retention is increased afterwards.
+    #[cfg(any(feature = "contracts", feature = "agents"))]
+    pub fn set_retention(&self, retention: Option<u64>) -> Result<()> {
+        use super::action_log::SUB_KEY_LOG_LEN;
+        use super::controller::{read_system_value, write_system_value};
+        use borderless::__private::storage_keys::BASE_KEY_ACTION_LOG;
+
+        let db_ptr = self.db.create_sub_db(CONTRACT_SUB_DB)?;
+        let hist_db = self.db.create_sub_db(STATE_HISTORY_SUB_DB)?;
+        let key = StorageKey::system_key(self.cid, BASE_KEY_STATE_HISTORY, SUB_KEY_RETENTION);
+        let mut txn = self.db.begin_rw_txn()?;
+        match retention {
+            Some(retention) => {
+                txn.write(&db_ptr, &key, &postcard::to_allocvec(&retention)?)?;
+                let len: u64 = read_system_value::<S, _, _>(
+                    &db_ptr,
+                    &txn,
+                    &self.cid,
+                    BASE_KEY_ACTION_LOG,
+                    SUB_KEY_LOG_LEN,
+                )?
+                .unwrap_or_default();
+                let cutoff = len.saturating_sub(retention);
+
+                // Prune the whole history of the contract
+                let prefix = self.cid.into_bytes();
+                let mut versions: BTreeMap<Vec<u8>, Vec<u64>> = BTreeMap::new();
+                let mut cursor = txn.rw_cursor(&hist_db)?;
+                for (key, _) in cursor.iter_prefix(&prefix) {
+                    if let Some((storage_key, version)) = split_history_key(key) {
+                        versions
+                            .entry(storage_key.to_vec())
+                            .or_default()
+                            .push(version);
+                    }
+                }
+                drop(cursor);
+                for (storage_key, versions) in versions {
+                    prune_versions::<S>(&hist_db, &mut txn, &storage_key, &versions, cutoff)?;
+                }
+                let pruned: u64 = read_system_value::<S, _, _>(
+                    &db_ptr,
+                    &txn,
+                    &self.cid,
+                    BASE_KEY_STATE_HISTORY,
+                    SUB_KEY_PRUNED,
+                )?
+                .unwrap_or_default();
+                write_system_value::<S, _, _>(
+                    &db_ptr,
+                    &mut txn,
+                    &self.cid,
+                    BASE_KEY_STATE_HISTORY,
+                    SUB_KEY_PRUNED,
+                    &cutoff.max(pruned),
+                )?;
+            }
+            None => txn.delete(&db_ptr, &key)?,
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Returns the current version of the contract state
+    pub fn current_version(&self) -> Result<u64> {
+        ActionLog::new(self.db, self.cid).len()
+    }
+
+    /// Returns the version, at which the recording of the history started
+    ///
+    /// Returns `None`, if nothing has been recorded for the contract yet.
+    pub fn baseline(&self) -> Result<Option<u64>> {
+        self.read_value(SUB_KEY_BASELINE)
+    }
+
+    /// Returns the oldest version, that can still be restored from the history
+    pub fn earliest_version(&self) -> Result<u64> {
+        let current = self.current_version()?;
+        // Without a baseline, only the current state is known
+        let baseline = self.baseline()?.unwrap_or(current);
+        let pruned = self.read_value(SUB_KEY_PRUNED)?.unwrap_or_default();
+        let retained = match self.retention()? {
+            Some(retention) => current.saturating_sub(retention),
+            None => 0,
+        };
+        Ok(baseline.max(pruned).max(retained))
+    }
+
+    /// Sets the baseline of contracts, that existed before the state history, to their current version
+    ///
+    /// Registered as migration of the [`STATE_HISTORY_SUB_DB`], see [`super::schema`].
+    pub fn init_baselines(db: &'a S) -> Result<()> {
+        use super::controller::Controller;
+
+        let db_ptr = db.open_sub_db(CONTRACT_SUB_DB)?;
+        for cid in Controller::new(db).contract_ids()? {
+            let history = StateHistory::new(db, cid);
+            if history.baseline()?.is_some() {
+                continue;
+            }
+            let current = history.current_version()?;
+            let key = StorageKey::system_key(cid, BASE_KEY_STATE_HISTORY, SUB_KEY_BASELINE);
+            let mut txn = db.begin_rw_txn()?;
+            txn.write(&db_ptr, &key, &postcard::to_allocvec(&current)?)?;
+            txn.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Returns an error, if the version is older than the earliest version of the history
+    fn check_version(&self, version: u64) -> Result<()> {
+        let earliest = self.earliest_version()?;
+        if version < earliest {
+            return Err(Error::msg(format!(
+                "version {version} is not covered by the history (earliest version is {earliest})"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Resolves the version from a query value
+    ///
+    /// The value can either be an action index (`<n>`),
+    /// a transaction identifier (`tx:<chain-id>.<block-number>.<tx-hash>`) or a block number (`block:<n>`).
+    /// The resulting version refers to the state *after* the action, transaction or block was applied.
+    ///
+    /// Returns an error, if the value is invalid or the version has already been pruned.
+    pub fn resolve(&self, at: &str) -> Result<u64> {
+        let invalid = || Error::msg(format!("invalid value for 'at': '{at}'"));
+        let current = self.current_version()?;
+        let version = if let Some(tx_id) = at.strip_prefix("tx:") {
+            let tx_id = parse_tx_id(tx_id).ok_or_else(invalid)?;
+            let rel_db = self.db.create_sub_db(ACTION_TX_REL_SUB_DB)?;
+            let txn = self.db.begin_ro_txn()?;
+            let relation = txn
+                .read(&rel_db, &tx_id.to_bytes())?
+                .map(RelTxAction::from_bytes);
+            txn.commit()?;
+            match relation {
+                Some(rel) if rel.cid == self.cid => rel.action_idx + 1,
+                _ => return Err(Error::msg(format!("unknown transaction '{tx_id}'"))),
+            }
+        } else if let Some(block) = at.strip_prefix("block:") {
+            let block = block.parse().map_err(|_| invalid())?;
+            ActionLog::new(self.db, self.cid).count_until_block(block)?
+        } else {
+            let idx: u64 = at.parse().map_err(|_| invalid())?;
+            if idx >= current {
+                return Err(Error::msg(format!("action index {idx} is out of range")));
+            }
+            idx + 1
+        };
+        if version < self.earliest_version()? {
+            return Err(Error::msg(format!(
+                "state at '{at}' is no longer available in the history"
+            )));
+        }
+        Ok(version)
+    }
+
+    /// Records the given changes of the contract state
+    ///
+    /// If a key has no history yet, its current value is recorded as baseline with version `0`,
+    /// so the state before the change can still be restored.
+    /// The first call stores the version before the change as baseline of the history (see [`StateHistory::baseline`]).
+    #[cfg(any(feature = "contracts", feature = "agents"))]
+    pub(crate) fn record(
+        &self,
+        db_ptr: &S::Handle,
+        txn: &mut <S as Db>::RwTx<'_>,
+        version: u64,
+        ops: &[HistoryOp<'_>],
+    ) -> Result<()> {
+        use super::controller::{read_system_value, write_system_value};
+
+        let hist_db = self.db.open_sub_db(STATE_HISTORY_SUB_DB)?;
+        let retention: Option<u64> = read_system_value::<S, _, _>(
+            db_ptr,
+            txn,
+            &self.cid,
+            BASE_KEY_STATE_HISTORY,
+            SUB_KEY_RETENTION,
+        )?;
+        let baseline: Option<u64> = read_system_value::<S, _, _>(
+            db_ptr,
+            txn,
+            &self.cid,
+            BASE_KEY_STATE_HISTORY,
+            SUB_KEY_BASELINE,
+        )?;
+        if baseline.is_none() {
+            write_system_value::<S, _, _>(
+                db_ptr,
+                txn,
+                &self.cid,
+                BASE_KEY_STATE_HISTORY,
+                SUB_KEY_BASELINE,
+                &version.saturating_sub(1),
+            )?;
+        }
+
+        for op in ops {
+            let (key, value) = match op {
+                HistoryOp::Write(key, value) => (*key, Some(*value)),
+                HistoryOp::Remove(key) => (*key, None),
+            };
+            let mut versions = history_versions::<S>(&hist_db, txn, key)?;
+            if versions.is_empty() && version > 0 {
+                if let Some(current) = txn.read(db_ptr, key)?.map(|v| v.to_vec()) {
+                    txn.write(
+                        &hist_db,
+                        &history_key(key, 0),
+                        &encode_value(Some(&current)),
+                    )?;
+                    versions.push(0);
+                }
+            }
+            txn.write(&hist_db, &history_key(key, version), &encode_value(value))?;
+            if versions.last() != Some(&version) {
+                versions.push(version);
+            }
+            if let Some(retention) = retention {
+                let cutoff = version.saturating_sub(retention);
+                prune_versions::<S>(&hist_db, txn, key.as_ref(), &versions, cutoff)?;
+            }
+        }
+        if let Some(retention) = retention {
+            let pruned: u64 = read_system_value::<S, _, _>(
+                db_ptr,
+                txn,
+                &self.cid,
+                BASE_KEY_STATE_HISTORY,
+                SUB_KEY_PRUNED,
+            )?
+            .unwrap_or_default();
+            write_system_value::<S, _, _>(
+                db_ptr,
+                txn,
+                &self.cid,
+                BASE_KEY_STATE_HISTORY,
+                SUB_KEY_PRUNED,
+                &version.saturating_sub(retention).max(pruned),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Reads the value of a key at the given version
+    ///
+    /// Keys without history have not been changed since the history was recorded, so the current value is returned.
+    /// Returns an error, if the version is older than the [`StateHistory::earliest_version`].
+    pub fn read_at(&self, key: &StorageKey, version: u64) -> Result<Option<Vec<u8>>> {
+        self.check_version(version)?;
+        let db_ptr = self.db.open_sub_db(CONTRACT_SUB_DB)?;
+        let hist_db = self.db.open_sub_db(STATE_HISTORY_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let mut cursor = txn.ro_cursor(&hist_db)?;
+
+        // The last change of the key up to the given version
+        let latest = cursor
+            .iter_from_rev(&history_key(key, version))
+            .next()
+            .filter(|(hist_key, _)| hist_key.starts_with(key.as_ref()));
+        let result = match latest {
+            Some((_, value)) => decode_value(value),
+            // The key was written for the first time after the given version
+            None if cursor.iter_prefix(key).next().is_some() => None,
+            None => txn.read(&db_ptr, key)?.map(|v| v.to_vec()),
+        };
+        drop(cursor);
+        txn.commit()?;
+        Ok(result)
+    }
+
+    /// Returns all sub-keys of the base-key in the given storage key, that existed at the given version
+    pub fn sub_keys_at(&self, key: &StorageKey, version: u64) -> Result<Vec<u64>> {
+        self.check_version(version)?;
+        let prefix = key.get_prefix();
+        let db_ptr = self.db.open_sub_db(CONTRACT_SUB_DB)?;
+        let hist_db = self.db.open_sub_db(STATE_HISTORY_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+
+        // Resolve all keys with history
+        let mut history: BTreeMap<u64, bool> = BTreeMap::new();
+        let mut cursor = txn.ro_cursor(&hist_db)?;
+        for (hist_key, value) in cursor.iter_prefix(&prefix) {
+            if let Some((k, v)) = split_history_key(hist_key) {
+                let exists = history
+                    .entry(
+                        StorageKey::try_from(k)
+                            .expect("Slice length error")
+                            .sub_key(),
+                    )
+                    .or_default();
+                if v <= version {
+                    *exists = value.first() == Some(&HISTORY_TAG_WRITE);
+                }
+            }
+        }
+        drop(cursor);
+
+        // Keys without history keep their current state
+        let mut keys: BTreeSet<u64> = BTreeSet::new();
+        let mut cursor = txn.ro_cursor(&db_ptr)?;
+        for (k, _) in cursor.iter_prefix(&prefix) {
+            let sub_key = StorageKey::try_from(k)
+                .expect("Slice length error")
+                .sub_key();
+            if !history.contains_key(&sub_key) {
+                keys.insert(sub_key);
+            }
+        }
+        drop(cursor);
+        txn.commit()?;
+
+        keys.extend(history.into_iter().filter(|(_, e)| *e).map(|(k, _)| k));
+        Ok(keys.into_iter().collect())
+    }
+
+    /// Returns the complete user-space storage of the contract at the given version
+    ///
+    /// The result maps the raw storage keys to their values.
+    pub fn state_at(&self, version: u64) -> Result<BTreeMap<Vec<u8>, Vec<u8>>> {
+        self.check_version(version)?;
+        let prefix = self.cid.into_bytes();
+        let db_ptr = self.db.open_sub_db(CONTRACT_SUB_DB)?;
+        let hist_db = self.db.open_sub_db(STATE_HISTORY_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+
+        // Resolve all keys with history
+        let mut history: BTreeMap<Vec<u8>, Option<Vec<u8>>> = BTreeMap::new();
+        let mut cursor = txn.ro_cursor(&hist_db)?;
+        for (hist_key, value) in cursor.iter_prefix(&prefix) {
+            if let Some((k, v)) = split_history_key(hist_key) {
+                let entry = history.entry(k.to_vec()).or_default();
+                if v <= version {
+                    *entry = decode_value(value);
+                }
+            }
+        }
+        drop(cursor);
+
+        // Keys without history keep their current value
+        let mut state = BTreeMap::new();
+        let mut cursor = txn.ro_cursor(&db_ptr)?;
+        for (k, value) in cursor.iter_prefix(&prefix) {
+            let is_user_key = StorageKey::try_from(k)
+                .expect("Slice length error")
+                .is_user_key();
+            if is_user_key && !history.contains_key(k) {
+                state.insert(k.to_vec(), value.to_vec());
+            }
+        }
+        drop(cursor);
+        txn.commit()?;
+
+        state.extend(history.into_iter().filter_map(|(k, v)| v.map(|v| (k, v))));
+        Ok(state)
+    }
+
+    fn read_value<D: DeserializeOwned>(&self, sub_key: u64) -> Result<Option<D>> {
+        let db_ptr = self.db.open_sub_db(CONTRACT_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let key = StorageKey::system_key(self.cid, BASE_KEY_STATE_HISTORY, sub_key);
+        let result = match txn.read(&db_ptr, &key)? {
+            Some(bytes) => Some(postcard::from_bytes(bytes)?),
+            None => None,
+        };
+        txn.commit()?;
+        Ok(result)
+    }
+}
+
+fn encode_value(value: Option<&[u8]>) -> Vec<u8> {
+    match value {
+        Some(value) => {
+            let mut out = Vec::with_capacity(value.len() + 1);
+            out.push(HISTORY_TAG_WRITE);
+            out.extend_from_slice(value);
+            out
+        }
+        None => vec![HISTORY_TAG_REMOVE],
+    }
+}
+
+/// Returns all recorded versions of the key in ascending order
+fn history_versions<S: Db>(
+    hist_db: &S::Handle,
+    txn: &mut <S as Db>::RwTx<'_>,
+    key: &StorageKey,
+) -> Result<Vec<u64>> {
+    let mut cursor = txn.rw_cursor(hist_db)?;
+    let out = cursor
+        .iter_prefix(key)
+        .filter_map(|(hist_key, _)| split_history_key(hist_key))
+        .map(|(_, version)| version)
+        .collect();
+    Ok(out)
+}
+
+/// Removes all versions of the key below the cutoff, except for the newest one,
+/// as it still represents the state at the cutoff.
+fn prune_versions<S: Db>(
+    hist_db: &S::Handle,
+    txn: &mut <S as Db>::RwTx<'_>,
+    key: &[u8],
+    versions: &[u64],
+    cutoff: u64,
+) -> Result<()> {
+    let below = versions.partition_point(|v| *v <= cutoff);
+    for version in &versions[..below.saturating_sub(1)] {
+        txn.delete(hist_db, &history_key_raw(key, *version))?;
+    }
+    Ok(())
+}
+
+/// Parses a full transaction identifier (`<chain-id>.<block-number>.<tx-hash>`)
+fn parse_tx_id(s: &str) -> Option<TxIdentifier> {
+    let mut parts = s.splitn(3, '.');
+    let chain_id = parts.next()?.parse().ok()?;
+    let number = parts.next()?.parse().ok()?;
+    let hex = parts.next()?;
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let bytes = (0..32)
+        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    let hash = Hash256::try_from(bytes.as_slice()).ok()?;
+    Some(TxIdentifier::new(chain_id, number, hash))
+}
+
+#[cfg(all(test, feature = "contracts"))]
+mod tests {
+    use super::*;
+    use crate::ACTION_INDEX_SUB_DB;
+    use borderless::contracts::TxCtx;
+    use borderless::{events::CallAction, BorderlessId};
+    use borderless_kv_store::backend::{
+        encrypted::{EncryptedDb, EncryptionKey},
+        lmdb::Lmdb,
+        memdb::MemDb,
+    };
+    use tempfile::tempdir;
+
+    fn open_tmp_lmdb() -> Lmdb {
+        let tmp_dir = tempdir().unwrap();
+        create_sub_dbs(Lmdb::new(tmp_dir.path(), 5).unwrap())
+    }
+
+    fn create_sub_dbs<S: Db>(env: S) -> S {
+        env.create_sub_db(CONTRACT_SUB_DB).unwrap();
+        env.create_sub_db(ACTION_TX_REL_SUB_DB).unwrap();
+        env.create_sub_db(ACTION_INDEX_SUB_DB).unwrap();
+        env.create_sub_db(STATE_HISTORY_SUB_DB).unwrap();
+        env
+    }
+
+    fn tx_id(number: u64) -> TxIdentifier {
+        TxIdentifier::new(1, number, Hash256::digest(&number.to_be_bytes()))
+    }
+
+    /// Applies the changes like the vm does - the introduction is committed without a tx-number
+    fn apply<S: Db>(db: &S, cid: ContractId, tx_number: Option<u64>, ops: &[HistoryOp<'_>]) {
+        let db_ptr = db.open_sub_db(CONTRACT_SUB_DB).unwrap();
+        let history = StateHistory::new(db, cid);
+        let version = history.current_version().unwrap() + tx_number.is_some() as u64;
+        let mut txn = db.begin_rw_txn().unwrap();
+        history.record(&db_ptr, &mut txn, version, ops).unwrap();
+        for op in ops {
+            match op {
+                HistoryOp::Write(key, value) => txn.write(&db_ptr, key, value).unwrap(),
+                HistoryOp::Remove(key) => txn.delete(&db_ptr, key).unwrap(),
+            }
+        }
+        if let Some(number) = tx_number {
+            let tx_ctx = TxCtx {
+                tx_id: tx_id(number),
+                index: 0,
+            };
+            let action = CallAction::by_method("set", serde_json::Value::Null);
+            ActionLog::new(db, cid)
+                .commit(
+                    &db_ptr,
+                    &mut txn,
+                    &action,
+                    BorderlessId::generate(),
+                    tx_ctx,
+                    None,
+                    None,
+                )
+                .unwrap();
+        }
+        txn.commit().unwrap();
+    }
+
+    fn setup() -> (Lmdb, ContractId, [StorageKey; 3]) {
+        setup_in(open_tmp_lmdb())
+    }
+
+    fn setup_in<S: Db>(db: S) -> (S, ContractId, [StorageKey; 3]) {
+        let cid = ContractId::generate();
+        let keys = [1, 2, 3].map(|sub_key| StorageKey::user_key(cid, 42, sub_key));
+        let [k1, k2, k3] = &keys;
+        apply(
+            &db,
+            cid,
+            None,
+            &[HistoryOp::Write(k1, b"a"), HistoryOp::Write(k2, b"b")],
+        );
+        apply(
+            &db,
+            cid,
+            Some(10),
+            &[HistoryOp::Write(k1, b"c"), HistoryOp::Remove(k2)],
+        );
+        apply(&db, cid, Some(20), &[HistoryOp::Write(k3, b"d")]);
+        (db, cid, keys)
+    }
+
+    #[test]
+    fn read_historic_state() -> Result<()> {
+        let (db, cid, [k1, k2, k3]) = setup();
+        let history = StateHistory::new(&db, cid);
+        assert_eq!(history.current_version()?, 2);
+
+        assert_eq!(history.read_at(&k1, 0)?, Some(b"a".to_vec()));
+        assert_eq!(history.read_at(&k1, 1)?, Some(b"c".to_vec()));
+        assert_eq!(history.read_at(&k1, 2)?, Some(b"c".to_vec()));
+        assert_eq!(history.read_at(&k2, 0)?, Some(b"b".to_vec()));
+        assert_eq!(history.read_at(&k2, 1)?, None);
+        assert_eq!(history.read_at(&k3, 1)?, None);
+        assert_eq!(history.read_at(&k3, 2)?, Some(b"d".to_vec()));
+
+        assert_eq!(history.sub_keys_at(&k1, 0)?, vec![1, 2]);
+        assert_eq!(history.sub_keys_at(&k1, 1)?, vec![1]);
+        assert_eq!(history.sub_keys_at(&k1, 2)?, vec![1, 3]);
+
+        // Values without history are read from the current state
+        let k4 = StorageKey::user_key(cid, 42, 4);
+        let db_ptr = db.open_sub_db(CONTRACT_SUB_DB)?;
+        let mut txn = db.begin_rw_txn()?;
+        txn.write(&db_ptr, &k4, b"e")?;
+        txn.commit()?;
+        assert_eq!(history.read_at(&k4, 0)?, Some(b"e".to_vec()));
+        assert_eq!(history.sub_keys_at(&k1, 0)?, vec![1, 2, 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn encrypted_db() -> Result<()> {
+        let keys = vec![(1, EncryptionKey::generate())];
+        let db = create_sub_dbs(EncryptedDb::new(MemDb::new(), keys)?);
+        let (db, cid, [k1, k2, k3]) = setup_in(db);
+        let history = StateHistory::new(&db, cid);
+        assert_eq!(history.current_version()?, 2);
+        assert_eq!(history.read_at(&k1, 0)?, Some(b"a".to_vec()));
+        assert_eq!(history.read_at(&k2, 1)?, None);
+        assert_eq!(history.read_at(&k3, 2)?, Some(b"d".to_vec()));
+        assert_eq!(history.sub_keys_at(&k1, 2)?, vec![1, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn full_state_at_version() -> Result<()> {
+        let (db, cid, [k1, k2, k3]) = setup();
+        let history = StateHistory::new(&db, cid);
+        let entry = |k: &StorageKey, v: &[u8]| (k.as_ref().to_vec(), v.to_vec());
+        let state = history.state_at(0)?;
+        assert_eq!(state, BTreeMap::from([entry(&k1, b"a"), entry(&k2, b"b")]));
+        let state = history.state_at(1)?;
+        assert_eq!(state, BTreeMap::from([entry(&k1, b"c")]));
+        let state = history.state_at(2)?;
+        assert_eq!(state, BTreeMap::from([entry(&k1, b"c"), entry(&k3, b"d")]));
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_versions() -> Result<()> {
+        let (db, cid, _) = setup();
+        let history = StateHistory::new(&db, cid);
+        assert_eq!(history.resolve("0")?, 1);
+        assert_eq!(history.resolve("1")?, 2);
+        assert!(history.resolve("2").is_err());
+        assert_eq!(history.resolve("block:5")?, 0);
+        assert_eq!(history.resolve("block:15")?, 1);
+        assert_eq!(history.resolve("block:100")?, 2);
+
+        let hash: String = tx_id(20)
+            .hash
+            .into_slice()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        assert_eq!(history.resolve(&format!("tx:1.20.{hash}"))?, 2);
+        assert!(history.resolve(&format!("tx:1.21.{hash}")).is_err());
+        assert!(history.resolve("tx:1.20.abc").is_err());
+        assert!(history.resolve("yesterday").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn history_starts_at_baseline() -> Result<()> {
+        use crate::db::controller::write_system_value;
+        use borderless::__private::storage_keys::{BASE_KEY_METADATA, META_SUB_KEY_ID};
+
+        let db = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let key = StorageKey::user_key(cid, 42, 1);
+        let db_ptr = db.open_sub_db(CONTRACT_SUB_DB)?;
+        let mut txn = db.begin_rw_txn()?;
+        write_system_value::<Lmdb, _, _>(
+            &db_ptr,
+            &mut txn,
+            &cid,
+            BASE_KEY_METADATA,
+            META_SUB_KEY_ID,
+            &cid,
+        )?;
+        txn.write(&db_ptr, &key, b"a")?;
+        txn.commit()?;
+        // Two actions, that were applied before the history existed
+        for number in [10, 20] {
+            apply(&db, cid, Some(number), &[]);
+        }
+        let history = StateHistory::new(&db, cid);
+        let mut txn = db.begin_rw_txn()?;
+        txn.delete(
+            &db_ptr,
+            &StorageKey::system_key(cid, BASE_KEY_STATE_HISTORY, SUB_KEY_BASELINE),
+        )?;
+        txn.commit()?;
+
+        // Without a baseline, only the current state is available
+        assert_eq!(history.earliest_version()?, 2);
+        assert!(history.resolve("0").is_err());
+        StateHistory::init_baselines(&db)?;
+        assert_eq!(history.baseline()?, Some(2));
+
+        apply(&db, cid, Some(30), &[HistoryOp::Write(&key, b"b")]);
+        assert_eq!(history.read_at(&key, 2)?, Some(b"a".to_vec()));
+        assert_eq!(history.read_at(&key, 3)?, Some(b"b".to_vec()));
+        assert!(history.read_at(&key, 1).is_err());
+        assert!(history.state_at(0).is_err());
+        assert_eq!(history.resolve("1")?, 2);
+        assert!(history.resolve("0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn retention_prunes_history() -> Result<()> {
+        let (db, cid, [k1, k2, _]) = setup();
+        let history = StateHistory::new(&db, cid);
+        history.set_retention(Some(1))?;
+        assert_eq!(history.retention()?, Some(1));
+        assert_eq!(history.earliest_version()?, 1);
+
+        // The state at the earliest version is still intact
+        assert_eq!(history.read_at(&k1, 1)?, Some(b"c".to_vec()));
+        assert_eq!(history.read_at(&k2, 1)?, None);
+        assert_eq!(history.resolve("0")?, 1);
+        assert!(history.resolve("block:5").is_err());
+
+        // New changes prune the history of the changed keys
+        apply(&db, cid, Some(30), &[HistoryOp::Write(&k1, b"f")]);
+        assert_eq!(history.read_at(&k1, 2)?, Some(b"c".to_vec()));
+        assert_eq!(history.read_at(&k1, 3)?, Some(b"f".to_vec()));
+        assert!(history.resolve("0").is_err());
+
+        // Pruned versions stay unavailable
+        history.set_retention(None)?;
+        assert_eq!(history.earliest_version()?, 2);
+        assert!(history.resolve("0").is_err());
+        assert_eq!(history.resolve("1")?, 2);
+        Ok(())
+    }
+}
@@ -3,21 +3,87 @@
 //! A ledger between two parties is persisted in the kv-store and can be modified through the ledger-api.
 
 use std::array::TryFromSliceError;
+use std::io::Write;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use ahash::{HashMap, HashMapExt};
 use borderless::{
     contracts::ledger::{Currency, LedgerEntry, Money},
+    hash::{Hash256, Hasher},
     http::{queries::Pagination, PaginatedElements},
     prelude::{ledger::EntryType, TxCtx},
     BorderlessId, Context, ContractId,
 };
-use borderless_kv_store::{Db, RawRead, RawWrite, RoCursor as _, RoTx};
+use borderless_kv_store::{Db, KvDatabase, RawRead, RawWrite, RoCursor, RoTx, Tx};
+use ed25519_dalek::Signer;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::{Error, Result, LEDGER_SUB_DB};
+use super::fx::{BaseTotal, FxDate, FxRates};
+
+use crate::{Error, Result, LEDGER_INDEX_SUB_DB, LEDGER_SUB_DB};
 
 use crate::log_shim::debug;
 
+// NOTE: The secondary indices of the ledger live in their own sub-db:
+// - Participant: INDEX_TAG_PARTICIPANT | borderless-id | ledger-id         -> empty
+// - Contract:    INDEX_TAG_CONTRACT    | contract-id   | ledger-id | line  -> empty
+//
+// All numbers are encoded in big-endian, so a cursor returns the ledgers of a participant ordered by their id,
+// and the lines of a contract ordered by ledger and line.
+const INDEX_TAG_PARTICIPANT: u8 = b'p';
+const INDEX_TAG_CONTRACT: u8 = b'c';
+
+/// Key that marks, that the secondary indices have been built for all existing ledgers
+const INDEX_VERSION_KEY: &[u8] = b"v";
+
+fn participant_index_prefix(participant: &BorderlessId) -> Vec<u8> {
+    let mut key = vec![INDEX_TAG_PARTICIPANT];
+    key.extend_from_slice(participant.as_bytes());
+    key
+}
+
+fn contract_index_prefix(cid: &ContractId) -> Vec<u8> {
+    let mut key = vec![INDEX_TAG_CONTRACT];
+    key.extend_from_slice(cid.as_bytes());
+    key
+}
+
+fn participant_index_key(participant: &BorderlessId, ledger_id: u64) -> Vec<u8> {
+    let mut key = participant_index_prefix(participant);
+    key.extend_from_slice(&ledger_id.to_be_bytes());
+    key
+}
+
+fn contract_index_key(cid: &ContractId, ledger_id: u64, line: u64) -> Vec<u8> {
+    let mut key = contract_index_prefix(cid);
+    key.extend_from_slice(&ledger_id.to_be_bytes());
+    key.extend_from_slice(&line.to_be_bytes());
+    key
+}
+
+/// Reads the big-endian encoded numbers behind the prefix of an index key
+fn index_suffix(key: &[u8], prefix_len: usize) -> Vec<u64> {
+    key[prefix_len..]
+        .chunks_exact(8)
+        .map(|c| u64::from_be_bytes(c.try_into().expect("Slice length error")))
+        .collect()
+}
+
+/// Applies the pagination to a list of elements, that have been collected from an index
+fn paginate<T>(mut items: Vec<T>, pagination: &Pagination) -> Vec<T> {
+    if pagination.reverse {
+        items.reverse();
+    }
+    let range = pagination.to_range();
+    items
+        .into_iter()
+        .skip(range.start)
+        .take(range.end - range.start)
+        .collect()
+}
+
 /// Ledger controller of the database
 pub struct Ledger<'a, S: Db> {
     db: &'a S,
@@ -28,23 +94,32 @@ impl<'a, S: Db> Ledger<'a, S> {
         Self { db }
     }
 
-    /// Commits a new ledger-entry in the given transaction
+    /// Commits a new ledger-entry in the given transaction and returns its line number
+    ///
+    /// Returns an error, if the entry is an invalid reversal.
     pub(crate) fn commit_entry(
         &self,
         txn: &mut <S as Db>::RwTx<'_>,
         entry: &LedgerEntry,
         cid: ContractId,
         tx_ctx: &TxCtx,
-    ) -> Result<()> {
+        commited: Option<u64>,
+    ) -> Result<u64> {
         let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
         // Read current ledger meta information
         let ledger_id = entry.creditor.merge_compact(&entry.debitor);
         let meta_key = LedgerKey::meta(ledger_id);
         let mut meta = match txn.read(&db_ptr, &meta_key)? {
-            Some(val) => postcard::from_bytes(val)?,
+            Some(val) => LedgerMeta::decode(val)?,
             None => LedgerMeta::new(entry.creditor, entry.debitor),
         };
 
+        // Reversals must reference a valid line of the same contract
+        let reversed = self
+            .select(ledger_id)
+            .check_reversal(txn, &db_ptr, entry, cid)?
+            .map_err(|e| Error::msg(e.to_string()))?;
+
         // Write ledger line
         let c_key = LedgerKey::new(ledger_id, meta.len, "creditor");
         let d_key = LedgerKey::new(ledger_id, meta.len, "debitor");
@@ -65,9 +140,51 @@ impl<'a, S: Db> Ledger<'a, S> {
         txn.write(&db_ptr, &tag_key, &entry.tag.as_bytes())?;
         txn.write(&db_ptr, &cid_key, &cid.as_bytes())?;
         txn.write(&db_ptr, &tx_ctx_key, &tx_ctx_bytes)?;
+        if let Some(commited) = commited {
+            let commited_key = LedgerKey::new(ledger_id, meta.len, "commited");
+            txn.write(&db_ptr, &commited_key, &commited.to_be_bytes())?;
+        }
+        if let Some(due_date) = entry.due_date {
+            let due_date_key = LedgerKey::new(ledger_id, meta.len, "due_date");
+            txn.write(&db_ptr, &due_date_key, &due_date.to_be_bytes())?;
+        }
+        if let Some(reference) = &entry.reference {
+            let reference_key = LedgerKey::new(ledger_id, meta.len, "reference");
+            txn.write(&db_ptr, &reference_key, &reference.as_bytes())?;
+        }
+        if let Some(payment_terms) = entry.payment_terms {
+            let terms_key = LedgerKey::new(ledger_id, meta.len, "payment_terms");
+            txn.write(&db_ptr, &terms_key, &payment_terms.to_be_bytes())?;
+        }
+        if let Some((line, _)) = &reversed {
+            let reverses_key = LedgerKey::new(ledger_id, meta.len, "reverses");
+            txn.write(&db_ptr, &reverses_key, &line.to_be_bytes())?;
+            let reversed_by_key = LedgerKey::new(ledger_id, *line, "reversed_by");
+            txn.write(&db_ptr, &reversed_by_key, &meta.len.to_be_bytes())?;
+        }
+
+        // Update secondary indices
+        let index_db = self.db.open_sub_db(LEDGER_INDEX_SUB_DB)?;
+        let creditor_key = participant_index_key(&entry.creditor, ledger_id);
+        txn.write(&index_db, &creditor_key, &[])?;
+        let debitor_key = participant_index_key(&entry.debitor, ledger_id);
+        txn.write(&index_db, &debitor_key, &[])?;
+        let contract_key = contract_index_key(&cid, ledger_id, meta.len);
+        txn.write(&index_db, &contract_key, &[])?;
+
+        // Extend the hash chain (older ledgers are chained by the schema migration, see `chain_all`)
+        let prev = meta.head.unwrap_or_else(Hash256::zero);
+        let head = self
+            .select(ledger_id)
+            .write_line_hash(txn, &db_ptr, &prev, meta.len)?;
+        meta.head = Some(head);
 
         // update meta information based on the current entry
-        meta.update(entry)?;
+        let line = meta.len;
+        match &reversed {
+            Some((_, original)) => meta.update_reversal(entry, original)?,
+            None => meta.update(entry)?,
+        }
 
         // Write meta back
         let meta_bytes = postcard::to_allocvec(&meta)?;
@@ -76,7 +193,7 @@ impl<'a, S: Db> Ledger<'a, S> {
             "commited ledger entry: {entry}, ledger-id={ledger_id}, len={}",
             meta.len
         );
-        Ok(())
+        Ok(line)
     }
 
     /// Opens a ledger for a pair of borderless-ids
@@ -102,22 +219,230 @@ impl<'a, S: Db> Ledger<'a, S> {
         let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
         let txn = self.db.begin_ro_txn()?;
         let mut cursor = txn.ro_cursor(&db_ptr)?;
+        for value in meta_values(&mut cursor) {
+            out.push(LedgerMeta::decode(value)?);
+        }
+        Ok(out)
+    }
 
-        // NOTE: We always iterate over the entire key-space.
-        // As this is all super low level, it is quite efficient,
-        // but on paper it does not scale very well.
-        // In the far or near future we have to migrate this to something different.
-        // (right now - parsing through 3TB of ledger data takes 300ms, so we have some time until this becomes relevant)
-        for (key, value) in cursor.iter() {
-            let key = LedgerKey::from_slice(key);
-            if key.is_meta() {
-                let ledger_meta = postcard::from_bytes(value)?;
-                out.push(ledger_meta);
-            }
+    /// Returns all ledger lines, that were written by the given contract
+    ///
+    /// The lines of each ledger are returned in the order in which they were commited.
+    pub fn contract_entries(&self, cid: ContractId) -> Result<Vec<LedgerLine>> {
+        let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
+        let index_db = self.db.open_sub_db(LEDGER_INDEX_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let mut out = Vec::new();
+        for (ledger_id, line) in self.contract_lines(&txn, &index_db, &cid)? {
+            let ledger = self.select(ledger_id);
+            let (entry, _, tx_ctx) = ledger
+                .get(&txn, &db_ptr, line)?
+                .context("indexed line must exist")?;
+            let commited = ledger.read_column(&txn, &db_ptr, line, "commited", u64_from_slice)?;
+            out.push(LedgerLine {
+                ledger_id,
+                line,
+                entry,
+                tx_ctx,
+                commited,
+            });
+        }
+        txn.commit()?;
+        Ok(out)
+    }
+
+    /// Returns a paginated list of all ledger lines, that were written by the given contract
+    ///
+    /// The lines are ordered by ledger and then by the order in which they were commited.
+    pub fn contract_entries_paginated(
+        &self,
+        cid: ContractId,
+        pagination: Pagination,
+    ) -> Result<PaginatedElements<LedgerEntryDto>> {
+        let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
+        let index_db = self.db.open_sub_db(LEDGER_INDEX_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let lines = self.contract_lines(&txn, &index_db, &cid)?;
+        let total_elements = lines.len();
+        let mut elements = Vec::new();
+        for (ledger_id, line) in paginate(lines, &pagination) {
+            let dto = self
+                .select(ledger_id)
+                .get_dto(&txn, &db_ptr, line)?
+                .context("indexed line must exist")?;
+            elements.push(dto);
+        }
+        txn.commit()?;
+        Ok(PaginatedElements {
+            elements,
+            total_elements,
+            pagination,
+        })
+    }
+
+    /// Returns a paginated list of all ledgers, in which the given participant is either creditor or debitor
+    pub fn participant_ledgers_paginated(
+        &self,
+        participant: BorderlessId,
+        pagination: Pagination,
+    ) -> Result<PaginatedElements<LedgerMetaDto>> {
+        let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
+        let index_db = self.db.open_sub_db(LEDGER_INDEX_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let ledger_ids = self.participant_ledger_ids(&txn, &index_db, &participant)?;
+
+        let total_elements = ledger_ids.len();
+        let mut elements = Vec::new();
+        for ledger_id in paginate(ledger_ids, &pagination) {
+            let meta: LedgerMeta = txn
+                .read(&db_ptr, &LedgerKey::meta(ledger_id))?
+                .map(LedgerMeta::decode)
+                .transpose()?
+                .context("indexed ledger must exist")?;
+            elements.push(meta.into_dto());
+        }
+        txn.commit()?;
+        Ok(PaginatedElements {
+            elements,
+            total_elements,
+            pagination,
+        })
+    }
+
+    /// Returns all ledgers, in which the given participant is either creditor or debitor
+    pub fn participant_ledgers(&self, participant: BorderlessId) -> Result<Vec<LedgerMeta>> {
+        let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
+        let index_db = self.db.open_sub_db(LEDGER_INDEX_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let mut out = Vec::new();
+        for ledger_id in self.participant_ledger_ids(&txn, &index_db, &participant)? {
+            let meta = txn
+                .read(&db_ptr, &LedgerKey::meta(ledger_id))?
+                .map(LedgerMeta::decode)
+                .transpose()?
+                .context("indexed ledger must exist")?;
+            out.push(meta);
         }
+        txn.commit()?;
         Ok(out)
     }
 
+    /// Returns the ids of all ledgers, in which the given participant is either creditor or debitor
+    fn participant_ledger_ids(
+        &self,
+        txn: &<S as Db>::RoTx<'_>,
+        index_db: &<S as Db>::Handle,
+        participant: &BorderlessId,
+    ) -> Result<Vec<u64>> {
+        let prefix = participant_index_prefix(participant);
+        let mut cursor = txn.ro_cursor(index_db)?;
+        let ledger_ids = cursor
+            .iter_prefix(&prefix)
+            .map(|(key, _)| index_suffix(key, prefix.len())[0])
+            .collect();
+        Ok(ledger_ids)
+    }
+
+    /// Returns the ledger-id and line of all ledger lines, that were written by the given contract
+    fn contract_lines(
+        &self,
+        txn: &<S as Db>::RoTx<'_>,
+        index_db: &<S as Db>::Handle,
+        cid: &ContractId,
+    ) -> Result<Vec<(u64, u64)>> {
+        let prefix = contract_index_prefix(cid);
+        let mut cursor = txn.ro_cursor(index_db)?;
+        let lines = cursor
+            .iter_prefix(&prefix)
+            .map(|(key, _)| {
+                let suffix = index_suffix(key, prefix.len());
+                (suffix[0], suffix[1])
+            })
+            .collect();
+        Ok(lines)
+    }
+
+    /// Builds the secondary indices, if they do not exist yet
+    ///
+    /// Only required for ledgers, that were written before the secondary indices existed.
+    pub fn ensure_index(&self) -> Result<()> {
+        let db_ptr = self.db.create_sub_db(LEDGER_SUB_DB)?;
+        let index_db = self.db.create_sub_db(LEDGER_INDEX_SUB_DB)?;
+        let mut keys = Vec::new();
+        {
+            let txn = self.db.begin_ro_txn()?;
+            let indexed = txn.read(&index_db, &INDEX_VERSION_KEY)?.is_some();
+            txn.commit()?;
+            if indexed {
+                return Ok(());
+            }
+            let ledgers = self.all()?;
+            let txn = self.db.begin_ro_txn()?;
+            for meta in ledgers {
+                let ledger_id = meta.creditor.merge_compact(&meta.debitor);
+                keys.push(participant_index_key(&meta.creditor, ledger_id));
+                keys.push(participant_index_key(&meta.debitor, ledger_id));
+                let ledger = self.select(ledger_id);
+                for line in 0..meta.len {
+                    let cid = ledger
+                        .read_column(&txn, &db_ptr, line, "contract_id", |b| {
+                            ContractId::from_slice(b).ok()
+                        })?
+                        .context("missing contract-id")?;
+                    keys.push(contract_index_key(&cid, ledger_id, line));
+                }
+            }
+            txn.commit()?;
+        }
+        let mut txn = self.db.begin_rw_txn()?;
+        for key in &keys {
+            txn.write(&index_db, key, &[])?;
+        }
+        txn.write(&index_db, &INDEX_VERSION_KEY, &[1])?;
+        txn.commit()?;
+        debug!("built ledger index with {} keys", keys.len());
+        Ok(())
+    }
+
+    /// Computes the hash chain of all ledgers, that have been written before the chain was introduced
+    ///
+    /// The meta information of these ledgers is rewritten in the current layout, together with the head of their chain.
+    /// Registered as migration of the [`LEDGER_SUB_DB`], see [`super::schema`].
+    pub fn chain_all(&self) -> Result<()> {
+        let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
+        let mut unchained = Vec::new();
+        {
+            let txn = self.db.begin_ro_txn()?;
+            let mut cursor = txn.ro_cursor(&db_ptr)?;
+            for value in meta_values(&mut cursor) {
+                let meta = LedgerMeta::upgrade(value)?;
+                if meta.head.is_none() {
+                    unchained.push(meta);
+                }
+            }
+            drop(cursor);
+            txn.commit()?;
+        }
+        let mut txn = self.db.begin_rw_txn()?;
+        for mut meta in unchained.into_iter() {
+            let ledger = self.select(meta.creditor.merge_compact(&meta.debitor));
+            let mut prev = Hash256::zero();
+            for line in 0..meta.len {
+                prev = ledger.write_line_hash(&mut txn, &db_ptr, &prev, line)?;
+            }
+            // Empty ledgers have no head, but their meta information is still rewritten in the current layout
+            meta.head = (meta.len > 0).then_some(prev);
+            let meta_bytes = postcard::to_allocvec(&meta)?;
+            txn.write(&db_ptr, &LedgerKey::meta(ledger.ledger_id), &meta_bytes)?;
+            debug!(
+                "chained ledger {} with {} lines",
+                ledger.ledger_id, meta.len
+            );
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
     /// Returns a list of all existing ledgers
     pub fn all_paginated(
         &self,
@@ -128,28 +453,16 @@ impl<'a, S: Db> Ledger<'a, S> {
         let txn = self.db.begin_ro_txn()?;
         let mut cursor = txn.ro_cursor(&db_ptr)?;
 
+        let values = meta_values(&mut cursor);
+        let total_elements = values.len();
         let range = pagination.to_range();
-        let mut idx = 0;
-
-        // NOTE: We always iterate over the entire key-space.
-        // As this is all super low level, it is quite efficient,
-        // but on paper it does not scale very well.
-        // In the far or near future we have to migrate this to something different.
-        // (right now - parsing through 3TB of ledger data takes 300ms, so we have some time until this becomes relevant)
-        for (key, value) in cursor.iter() {
-            let key = LedgerKey::from_slice(key);
-            if !key.is_meta() {
-                continue;
-            }
-            if range.start <= idx && idx < range.end {
-                let ledger_meta: LedgerMeta = postcard::from_bytes(value)?;
-                elements.push(ledger_meta.into_dto());
-            }
-            idx += 1;
+        for value in values.into_iter().skip(range.start).take(range.len()) {
+            let ledger_meta = LedgerMeta::decode(value)?;
+            elements.push(ledger_meta.into_dto());
         }
         let paginated = PaginatedElements {
             elements,
-            total_elements: idx,
+            total_elements,
             pagination,
         };
         Ok(paginated)
@@ -160,16 +473,9 @@ impl<'a, S: Db> Ledger<'a, S> {
         let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
         let txn = self.db.begin_ro_txn()?;
         let mut cursor = txn.ro_cursor(&db_ptr)?;
-        let mut last_ledger_id = 0;
-        for (key, _) in cursor.iter() {
-            let key = LedgerKey::from_slice(key);
-            let ledger_id = key.ledger_id();
-            if ledger_id == last_ledger_id {
-                continue;
-            }
-            last_ledger_id = ledger_id;
+        for key in first_keys(&mut cursor) {
             // Read creditor and debitor
-            let elem = self.get_ledger_id(&txn, &db_ptr, ledger_id, key.line())?;
+            let elem = self.get_ledger_id(&txn, &db_ptr, key.ledger_id(), key.line())?;
             out.push(elem);
         }
         Ok(out)
@@ -183,34 +489,17 @@ impl<'a, S: Db> Ledger<'a, S> {
         let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
         let txn = self.db.begin_ro_txn()?;
         let mut cursor = txn.ro_cursor(&db_ptr)?;
-        let mut last_ledger_id = 0;
-
+        let keys = first_keys(&mut cursor);
+        let total_elements = keys.len();
         let range = pagination.to_range();
-        let mut idx = 0;
-
-        // NOTE: We always iterate over the entire key-space.
-        // As this is all super low level, it is quite efficient,
-        // but on paper it does not scale very well.
-        // In the far or near future we have to migrate this to something different.
-        // (right now - parsing through 3TB of ledger data takes 300ms, so we have some time until this becomes relevant)
-        for (key, _) in cursor.iter() {
-            let key = LedgerKey::from_slice(key);
-            let ledger_id = key.ledger_id();
-            if ledger_id == last_ledger_id {
-                continue;
-            }
-            last_ledger_id = ledger_id;
-
-            if range.start <= idx && idx < range.end {
-                // Read creditor and debitor
-                let elem = self.get_ledger_id(&txn, &db_ptr, ledger_id, key.line())?;
-                elements.push(elem);
-            }
-            idx += 1;
+        for key in keys.into_iter().skip(range.start).take(range.len()) {
+            // Read creditor and debitor
+            let elem = self.get_ledger_id(&txn, &db_ptr, key.ledger_id(), key.line())?;
+            elements.push(elem);
         }
         let paginated = PaginatedElements {
             elements,
-            total_elements: idx,
+            total_elements,
             pagination,
         };
         Ok(paginated)
@@ -258,7 +547,7 @@ impl<'a, S: Db> SelectedLedger<'a, S> {
         let txn = self.db.begin_ro_txn()?;
         match txn.read(&db_ptr, &key)? {
             Some(val) => {
-                let out = postcard::from_bytes(val)?;
+                let out = LedgerMeta::decode(val)?;
                 Ok(Some(out))
             }
             None => Ok(None),
@@ -268,13 +557,14 @@ impl<'a, S: Db> SelectedLedger<'a, S> {
     /// Returns the length of the ledger (if it exists)
     pub fn meta_for_contract(&self, cid: ContractId) -> Result<Option<LedgerMetaDto>> {
         let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
+        let index_db = self.db.open_sub_db(LEDGER_INDEX_SUB_DB)?;
         let txn = self.db.begin_ro_txn()?;
 
         // Read ledger meta
         let key = LedgerKey::meta(self.ledger_id);
-        let mut meta: LedgerMeta = match txn
+        let mut meta = match txn
             .read(&db_ptr, &key)?
-            .and_then(|b| postcard::from_bytes(b).ok())
+            .and_then(|b| LedgerMeta::decode(b).ok())
         {
             Some(m) => m,
             None => return Ok(None),
@@ -282,24 +572,21 @@ impl<'a, S: Db> SelectedLedger<'a, S> {
         // Reset it, to only keep the creditor -> debitor info
         meta.reset_balance();
 
-        let mut line = 0;
-        loop {
-            // If there are no more lines to read, then break
-            match self.check_line(&txn, &db_ptr, line as u64, cid)? {
-                Some(true) => { /* execute the logic below */ }
-                Some(false) => {
-                    line += 1;
-                    continue;
-                }
-                None => break,
-            }
+        for line in self.contract_lines(&txn, &index_db, cid)? {
             let (entry, entry_cid, _tx_ctx) = self
-                .get(&txn, &db_ptr, line as u64)?
-                .context("line must exist")?;
+                .get(&txn, &db_ptr, line)?
+                .context("indexed line must exist")?;
             debug_assert_eq!(entry_cid, cid);
             // Update the ledger-meta based on the new entry
-            meta.update(&entry)?;
-            line += 1;
+            match entry.reverses {
+                Some(original) => {
+                    let (original, _, _) = self
+                        .get(&txn, &db_ptr, original)?
+                        .context("reversed line must exist")?;
+                    meta.update_reversal(&entry, &original)?;
+                }
+                None => meta.update(&entry)?,
+            }
         }
         let mut dto = meta.into_dto();
         dto.contract_id = Some(cid);
@@ -318,7 +605,7 @@ impl<'a, S: Db> SelectedLedger<'a, S> {
         let meta_key = LedgerKey::meta(self.ledger_id);
         let total_elements = match txn
             .read(&db_ptr, &meta_key)?
-            .and_then(|b| postcard::from_bytes::<LedgerMeta>(b).ok())
+            .and_then(|b| LedgerMeta::decode(b).ok())
         {
             Some(meta) => meta.len as usize,
             None => return Ok(PaginatedElements::empty(pagination)),
@@ -327,10 +614,8 @@ impl<'a, S: Db> SelectedLedger<'a, S> {
         let mut elements = Vec::new();
         if !pagination.reverse {
             for idx in pagination.to_range() {
-                match self.get(&txn, &db_ptr, idx as u64)? {
-                    Some((entry, cid, tx_ctx)) => {
-                        elements.push(LedgerEntryDto::new(entry, cid, tx_ctx));
-                    }
+                match self.get_dto(&txn, &db_ptr, idx as u64)? {
+                    Some(dto) => elements.push(dto),
                     None => break,
                 }
             }
@@ -341,10 +626,10 @@ impl<'a, S: Db> SelectedLedger<'a, S> {
                 // NOTE: We start with idx == total_elements if range.start == 0;
                 // So we decrease in advance. Otherwise the idx > 0 would result in us leaving out the last element.
                 idx -= 1;
-                let (entry, cid, tx_ctx) = self
-                    .get(&txn, &db_ptr, idx as u64)?
+                let dto = self
+                    .get_dto(&txn, &db_ptr, idx as u64)?
                     .context("entry idx < max_len must exist")?;
-                elements.push(LedgerEntryDto::new(entry, cid, tx_ctx));
+                elements.push(dto);
                 if range.end - range.start <= elements.len() {
                     break;
                 }
@@ -357,85 +642,197 @@ impl<'a, S: Db> SelectedLedger<'a, S> {
         })
     }
 
-    pub fn get_contract_paginated(
+    /// Returns a paginated list of ledger entries, that match the given filter
+    ///
+    /// Falls back to [`get_entries_paginated`](Self::get_entries_paginated), if the filter is empty.
+    pub fn get_entries_filtered(
         &self,
-        cid: ContractId,
+        filter: &EntryFilter,
         pagination: Pagination,
     ) -> Result<PaginatedElements<LedgerEntryDto>> {
+        if filter.is_empty() {
+            return self.get_entries_paginated(pagination);
+        }
         let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
+        let index_db = self.db.open_sub_db(LEDGER_INDEX_SUB_DB)?;
         let txn = self.db.begin_ro_txn()?;
 
-        let mut elements = Vec::new();
+        let mut lines = self.filter_lines(&txn, &db_ptr, &index_db, filter)?;
+        if pagination.reverse {
+            lines.reverse();
+        }
         let range = pagination.to_range();
-        let mut idx = 0;
-
-        if !pagination.reverse {
-            // Go forward and ignore all entries, where the contract-id does not match
-            let mut line = 0;
-            loop {
-                // If there are no more lines to read, then break
-                match self.check_line(&txn, &db_ptr, line as u64, cid)? {
-                    Some(true) => { /* execute the logic below */ }
-                    Some(false) => {
-                        line += 1;
-                        continue;
-                    }
-                    None => break,
-                }
-                // Take as many items as fit in the page
-                if range.start <= idx && idx < range.end {
-                    let (entry, cid, tx_ctx) = self
-                        .get(&txn, &db_ptr, line as u64)?
-                        .context("line must exist")?;
-                    elements.push(LedgerEntryDto::new(entry, cid, tx_ctx));
-                }
-                // Keep counting, since we don't know the 'total_elements' in advance
-                idx += 1;
-                line += 1;
-            }
-        } else {
-            // Go backward and ignore all entries, where the contract-id does not match
-            let meta_key = LedgerKey::meta(self.ledger_id);
-            let all_entries = match txn
-                .read(&db_ptr, &meta_key)?
-                .and_then(|b| postcard::from_bytes::<LedgerMeta>(b).ok())
-            {
-                Some(meta) => meta.len as usize,
-                None => return Ok(PaginatedElements::empty(pagination)),
+        let mut elements = Vec::new();
+        let mut total_elements = 0;
+        for line in lines {
+            let dto = match self.get_dto(&txn, &db_ptr, line)? {
+                Some(dto) => dto,
+                None => break,
             };
-
-            let mut line = all_entries;
-            while line > 0 {
-                // NOTE: We start with line == total_elements
-                // So we decrease in advance. Otherwise the line > 0 would result in us leaving out the last element.
-                line -= 1;
-                // if it returns 'false' we want to continue, as this is a line not matching to our contract-id
-                if !self
-                    .check_line(&txn, &db_ptr, line as u64, cid)?
-                    .unwrap_or_default()
-                {
-                    continue;
-                }
-                if range.start <= idx && idx < range.end {
-                    let (entry, cid, tx_ctx) = self
-                        .get(&txn, &db_ptr, line as u64)?
-                        .context("line must exist")?;
-                    elements.push(LedgerEntryDto::new(entry, cid, tx_ctx));
-                }
-                idx += 1;
+            if !filter.matches(&dto) {
+                continue;
             }
+            if range.contains(&total_elements) {
+                elements.push(dto);
+            }
+            total_elements += 1;
         }
         Ok(PaginatedElements {
             elements,
-            total_elements: idx,
+            total_elements,
             pagination,
         })
     }
 
-    /// Reads a single column in an existing db-txn
-    fn read_column<T>(
+    /// Writes all ledger entries, that match the given filter, in the given format
+    ///
+    /// The entries are written page by page in commit order (see [`ExportPages`]).
+    /// Returns the number of exported entries.
+    pub fn export(
+        &self,
+        filter: &EntryFilter,
+        format: ExportFormat,
+        out: &mut impl Write,
+    ) -> Result<usize> {
+        let mut pages = self.export_pages(filter.clone(), format)?;
+        for page in pages.by_ref() {
+            out.write_all(&page?)?;
+        }
+        out.flush()?;
+        Ok(pages.exported())
+    }
+
+    /// Returns the export of all ledger entries, that match the given filter, as an iterator over pages
+    ///
+    /// The candidate lines are collected upfront, the entries themselves are only read when a page is requested.
+    pub fn export_pages(
+        &self,
+        filter: EntryFilter,
+        format: ExportFormat,
+    ) -> Result<ExportPages<S>> {
+        let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
+        let index_db = self.db.open_sub_db(LEDGER_INDEX_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let lines = self.filter_lines(&txn, &db_ptr, &index_db, &filter)?;
+        txn.commit()?;
+        Ok(ExportPages {
+            db: self.db.clone(),
+            ledger_id: self.ledger_id,
+            filter,
+            format,
+            lines,
+            pos: 0,
+            header: matches!(format, ExportFormat::Csv),
+            exported: 0,
+        })
+    }
+
+    /// Checks, if the entry is a valid reversal of a line in this ledger
+    ///
+    /// Returns the reversed line and its entry, or `None` if the entry is no reversal.
+    pub(crate) fn check_reversal<'t>(
+        &self,
+        txn: &impl RawRead<'t, S::DB>,
+        db_ptr: &<S as Db>::Handle,
+        entry: &LedgerEntry,
+        cid: ContractId,
+    ) -> Result<std::result::Result<Option<(u64, LedgerEntry)>, InvalidReversal>> {
+        let line = match (entry.kind, entry.reverses) {
+            (EntryType::REVERSAL, Some(line)) => line,
+            (EntryType::REVERSAL, None) => return Ok(Err(InvalidReversal::MissingLine)),
+            (_, Some(_)) => return Ok(Err(InvalidReversal::NotAReversal)),
+            (_, None) => return Ok(Ok(None)),
+        };
+        let (original, original_cid, _) = match self.get(txn, db_ptr, line)? {
+            Some(line) => line,
+            None => return Ok(Err(InvalidReversal::UnknownLine)),
+        };
+        if original_cid != cid {
+            return Ok(Err(InvalidReversal::OtherContract));
+        }
+        if self
+            .read_column(txn, db_ptr, line, "reversed_by", u64_from_slice)?
+            .is_some()
+        {
+            return Ok(Err(InvalidReversal::AlreadyReversed));
+        }
+        if original.kind == EntryType::REVERSAL
+            || (entry.creditor, entry.debitor) != (original.creditor, original.debitor)
+            || (entry.amount_milli, entry.currency) != (original.amount_milli, original.currency)
+        {
+            return Ok(Err(InvalidReversal::Mismatch));
+        }
+        Ok(Ok(Some((line, original))))
+    }
+
+    /// Returns the candidates for a filtered query in commit order
+    ///
+    /// If the filter selects a contract, only the lines of that contract are returned (using the secondary index).
+    fn filter_lines(
+        &self,
+        txn: &<S as Db>::RoTx<'_>,
+        db_ptr: &<S as Db>::Handle,
+        index_db: &<S as Db>::Handle,
+        filter: &EntryFilter,
+    ) -> Result<Vec<u64>> {
+        if let Some(cid) = filter.contract_id {
+            return self.contract_lines(txn, index_db, cid);
+        }
+        let len = txn
+            .read(db_ptr, &LedgerKey::meta(self.ledger_id))?
+            .map(LedgerMeta::decode)
+            .transpose()?
+            .map(|meta| meta.len)
+            .unwrap_or_default();
+        Ok((0..len).collect())
+    }
+
+    /// Returns a paginated list of ledger entries, that were written by the given contract
+    pub fn get_contract_paginated(
+        &self,
+        cid: ContractId,
+        pagination: Pagination,
+    ) -> Result<PaginatedElements<LedgerEntryDto>> {
+        let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
+        let index_db = self.db.open_sub_db(LEDGER_INDEX_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let lines = self.contract_lines(&txn, &index_db, cid)?;
+        let total_elements = lines.len();
+        let mut elements = Vec::new();
+        for line in paginate(lines, &pagination) {
+            let dto = self
+                .get_dto(&txn, &db_ptr, line)?
+                .context("indexed line must exist")?;
+            elements.push(dto);
+        }
+        Ok(PaginatedElements {
+            elements,
+            total_elements,
+            pagination,
+        })
+    }
+
+    /// Returns the lines of this ledger, that were written by the given contract
+    fn contract_lines(
         &self,
         txn: &<S as Db>::RoTx<'_>,
+        index_db: &<S as Db>::Handle,
+        cid: ContractId,
+    ) -> Result<Vec<u64>> {
+        let mut prefix = contract_index_prefix(&cid);
+        prefix.extend_from_slice(&self.ledger_id.to_be_bytes());
+        let mut cursor = txn.ro_cursor(index_db)?;
+        let lines = cursor
+            .iter_prefix(&prefix)
+            .map(|(key, _)| index_suffix(key, prefix.len())[0])
+            .collect();
+        Ok(lines)
+    }
+
+    /// Reads a single column in an existing db-txn
+    fn read_column<'t, T>(
+        &self,
+        txn: &impl RawRead<'t, S::DB>,
         db_ptr: &<S as Db>::Handle,
         line: u64,
         column: &'static str,
@@ -446,41 +843,216 @@ impl<'a, S: Db> SelectedLedger<'a, S> {
         Ok(out)
     }
 
-    /// Checks if a ledger entry exists and matches a specific contract-id
+    /// Returns all debts of the ledger, that are not (fully) settled or cancelled
+    ///
+    /// Settlements and cancellations with a reference are matched against the debts with the same reference.
+    /// Settlements without a reference are applied to the oldest open debts.
+    /// In both cases only debts of the same direction and currency are considered.
+    /// Reversed lines (and their reversals) are ignored.
+    ///
+    /// The overdue days are calculated relative to `as_of` (milliseconds since unix-epoch).
+    pub fn open_items(&self, as_of: u64) -> Result<Vec<OpenItem>> {
+        let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let len = txn
+            .read(&db_ptr, &LedgerKey::meta(self.ledger_id))?
+            .map(LedgerMeta::decode)
+            .transpose()?
+            .map(|meta| meta.len)
+            .unwrap_or_default();
+
+        let mut items: Vec<OpenItem> = Vec::new();
+        for line in 0..len {
+            let (entry, _, _) = self
+                .get(&txn, &db_ptr, line)?
+                .context("entry idx < max_len must exist")?;
+            // A reversal and its original line cancel each other out
+            let reversed_by =
+                self.read_column(&txn, &db_ptr, line, "reversed_by", u64_from_slice)?;
+            if entry.kind == EntryType::REVERSAL || reversed_by.is_some() {
+                continue;
+            }
+            if entry.kind == EntryType::CREATE {
+                let commited = self.read_column(&txn, &db_ptr, line, "commited", u64_from_slice)?;
+                items.push(OpenItem::new(line, entry, commited, as_of));
+                continue;
+            }
+            let mut remaining = entry.amount_milli;
+            let candidates = items.iter_mut().filter(|item| {
+                item.open_milli > 0
+                    && (item.entry.creditor, item.entry.debitor, item.entry.currency)
+                        == (entry.creditor, entry.debitor, entry.currency)
+                    && (entry.reference.is_none() || item.entry.reference == entry.reference)
+            });
+            for item in candidates {
+                if remaining <= 0 {
+                    break;
+                }
+                let applied = remaining.min(item.open_milli);
+                item.open_milli -= applied;
+                remaining -= applied;
+            }
+        }
+        txn.commit()?;
+        items.retain(|item| item.open_milli > 0);
+        for item in items.iter_mut() {
+            item.open = Money::from_milli(item.entry.currency, item.open_milli).to_string();
+        }
+        Ok(items)
+    }
+
+    /// Generates an aging report of all open debts relative to `as_of` (milliseconds since unix-epoch)
+    pub fn aging_report(&self, as_of: u64) -> Result<AgingReport> {
+        let items = self.open_items(as_of)?;
+        let mut buckets: HashMap<Currency, AgingBuckets> = HashMap::new();
+        for item in items.iter() {
+            let bucket = buckets.entry(item.entry.currency).or_default();
+            let open = item.open_milli as f64 / 1000.0;
+            match item.days_overdue {
+                None if item.due_date.is_none() => bucket.undated += open,
+                None => bucket.not_due += open,
+                Some(0..=30) => bucket.days_0_30 += open,
+                Some(31..=60) => bucket.days_31_60 += open,
+                Some(61..=90) => bucket.days_61_90 += open,
+                Some(_) => bucket.days_over_90 += open,
+            }
+        }
+        Ok(AgingReport {
+            ledger_id: self.ledger_id,
+            as_of,
+            buckets,
+            items,
+        })
+    }
+
+    /// Recomputes the hash chain of the ledger and compares it with the stored hashes
+    ///
+    /// A checkpoint is emitted every `interval` lines (and for the last line) of the verified part of the chain.
+    /// These checkpoints should be kept outside of the node - passing them back as `trusted` checkpoints
+    /// detects modifications, even if the entire chain has been rewritten.
+    pub fn verify_chain(&self, interval: u64, trusted: &[Checkpoint]) -> Result<ChainReport> {
+        let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let meta = txn
+            .read(&db_ptr, &LedgerKey::meta(self.ledger_id))?
+            .map(LedgerMeta::decode)
+            .transpose()?;
+        let (len, head) = meta.map(|m| (m.len, m.head)).unwrap_or_default();
+        let trusted: HashMap<u64, Hash256> = trusted.iter().map(|c| (c.line, c.hash)).collect();
+        let interval = interval.max(1);
+
+        let mut prev = Hash256::zero();
+        let mut first_invalid = None;
+        let mut checkpoint_mismatch = None;
+        let mut checkpoints = Vec::new();
+        for line in 0..len {
+            let hash = self.line_hash(&txn, &db_ptr, &prev, line)?;
+            let stored =
+                self.read_column(&txn, &db_ptr, line, "hash", |b| Hash256::try_from(b).ok())?;
+            if first_invalid.is_none() && stored != Some(hash) {
+                first_invalid = Some(line);
+            }
+            if checkpoint_mismatch.is_none() && trusted.get(&line).is_some_and(|t| *t != hash) {
+                checkpoint_mismatch = Some(line);
+            }
+            if first_invalid.is_none() && ((line + 1) % interval == 0 || line + 1 == len) {
+                checkpoints.push(Checkpoint {
+                    line,
+                    hash,
+                    signature: None,
+                });
+            }
+            prev = hash;
+        }
+        txn.commit()?;
+
+        let head_valid = match head {
+            Some(head) => head == prev,
+            None => len == 0,
+        };
+        let valid = head_valid && first_invalid.is_none() && checkpoint_mismatch.is_none();
+        Ok(ChainReport {
+            ledger_id: self.ledger_id,
+            len,
+            head,
+            valid,
+            first_invalid,
+            checkpoint_mismatch,
+            verified_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("timestamp < 1970")
+                .as_millis()
+                .try_into()
+                .expect("u64 should fit for 584942417 years"),
+            checkpoints,
+            signer: None,
+        })
+    }
+
+    /// Computes the hash of a line as `Hash256(prev_hash || line_bytes)`
     ///
-    /// Returns `Some(true)` if the line matches the contract-id and `Some(false)` if not.
-    /// If no line is found for the given index, `None` is returned.
-    fn check_line(
+    /// The line bytes are the raw values of all [`HASHED_COLUMNS`] (each prefixed by its name and length).
+    fn line_hash<'t>(
         &self,
-        txn: &<S as Db>::RoTx<'_>,
+        txn: &impl RawRead<'t, S::DB>,
         db_ptr: &<S as Db>::Handle,
+        prev: &Hash256,
         line: u64,
-        target_cid: ContractId,
-    ) -> Result<Option<bool>> {
-        let contract_id = match self.read_column(txn, db_ptr, line, "contract_id", |b| {
-            ContractId::from_slice(b).ok()
-        })? {
-            Some(c) => c,
-            None => {
-                // Early return here - if this value exists, all the others must exist too
-                return Ok(None);
+    ) -> Result<Hash256> {
+        let mut hasher = Hasher::new();
+        hasher.update(prev);
+        for column in HASHED_COLUMNS {
+            let key = LedgerKey::new(self.ledger_id, line, column);
+            if let Some(value) = txn.read(db_ptr, &key)? {
+                hasher.update(&column);
+                hasher.update(&(value.len() as u32).to_be_bytes());
+                hasher.update(&value);
             }
-        };
-        // If we only want to match a single contract-id, we can do it like this:
-        Ok(Some(target_cid == contract_id))
+        }
+        Ok(hasher.finalize())
     }
 
-    /// Reads a line from the ledger
-    fn get(
+    /// Computes the hash of a line and writes it into the "hash" column
+    fn write_line_hash(
+        &self,
+        txn: &mut <S as Db>::RwTx<'_>,
+        db_ptr: &<S as Db>::Handle,
+        prev: &Hash256,
+        line: u64,
+    ) -> Result<Hash256> {
+        let hash = self.line_hash(&*txn, db_ptr, prev, line)?;
+        txn.write(db_ptr, &LedgerKey::new(self.ledger_id, line, "hash"), &hash)?;
+        Ok(hash)
+    }
+
+    /// Reads a line from the ledger and converts it for usage in APIs
+    fn get_dto(
         &self,
         txn: &<S as Db>::RoTx<'_>,
         db_ptr: &<S as Db>::Handle,
         line: u64,
-    ) -> Result<Option<(LedgerEntry, ContractId, TxCtx)>> {
-        let contract_id = match self.read_column(txn, db_ptr, line, "contract_id", |b| {
-            ContractId::from_slice(b).ok()
-        })? {
-            Some(c) => c,
+    ) -> Result<Option<LedgerEntryDto>> {
+        let (entry, cid, tx_ctx) = match self.get(txn, db_ptr, line)? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        let commited = self.read_column(txn, db_ptr, line, "commited", u64_from_slice)?;
+        let mut dto = LedgerEntryDto::new(line, entry, cid, tx_ctx, commited);
+        dto.reversed_by = self.read_column(txn, db_ptr, line, "reversed_by", u64_from_slice)?;
+        Ok(Some(dto))
+    }
+
+    /// Reads a line from the ledger
+    fn get<'t>(
+        &self,
+        txn: &impl RawRead<'t, S::DB>,
+        db_ptr: &<S as Db>::Handle,
+        line: u64,
+    ) -> Result<Option<(LedgerEntry, ContractId, TxCtx)>> {
+        let contract_id = match self.read_column(txn, db_ptr, line, "contract_id", |b| {
+            ContractId::from_slice(b).ok()
+        })? {
+            Some(c) => c,
             None => {
                 // Early return here - if this value exists, all the others must exist too
                 return Ok(None);
@@ -519,6 +1091,15 @@ impl<'a, S: Db> SelectedLedger<'a, S> {
                 postcard::from_bytes(b).ok()
             })?
             .context("missing field tx-ctx")?;
+        // Optional columns
+        let due_date = self.read_column(txn, db_ptr, line, "due_date", u64_from_slice)?;
+        let reference = self.read_column(txn, db_ptr, line, "reference", |b| {
+            Some(String::from_utf8_lossy(b).to_string())
+        })?;
+        let payment_terms = self.read_column(txn, db_ptr, line, "payment_terms", |b| {
+            Some(u32::from_be_bytes(b.try_into().ok()?))
+        })?;
+        let reverses = self.read_column(txn, db_ptr, line, "reverses", u64_from_slice)?;
         let entry = LedgerEntry {
             creditor,
             debitor,
@@ -527,6 +1108,10 @@ impl<'a, S: Db> SelectedLedger<'a, S> {
             currency,
             kind,
             tag: tag.to_string(),
+            due_date,
+            reference,
+            payment_terms,
+            reverses,
         };
         Ok(Some((entry, contract_id, tx_ctx)))
     }
@@ -537,9 +1122,142 @@ fn i64_from_slice(slice: &[u8]) -> Option<i64> {
     Some(i64::from_be_bytes(b))
 }
 
+fn u64_from_slice(slice: &[u8]) -> Option<u64> {
+    let b = slice.try_into().ok()?;
+    Some(u64::from_be_bytes(b))
+}
+
+/// Columns, that are covered by the hash of a line (in this order)
+///
+/// New columns must only be appended, so the hashes of existing lines stay the same.
+/// The `reversed_by` column is not covered, as it is written after the line, once it gets reversed.
+const HASHED_COLUMNS: [&str; 14] = [
+    "creditor",
+    "debitor",
+    "amount",
+    "tax",
+    "currency",
+    "kind",
+    "tag",
+    "contract_id",
+    "tx_ctx",
+    "commited",
+    "due_date",
+    "reference",
+    "payment_terms",
+    "reverses",
+];
+
+/// Key of the node, that signs off the checkpoints of a verified hash chain
+pub use ed25519_dalek::{SigningKey as NodeKey, VerifyingKey as NodePublicKey};
+
+/// Generates a new random [`NodeKey`]
+pub fn generate_node_key() -> NodeKey {
+    let mut secret = [0u8; 32];
+    rand::rng().fill(&mut secret);
+    NodeKey::from_bytes(&secret)
+}
+
+/// A verified position in the hash chain of a ledger
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub line: u64,
+    pub hash: Hash256,
+    /// Signature of the node over ledger-id, line and hash (base16 encoded)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl Checkpoint {
+    /// Message, that is signed by the node (`ledger-id || line || hash`)
+    fn message(&self, ledger_id: u64) -> Vec<u8> {
+        let mut msg = Vec::with_capacity(48);
+        msg.extend_from_slice(&ledger_id.to_be_bytes());
+        msg.extend_from_slice(&self.line.to_be_bytes());
+        msg.extend_from_slice(self.hash.as_ref());
+        msg
+    }
+
+    /// Returns `true`, if the checkpoint of the given ledger has been signed off with the node key
+    pub fn verify_signature(&self, ledger_id: u64, signer: &NodePublicKey) -> bool {
+        let signature = match self.signature.as_deref().map(base16::decode) {
+            Some(Ok(bytes)) => bytes,
+            _ => return false,
+        };
+        match ed25519_dalek::Signature::from_slice(&signature) {
+            Ok(signature) => signer
+                .verify_strict(&self.message(ledger_id), &signature)
+                .is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+impl FromStr for Checkpoint {
+    type Err = Error;
+
+    /// Parses a checkpoint in the format `{line}:{hash}`
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::msg(format!("invalid checkpoint '{s}'"));
+        let (line, hash) = s.split_once(':').ok_or_else(invalid)?;
+        // Hashes use their base16 representation in serde
+        let hash = serde_json::Value::String(hash.to_string());
+        Ok(Checkpoint {
+            line: line.parse().map_err(|_| invalid())?,
+            hash: serde_json::from_value(hash).map_err(|_| invalid())?,
+            signature: None,
+        })
+    }
+}
+
+/// Result of the verification of a ledger's hash chain
+#[derive(Debug, Serialize)]
+pub struct ChainReport {
+    pub ledger_id: u64,
+    pub len: u64,
+    /// Head of the chain, that is stored in the ledger meta information
+    pub head: Option<Hash256>,
+    /// `true`, if all stored hashes and the head match the recomputed chain
+    pub valid: bool,
+    /// First line, whose stored hash does not match the recomputed chain
+    pub first_invalid: Option<u64>,
+    /// First trusted checkpoint, that does not match the recomputed chain
+    pub checkpoint_mismatch: Option<u64>,
+    /// Timestamp (as milliseconds since unix-epoch) of the verification
+    pub verified_at: u64,
+    /// Checkpoints of the verified part of the chain
+    pub checkpoints: Vec<Checkpoint>,
+    /// Public key of the node, that signed off the checkpoints (base16 encoded)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+}
+
+impl ChainReport {
+    /// Signs off all checkpoints of the report with the node key
+    pub fn sign_off(&mut self, key: &NodeKey) {
+        for checkpoint in self.checkpoints.iter_mut() {
+            let signature = key.sign(&checkpoint.message(self.ledger_id));
+            checkpoint.signature = Some(base16::encode_lower(&signature.to_bytes()));
+        }
+        self.signer = Some(base16::encode_lower(key.verifying_key().as_bytes()));
+    }
+}
+
+/// A single ledger line together with its position in the ledger
+pub struct LedgerLine {
+    pub ledger_id: u64,
+    pub line: u64,
+    pub entry: LedgerEntry,
+    pub tx_ctx: TxCtx,
+    /// Timestamp (as milliseconds since unix-epoch), when the line was commited
+    pub commited: Option<u64>,
+}
+
 /// A ledger-entry meant to be consumed by APIs
 #[derive(Serialize)]
 pub struct LedgerEntryDto {
+    /// Line of the entry in its ledger
+    pub line: u64,
     pub creditor: BorderlessId,
     pub debitor: BorderlessId,
     pub amount: String,
@@ -548,22 +1266,471 @@ pub struct LedgerEntryDto {
     pub tag: String,
     pub contract_id: ContractId,
     pub tx_ctx: TxCtx,
+    /// Timestamp (as milliseconds since unix-epoch), when the entry was commited
+    ///
+    /// This is `None` for entries, that were written before the timestamp was tracked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commited: Option<u64>,
+    /// Line, that is reversed by this entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reverses: Option<u64>,
+    /// Line of the reversal, if this entry has been reversed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reversed_by: Option<u64>,
+    #[serde(skip)]
+    entry: LedgerEntry,
 }
 
 impl LedgerEntryDto {
-    pub fn new(entry: LedgerEntry, contract_id: ContractId, tx_ctx: TxCtx) -> LedgerEntryDto {
+    pub fn new(
+        line: u64,
+        entry: LedgerEntry,
+        contract_id: ContractId,
+        tx_ctx: TxCtx,
+        commited: Option<u64>,
+    ) -> LedgerEntryDto {
         let amount = Money::from_milli(entry.currency, entry.amount_milli).to_string();
         let tax = Money::from_milli(entry.currency, entry.tax_milli).to_string();
         LedgerEntryDto {
+            line,
             creditor: entry.creditor,
             debitor: entry.debitor,
             amount,
             tax,
             kind: entry.kind.to_string(),
-            tag: entry.tag,
+            tag: entry.tag.clone(),
             contract_id,
             tx_ctx,
+            commited,
+            reverses: entry.reverses,
+            reversed_by: None,
+            entry,
+        }
+    }
+}
+
+/// Reason, why a reversal entry is rejected
+///
+/// The error codes are returned to the contract by the `create_ledger_entry` host function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidReversal {
+    /// The reversed line does not exist
+    UnknownLine,
+    /// The reversed line was written by another contract
+    OtherContract,
+    /// The reversed line has already been reversed
+    AlreadyReversed,
+    /// Participants, amount or currency differ from the reversed line (or it is a reversal itself)
+    Mismatch,
+    /// The reversal does not reference a line
+    MissingLine,
+    /// The entry references a line, but is no reversal
+    NotAReversal,
+}
+
+impl InvalidReversal {
+    /// Error code of the `create_ledger_entry` host function
+    pub fn code(&self) -> u64 {
+        match self {
+            InvalidReversal::UnknownLine => 4,
+            InvalidReversal::OtherContract => 5,
+            InvalidReversal::AlreadyReversed => 6,
+            InvalidReversal::Mismatch => 7,
+            InvalidReversal::MissingLine => 8,
+            InvalidReversal::NotAReversal => 9,
+        }
+    }
+}
+
+impl std::fmt::Display for InvalidReversal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            InvalidReversal::UnknownLine => "cannot reverse unknown line",
+            InvalidReversal::OtherContract => "cannot reverse line of another contract",
+            InvalidReversal::AlreadyReversed => "line is already reversed",
+            InvalidReversal::Mismatch => "reversal does not match the original entry",
+            InvalidReversal::MissingLine => "reversal must reference the original line",
+            InvalidReversal::NotAReversal => "only reversals can reference a line",
+        };
+        f.write_str(msg)
+    }
+}
+
+/// Filter for ledger entries
+///
+/// All fields are optional - only entries that match all given fields are selected.
+#[derive(Debug, Default, Clone)]
+pub struct EntryFilter {
+    pub kind: Option<EntryType>,
+    pub currency: Option<Currency>,
+    pub tag: Option<String>,
+    pub contract_id: Option<ContractId>,
+    /// Only entries commited at or after this timestamp (milliseconds since unix-epoch)
+    pub from: Option<u64>,
+    /// Only entries commited at or before this timestamp (milliseconds since unix-epoch)
+    pub to: Option<u64>,
+}
+
+impl EntryFilter {
+    /// Parses the filter from a http query string
+    ///
+    /// Supported keys: `kind`, `currency` (ISO 4217 code), `tag`, `contract_id`, `from` and `to`.
+    /// Unknown keys (like the pagination) are ignored.
+    pub fn from_query(query: Option<&str>) -> Result<Self> {
+        let mut out = EntryFilter::default();
+        let query = match query {
+            Some(q) => q,
+            None => return Ok(out),
+        };
+        let invalid = |key: &str, value: &str| Error::msg(format!("invalid {key} '{value}'"));
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            if value.is_empty() {
+                continue;
+            }
+            let (key, value) = (key.as_ref(), value.as_ref());
+            // Currency and entry-type use their variant names in serde
+            let variant = || serde_json::Value::String(value.to_ascii_uppercase());
+            match key {
+                "kind" => {
+                    out.kind =
+                        Some(serde_json::from_value(variant()).map_err(|_| invalid(key, value))?)
+                }
+                "currency" => {
+                    out.currency =
+                        Some(serde_json::from_value(variant()).map_err(|_| invalid(key, value))?)
+                }
+                "tag" => out.tag = Some(value.to_string()),
+                "contract_id" => {
+                    out.contract_id = Some(value.parse().map_err(|_| invalid(key, value))?)
+                }
+                "from" => out.from = Some(value.parse().map_err(|_| invalid(key, value))?),
+                "to" => out.to = Some(value.parse().map_err(|_| invalid(key, value))?),
+                _ => (),
+            }
         }
+        Ok(out)
+    }
+
+    /// Returns `true` if the filter would match every entry
+    pub fn is_empty(&self) -> bool {
+        self.kind.is_none()
+            && self.currency.is_none()
+            && self.tag.is_none()
+            && self.contract_id.is_none()
+            && self.from.is_none()
+            && self.to.is_none()
+    }
+
+    /// Returns `true` if the entry matches the filter
+    ///
+    /// Entries without timestamp never match a time range.
+    fn matches(&self, dto: &LedgerEntryDto) -> bool {
+        let in_range = match (self.from, self.to, dto.commited) {
+            (None, None, _) => true,
+            (_, _, None) => false,
+            (from, to, Some(ts)) => from.is_none_or(|f| f <= ts) && to.is_none_or(|t| ts <= t),
+        };
+        in_range
+            && self.kind.is_none_or(|k| k == dto.entry.kind)
+            && self.currency.is_none_or(|c| c == dto.entry.currency)
+            && self.tag.as_ref().is_none_or(|t| *t == dto.entry.tag)
+            && self.contract_id.is_none_or(|c| c == dto.contract_id)
+    }
+}
+
+/// Output format of a ledger export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma separated values with a header line
+    Csv,
+    /// One json object per line
+    JsonLines,
+}
+
+impl ExportFormat {
+    /// Returns the mime-type of the format
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::JsonLines => "application/jsonl",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" | "ndjson" => Ok(ExportFormat::JsonLines),
+            other => Err(Error::msg(format!("unknown export format '{other}'"))),
+        }
+    }
+}
+
+/// Number of candidate lines, that are rendered into a single page of an export
+const EXPORT_PAGE_SIZE: usize = 256;
+
+/// Export of a ledger, that is rendered page by page
+///
+/// Every page is read in its own read-transaction, so an export neither holds a transaction open
+/// nor the whole output in memory. The csv header is part of the first page.
+pub struct ExportPages<S: Db> {
+    db: S,
+    ledger_id: u64,
+    filter: EntryFilter,
+    format: ExportFormat,
+    lines: Vec<u64>,
+    pos: usize,
+    header: bool,
+    exported: usize,
+}
+
+impl<S: Db> ExportPages<S> {
+    /// Number of entries, that have been exported so far
+    pub fn exported(&self) -> usize {
+        self.exported
+    }
+
+    fn render_page(&mut self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        if std::mem::take(&mut self.header) {
+            writeln!(out, "{}", ExportRecord::CSV_HEADER)?;
+        }
+        let ledger = SelectedLedger {
+            db: &self.db,
+            ledger_id: self.ledger_id,
+        };
+        let db_ptr = self.db.open_sub_db(LEDGER_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let end = (self.pos + EXPORT_PAGE_SIZE).min(self.lines.len());
+        for &line in &self.lines[self.pos..end] {
+            let dto = match ledger.get_dto(&txn, &db_ptr, line)? {
+                Some(dto) => dto,
+                None => {
+                    // Lines are contiguous, so there is nothing left to export
+                    self.pos = self.lines.len();
+                    break;
+                }
+            };
+            self.pos += 1;
+            if !self.filter.matches(&dto) {
+                continue;
+            }
+            let record = ExportRecord::new(self.ledger_id, line, dto);
+            match self.format {
+                ExportFormat::Csv => writeln!(out, "{}", record.to_csv())?,
+                ExportFormat::JsonLines => {
+                    serde_json::to_writer(&mut out, &record)?;
+                    writeln!(out)?;
+                }
+            }
+            self.exported += 1;
+        }
+        txn.commit()?;
+        Ok(out)
+    }
+}
+
+impl<S: Db> Iterator for ExportPages<S> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.lines.len() && !self.header {
+            return None;
+        }
+        match self.render_page() {
+            Ok(page) => Some(Ok(page)),
+            Err(e) => {
+                // Stop after the first error
+                self.pos = self.lines.len();
+                self.header = false;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// A single line of a ledger export
+///
+/// Amounts are plain decimal numbers in the major unit of the currency (e.g. `12.50`),
+/// and the currency is given by its ISO 4217 code, so the export can be consumed by accounting software.
+#[derive(Serialize)]
+pub struct ExportRecord {
+    pub ledger_id: u64,
+    pub line: u64,
+    pub commited: Option<u64>,
+    pub kind: EntryType,
+    pub creditor: BorderlessId,
+    pub debitor: BorderlessId,
+    pub amount: String,
+    pub tax: String,
+    pub currency: Currency,
+    pub tag: String,
+    pub due_date: Option<u64>,
+    pub reference: Option<String>,
+    pub payment_terms: Option<u32>,
+    pub reverses: Option<u64>,
+    pub reversed_by: Option<u64>,
+    pub contract_id: ContractId,
+    pub tx_ctx: TxCtx,
+}
+
+impl ExportRecord {
+    const CSV_HEADER: &'static str = "ledger_id,line,commited,kind,creditor,debitor,amount,tax,currency,tag,due_date,reference,payment_terms,reverses,reversed_by,contract_id,tx_chain_id,tx_number,tx_hash,tx_index";
+
+    fn new(ledger_id: u64, line: u64, dto: LedgerEntryDto) -> Self {
+        let entry = dto.entry;
+        let fracs = entry.currency.fracs();
+        ExportRecord {
+            ledger_id,
+            line,
+            commited: dto.commited,
+            kind: entry.kind,
+            creditor: entry.creditor,
+            debitor: entry.debitor,
+            amount: decimal(entry.amount_milli, fracs),
+            tax: decimal(entry.tax_milli, fracs),
+            currency: entry.currency,
+            tag: entry.tag,
+            due_date: entry.due_date,
+            reference: entry.reference,
+            payment_terms: entry.payment_terms,
+            reverses: dto.reverses,
+            reversed_by: dto.reversed_by,
+            contract_id: dto.contract_id,
+            tx_ctx: dto.tx_ctx,
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        let tx_id = &self.tx_ctx.tx_id;
+        format!(
+            "{},{},{},{},{},{},{},{},{:?},{},{},{},{},{},{},{},{},{},{},{}",
+            self.ledger_id,
+            self.line,
+            self.commited.map(|c| c.to_string()).unwrap_or_default(),
+            self.kind,
+            self.creditor,
+            self.debitor,
+            self.amount,
+            self.tax,
+            self.currency,
+            csv_escape(&self.tag),
+            self.due_date.map(|d| d.to_string()).unwrap_or_default(),
+            self.reference
+                .as_deref()
+                .map(csv_escape)
+                .unwrap_or_default(),
+            self.payment_terms
+                .map(|t| t.to_string())
+                .unwrap_or_default(),
+            self.reverses.map(|l| l.to_string()).unwrap_or_default(),
+            self.reversed_by.map(|l| l.to_string()).unwrap_or_default(),
+            self.contract_id,
+            tx_id.chain_id,
+            tx_id.number,
+            tx_id.hash,
+            self.tx_ctx.index,
+        )
+    }
+}
+
+/// Formats an amount in thousandths as decimal number with the given number of fractional digits
+fn decimal(amount_milli: i64, fracs: u8) -> String {
+    let sign = if amount_milli < 0 { "-" } else { "" };
+    let abs = amount_milli.unsigned_abs();
+    let (integral, fractional) = (abs / 1000, abs % 1000);
+    match fracs.min(3) {
+        0 => format!("{sign}{integral}"),
+        fracs => {
+            let fractional = format!("{fractional:03}");
+            format!("{sign}{integral}.{}", &fractional[..fracs as usize])
+        }
+    }
+}
+
+/// Milliseconds per day
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+/// A debt, that has not (yet) been fully settled or cancelled
+#[derive(Serialize)]
+pub struct OpenItem {
+    /// Line of the debt in the ledger
+    pub line: u64,
+    pub creditor: BorderlessId,
+    pub debitor: BorderlessId,
+    /// Original amount of the debt
+    pub amount: String,
+    /// Open amount of the debt
+    pub open: String,
+    pub reference: Option<String>,
+    /// Effective due date (milliseconds since unix-epoch)
+    ///
+    /// This is either the explicit due date of the entry, or calculated from its payment terms and commit timestamp.
+    pub due_date: Option<u64>,
+    /// Number of days, that the debt is overdue (`None` if it is not yet due)
+    pub days_overdue: Option<u64>,
+    #[serde(skip)]
+    open_milli: i64,
+    #[serde(skip)]
+    entry: LedgerEntry,
+}
+
+impl OpenItem {
+    fn new(line: u64, entry: LedgerEntry, commited: Option<u64>, as_of: u64) -> Self {
+        let due_date = entry.due_date.or_else(|| {
+            let terms = entry.payment_terms.unwrap_or_default() as u64;
+            commited.map(|c| c + terms * DAY_MILLIS)
+        });
+        let days_overdue = due_date
+            .filter(|due| *due <= as_of)
+            .map(|due| (as_of - due) / DAY_MILLIS);
+        OpenItem {
+            line,
+            creditor: entry.creditor,
+            debitor: entry.debitor,
+            amount: Money::from_milli(entry.currency, entry.amount_milli).to_string(),
+            open: String::new(),
+            reference: entry.reference.clone(),
+            due_date,
+            days_overdue,
+            open_milli: entry.amount_milli,
+            entry,
+        }
+    }
+}
+
+/// Open amounts of a currency, grouped by the number of days they are overdue
+#[derive(Debug, Default, Serialize)]
+pub struct AgingBuckets {
+    /// Debts, that are not yet due
+    pub not_due: f64,
+    pub days_0_30: f64,
+    pub days_31_60: f64,
+    pub days_61_90: f64,
+    pub days_over_90: f64,
+    /// Debts without due date (written before commit timestamps were tracked)
+    pub undated: f64,
+}
+
+/// Aging report of a ledger
+#[derive(Serialize)]
+pub struct AgingReport {
+    pub ledger_id: u64,
+    /// Reference timestamp of the report (milliseconds since unix-epoch)
+    pub as_of: u64,
+    pub buckets: HashMap<Currency, AgingBuckets>,
+    pub items: Vec<OpenItem>,
+}
+
+/// Quotes a csv field, if it contains special characters
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
     }
 }
 
@@ -585,6 +1752,19 @@ pub struct LedgerMeta {
     pub len: u64,
     /// Balances by currency ( values are in 1000 units, so 1€ = 1000 )
     pub balances: HashMap<Currency, i64>,
+    /// Head of the hash chain over all lines of the ledger
+    ///
+    /// This is only `None` for empty ledgers.
+    pub head: Option<Hash256>,
+}
+
+/// Layout of the ledger meta information before the hash chain was introduced
+#[derive(Deserialize)]
+struct LegacyLedgerMeta {
+    creditor: BorderlessId,
+    debitor: BorderlessId,
+    len: u64,
+    balances: HashMap<Currency, i64>,
 }
 
 /// Meta information about this ledger (DTO for JSON-APIs)
@@ -607,6 +1787,30 @@ pub struct LedgerMetaDto {
     #[serde(skip_serializing_if = "Option::is_none")]
     /// (Optional) Contract-ID, if the query was for a single contract-id only.
     pub contract_id: Option<ContractId>,
+    /// Head of the hash chain (if the ledger is chained)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head: Option<Hash256>,
+    /// Total of all balances in a base currency (only if requested)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_total: Option<BaseTotal>,
+    /// Balances in milli-units, which are required for exact currency conversions
+    #[serde(skip)]
+    balances_milli: HashMap<Currency, i64>,
+}
+
+impl LedgerMetaDto {
+    /// Converts all balances into the base currency, using the exchange rates that are valid at the given date
+    pub fn with_base_total<S: Db>(
+        mut self,
+        rates: &FxRates<'_, S>,
+        base: Currency,
+        date: FxDate,
+    ) -> Result<Self> {
+        self.base_total = Some(rates.total(&self.balances_milli, base, date)?);
+        Ok(self)
+    }
 }
 
 impl LedgerMeta {
@@ -616,6 +1820,32 @@ impl LedgerMeta {
             debitor,
             len: 0,
             balances: HashMap::new(),
+            head: None,
+        }
+    }
+
+    /// Decodes the stored ledger meta information
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        Ok(postcard::from_bytes(bytes)?)
+    }
+
+    /// Decodes ledger meta information, that may still use the legacy layout (without hash chain)
+    ///
+    /// Only used by the migration, that rewrites the meta information, see [`Ledger::chain_all`].
+    fn upgrade(bytes: &[u8]) -> Result<Self> {
+        let e = match postcard::from_bytes::<LedgerMeta>(bytes) {
+            Ok(meta) => return Ok(meta),
+            Err(e) => e,
+        };
+        match postcard::from_bytes::<LegacyLedgerMeta>(bytes) {
+            Ok(legacy) => Ok(LedgerMeta {
+                creditor: legacy.creditor,
+                debitor: legacy.debitor,
+                len: legacy.len,
+                balances: legacy.balances,
+                head: None,
+            }),
+            Err(_) => Err(e.into()),
         }
     }
 
@@ -623,8 +1853,8 @@ impl LedgerMeta {
         let ledger_id = self.creditor.merge_compact(&self.debitor);
         let balances = self
             .balances
-            .into_iter()
-            .map(|(k, v)| (k, v as f64 / 1000.0))
+            .iter()
+            .map(|(k, v)| (*k, *v as f64 / 1000.0))
             .collect();
         LedgerMetaDto {
             ledger_id,
@@ -633,6 +1863,9 @@ impl LedgerMeta {
             len: self.len,
             balances,
             contract_id: None,
+            head: self.head,
+            base_total: None,
+            balances_milli: self.balances,
         }
     }
 
@@ -665,10 +1898,81 @@ impl LedgerMeta {
             EntryType::SETTLE | EntryType::CANCEL => {
                 *balance -= mul * entry.amount_milli;
             }
+            EntryType::REVERSAL => {
+                return Err(Error::msg("reversals require the original entry"));
+            }
         }
         self.len += 1;
         Ok(())
     }
+
+    /// Updates the ledger meta information with a reversal of the `original` entry
+    ///
+    /// The reversal undoes the effect of the original entry on the balance.
+    /// Returns an error, if participants, amount or currency of both entries do not match.
+    pub fn update_reversal(
+        &mut self,
+        reversal: &LedgerEntry,
+        original: &LedgerEntry,
+    ) -> Result<()> {
+        if original.kind == EntryType::REVERSAL {
+            return Err(Error::msg("a reversal cannot be reversed"));
+        }
+        if (reversal.creditor, reversal.debitor) != (original.creditor, original.debitor) {
+            return Err(Error::msg(
+                "participants of reversal do not match the original entry",
+            ));
+        }
+        if reversal.amount_milli != original.amount_milli || reversal.currency != original.currency
+        {
+            return Err(Error::msg(
+                "amount of reversal does not match the original entry",
+            ));
+        }
+        // Apply the original entry in the inverse direction
+        let inverse = LedgerEntry {
+            creditor: original.debitor,
+            debitor: original.creditor,
+            ..original.clone()
+        };
+        self.update(&inverse)
+    }
+}
+
+/// Returns the meta information of all ledgers (ordered by ledger-id)
+///
+/// The meta-key is the last key of every ledger, so instead of iterating over all ledger lines,
+/// the cursor jumps backwards from one meta-key to the next.
+fn meta_values<'txn, DB: KvDatabase>(cursor: &mut impl RoCursor<'txn, DB>) -> Vec<&'txn [u8]> {
+    let mut out = Vec::new();
+    let mut next = cursor.iter_rev().next();
+    while let Some((key, value)) = next {
+        let key = LedgerKey::from_slice(key);
+        // The ledger-id alone is smaller than all keys of the ledger
+        next = cursor.iter_from_rev(&key.ledger_id().to_be_bytes()).next();
+        if key.is_meta() {
+            out.push(value);
+        }
+    }
+    out.reverse();
+    out
+}
+
+/// Returns the first key of every ledger (ordered by ledger-id)
+///
+/// Like [`meta_values`], the cursor jumps from ledger to ledger instead of iterating over all lines.
+fn first_keys<'txn, DB: KvDatabase>(cursor: &mut impl RoCursor<'txn, DB>) -> Vec<LedgerKey> {
+    let mut out = Vec::new();
+    let mut next = cursor.iter_start().next();
+    while let Some((key, _)) = next {
+        let key = LedgerKey::from_slice(key);
+        next = match key.ledger_id().checked_add(1) {
+            Some(next_id) => cursor.iter_from(&next_id.to_be_bytes()).next(),
+            None => None,
+        };
+        out.push(key);
+    }
+    out
 }
 
 /// A 24-bit ledger key constructed from a pair of borderless-ids, a line-index and a 'column' name.
@@ -750,7 +2054,599 @@ impl AsRef<[u8]> for LedgerKey {
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-// }
+#[cfg(all(test, feature = "contracts"))]
+mod tests {
+    use super::*;
+    use borderless::{hash::Hash256, TxIdentifier};
+    use borderless_kv_store::{backend::lmdb::Lmdb, RwCursor as _, RwTx};
+    use tempfile::tempdir;
+
+    fn open_tmp_lmdb() -> Lmdb {
+        let tmp_dir = tempdir().unwrap();
+        let env = Lmdb::new(tmp_dir.path(), 2).unwrap();
+        env.create_sub_db(LEDGER_SUB_DB).unwrap();
+        env.create_sub_db(LEDGER_INDEX_SUB_DB).unwrap();
+        env
+    }
+
+    fn commit(db: &Lmdb, cid: ContractId, creditor: BorderlessId, debitor: BorderlessId, n: u64) {
+        commit_kind(
+            db,
+            cid,
+            creditor,
+            debitor,
+            n,
+            EntryType::CREATE,
+            Currency::EUR,
+        );
+    }
+
+    /// Commits a line with amount `n` and tag `invoice-{n}` at timestamp `n`
+    fn commit_kind(
+        db: &Lmdb,
+        cid: ContractId,
+        creditor: BorderlessId,
+        debitor: BorderlessId,
+        n: u64,
+        kind: EntryType,
+        currency: Currency,
+    ) {
+        let entry = LedgerEntry {
+            creditor,
+            debitor,
+            amount_milli: 1000 * n as i64,
+            tax_milli: 0,
+            currency,
+            kind,
+            tag: format!("invoice-{n}"),
+            due_date: None,
+            reference: None,
+            payment_terms: None,
+            reverses: None,
+        };
+        let tx_ctx = TxCtx {
+            tx_id: TxIdentifier::new(1, n, Hash256::empty()),
+            index: 0,
+        };
+        let mut txn = db.begin_rw_txn().unwrap();
+        Ledger::new(db)
+            .commit_entry(&mut txn, &entry, cid, &tx_ctx, Some(n))
+            .unwrap();
+        txn.commit().unwrap();
+    }
+
+    fn page(per_page: usize, reverse: bool) -> Pagination {
+        Pagination {
+            page: 1,
+            per_page,
+            reverse,
+        }
+    }
+
+    /// Commits lines for the ledgers alice<->bob and bob<->carol from two contracts
+    fn setup() -> (Lmdb, [BorderlessId; 3], [ContractId; 2]) {
+        let db = open_tmp_lmdb();
+        let [alice, bob, carol] = [(); 3].map(|_| BorderlessId::generate());
+        let [c1, c2] = [(); 2].map(|_| ContractId::generate());
+        commit(&db, c1, alice, bob, 1);
+        commit(&db, c2, alice, bob, 2);
+        commit(&db, c1, bob, carol, 3);
+        commit(&db, c1, alice, bob, 4);
+        (db, [alice, bob, carol], [c1, c2])
+    }
+
+    fn check_queries(db: &Lmdb, [alice, bob, carol]: [BorderlessId; 3], [c1, c2]: [ContractId; 2]) {
+        let ledger = Ledger::new(db);
+        let result = ledger
+            .participant_ledgers_paginated(alice, page(10, false))
+            .unwrap();
+        assert_eq!(result.total_elements, 1);
+        assert_eq!(result.elements[0].len, 3);
+        let result = ledger
+            .participant_ledgers_paginated(bob, page(10, false))
+            .unwrap();
+        assert_eq!(result.total_elements, 2);
+        let result = ledger
+            .participant_ledgers_paginated(carol, page(10, false))
+            .unwrap();
+        assert_eq!(result.total_elements, 1);
+
+        let result = ledger
+            .contract_entries_paginated(c1, page(10, false))
+            .unwrap();
+        assert_eq!(result.total_elements, 3);
+        assert!(result.elements.iter().all(|e| e.contract_id == c1));
+        assert_eq!(ledger.contract_entries(c2).unwrap().len(), 1);
+
+        let selected = ledger.open(alice, bob);
+        let result = selected.get_contract_paginated(c1, page(1, true)).unwrap();
+        assert_eq!(result.total_elements, 2);
+        assert_eq!(result.elements[0].tag, "invoice-4");
+        let meta = selected.meta_for_contract(c2).unwrap().unwrap();
+        assert_eq!(meta.len, 1);
+        assert_eq!(meta.balances[&Currency::EUR], 2.0);
+    }
+
+    #[test]
+    fn query_by_participant_and_contract() {
+        let (db, participants, contracts) = setup();
+        check_queries(&db, participants, contracts);
+    }
+
+    #[test]
+    fn index_is_built_for_existing_ledgers() -> Result<()> {
+        let (db, participants, contracts) = setup();
+
+        // Remove the index, as if the ledger was written by an older version
+        let index_db = db.open_sub_db(LEDGER_INDEX_SUB_DB)?;
+        let mut txn = db.begin_rw_txn()?;
+        let mut cursor = txn.rw_cursor(&index_db)?;
+        let keys: Vec<Vec<u8>> = cursor.iter().map(|(k, _)| k.to_vec()).collect();
+        drop(cursor);
+        for key in keys {
+            txn.delete(&index_db, &key)?;
+        }
+        txn.commit()?;
+        let result =
+            Ledger::new(&db).participant_ledgers_paginated(participants[0], page(10, false))?;
+        assert_eq!(result.total_elements, 0);
+
+        Ledger::new(&db).ensure_index()?;
+        check_queries(&db, participants, contracts);
+        Ok(())
+    }
+
+    #[test]
+    fn list_all_ledgers() -> Result<()> {
+        let (db, [alice, bob, carol], _) = setup();
+        let ledger = Ledger::new(&db);
+        let all = ledger.all()?;
+        let ids = ledger.all_ids()?;
+        assert_eq!(all.len(), 2);
+        assert_eq!(ids.len(), 2);
+        assert!(ids[0].ledger_id < ids[1].ledger_id, "ordered by ledger-id");
+        for (meta, ids) in all.iter().zip(&ids) {
+            assert_eq!((meta.creditor, meta.debitor), (ids.creditor, ids.debitor));
+        }
+        let lens: Vec<u64> = all.iter().map(|meta| meta.len).collect();
+        let expected = if all[0].debitor == carol {
+            [1, 3]
+        } else {
+            [3, 1]
+        };
+        assert_eq!(lens, expected);
+        assert!(all.iter().all(|m| m.creditor == bob || m.debitor == bob));
+        assert!(all.iter().any(|m| m.creditor == alice));
+
+        let result = ledger.all_paginated(Pagination {
+            page: 2,
+            per_page: 1,
+            reverse: false,
+        })?;
+        assert_eq!(result.total_elements, 2);
+        assert_eq!(result.elements.len(), 1);
+        assert_eq!(result.elements[0].len, lens[1]);
+        let result = ledger.all_ids_paginated(page(1, false))?;
+        assert_eq!(result.total_elements, 2);
+        assert_eq!(result.elements[0].ledger_id, ids[0].ledger_id);
+        Ok(())
+    }
+
+    #[test]
+    fn filter_entries() -> Result<()> {
+        let (db, [alice, bob, _], [c1, c2]) = setup();
+        commit_kind(&db, c2, alice, bob, 5, EntryType::SETTLE, Currency::EUR);
+        commit_kind(&db, c1, alice, bob, 6, EntryType::SETTLE, Currency::USD);
+        let selected = Ledger::new(&db).open(alice, bob);
+        let tags = |filter: &str, pagination| -> Result<(usize, Vec<String>)> {
+            let filter = EntryFilter::from_query(Some(filter))?;
+            let result = selected.get_entries_filtered(&filter, pagination)?;
+            let tags = result.elements.into_iter().map(|e| e.tag).collect();
+            Ok((result.total_elements, tags))
+        };
+
+        let (total, result) = tags("kind=SETTLE", page(10, false))?;
+        assert_eq!(total, 2);
+        assert_eq!(result, ["invoice-5", "invoice-6"]);
+        let (_, result) = tags("kind=settle&currency=EUR", page(10, false))?;
+        assert_eq!(result, ["invoice-5"]);
+        let (total, result) = tags(&format!("contract_id={c1}&from=2&to=6"), page(10, false))?;
+        assert_eq!(total, 2);
+        assert_eq!(result, ["invoice-4", "invoice-6"]);
+        let (total, result) = tags("from=2&per_page=2", page(1, true))?;
+        assert_eq!(total, 4);
+        assert_eq!(result, ["invoice-6"]);
+        let (_, result) = tags("tag=invoice-2", page(10, false))?;
+        assert_eq!(result, ["invoice-2"]);
+        // Values are percent-decoded
+        let (_, result) = tags("tag=invoice%2D5&kind=%53ettle", page(10, false))?;
+        assert_eq!(result, ["invoice-5"]);
+        let (total, _) = tags("", page(10, false))?;
+        assert_eq!(total, 5);
+
+        assert!(EntryFilter::from_query(Some("kind=foo")).is_err());
+        assert!(EntryFilter::from_query(Some("from=yesterday")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn export_entries() -> Result<()> {
+        let (db, [alice, bob, _], [c1, _]) = setup();
+        let selected = Ledger::new(&db).open(alice, bob);
+        let filter = EntryFilter::from_query(Some(&format!("contract_id={c1}")))?;
+
+        let mut csv = Vec::new();
+        assert_eq!(selected.export(&filter, ExportFormat::Csv, &mut csv)?, 2);
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], ExportRecord::CSV_HEADER);
+        assert!(lines[1].starts_with(&format!(
+            "{},0,1,CREATE,{alice},{bob},1.00,0.00,EUR,invoice-1,,,,,,{c1},1,1,",
+            selected.ledger_id
+        )));
+
+        let mut jsonl = Vec::new();
+        assert_eq!(
+            selected.export(&filter, ExportFormat::JsonLines, &mut jsonl)?,
+            2
+        );
+        let records: Vec<serde_json::Value> = jsonl
+            .split(|b| *b == b'\n')
+            .filter(|l| !l.is_empty())
+            .map(serde_json::from_slice)
+            .collect::<std::result::Result<_, _>>()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1]["line"], 2);
+        assert_eq!(records[1]["amount"], "4.00");
+        assert_eq!(records[1]["currency"], "EUR");
+        assert_eq!(records[1]["tx_ctx"]["tx_id"]["number"], 4);
+        Ok(())
+    }
+
+    #[test]
+    fn export_payment_details() -> Result<()> {
+        let db = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let [alice, bob] = [(); 2].map(|_| BorderlessId::generate());
+        let entry = LedgerEntry {
+            creditor: alice,
+            debitor: bob,
+            amount_milli: 1000,
+            tax_milli: 0,
+            currency: Currency::EUR,
+            kind: EntryType::CREATE,
+            tag: "invoice".to_string(),
+            due_date: Some(42),
+            reference: Some("INV-1, rev. 2".to_string()),
+            payment_terms: Some(30),
+            reverses: None,
+        };
+        let mut txn = db.begin_rw_txn()?;
+        Ledger::new(&db).commit_entry(&mut txn, &entry, cid, &TxCtx::dummy(), Some(1))?;
+        txn.commit()?;
+        let selected = Ledger::new(&db).open(alice, bob);
+
+        let mut csv = Vec::new();
+        selected.export(&EntryFilter::default(), ExportFormat::Csv, &mut csv)?;
+        let csv = String::from_utf8(csv).unwrap();
+        let line = csv.lines().nth(1).unwrap();
+        assert!(line.contains(",invoice,42,\"INV-1, rev. 2\",30,"));
+
+        let mut jsonl = Vec::new();
+        selected.export(&EntryFilter::default(), ExportFormat::JsonLines, &mut jsonl)?;
+        let record: serde_json::Value = serde_json::from_slice(jsonl.trim_ascii_end())?;
+        assert_eq!(record["due_date"], 42);
+        assert_eq!(record["reference"], "INV-1, rev. 2");
+        assert_eq!(record["payment_terms"], 30);
+        Ok(())
+    }
+
+    #[test]
+    fn export_in_pages() -> Result<()> {
+        let (db, [alice, bob, _], [c1, _]) = setup();
+        for i in 0..EXPORT_PAGE_SIZE {
+            commit_kind(
+                &db,
+                c1,
+                alice,
+                bob,
+                10 + i as u64,
+                EntryType::CREATE,
+                Currency::EUR,
+            );
+        }
+        let selected = Ledger::new(&db).open(alice, bob);
+        let filter = EntryFilter::from_query(Some("currency=EUR"))?;
+
+        let mut pages = selected.export_pages(filter.clone(), ExportFormat::Csv)?;
+        let first = String::from_utf8(pages.next().unwrap()?).unwrap();
+        assert!(first.starts_with(ExportRecord::CSV_HEADER));
+        let rest: Vec<Vec<u8>> = pages.by_ref().collect::<Result<_>>()?;
+        assert_eq!(rest.len(), 1);
+        assert!(!String::from_utf8_lossy(&rest[0]).contains(ExportRecord::CSV_HEADER));
+
+        let mut csv = Vec::new();
+        let exported = selected.export(&filter, ExportFormat::Csv, &mut csv)?;
+        assert_eq!(exported, pages.exported());
+        assert_eq!(csv, [first.into_bytes(), rest.concat()].concat());
+        Ok(())
+    }
+
+    #[test]
+    fn export_helpers() {
+        assert_eq!(decimal(12_500, 2), "12.50");
+        assert_eq!(decimal(-1_234, 2), "-1.23");
+        assert_eq!(decimal(7_000, 0), "7");
+        assert_eq!(csv_escape("a,\"b\""), "\"a,\"\"b\"\"\"");
+        assert_eq!(
+            "ndjson".parse::<ExportFormat>().unwrap(),
+            ExportFormat::JsonLines
+        );
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    fn open_items_and_aging() -> Result<()> {
+        let db = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let [alice, bob] = [(); 2].map(|_| BorderlessId::generate());
+        let day = DAY_MILLIS;
+        let commit_line =
+            |kind, amount: i64, reference: Option<&str>, due_date, terms, commited| {
+                let entry = LedgerEntry {
+                    creditor: alice,
+                    debitor: bob,
+                    amount_milli: amount * 1000,
+                    tax_milli: 0,
+                    currency: Currency::EUR,
+                    kind,
+                    tag: String::new(),
+                    due_date,
+                    reference: reference.map(str::to_string),
+                    payment_terms: terms,
+                    reverses: None,
+                };
+                let mut txn = db.begin_rw_txn()?;
+                let tx_ctx = TxCtx::dummy();
+                Ledger::new(&db).commit_entry(&mut txn, &entry, cid, &tx_ctx, commited)?;
+                txn.commit()?;
+                Result::Ok(())
+            };
+        use EntryType::*;
+        commit_line(CREATE, 100, Some("A"), Some(0), None, Some(0))?;
+        commit_line(CREATE, 50, Some("B"), None, Some(30), Some(0))?;
+        commit_line(CREATE, 20, None, None, None, Some(10 * day))?;
+        commit_line(SETTLE, 40, Some("A"), None, None, Some(20 * day))?;
+        // Unreferenced settlements are applied to the oldest debt
+        commit_line(SETTLE, 30, None, None, None, Some(20 * day))?;
+        commit_line(CANCEL, 50, Some("B"), None, None, Some(20 * day))?;
+        // Settlements with an unknown reference do not match anything
+        commit_line(SETTLE, 5, Some("X"), None, None, Some(20 * day))?;
+        commit_line(CREATE, 10, Some("C"), Some(200 * day), None, Some(20 * day))?;
+        commit_line(CREATE, 7, None, None, None, None)?;
+
+        let selected = Ledger::new(&db).open(alice, bob);
+        let items = selected.open_items(100 * day)?;
+        let open: Vec<(u64, &str)> = items.iter().map(|i| (i.line, i.open.as_str())).collect();
+        assert_eq!(open, [(0, "30 €"), (2, "20 €"), (7, "10 €"), (8, "7 €")]);
+        assert_eq!(items[0].days_overdue, Some(100));
+        assert_eq!(items[1].due_date, Some(10 * day));
+
+        let report = selected.aging_report(100 * day)?;
+        let eur = &report.buckets[&Currency::EUR];
+        assert_eq!(eur.days_over_90, 30.0);
+        assert_eq!(eur.days_61_90, 20.0);
+        assert_eq!(eur.not_due, 10.0);
+        assert_eq!(eur.undated, 7.0);
+        assert_eq!(eur.days_0_30 + eur.days_31_60, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn reversal_of_lines() -> Result<()> {
+        let db = open_tmp_lmdb();
+        let [c1, c2] = [(); 2].map(|_| ContractId::generate());
+        let [alice, bob] = [(); 2].map(|_| BorderlessId::generate());
+        commit_kind(&db, c1, alice, bob, 10, EntryType::CREATE, Currency::EUR);
+        commit_kind(&db, c1, alice, bob, 4, EntryType::SETTLE, Currency::EUR);
+        commit_kind(&db, c2, alice, bob, 5, EntryType::CREATE, Currency::EUR);
+
+        let reverse = |cid, line, amount: i64, currency| {
+            let entry = LedgerEntry {
+                creditor: alice,
+                debitor: bob,
+                amount_milli: amount * 1000,
+                tax_milli: 0,
+                currency,
+                kind: EntryType::REVERSAL,
+                tag: "correction".to_string(),
+                due_date: None,
+                reference: None,
+                payment_terms: None,
+                reverses: Some(line),
+            };
+            let mut txn = db.begin_rw_txn()?;
+            let result =
+                Ledger::new(&db).commit_entry(&mut txn, &entry, cid, &TxCtx::dummy(), None);
+            match result {
+                Ok(line) => {
+                    txn.commit()?;
+                    Ok(line)
+                }
+                Err(e) => {
+                    txn.abort();
+                    Err(e)
+                }
+            }
+        };
+        let balance = || -> Result<f64> {
+            let meta = Ledger::new(&db)
+                .open(alice, bob)
+                .meta()?
+                .unwrap()
+                .into_dto();
+            Ok(meta.balances[&Currency::EUR])
+        };
+        assert_eq!(balance()?, 11.0);
+
+        // Amount and currency must match, and lines of other contracts cannot be reversed
+        assert!(reverse(c1, 1, 5, Currency::EUR).is_err());
+        assert!(reverse(c1, 1, 4, Currency::USD).is_err());
+        assert!(reverse(c1, 2, 5, Currency::EUR).is_err());
+        assert!(reverse(c1, 42, 4, Currency::EUR).is_err());
+
+        // Reversing the settlement restores the debt
+        assert_eq!(reverse(c1, 1, 4, Currency::EUR)?, 3);
+        assert_eq!(balance()?, 15.0);
+        // Lines cannot be reversed twice, and reversals cannot be reversed
+        assert!(reverse(c1, 1, 4, Currency::EUR).is_err());
+        assert!(reverse(c1, 3, 4, Currency::EUR).is_err());
+
+        assert_eq!(reverse(c1, 0, 10, Currency::EUR)?, 4);
+        assert_eq!(balance()?, 5.0);
+        let meta = Ledger::new(&db)
+            .open(alice, bob)
+            .meta_for_contract(c1)?
+            .unwrap();
+        assert_eq!(meta.balances[&Currency::EUR], 0.0);
+
+        let entries = Ledger::new(&db)
+            .open(alice, bob)
+            .get_entries_paginated(page(10, false))?
+            .elements;
+        assert_eq!(entries[0].reversed_by, Some(4));
+        assert_eq!(entries[1].reversed_by, Some(3));
+        assert_eq!(entries[2].reversed_by, None);
+        assert_eq!(entries[3].reverses, Some(1));
+        assert_eq!(entries[3].kind, "REVERSAL");
+        // The exports link the reversal with the reversed line
+        let mut jsonl = Vec::new();
+        let filter = EntryFilter::default();
+        Ledger::new(&db)
+            .open(alice, bob)
+            .export(&filter, ExportFormat::JsonLines, &mut jsonl)?;
+        let records: Vec<serde_json::Value> = jsonl
+            .split(|b| *b == b'\n')
+            .filter(|l| !l.is_empty())
+            .map(serde_json::from_slice)
+            .collect::<std::result::Result<_, _>>()?;
+        assert_eq!(records[1]["reversed_by"], 3);
+        assert_eq!(records[3]["reverses"], 1);
+        assert!(records[2]["reverses"].is_null());
+        // Reversed lines are no longer open
+        let open = Ledger::new(&db).open(alice, bob).open_items(0)?;
+        assert_eq!(open.iter().map(|i| i.line).collect::<Vec<_>>(), [2]);
+        Ok(())
+    }
+
+    #[test]
+    fn hash_chain_detects_tampering() -> Result<()> {
+        let (db, [alice, bob, _], _) = setup();
+        let selected = Ledger::new(&db).open(alice, bob);
+        let report = selected.verify_chain(2, &[])?;
+        assert!(report.valid, "{report:?}");
+        assert_eq!(report.len, 3);
+        assert_eq!(report.head, Some(report.checkpoints.last().unwrap().hash));
+        assert_eq!(
+            report
+                .checkpoints
+                .iter()
+                .map(|c| c.line)
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+        let trusted = report.checkpoints.clone();
+        let parsed: Checkpoint =
+            format!("{}:{}", trusted[0].line, String::from(trusted[0].hash)).parse()?;
+        assert_eq!(parsed, trusted[0]);
+
+        // Silently change the amount of the second line
+        let db_ptr = db.open_sub_db(LEDGER_SUB_DB)?;
+        let amount_key = LedgerKey::new(selected.ledger_id, 1, "amount");
+        let mut txn = db.begin_rw_txn()?;
+        txn.write(&db_ptr, &amount_key, &999_000i64.to_be_bytes())?;
+        txn.commit()?;
+        let report = selected.verify_chain(2, &[])?;
+        assert!(!report.valid);
+        assert_eq!(report.first_invalid, Some(1));
+        assert_eq!(report.checkpoints.len(), 0);
+
+        // Rewriting the entire chain is detected by the trusted checkpoints
+        let mut txn = db.begin_rw_txn()?;
+        let mut prev = Hash256::zero();
+        for line in 0..3 {
+            prev = selected.write_line_hash(&mut txn, &db_ptr, &prev, line)?;
+        }
+        let meta_key = LedgerKey::meta(selected.ledger_id);
+        let mut meta = LedgerMeta::decode(txn.read(&db_ptr, &meta_key)?.unwrap())?;
+        meta.head = Some(prev);
+        txn.write(&db_ptr, &meta_key, &postcard::to_allocvec(&meta)?)?;
+        txn.commit()?;
+        assert!(selected.verify_chain(2, &[])?.valid);
+        let report = selected.verify_chain(2, &trusted)?;
+        assert!(!report.valid);
+        assert_eq!(report.checkpoint_mismatch, Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn checkpoints_are_signed_off() -> Result<()> {
+        let (db, [alice, bob, _], _) = setup();
+        let selected = Ledger::new(&db).open(alice, bob);
+        let key = generate_node_key();
+        let mut report = selected.verify_chain(2, &[])?;
+        assert!(report.signer.is_none());
+        report.sign_off(&key);
+        assert_eq!(
+            report.signer,
+            Some(base16::encode_lower(key.verifying_key().as_bytes()))
+        );
+        assert_eq!(report.checkpoints.len(), 2);
+        for checkpoint in &report.checkpoints {
+            assert!(checkpoint.verify_signature(selected.ledger_id, &key.verifying_key()));
+            // The signature is bound to the ledger and the node key
+            assert!(!checkpoint.verify_signature(selected.ledger_id + 1, &key.verifying_key()));
+            assert!(!checkpoint
+                .verify_signature(selected.ledger_id, &generate_node_key().verifying_key()));
+        }
+        let mut forged = report.checkpoints[0].clone();
+        forged.line += 1;
+        assert!(!forged.verify_signature(selected.ledger_id, &key.verifying_key()));
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_ledgers_are_chained_by_migration() -> Result<()> {
+        let (db, [alice, bob, _], [c1, _]) = setup();
+        let selected = Ledger::new(&db).open(alice, bob);
+
+        // Downgrade the ledger to the layout before the hash chain
+        let db_ptr = db.open_sub_db(LEDGER_SUB_DB)?;
+        let meta_key = LedgerKey::meta(selected.ledger_id);
+        let mut txn = db.begin_rw_txn()?;
+        let meta = LedgerMeta::decode(txn.read(&db_ptr, &meta_key)?.unwrap())?;
+        let legacy = (meta.creditor, meta.debitor, meta.len, meta.balances);
+        txn.write(&db_ptr, &meta_key, &postcard::to_allocvec(&legacy)?)?;
+        for line in 0..3 {
+            txn.delete(&db_ptr, &LedgerKey::new(selected.ledger_id, line, "hash"))?;
+        }
+        txn.commit()?;
+        // The legacy layout is only understood by the migration
+        assert!(selected.meta().is_err());
+
+        Ledger::new(&db).chain_all()?;
+        let report = selected.verify_chain(10, &[])?;
+        assert!(report.valid, "{report:?}");
+        assert_eq!(selected.meta()?.unwrap().head, report.head);
+        // Running the migration again does not change anything
+        Ledger::new(&db).chain_all()?;
+        assert_eq!(selected.meta()?.unwrap().head, report.head);
+
+        commit(&db, c1, alice, bob, 7);
+        let report = selected.verify_chain(10, &[])?;
+        assert!(report.valid, "{report:?}");
+        assert_eq!(report.len, 4);
+        Ok(())
+    }
+}
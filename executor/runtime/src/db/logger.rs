@@ -1,4 +1,5 @@
 use std::cmp::min;
+use std::str::FromStr;
 
 use borderless::{
     __private::storage_keys::{StorageKey, BASE_KEY_LOGS},
@@ -9,14 +10,83 @@ use borderless::{
 use borderless_kv_store::*;
 use serde::{Deserialize, Serialize};
 
+use super::controller::system_sub_key;
 use crate::log_shim::{debug, error, info, trace, warn};
 use crate::{Result, AGENT_SUB_DB, CONTRACT_SUB_DB};
 
 /// Storage key, where the meta-information about the buffer is saved
 const SUB_KEY_META: u64 = u64::MAX;
 
-/// We keep a maximum of 32k log-lines ( which should be sufficient for debugging )
-const MAX_LOG_BUFFER_SIZE: u64 = 32 * 1024;
+/// Storage key, where the configured capacity of the buffer is saved
+const SUB_KEY_CAPACITY: u64 = u64::MAX - 1;
+
+/// Per default we keep 32k log-lines ( which should be sufficient for debugging )
+pub const DEFAULT_LOG_BUFFER_SIZE: u64 = 32 * 1024;
+
+/// Upper limit for the configurable buffer size
+pub const MAX_LOG_BUFFER_SIZE: u64 = 1024 * 1024;
+
+/// Layout of log lines before structured fields were introduced
+#[derive(Deserialize)]
+struct LegacyLogLine {
+    timestamp: u128,
+    level: LogLevel,
+    msg: String,
+}
+
+/// Decodes a stored log line
+fn decode_line(bytes: &[u8]) -> Result<LogLine> {
+    Ok(postcard::from_bytes(bytes)?)
+}
+
+/// Re-encodes an entry of the contract or agent sub-db, if it is a log line in the legacy layout (without fields)
+///
+/// Returns `None` for all other entries and for lines, that already use the current layout.
+pub(crate) fn upgrade_log_line(key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+    match system_sub_key(key, BASE_KEY_LOGS) {
+        Some(SUB_KEY_META | SUB_KEY_CAPACITY) | None => Ok(None),
+        Some(_) => {
+            let e = match postcard::from_bytes::<LogLine>(value) {
+                Ok(_) => return Ok(None),
+                Err(e) => e,
+            };
+            let legacy = postcard::from_bytes::<LegacyLogLine>(value).map_err(|_| e)?;
+            let line = LogLine {
+                timestamp: legacy.timestamp,
+                level: legacy.level,
+                msg: legacy.msg,
+                fields: Default::default(),
+            };
+            Ok(Some(postcard::to_allocvec(&line)?))
+        }
+    }
+}
+
+/// Rewrites all log lines of the given sub-db, that were stored in the legacy layout
+///
+/// Registered as migration of the [`CONTRACT_SUB_DB`] and [`AGENT_SUB_DB`], see [`super::schema`].
+pub(crate) fn upgrade_log_lines<S: Db>(db: &S, sub_db: &str) -> Result<()> {
+    let db_ptr = db.open_sub_db(sub_db)?;
+    let mut upgraded = Vec::new();
+    {
+        let txn = db.begin_ro_txn()?;
+        let mut cursor = txn.ro_cursor(&db_ptr)?;
+        for (key, value) in cursor.iter() {
+            if let Some(line) = upgrade_log_line(key, value)? {
+                upgraded.push((key.to_vec(), line));
+            }
+        }
+        drop(cursor);
+        txn.commit()?;
+    }
+    let mut txn = db.begin_rw_txn()?;
+    for (key, value) in &upgraded {
+        txn.write(&db_ptr, key, value)?;
+    }
+    txn.commit()?;
+    debug!("upgraded {} log lines in '{sub_db}'", upgraded.len());
+    Ok(())
+}
 
 #[derive(Serialize, Deserialize, Default)]
 struct BufferMeta {
@@ -28,9 +98,129 @@ struct BufferMeta {
     last_flush_count: u64,
 }
 
+/// Filter for log lines
+///
+/// Can be parsed from a query string like `level=warn&from=<ts>&to=<ts>&contains=<text>&field.<key>=<value>`,
+/// where `level` is the minimum log level and `from` and `to` are timestamps in nanoseconds since unix-epoch.
+/// An empty filter matches every log line.
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// Minimum log level
+    pub level: Option<LogLevel>,
+    /// Lower bound for the timestamp (inclusive)
+    pub from: Option<u128>,
+    /// Upper bound for the timestamp (inclusive)
+    pub to: Option<u128>,
+    /// Substring, that the log message must contain
+    pub contains: Option<String>,
+    /// Structured fields, that the log line must contain with exactly this value
+    pub fields: Vec<(String, String)>,
+}
+
+impl LogFilter {
+    /// Extracts a filter from some query string.
+    ///
+    /// Unknown keys are ignored, but invalid values for known keys result in an error.
+    pub fn from_query(query: Option<&str>) -> Result<Self> {
+        let mut filter = LogFilter::default();
+        let query = match query {
+            Some(q) => q,
+            None => return Ok(filter),
+        };
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            if value.is_empty() {
+                continue;
+            }
+            match key.as_ref() {
+                "level" => {
+                    let level =
+                        LogLevel::from_str(&value).map_err(|e| crate::Error::msg(e.to_string()))?;
+                    filter.level = Some(level);
+                }
+                "from" => filter.from = Some(parse_ts(&value)?),
+                "to" => filter.to = Some(parse_ts(&value)?),
+                "contains" | "q" => filter.contains = Some(value.into_owned()),
+                other => {
+                    if let Some(field) = other.strip_prefix("field.") {
+                        filter.fields.push((field.to_string(), value.into_owned()));
+                    }
+                }
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Returns `true` if the filter would match every log line
+    pub fn is_empty(&self) -> bool {
+        self.level.is_none()
+            && self.from.is_none()
+            && self.to.is_none()
+            && self.contains.is_none()
+            && self.fields.is_empty()
+    }
+
+    /// Returns `true` if the log line matches the filter
+    pub fn matches(&self, line: &LogLine) -> bool {
+        if let Some(level) = self.level {
+            if line.level < level {
+                return false;
+            }
+        }
+        if let Some(from) = self.from {
+            if line.timestamp < from {
+                return false;
+            }
+        }
+        if let Some(to) = self.to {
+            if line.timestamp > to {
+                return false;
+            }
+        }
+        if let Some(needle) = &self.contains {
+            if !line.msg.contains(needle.as_str()) {
+                return false;
+            }
+        }
+        self.fields
+            .iter()
+            .all(|(key, value)| line.fields.get(key) == Some(value))
+    }
+}
+
+fn check_capacity(capacity: u64) -> Result<()> {
+    if capacity == 0 || capacity > MAX_LOG_BUFFER_SIZE {
+        return Err(crate::Error::msg(format!(
+            "log buffer size must be between 1 and {MAX_LOG_BUFFER_SIZE}"
+        )));
+    }
+    Ok(())
+}
+
+/// Writes the initial capacity of the ring-buffer, when a contract or agent is introduced
+///
+/// In contrast to [`Logger::set_capacity`], this does not move any existing log lines.
+#[cfg(any(feature = "contracts", feature = "agents"))]
+pub(crate) fn init_capacity<S: Db>(
+    db_ptr: &S::Handle,
+    txn: &mut <S as Db>::RwTx<'_>,
+    id: Id,
+    capacity: u64,
+) -> Result<()> {
+    check_capacity(capacity)?;
+    let key = StorageKey::system_key(id, BASE_KEY_LOGS, SUB_KEY_CAPACITY);
+    txn.write(db_ptr, &key, &postcard::to_allocvec(&capacity)?)?;
+    Ok(())
+}
+
+fn parse_ts(value: &str) -> Result<u128> {
+    u128::from_str(value).map_err(|_| crate::Error::msg(format!("invalid timestamp '{value}'")))
+}
+
 /// Logger instance that is created over a key-value storage for a given contract-id
 ///
 /// The logger is essentially a ring-buffer with a fixed size, that uses a specific key-space.
+/// The size of the ring-buffer can be configured per entity (see [`Logger::set_capacity`]),
+/// and defaults to [`DEFAULT_LOG_BUFFER_SIZE`].
 pub struct Logger<'a, S: Db> {
     db: &'a S,
     id: Id,
@@ -46,7 +236,7 @@ impl<'a, S: Db> Logger<'a, S> {
     /// This function writes a batch of log lines into the underlying key-value storage. It performs the following steps:
     ///
     /// 1. Reads the current buffer metadata, which includes the logical start and end indices of the stored log lines.
-    /// 2. Determines if adding the new log lines would exceed the fixed capacity (`MAX_LOG_BUFFER_SIZE`). If so,
+    /// 2. Determines if adding the new log lines would exceed the configured capacity. If so,
     ///    it advances the start index to overwrite the oldest entries.
     /// 3. Records the flush metadata (`last_flush_start` and `last_flush_count`) to track the range of log lines added in this flush.
     /// 4. Writes the new log lines to storage using modulo arithmetic to map the logical indices to physical storage keys.
@@ -82,12 +272,13 @@ impl<'a, S: Db> Logger<'a, S> {
             }
         };
 
+        let capacity = self.read_capacity(txn, db_ptr)?;
         let new_line_count = lines.len() as u64;
         let current_count = meta.end - meta.start;
 
         // If adding new lines would overflow the ring buffer, adjust the start index.
-        if current_count + new_line_count > MAX_LOG_BUFFER_SIZE {
-            let drop_count = current_count + new_line_count - MAX_LOG_BUFFER_SIZE;
+        if current_count + new_line_count > capacity {
+            let drop_count = current_count + new_line_count - capacity;
             meta.start += drop_count;
         }
 
@@ -97,7 +288,7 @@ impl<'a, S: Db> Logger<'a, S> {
 
         // Write each new log line using modulo arithmetic to wrap-around.
         for (i, line) in lines.iter().enumerate() {
-            let index = (meta.end + i as u64) % MAX_LOG_BUFFER_SIZE;
+            let index = (meta.end + i as u64) % capacity;
             let key = StorageKey::system_key(self.id, BASE_KEY_LOGS, index);
             let bytes = postcard::to_allocvec(line)?;
             txn.write(db_ptr, &key, &bytes)?;
@@ -110,6 +301,65 @@ impl<'a, S: Db> Logger<'a, S> {
         Ok(())
     }
 
+    /// Returns the capacity of the ring-buffer
+    pub fn capacity(&self) -> Result<u64> {
+        let db_ptr = self.open_db()?;
+        let txn = self.db.begin_ro_txn()?;
+        self.read_capacity(&txn, &db_ptr)
+    }
+
+    /// Changes the capacity of the ring-buffer.
+    ///
+    /// If the buffer shrinks, the oldest log lines are dropped.
+    /// All remaining lines keep their absolute index, so [`Logger::total_log_lines`] is not affected by this.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the capacity is zero or exceeds [`MAX_LOG_BUFFER_SIZE`].
+    pub fn set_capacity(&self, capacity: u64) -> Result<()> {
+        check_capacity(capacity)?;
+        let db_ptr = self.open_db()?;
+        let mut txn = self.db.begin_rw_txn()?;
+        let old_capacity = self.read_capacity(&txn, &db_ptr)?;
+        let meta_key = StorageKey::system_key(self.id, BASE_KEY_LOGS, SUB_KEY_META);
+        let mut meta: BufferMeta = match txn.read(&db_ptr, &meta_key)? {
+            Some(bytes) => postcard::from_bytes(bytes)?,
+            None => BufferMeta::default(),
+        };
+
+        // Read all lines, that still fit into the new buffer
+        let new_start = meta.start.max(meta.end.saturating_sub(capacity));
+        let mut lines = Vec::new();
+        for i in new_start..meta.end {
+            let key = StorageKey::system_key(self.id, BASE_KEY_LOGS, i % old_capacity);
+            if let Some(bytes) = txn.read(&db_ptr, &key)? {
+                lines.push(decode_line(bytes)?);
+            }
+        }
+
+        // Remove the old layout and write the lines according to the new capacity
+        for i in meta.start..meta.end {
+            let key = StorageKey::system_key(self.id, BASE_KEY_LOGS, i % old_capacity);
+            txn.delete(&db_ptr, &key)?;
+        }
+        for (i, line) in (new_start..meta.end).zip(lines.iter()) {
+            let key = StorageKey::system_key(self.id, BASE_KEY_LOGS, i % capacity);
+            txn.write(&db_ptr, &key, &postcard::to_allocvec(line)?)?;
+        }
+
+        // The last flush may have been partially dropped
+        let flush_end = meta.last_flush_start + meta.last_flush_count;
+        meta.start = new_start;
+        meta.last_flush_start = meta.last_flush_start.max(new_start);
+        meta.last_flush_count = flush_end.saturating_sub(meta.last_flush_start);
+        txn.write(&db_ptr, &meta_key, &postcard::to_allocvec(&meta)?)?;
+
+        let capacity_key = StorageKey::system_key(self.id, BASE_KEY_LOGS, SUB_KEY_CAPACITY);
+        txn.write(&db_ptr, &capacity_key, &postcard::to_allocvec(&capacity)?)?;
+        txn.commit()?;
+        Ok(())
+    }
+
     /// Retrieves the full log from the buffer in chronological order.
     pub fn get_full_log(&self) -> Result<Vec<LogLine>> {
         self.get_log_lines(0, MAX_LOG_BUFFER_SIZE)
@@ -136,6 +386,7 @@ impl<'a, S: Db> Logger<'a, S> {
             None => BufferMeta::default(),
         };
 
+        let capacity = self.read_capacity(&txn, &db_ptr)?;
         let total_count = meta.end - meta.start;
         // If the requested start offset is beyond the current log count, return an empty Vec.
         if start_offset >= total_count {
@@ -149,10 +400,10 @@ impl<'a, S: Db> Logger<'a, S> {
         // Iterate over the specified range and fetch each log line.
         for i in range_start..range_end {
             // Compute the physical index using modulo arithmetic.
-            let index = i % MAX_LOG_BUFFER_SIZE;
+            let index = i % capacity;
             let key = StorageKey::system_key(self.id, BASE_KEY_LOGS, index);
             if let Some(bytes) = txn.read(&db_ptr, &key)? {
-                let log_line: LogLine = postcard::from_bytes(bytes)?;
+                let log_line = decode_line(bytes)?;
                 logs.push(log_line);
             }
         }
@@ -173,6 +424,7 @@ impl<'a, S: Db> Logger<'a, S> {
             None => return Ok(Vec::new()),
         };
 
+        let capacity = self.read_capacity(&txn, &db_ptr)?;
         let mut logs = Vec::new();
         let flush_start = meta.last_flush_start;
         let flush_count = meta.last_flush_count;
@@ -180,10 +432,10 @@ impl<'a, S: Db> Logger<'a, S> {
         // Iterate over the range corresponding to the last flush.
         for i in flush_start..(flush_start + flush_count) {
             // Compute the physical index using modulo arithmetic.
-            let index = i % MAX_LOG_BUFFER_SIZE;
+            let index = i % capacity;
             let key = StorageKey::system_key(self.id, BASE_KEY_LOGS, index);
             if let Some(bytes) = txn.read(&db_ptr, &key)? {
-                let log_line: LogLine = postcard::from_bytes(bytes)?;
+                let log_line = decode_line(bytes)?;
                 logs.push(log_line);
             }
         }
@@ -256,13 +508,14 @@ impl<'a, S: Db> Logger<'a, S> {
         let page_end = std::cmp::min(meta.start + page * per_page, meta.end);
 
         // Retrieve the logs for the calculated range.
+        let capacity = self.read_capacity(&txn, &db_ptr)?;
         let mut logs = Vec::new();
         for i in page_start..page_end {
             // Map the logical index to the physical index in the ring-buffer.
-            let physical_index = i % MAX_LOG_BUFFER_SIZE;
+            let physical_index = i % capacity;
             let key = StorageKey::system_key(self.id, BASE_KEY_LOGS, physical_index);
             if let Some(bytes) = txn.read(&db_ptr, &key)? {
-                let log_line: LogLine = postcard::from_bytes(bytes)?;
+                let log_line = decode_line(bytes)?;
                 logs.push(log_line);
             }
         }
@@ -272,13 +525,69 @@ impl<'a, S: Db> Logger<'a, S> {
             pagination,
         })
     }
+
+    /// Retrieves all log lines that match the filter, for the given page.
+    ///
+    /// In contrast to [`Logger::get_logs_paginated`], this has to scan the entire buffer,
+    /// and the number of total elements refers to the number of matching log lines.
+    pub fn get_logs_filtered(
+        &self,
+        filter: &LogFilter,
+        pagination: Pagination,
+    ) -> Result<PaginatedElements<LogLine>> {
+        let mut matches: Vec<LogLine> = self
+            .get_full_log()?
+            .into_iter()
+            .filter(|line| filter.matches(line))
+            .collect();
+        if pagination.reverse {
+            matches.reverse();
+        }
+        let total_elements = matches.len();
+        let range = pagination.to_range();
+        let elements = matches
+            .into_iter()
+            .skip(range.start)
+            .take(range.len())
+            .collect();
+        Ok(PaginatedElements {
+            elements,
+            total_elements,
+            pagination,
+        })
+    }
+
+    /// Opens the sub-db, that matches the id-type
+    fn open_db(&self) -> Result<S::Handle> {
+        let db_ptr = match self.id {
+            Id::Contract { .. } => self.db.open_sub_db(CONTRACT_SUB_DB)?,
+            Id::Agent { .. } => self.db.open_sub_db(AGENT_SUB_DB)?,
+        };
+        Ok(db_ptr)
+    }
+
+    /// Reads the configured capacity of the ring-buffer ( or the default value if nothing is configured )
+    fn read_capacity<'env>(
+        &self,
+        txn: &impl RawRead<'env, S::DB>,
+        db_ptr: &S::Handle,
+    ) -> Result<u64> {
+        let key = StorageKey::system_key(self.id, BASE_KEY_LOGS, SUB_KEY_CAPACITY);
+        match txn.read(db_ptr, &key)? {
+            Some(bytes) => Ok(postcard::from_bytes(bytes)?),
+            None => Ok(DEFAULT_LOG_BUFFER_SIZE),
+        }
+    }
 }
 
 /// Just prints a log line to stdout
 ///
-/// Ignores the timestamp
+/// Ignores the timestamp. Structured fields are appended to the message.
 pub fn print_log_line(line: LogLine) {
-    let msg = line.msg;
+    let mut msg = line.msg;
+    for (key, value) in line.fields {
+        msg.push_str(&format!(" {key}={value}"));
+    }
     match line.level {
         LogLevel::Trace => trace!("{msg}"),
         LogLevel::Debug => debug!("{msg}"),
@@ -287,3 +596,265 @@ pub fn print_log_line(line: LogLine) {
         LogLevel::Error => error!("{msg}"),
     }
 }
+
+#[cfg(feature = "code-store")]
+pub use tail::{LogStream, LogTail};
+
+#[cfg(feature = "code-store")]
+mod tail {
+    use super::LogFilter;
+    use crate::log_shim::warn;
+    use borderless::{log::LogLine, prelude::Id};
+    use futures_util::Stream;
+    use std::{collections::VecDeque, pin::Pin, sync::Arc};
+    use tokio::sync::broadcast::{self, error::RecvError};
+
+    /// Number of flushes that can be buffered, before a slow subscriber starts to lag behind
+    const TAIL_CHANNEL_SIZE: usize = 1024;
+
+    /// Stream of live log lines
+    pub type LogStream = Pin<Box<dyn Stream<Item = LogLine> + Send>>;
+
+    /// Broadcasts freshly committed log lines to all subscribers
+    ///
+    /// The runtime publishes the log lines of every execution after the storage commit,
+    /// so subscribers only see lines that are also persisted in the [`Logger`](super::Logger).
+    #[derive(Clone)]
+    pub struct LogTail {
+        tx: broadcast::Sender<(Id, Arc<[LogLine]>)>,
+    }
+
+    impl Default for LogTail {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl LogTail {
+        pub fn new() -> Self {
+            let (tx, _) = broadcast::channel(TAIL_CHANNEL_SIZE);
+            Self { tx }
+        }
+
+        /// Publishes the log lines of a single execution
+        pub(crate) fn publish(&self, id: Id, lines: &[LogLine]) {
+            // Avoid the allocation, if nobody is listening
+            if lines.is_empty() || self.tx.receiver_count() == 0 {
+                return;
+            }
+            // NOTE: This only fails, if there are no receivers
+            let _ = self.tx.send((id, lines.into()));
+        }
+
+        /// Subscribes to the log lines of the given contract or agent
+        ///
+        /// Only lines that match the filter are returned.
+        /// The stream ends, once the sender is dropped.
+        pub fn subscribe(&self, id: impl Into<Id>, filter: LogFilter) -> LogStream {
+            let id = id.into();
+            let rx = self.tx.subscribe();
+            let stream = futures_util::stream::unfold(
+                (rx, VecDeque::new()),
+                move |(mut rx, mut pending)| {
+                    let filter = filter.clone();
+                    async move {
+                        loop {
+                            if let Some(line) = pending.pop_front() {
+                                return Some((line, (rx, pending)));
+                            }
+                            match rx.recv().await {
+                                Ok((src, lines)) if src == id => pending
+                                    .extend(lines.iter().filter(|l| filter.matches(l)).cloned()),
+                                Ok(_) => continue,
+                                Err(RecvError::Lagged(n)) => {
+                                    warn!("log stream for {id} lagged behind, skipped {n} flushes")
+                                }
+                                Err(RecvError::Closed) => return None,
+                            }
+                        }
+                    }
+                },
+            );
+            Box::pin(stream)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borderless::ContractId;
+    use borderless_kv_store::backend::lmdb::Lmdb;
+    use tempfile::tempdir;
+
+    fn open_tmp_lmdb() -> Lmdb {
+        let tmp_dir = tempdir().unwrap();
+        let env = Lmdb::new(tmp_dir.path(), 1).unwrap();
+        env.create_sub_db(CONTRACT_SUB_DB).unwrap();
+        env
+    }
+
+    fn line(i: u128, level: LogLevel) -> LogLine {
+        LogLine {
+            timestamp: i,
+            level,
+            msg: format!("line {i}"),
+            fields: Default::default(),
+        }
+    }
+
+    fn flush(db: &Lmdb, logger: &Logger<'_, Lmdb>, lines: &[LogLine]) -> Result<()> {
+        let db_ptr = db.open_sub_db(CONTRACT_SUB_DB)?;
+        let mut txn = db.begin_rw_txn()?;
+        logger.flush_lines(lines, &db_ptr, &mut txn)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    #[test]
+    fn capacity_limits_buffer() -> Result<()> {
+        let db = open_tmp_lmdb();
+        let logger = Logger::new(&db, ContractId::generate());
+        assert_eq!(logger.capacity()?, DEFAULT_LOG_BUFFER_SIZE);
+        logger.set_capacity(4)?;
+        assert_eq!(logger.capacity()?, 4);
+
+        let lines: Vec<_> = (0..10).map(|i| line(i, LogLevel::Info)).collect();
+        flush(&db, &logger, &lines)?;
+        let full = logger.get_full_log()?;
+        let ts: Vec<_> = full.iter().map(|l| l.timestamp).collect();
+        assert_eq!(ts, vec![6, 7, 8, 9]);
+        assert_eq!(logger.total_log_lines()?, 10);
+
+        assert!(logger.set_capacity(0).is_err());
+        assert!(logger.set_capacity(MAX_LOG_BUFFER_SIZE + 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn initial_capacity() -> Result<()> {
+        let db = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let db_ptr = db.open_sub_db(CONTRACT_SUB_DB)?;
+        let mut txn = db.begin_rw_txn()?;
+        assert!(init_capacity::<Lmdb>(&db_ptr, &mut txn, cid.into(), 0).is_err());
+        init_capacity::<Lmdb>(&db_ptr, &mut txn, cid.into(), 2)?;
+        txn.commit()?;
+
+        let logger = Logger::new(&db, cid);
+        assert_eq!(logger.capacity()?, 2);
+        let lines: Vec<_> = (0..3).map(|i| line(i, LogLevel::Info)).collect();
+        flush(&db, &logger, &lines)?;
+        assert_eq!(logger.get_full_log()?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn resize_keeps_newest_lines() -> Result<()> {
+        let db = open_tmp_lmdb();
+        let logger = Logger::new(&db, ContractId::generate());
+        let lines: Vec<_> = (0..10).map(|i| line(i, LogLevel::Info)).collect();
+        flush(&db, &logger, &lines)?;
+
+        logger.set_capacity(3)?;
+        let ts: Vec<_> = logger.get_full_log()?.iter().map(|l| l.timestamp).collect();
+        assert_eq!(ts, vec![7, 8, 9]);
+        assert_eq!(logger.get_last_log()?.len(), 3);
+
+        // Growing the buffer again keeps the content
+        logger.set_capacity(8)?;
+        flush(&db, &logger, &[line(10, LogLevel::Info)])?;
+        let ts: Vec<_> = logger.get_full_log()?.iter().map(|l| l.timestamp).collect();
+        assert_eq!(ts, vec![7, 8, 9, 10]);
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_lines_are_upgraded_by_migration() -> Result<()> {
+        #[derive(Serialize)]
+        struct Legacy {
+            timestamp: u128,
+            level: LogLevel,
+            msg: String,
+        }
+        let db = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let logger = Logger::new(&db, cid);
+        let new = line(8, LogLevel::Info).with_fields([("k".to_string(), "v".to_string())]);
+        flush(&db, &logger, &[line(7, LogLevel::Warn), new])?;
+
+        // Replace the first line with the layout before structured fields existed
+        let db_ptr = db.open_sub_db(CONTRACT_SUB_DB)?;
+        let mut txn = db.begin_rw_txn()?;
+        let legacy = postcard::to_allocvec(&Legacy {
+            timestamp: 7,
+            level: LogLevel::Warn,
+            msg: "old".to_string(),
+        })?;
+        txn.write(
+            &db_ptr,
+            &StorageKey::system_key(cid, BASE_KEY_LOGS, 0),
+            &legacy,
+        )?;
+        txn.commit()?;
+        assert!(logger.get_full_log().is_err());
+
+        upgrade_log_lines(&db, CONTRACT_SUB_DB)?;
+        // Running the migration again does not touch upgraded lines
+        upgrade_log_lines(&db, CONTRACT_SUB_DB)?;
+        let lines = logger.get_full_log()?;
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].timestamp, lines[0].msg.as_str()), (7, "old"));
+        assert!(lines[0].fields.is_empty());
+        assert_eq!(lines[1].fields.get("k").map(String::as_str), Some("v"));
+        // The meta information of the buffer is not touched
+        assert_eq!(logger.total_log_lines()?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn filter_logs() -> Result<()> {
+        let db = open_tmp_lmdb();
+        let logger = Logger::new(&db, ContractId::generate());
+        let lines: Vec<_> = (0..20)
+            .map(|i| {
+                let level = if i % 2 == 0 {
+                    LogLevel::Debug
+                } else {
+                    LogLevel::Error
+                };
+                line(i, level)
+            })
+            .collect();
+        flush(&db, &logger, &lines)?;
+
+        let filter = LogFilter::from_query(Some("level=warn&from=5&to=15&page=1&per_page=2"))?;
+        let pagination = Pagination::from_query(Some("page=1&per_page=2")).unwrap();
+        let result = logger.get_logs_filtered(&filter, pagination)?;
+        assert_eq!(result.total_elements, 6);
+        let ts: Vec<_> = result.elements.iter().map(|l| l.timestamp).collect();
+        assert_eq!(ts, vec![5, 7]);
+
+        let filter = LogFilter::from_query(Some("contains=line+1"))?;
+        assert!(filter.matches(&line(12, LogLevel::Info)));
+        assert!(!filter.matches(&line(2, LogLevel::Info)));
+
+        let filter = LogFilter::from_query(Some("field.order_id=42"))?;
+        let tagged =
+            line(3, LogLevel::Info).with_fields([("order_id".to_string(), "42".to_string())]);
+        assert!(filter.matches(&tagged));
+        assert!(!filter.matches(&line(3, LogLevel::Info)));
+
+        // Values are percent-decoded, so they may contain reserved characters
+        let filter = LogFilter::from_query(Some("q=a%26b%3Dc&field.note=50%25+off"))?;
+        assert_eq!(filter.contains.as_deref(), Some("a&b=c"));
+        assert_eq!(
+            filter.fields,
+            vec![("note".to_string(), "50% off".to_string())]
+        );
+
+        assert!(LogFilter::from_query(Some("level=loud")).is_err());
+        assert!(LogFilter::from_query(Some("page=2")).unwrap().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,373 @@
+//! Multilateral netting of ledger balances
+//!
+//! Ledgers are strictly bilateral. Within a group of participants, many of these bilateral debts cancel out,
+//! so the group can settle all of them with a much smaller number of payments.
+//! The [`Netting`] engine computes the minimal set of these payments from the balances of the ledgers within the group.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use borderless::contracts::ledger::{Currency, EntryType, LedgerEntry, Money};
+use borderless::BorderlessId;
+use borderless_kv_store::Db;
+use serde::Serialize;
+
+use super::ledger::Ledger;
+use crate::Result;
+
+/// Tag of all ledger entries, that are proposed by the netting engine
+pub const NETTING_TAG: &str = "netting";
+
+/// Netting engine over the ledgers of a group of participants
+pub struct Netting<'a, S: Db> {
+    ledger: Ledger<'a, S>,
+}
+
+impl<'a, S: Db> Netting<'a, S> {
+    pub fn new(db: &'a S) -> Self {
+        Self {
+            ledger: Ledger::new(db),
+        }
+    }
+
+    /// Computes a netting proposal for every currency, in which the group has open balances
+    ///
+    /// Only ledgers between two members of the group are taken into account.
+    pub fn propose(&self, participants: &[BorderlessId]) -> Result<Vec<NettingProposal>> {
+        let group: BTreeSet<BorderlessId> = participants.iter().copied().collect();
+
+        // Collect the bilateral balances of the group per currency
+        let mut balances: HashMap<Currency, Vec<(BorderlessId, BorderlessId, i64)>> =
+            HashMap::new();
+        for creditor in &group {
+            // Every ledger is indexed for both of its participants, so we only look at it from the creditor side
+            let ledgers = self.ledger.participant_ledgers(*creditor)?;
+            for meta in ledgers {
+                if meta.creditor != *creditor || !group.contains(&meta.debitor) {
+                    continue;
+                }
+                for (currency, balance) in meta.balances {
+                    // A positive balance means, that the debitor owes the creditor
+                    let debt = match balance {
+                        0 => continue,
+                        b if b > 0 => (meta.debitor, meta.creditor, b),
+                        b => (meta.creditor, meta.debitor, -b),
+                    };
+                    balances.entry(currency).or_default().push(debt);
+                }
+            }
+        }
+
+        let mut proposals: Vec<_> = balances
+            .into_iter()
+            .map(|(currency, debts)| NettingProposal::new(currency, &group, debts))
+            .collect();
+        proposals.sort_by_key(|p| p.currency as u32);
+        Ok(proposals)
+    }
+
+    /// Computes the netting proposal for a single currency
+    pub fn propose_currency(
+        &self,
+        participants: &[BorderlessId],
+        currency: Currency,
+    ) -> Result<Option<NettingProposal>> {
+        let proposal = self
+            .propose(participants)?
+            .into_iter()
+            .find(|p| p.currency == currency);
+        Ok(proposal)
+    }
+}
+
+/// A payment, that has to be made to settle the netted debts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NetTransfer {
+    pub from: BorderlessId,
+    pub to: BorderlessId,
+    pub amount_milli: i64,
+    pub amount: String,
+}
+
+/// Result of a multilateral netting for a single currency
+#[derive(Serialize)]
+pub struct NettingProposal {
+    pub currency: Currency,
+    /// Sum of all bilateral debts within the group
+    pub gross: String,
+    /// Sum of all payments after netting
+    pub net: String,
+    /// Net position of every participant (positive values are receivables)
+    pub positions: BTreeMap<BorderlessId, i64>,
+    /// Payments, that settle all debts of the group
+    pub transfers: Vec<NetTransfer>,
+    /// Settlement entries, that close the bilateral balances once the transfers have been made
+    ///
+    /// These are equivalent to `settle_debt(debitor, creditor)` and can be executed by a contract.
+    pub settlements: Vec<LedgerEntry>,
+}
+
+impl NettingProposal {
+    fn new(
+        currency: Currency,
+        group: &BTreeSet<BorderlessId>,
+        debts: Vec<(BorderlessId, BorderlessId, i64)>,
+    ) -> Self {
+        let mut positions: BTreeMap<BorderlessId, i64> = group.iter().map(|id| (*id, 0)).collect();
+        let mut gross = 0;
+        let mut settlements = Vec::with_capacity(debts.len());
+        for (debitor, creditor, amount) in debts {
+            *positions.entry(debitor).or_default() -= amount;
+            *positions.entry(creditor).or_default() += amount;
+            gross += amount;
+            settlements.push(LedgerEntry {
+                creditor,
+                debitor,
+                amount_milli: amount,
+                tax_milli: 0,
+                currency,
+                kind: EntryType::SETTLE,
+                tag: NETTING_TAG.to_string(),
+                due_date: None,
+                reference: None,
+                payment_terms: None,
+                reverses: None,
+            });
+        }
+        let transfers = net_transfers(&positions)
+            .into_iter()
+            .map(|(from, to, amount_milli)| NetTransfer {
+                from,
+                to,
+                amount_milli,
+                amount: Money::from_milli(currency, amount_milli).to_string(),
+            })
+            .collect::<Vec<_>>();
+        let net = transfers.iter().map(|t| t.amount_milli).sum();
+        NettingProposal {
+            currency,
+            gross: Money::from_milli(currency, gross).to_string(),
+            net: Money::from_milli(currency, net).to_string(),
+            positions,
+            transfers,
+            settlements,
+        }
+    }
+}
+
+/// Maximum number of participants with open positions, for which the minimal set of payments is computed
+const EXACT_NETTING_LIMIT: usize = 18;
+
+/// Computes the minimal set of payments, that balance the given net positions
+///
+/// For `n` participants with open positions, the minimum is `n - k` payments,
+/// where `k` is the maximum number of zero-sum groups, that the participants can be split into.
+/// Finding these groups requires a search over all subsets, so it is limited to [`EXACT_NETTING_LIMIT`] participants.
+/// Larger groups are settled as a whole, which requires at most `n - 1` payments.
+fn net_transfers(
+    positions: &BTreeMap<BorderlessId, i64>,
+) -> Vec<(BorderlessId, BorderlessId, i64)> {
+    let open: Vec<(BorderlessId, i64)> = positions
+        .iter()
+        .filter(|(_, p)| **p != 0)
+        .map(|(id, p)| (*id, *p))
+        .collect();
+    let groups = if open.len() <= EXACT_NETTING_LIMIT {
+        zero_sum_groups(&open)
+    } else {
+        vec![open]
+    };
+    groups.iter().flat_map(|g| settle_group(g)).collect()
+}
+
+/// Splits the positions into the maximum number of groups, whose positions sum up to zero
+fn zero_sum_groups(open: &[(BorderlessId, i64)]) -> Vec<Vec<(BorderlessId, i64)>> {
+    let n = open.len();
+    let full = (1usize << n) - 1;
+    let contains = |mask: usize, i: usize| mask & (1 << i) != 0;
+
+    // Sum of the positions of every subset
+    let mut sums = vec![0i128; full + 1];
+    for mask in 1..=full {
+        let i = mask.trailing_zeros() as usize;
+        sums[mask] = sums[mask & (mask - 1)] + open[i].1 as i128;
+    }
+    // Maximum number of zero-sum groups, when the subset is built up one participant at a time
+    let mut groups = vec![0u8; full + 1];
+    for mask in 1..=full {
+        let best = (0..n)
+            .filter(|i| contains(mask, *i))
+            .map(|i| groups[mask ^ (1 << i)])
+            .max()
+            .unwrap_or_default();
+        groups[mask] = best + u8::from(sums[mask] == 0);
+    }
+
+    // Walk back from the full set - every zero-sum subset on the way closes a group
+    let mut out = Vec::new();
+    let mut current = Vec::new();
+    let mut mask = full;
+    while mask != 0 {
+        let target = groups[mask] - u8::from(sums[mask] == 0);
+        let i = (0..n)
+            .find(|i| contains(mask, *i) && groups[mask ^ (1 << i)] == target)
+            .expect("at least one participant leads to the optimum");
+        current.push(open[i]);
+        mask ^= 1 << i;
+        if sums[mask] == 0 {
+            out.push(std::mem::take(&mut current));
+        }
+    }
+    out
+}
+
+/// Computes the payments, that balance the positions of a single group
+///
+/// The largest debtor always pays the largest creditor, until one of both positions is balanced.
+/// This requires at most `n - 1` payments for `n` participants.
+fn settle_group(group: &[(BorderlessId, i64)]) -> Vec<(BorderlessId, BorderlessId, i64)> {
+    // Sort by amount (descending) - ties are resolved by the id, so the result is deterministic
+    let sorted = |sign: i64| {
+        let mut out: Vec<(BorderlessId, i64)> = group
+            .iter()
+            .filter(|(_, p)| p.signum() == sign)
+            .map(|(id, p)| (*id, p.abs()))
+            .collect();
+        out.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        out
+    };
+    let mut debtors = sorted(-1);
+    let mut creditors = sorted(1);
+
+    let mut out = Vec::new();
+    let (mut d, mut c) = (0, 0);
+    while d < debtors.len() && c < creditors.len() {
+        let amount = debtors[d].1.min(creditors[c].1);
+        out.push((debtors[d].0, creditors[c].0, amount));
+        debtors[d].1 -= amount;
+        creditors[c].1 -= amount;
+        if debtors[d].1 == 0 {
+            d += 1;
+        }
+        if creditors[c].1 == 0 {
+            c += 1;
+        }
+    }
+    out
+}
+
+#[cfg(all(test, feature = "contracts"))]
+mod tests {
+    use super::*;
+    use crate::LEDGER_INDEX_SUB_DB;
+    use crate::LEDGER_SUB_DB;
+    use borderless::contracts::TxCtx;
+    use borderless::ContractId;
+    use borderless_kv_store::{backend::lmdb::Lmdb, Tx};
+    use tempfile::tempdir;
+
+    fn transfer(db: &Lmdb, debitor: BorderlessId, creditor: BorderlessId, amount: i64) {
+        let entry = LedgerEntry {
+            creditor,
+            debitor,
+            amount_milli: amount * 1000,
+            tax_milli: 0,
+            currency: Currency::EUR,
+            kind: EntryType::CREATE,
+            tag: String::new(),
+            due_date: None,
+            reference: None,
+            payment_terms: None,
+            reverses: None,
+        };
+        let mut txn = db.begin_rw_txn().unwrap();
+        Ledger::new(db)
+            .commit_entry(
+                &mut txn,
+                &entry,
+                ContractId::generate(),
+                &TxCtx::dummy(),
+                None,
+            )
+            .unwrap();
+        txn.commit().unwrap();
+    }
+
+    #[test]
+    fn minimal_transfers() {
+        let [a, b, c, d, e] = [(); 5].map(|_| BorderlessId::generate());
+        // Paying the largest creditor first would require four payments
+        let positions = BTreeMap::from([(a, 5), (b, 4), (c, -4), (d, -3), (e, -2)]);
+        let mut transfers = net_transfers(&positions);
+        transfers.sort();
+        let mut expected = vec![(c, b, 4), (d, a, 3), (e, a, 2)];
+        expected.sort();
+        assert_eq!(transfers, expected);
+
+        // Without zero-sum subgroups, all but one participant have to pay or get paid
+        let positions = BTreeMap::from([(a, 7), (b, 3), (c, -5), (d, -5), (e, 0)]);
+        let transfers = net_transfers(&positions);
+        assert_eq!(transfers.len(), 3);
+        for (id, position) in positions {
+            let paid: i64 = transfers.iter().filter(|t| t.0 == id).map(|t| t.2).sum();
+            let received: i64 = transfers.iter().filter(|t| t.1 == id).map(|t| t.2).sum();
+            assert_eq!(received - paid, position);
+        }
+    }
+
+    #[test]
+    fn netting_cancels_cycles() -> Result<()> {
+        let tmp_dir = tempdir().unwrap();
+        let db = Lmdb::new(tmp_dir.path(), 2)?;
+        db.create_sub_db(LEDGER_SUB_DB)?;
+        db.create_sub_db(LEDGER_INDEX_SUB_DB)?;
+        let [a, b, c, outsider] = [(); 4].map(|_| BorderlessId::generate());
+
+        // a -> b -> c -> a is a cycle, so only the excess has to be paid
+        transfer(&db, a, b, 100);
+        transfer(&db, b, c, 80);
+        transfer(&db, c, a, 50);
+        transfer(&db, b, a, 10);
+        // Ledgers with participants outside of the group are ignored
+        transfer(&db, a, outsider, 1000);
+
+        let proposals = Netting::new(&db).propose(&[a, b, c])?;
+        assert_eq!(proposals.len(), 1);
+        let proposal = &proposals[0];
+        assert_eq!(
+            proposal.gross,
+            Money::from_milli(Currency::EUR, 220_000).to_string()
+        );
+        assert_eq!(proposal.positions[&a], -40_000);
+        assert_eq!(proposal.positions[&b], 10_000);
+        assert_eq!(proposal.positions[&c], 30_000);
+
+        let mut transfers: Vec<_> = proposal
+            .transfers
+            .iter()
+            .map(|t| (t.from, t.to, t.amount_milli))
+            .collect();
+        transfers.sort();
+        let mut expected = vec![(a, c, 30_000), (a, b, 10_000)];
+        expected.sort();
+        assert_eq!(transfers, expected);
+
+        let json = serde_json::to_value(proposal)?;
+        assert_eq!(json["positions"][a.to_string()], -40_000);
+
+        // Executing the settlements closes all ledgers of the group
+        assert_eq!(proposal.settlements.len(), 3);
+        let mut txn = db.begin_rw_txn()?;
+        for entry in &proposal.settlements {
+            Ledger::new(&db).commit_entry(
+                &mut txn,
+                entry,
+                ContractId::generate(),
+                &TxCtx::dummy(),
+                None,
+            )?;
+        }
+        txn.commit()?;
+        assert!(Netting::new(&db).propose(&[a, b, c])?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,510 @@
+//! Versioned storage schema of the runtime sub-databases
+//!
+//! The schema version of every sub-database is stored in the [`SCHEMA_SUB_DB`].
+//! Sub-databases without a version were written before schema versions existed, and are at the [`BASELINE_VERSION`].
+//!
+//! Every change to the layout of a sub-database is registered as a [`Migration`] in [`migrations`],
+//! which raises the version of the sub-database by one. The [`Migrator`] applies all pending migrations in the order
+//! of the registry, and records the new version after each migration.
+//!
+//! NOTE: A migration and its version are not written in the same transaction, so a migration may be re-run,
+//! if the process is killed in between. All migrations must therefore be idempotent.
+
+use std::collections::BTreeMap;
+
+use borderless_kv_store::table::Table;
+use borderless_kv_store::{self as kv, Db, Tx};
+use serde::Serialize;
+
+use super::action_log::{upgrade_action_records, ActionLog};
+use super::controller::upgrade_pkg_defs;
+use super::history::StateHistory;
+use super::ledger::Ledger;
+use super::logger::upgrade_log_lines;
+use super::subscriptions::SubscriptionHandler;
+use crate::error::ErrorKind;
+use crate::log_shim::info;
+use crate::{
+    Result, ACTION_INDEX_SUB_DB, ACTION_TX_REL_SUB_DB, AGENT_SUB_DB, CONTRACT_SUB_DB,
+    FX_RATES_SUB_DB, LEDGER_INDEX_SUB_DB, LEDGER_SUB_DB, SCHEMA_SUB_DB, STATE_HISTORY_SUB_DB,
+    STATE_TREE_SUB_DB, SUBSCRIPTION_REL_SUB_DB, WASM_CODE_SUB_DB,
+};
+
+/// Version of all sub-databases, that have no version yet
+pub const BASELINE_VERSION: u32 = 1;
+
+/// All sub-databases, whose schema is versioned
+pub const VERSIONED_SUB_DBS: &[&str] = &[
+    CONTRACT_SUB_DB,
+    AGENT_SUB_DB,
+    WASM_CODE_SUB_DB,
+    ACTION_TX_REL_SUB_DB,
+    ACTION_INDEX_SUB_DB,
+    STATE_HISTORY_SUB_DB,
+    STATE_TREE_SUB_DB,
+    SUBSCRIPTION_REL_SUB_DB,
+    LEDGER_SUB_DB,
+    LEDGER_INDEX_SUB_DB,
+    FX_RATES_SUB_DB,
+];
+
+/// Maps the name of a sub-database to its schema version
+type SchemaTable<S> = Table<S, String, u32>;
+
+/// Migrates a sub-database from `version - 1` to `version`
+pub struct Migration<S: Db> {
+    /// Name of the sub-database
+    pub sub_db: &'static str,
+    /// Version of the sub-database after the migration
+    pub version: u32,
+    /// Short description of the change
+    pub description: &'static str,
+    /// Function that performs the migration
+    pub run: fn(&S) -> Result<()>,
+}
+
+impl<S: Db> Migration<S> {
+    pub fn info(&self) -> MigrationInfo {
+        MigrationInfo {
+            sub_db: self.sub_db,
+            version: self.version,
+            description: self.description,
+        }
+    }
+}
+
+/// Registry of all migrations, in the order in which they are applied
+///
+/// Migrations, that rewrite stored records in their current layout, come first,
+/// as the other migrations can only decode the current layout.
+pub fn migrations<S: Db>() -> Vec<Migration<S>> {
+    vec![
+        Migration {
+            sub_db: CONTRACT_SUB_DB,
+            version: 2,
+            description: "add the log buffer size to stored package definitions",
+            run: |db| upgrade_pkg_defs(db, CONTRACT_SUB_DB),
+        },
+        Migration {
+            sub_db: CONTRACT_SUB_DB,
+            version: 3,
+            description: "rewrite action records of older versions in the current layout",
+            run: |db| upgrade_action_records(db),
+        },
+        Migration {
+            sub_db: CONTRACT_SUB_DB,
+            version: 4,
+            description: "rewrite log lines of older versions in the current layout",
+            run: |db| upgrade_log_lines(db, CONTRACT_SUB_DB),
+        },
+        Migration {
+            sub_db: AGENT_SUB_DB,
+            version: 2,
+            description: "add the log buffer size to stored package definitions",
+            run: |db| upgrade_pkg_defs(db, AGENT_SUB_DB),
+        },
+        Migration {
+            sub_db: AGENT_SUB_DB,
+            version: 3,
+            description: "rewrite log lines of older versions in the current layout",
+            run: |db| upgrade_log_lines(db, AGENT_SUB_DB),
+        },
+        Migration {
+            sub_db: LEDGER_SUB_DB,
+            version: 2,
+            description:
+                "rewrite the meta information of older ledgers and compute their hash chain",
+            run: |db| Ledger::new(db).chain_all(),
+        },
+        Migration {
+            sub_db: LEDGER_INDEX_SUB_DB,
+            version: 2,
+            description: "build secondary indices for ledgers without an index",
+            run: |db| Ledger::new(db).ensure_index(),
+        },
+        Migration {
+            sub_db: ACTION_INDEX_SUB_DB,
+            version: 2,
+            description: "build secondary indices for action logs without an index",
+            run: |db| ActionLog::rebuild_all_indices(db),
+        },
+        Migration {
+            sub_db: STATE_HISTORY_SUB_DB,
+            version: 2,
+            description: "start the state history of existing contracts at their current version",
+            run: |db| StateHistory::init_baselines(db),
+        },
+        Migration {
+            sub_db: SUBSCRIPTION_REL_SUB_DB,
+            version: 2,
+            description: "index the existing subscriptions by subscriber",
+            run: |db| SubscriptionHandler::index_subscribers(db),
+        },
+    ]
+}
+
+/// Description of a (pending or applied) migration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationInfo {
+    pub sub_db: &'static str,
+    pub version: u32,
+    pub description: &'static str,
+}
+
+/// Schema version of a single sub-database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubDbSchema {
+    pub sub_db: &'static str,
+    /// Stored version - `None` if the sub-database has no version yet
+    pub stored: Option<u32>,
+    /// Version of the sub-database after all migrations have been applied
+    pub current: u32,
+}
+
+impl SubDbSchema {
+    /// Returns the version of the sub-database
+    pub fn version(&self) -> u32 {
+        self.stored.unwrap_or(BASELINE_VERSION)
+    }
+
+    /// Returns `true` if the version is stored and no migration is pending
+    pub fn is_up_to_date(&self) -> bool {
+        self.stored == Some(self.current)
+    }
+}
+
+/// Applies the registered migrations to a database
+pub struct Migrator<'a, S: Db> {
+    db: &'a S,
+    sub_dbs: &'a [&'static str],
+    migrations: Vec<Migration<S>>,
+}
+
+impl<'a, S: Db> Migrator<'a, S> {
+    /// Creates a new migrator for all [`VERSIONED_SUB_DBS`] with the default registry
+    pub fn new(db: &'a S) -> Self {
+        Self::with_migrations(db, VERSIONED_SUB_DBS, migrations())
+    }
+
+    /// Creates a new migrator with a custom registry
+    ///
+    /// # Panics
+    ///
+    /// Panics, if the migrations of a sub-database do not increase its version one by one, starting at the [`BASELINE_VERSION`].
+    pub fn with_migrations(
+        db: &'a S,
+        sub_dbs: &'a [&'static str],
+        migrations: Vec<Migration<S>>,
+    ) -> Self {
+        let mut versions: BTreeMap<&str, u32> = BTreeMap::new();
+        for m in &migrations {
+            let version = versions.entry(m.sub_db).or_insert(BASELINE_VERSION);
+            assert!(
+                sub_dbs.contains(&m.sub_db),
+                "migration for unversioned sub-db '{}'",
+                m.sub_db
+            );
+            assert_eq!(
+                m.version,
+                *version + 1,
+                "migrations of sub-db '{}' are out of order",
+                m.sub_db
+            );
+            *version = m.version;
+        }
+        Self {
+            db,
+            sub_dbs,
+            migrations,
+        }
+    }
+
+    /// Returns the current version of a sub-database, after all migrations have been applied
+    fn current_version(&self, sub_db: &str) -> u32 {
+        self.migrations
+            .iter()
+            .filter(|m| m.sub_db == sub_db)
+            .map(|m| m.version)
+            .max()
+            .unwrap_or(BASELINE_VERSION)
+    }
+
+    /// Returns the schema versions of all existing sub-databases
+    ///
+    /// Sub-databases that have not been created yet are omitted.
+    pub fn status(&self) -> Result<Vec<SubDbSchema>> {
+        let stored = self.stored_versions()?;
+        let mut out = Vec::new();
+        for &sub_db in self.sub_dbs {
+            match self.db.open_sub_db(sub_db) {
+                Ok(_) => (),
+                Err(kv::Error::DbNotFound(_)) => continue,
+                Err(e) => return Err(e.into()),
+            }
+            out.push(SubDbSchema {
+                sub_db,
+                stored: stored.get(sub_db).copied(),
+                current: self.current_version(sub_db),
+            });
+        }
+        Ok(out)
+    }
+
+    /// Returns all migrations, that would be applied by [`Migrator::upgrade`] - without touching the database
+    ///
+    /// Returns an error, if a sub-database was written by a newer version of the runtime.
+    pub fn pending(&self) -> Result<Vec<MigrationInfo>> {
+        let status = self.status()?;
+        let mut versions = BTreeMap::new();
+        for schema in &status {
+            if schema.version() > schema.current {
+                return Err(ErrorKind::UnsupportedSchema {
+                    sub_db: schema.sub_db.to_string(),
+                    version: schema.version(),
+                    supported: schema.current,
+                }
+                .into());
+            }
+            versions.insert(schema.sub_db, schema.version());
+        }
+        Ok(self
+            .migrations
+            .iter()
+            .filter(|m| versions.get(m.sub_db).is_some_and(|v| m.version > *v))
+            .map(Migration::info)
+            .collect())
+    }
+
+    /// Applies all pending migrations and returns them
+    ///
+    /// Afterwards, all existing sub-databases have a stored version.
+    pub fn upgrade(&self) -> Result<Vec<MigrationInfo>> {
+        let pending = self.pending()?;
+        let table = SchemaTable::create(self.db, SCHEMA_SUB_DB)?;
+        for info in &pending {
+            let migration = self
+                .migrations
+                .iter()
+                .find(|m| m.sub_db == info.sub_db && m.version == info.version)
+                .expect("pending migrations are part of the registry");
+            info!(
+                "migrating sub-db '{}' to version {}: {}",
+                info.sub_db, info.version, info.description
+            );
+            (migration.run)(self.db)?;
+            let mut txn = self.db.begin_rw_txn()?;
+            table.put(&mut txn, &info.sub_db.to_string(), &info.version)?;
+            txn.commit()?;
+        }
+        // Sub-databases without any migrations are at their current version
+        let missing: Vec<_> = self
+            .status()?
+            .into_iter()
+            .filter(|schema| schema.stored.is_none())
+            .collect();
+        if !missing.is_empty() {
+            let mut txn = self.db.begin_rw_txn()?;
+            for schema in missing {
+                table.put(&mut txn, &schema.sub_db.to_string(), &schema.current)?;
+            }
+            txn.commit()?;
+        }
+        Ok(pending)
+    }
+
+    fn stored_versions(&self) -> Result<BTreeMap<String, u32>> {
+        let table = match SchemaTable::open(self.db, SCHEMA_SUB_DB) {
+            Ok(table) => table,
+            Err(kv::Error::DbNotFound(_)) => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let txn = self.db.begin_ro_txn()?;
+        let versions = table
+            .iter(&txn)?
+            .collect::<std::result::Result<_, kv::Error>>()?;
+        txn.commit()?;
+        Ok(versions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::controller::Controller;
+    use borderless_kv_store::backend::memdb::MemDb;
+    use borderless_kv_store::{RawRead, RawWrite};
+
+    const TEST_SUB_DB: &str = "test-db";
+    const OTHER_SUB_DB: &str = "other-db";
+    const MISSING_SUB_DB: &str = "missing-db";
+    const SUB_DBS: &[&str] = &[TEST_SUB_DB, OTHER_SUB_DB, MISSING_SUB_DB];
+
+    fn write_step(db: &MemDb, step: u8) -> Result<()> {
+        let db_ptr = db.open_sub_db(TEST_SUB_DB)?;
+        let mut txn = db.begin_rw_txn()?;
+        let mut steps = txn.read(&db_ptr, b"steps")?.unwrap_or_default().to_vec();
+        steps.push(step);
+        txn.write(&db_ptr, b"steps", &steps)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn test_migrations() -> Vec<Migration<MemDb>> {
+        vec![
+            Migration {
+                sub_db: TEST_SUB_DB,
+                version: 2,
+                description: "first",
+                run: |db| write_step(db, 2),
+            },
+            Migration {
+                sub_db: MISSING_SUB_DB,
+                version: 2,
+                description: "never runs",
+                run: |_| panic!("sub-db does not exist"),
+            },
+            Migration {
+                sub_db: TEST_SUB_DB,
+                version: 3,
+                description: "second",
+                run: |db| write_step(db, 3),
+            },
+        ]
+    }
+
+    fn steps(db: &MemDb) -> Result<Vec<u8>> {
+        let db_ptr = db.open_sub_db(TEST_SUB_DB)?;
+        let txn = db.begin_ro_txn()?;
+        let steps = txn.read(&db_ptr, b"steps")?.unwrap_or_default().to_vec();
+        txn.commit()?;
+        Ok(steps)
+    }
+
+    #[test]
+    fn upgrade_applies_pending_migrations_in_order() -> Result<()> {
+        let db = MemDb::new();
+        db.create_sub_db(TEST_SUB_DB)?;
+        db.create_sub_db(OTHER_SUB_DB)?;
+        let migrator = Migrator::with_migrations(&db, SUB_DBS, test_migrations());
+
+        let status = migrator.status()?;
+        assert_eq!(status.len(), 2, "missing sub-dbs are omitted");
+        assert!(status.iter().all(|s| s.stored.is_none()));
+        assert_eq!(status[0].current, 3);
+        assert_eq!(status[1].current, BASELINE_VERSION);
+
+        // Dry-run does not touch the database
+        let pending = migrator.pending()?;
+        let versions: Vec<_> = pending.iter().map(|m| m.version).collect();
+        assert_eq!(versions, vec![2, 3]);
+        assert!(steps(&db)?.is_empty());
+        assert_eq!(migrator.pending()?, pending);
+
+        assert_eq!(migrator.upgrade()?, pending);
+        assert_eq!(steps(&db)?, vec![2, 3]);
+        let status = migrator.status()?;
+        assert!(status.iter().all(SubDbSchema::is_up_to_date));
+
+        // Nothing left to do
+        assert!(migrator.upgrade()?.is_empty());
+        assert_eq!(steps(&db)?, vec![2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn upgrade_resumes_from_stored_version() -> Result<()> {
+        let db = MemDb::new();
+        db.create_sub_db(TEST_SUB_DB)?;
+        let table = SchemaTable::create(&db, SCHEMA_SUB_DB)?;
+        let mut txn = db.begin_rw_txn()?;
+        table.put(&mut txn, &TEST_SUB_DB.to_string(), &2)?;
+        txn.commit()?;
+
+        let migrator = Migrator::with_migrations(&db, SUB_DBS, test_migrations());
+        let applied = migrator.upgrade()?;
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].description, "second");
+        assert_eq!(steps(&db)?, vec![3]);
+        Ok(())
+    }
+
+    #[test]
+    fn newer_schema_is_rejected() -> Result<()> {
+        let db = MemDb::new();
+        db.create_sub_db(TEST_SUB_DB)?;
+        let table = SchemaTable::create(&db, SCHEMA_SUB_DB)?;
+        let mut txn = db.begin_rw_txn()?;
+        table.put(&mut txn, &TEST_SUB_DB.to_string(), &4)?;
+        txn.commit()?;
+
+        let migrator = Migrator::with_migrations(&db, SUB_DBS, test_migrations());
+        assert!(migrator.pending().is_err());
+        assert!(migrator.upgrade().is_err());
+        assert!(steps(&db)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "out of order")]
+    fn registry_must_be_ordered() {
+        let db = MemDb::new();
+        let mut migrations = test_migrations();
+        migrations.swap(0, 2);
+        let _ = Migrator::with_migrations(&db, SUB_DBS, migrations);
+    }
+
+    #[test]
+    fn default_registry_upgrades_runtime_sub_dbs() -> Result<()> {
+        let db = MemDb::new();
+        db.create_sub_db(LEDGER_SUB_DB)?;
+        db.create_sub_db(LEDGER_INDEX_SUB_DB)?;
+        let migrator = Migrator::new(&db);
+        let applied = migrator.upgrade()?;
+        assert_eq!(applied.len(), 2);
+        assert_eq!(applied[0].sub_db, LEDGER_SUB_DB);
+        assert_eq!(applied[1].sub_db, LEDGER_INDEX_SUB_DB);
+        let status = migrator.status()?;
+        assert_eq!(status.len(), 2);
+        assert!(status.iter().all(SubDbSchema::is_up_to_date));
+        Ok(())
+    }
+
+    #[test]
+    fn package_definitions_are_upgraded() -> Result<()> {
+        use borderless::__private::storage_keys::*;
+        use borderless::pkg::{Author, Capabilities, PkgType};
+        use borderless::ContractId;
+
+        let db = MemDb::new();
+        let db_ptr = db.create_sub_db(CONTRACT_SUB_DB)?;
+        let cid = ContractId::generate();
+        // Package definition without the log buffer size
+        let pkg_def = (
+            "flipper",
+            None::<String>,
+            None::<String>,
+            None::<Capabilities>,
+            PkgType::Contract,
+            (
+                Vec::<Author>::new(),
+                None::<String>,
+                None::<String>,
+                None::<String>,
+                None::<String>,
+            ),
+        );
+        let key = StorageKey::system_key(cid, BASE_KEY_METADATA, META_SUB_KEY_PACKAGE_DEF);
+        let mut txn = db.begin_rw_txn()?;
+        txn.write(&db_ptr, &key, &postcard::to_allocvec(&pkg_def)?)?;
+        txn.commit()?;
+
+        let applied = Migrator::new(&db).upgrade()?;
+        assert!(applied.iter().any(|m| m.sub_db == CONTRACT_SUB_DB));
+        // Running the migration again does not touch upgraded definitions
+        upgrade_pkg_defs(&db, CONTRACT_SUB_DB)?;
+        let pkg_def = Controller::new(&db).contract_pkg_def(&cid)?.unwrap();
+        assert_eq!(pkg_def.name, "flipper");
+        assert!(pkg_def.meta.log_buffer_size.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::HashSet;
+
+use borderless::__private::storage_keys::StorageKey;
+use borderless::hash::{Hash256, Hasher};
+use borderless::ContractId;
+use borderless_kv_store::*;
+use serde::{Deserialize, Serialize};
+
+use super::history::HistoryOp;
+#[allow(unused_imports)]
+use crate::log_shim::*;
+use crate::{Result, CONTRACT_SUB_DB, STATE_TREE_SUB_DB};
+
+// NOTE: The state-tree is a sparse merkle tree over all user-space keys of a contract.
+// The position of a leaf is determined by the hash of its storage-key (the "path").
+// Subtrees without leaves have the hash `Hash256::zero()`, and subtrees with exactly one leaf
+// are represented by the hash of the leaf itself. Only nodes that cover at least two leaves are stored,
+// so updating a key touches ~log2(n) nodes instead of the full depth of 256 levels.
+//
+// The tree lives in its own sub-db, and all keys are prefixed with the contract-id:
+// - Leaf: cid | TAG_LEAF | path         -> leaf-hash
+// - Node: cid | TAG_NODE | depth | path -> node-hash (bits of the path below the depth are zero)
+// - Init: cid | TAG_INIT                -> empty (marks, that the tree has been built from the contract state)
+const TAG_LEAF: u8 = 0;
+const TAG_NODE: u8 = 1;
+const TAG_INIT: u8 = 2;
+
+/// Maximum depth of the tree (number of bits in the path)
+const MAX_DEPTH: u16 = 256;
+
+/// Calculates the hash of a leaf in the state-tree
+pub fn leaf_hash(key: &StorageKey, value: &[u8]) -> Hash256 {
+    let mut hasher = Hasher::new();
+    hasher.update(b"\x00");
+    hasher.update(key);
+    hasher.update(&value);
+    hasher.finalize()
+}
+
+/// Calculates the hash of an inner node in the state-tree
+fn node_hash(left: &Hash256, right: &Hash256) -> Hash256 {
+    let mut hasher = Hasher::new();
+    hasher.update(b"\x01");
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize()
+}
+
+fn path_of(key: &[u8]) -> [u8; 32] {
+    Hash256::digest(&key).into_slice()
+}
+
+/// Returns the bit of the path at the given depth
+fn bit(path: &[u8; 32], depth: u16) -> bool {
+    let depth = depth as usize;
+    path[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+/// Returns the path, where all bits at and below the given depth are cleared
+fn prefix(path: &[u8; 32], depth: u16) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    let depth = depth as usize;
+    out[..depth / 8].copy_from_slice(&path[..depth / 8]);
+    if !depth.is_multiple_of(8) {
+        out[depth / 8] = path[depth / 8] & !(0xff >> (depth % 8));
+    }
+    out
+}
+
+/// Sets the bit of the path at the given depth
+fn with_bit(path: &[u8; 32], depth: u16) -> [u8; 32] {
+    let mut out = *path;
+    let depth = depth as usize;
+    out[depth / 8] |= 0x80 >> (depth % 8);
+    out
+}
+
+fn leaf_key(cid: &ContractId, path: &[u8; 32]) -> [u8; 49] {
+    let mut out = [0u8; 49];
+    out[..16].copy_from_slice(cid.as_ref());
+    out[16] = TAG_LEAF;
+    out[17..].copy_from_slice(path);
+    out
+}
+
+fn node_key(cid: &ContractId, depth: u16, path: &[u8; 32]) -> [u8; 51] {
+    let mut out = [0u8; 51];
+    out[..16].copy_from_slice(cid.as_ref());
+    out[16] = TAG_NODE;
+    out[17..19].copy_from_slice(&depth.to_be_bytes());
+    out[19..].copy_from_slice(&prefix(path, depth));
+    out
+}
+
+fn init_key(cid: &ContractId) -> [u8; 17] {
+    let mut out = [0u8; 17];
+    out[..16].copy_from_slice(cid.as_ref());
+    out[16] = TAG_INIT;
+    out
+}
+
+fn decode_hash(bytes: &[u8]) -> Hash256 {
+    Hash256::try_from(bytes).expect("Slice length error")
+}
+
+/// Collects up to two leaves of the subtree at the given depth and path
+fn subtree_leaves<'c>(
+    iter: impl Iterator<Item = (&'c [u8], &'c [u8])>,
+    cid: &ContractId,
+    depth: u16,
+    path: &[u8; 32],
+) -> Vec<Hash256> {
+    let target = prefix(path, depth);
+    let cid: &[u8; 16] = cid.as_ref();
+    iter.take_while(|(key, _)| {
+        key.len() == 49
+            && key[..16] == cid[..]
+            && key[16] == TAG_LEAF
+            && prefix(key[17..].try_into().expect("Slice length error"), depth) == target
+    })
+    .take(2)
+    .map(|(_, value)| decode_hash(value))
+    .collect()
+}
+
+/// Access to the nodes and leaves of the tree, either in a read-only or read-write transaction
+trait NodeStore {
+    fn node(&mut self, depth: u16, path: &[u8; 32]) -> Result<Option<Hash256>>;
+
+    fn leaves(&mut self, depth: u16, path: &[u8; 32]) -> Result<Vec<Hash256>>;
+
+    fn put_node(&mut self, depth: u16, path: &[u8; 32], hash: &Hash256) -> Result<()>;
+}
+
+struct RoStore<'a, 'env, S: Db + 'env> {
+    txn: &'a S::RoTx<'env>,
+    tree_db: &'a S::Handle,
+    cid: ContractId,
+}
+
+impl<'env, S: Db + 'env> NodeStore for RoStore<'_, 'env, S> {
+    fn node(&mut self, depth: u16, path: &[u8; 32]) -> Result<Option<Hash256>> {
+        let node = self
+            .txn
+            .read(self.tree_db, &node_key(&self.cid, depth, path))?;
+        Ok(node.map(decode_hash))
+    }
+
+    fn leaves(&mut self, depth: u16, path: &[u8; 32]) -> Result<Vec<Hash256>> {
+        let mut cursor = self.txn.ro_cursor(self.tree_db)?;
+        let start = leaf_key(&self.cid, &prefix(path, depth));
+        Ok(subtree_leaves(
+            cursor.iter_from(&start),
+            &self.cid,
+            depth,
+            path,
+        ))
+    }
+
+    fn put_node(&mut self, _depth: u16, _path: &[u8; 32], _hash: &Hash256) -> Result<()> {
+        // All relevant nodes are already stored - nothing to do in a read-only transaction
+        Ok(())
+    }
+}
+
+struct RwStore<'a, 'env, S: Db + 'env> {
+    txn: &'a mut S::RwTx<'env>,
+    tree_db: &'a S::Handle,
+    cid: ContractId,
+}
+
+impl<'env, S: Db + 'env> NodeStore for RwStore<'_, 'env, S> {
+    fn node(&mut self, depth: u16, path: &[u8; 32]) -> Result<Option<Hash256>> {
+        let node = self
+            .txn
+            .read(self.tree_db, &node_key(&self.cid, depth, path))?;
+        Ok(node.map(decode_hash))
+    }
+
+    fn leaves(&mut self, depth: u16, path: &[u8; 32]) -> Result<Vec<Hash256>> {
+        let mut cursor = self.txn.rw_cursor(self.tree_db)?;
+        let start = leaf_key(&self.cid, &prefix(path, depth));
+        Ok(subtree_leaves(
+            cursor.iter_from(&start),
+            &self.cid,
+            depth,
+            path,
+        ))
+    }
+
+    fn put_node(&mut self, depth: u16, path: &[u8; 32], hash: &Hash256) -> Result<()> {
+        self.txn
+            .write(self.tree_db, &node_key(&self.cid, depth, path), hash)?;
+        Ok(())
+    }
+}
+
+/// Calculates the hash of the subtree at the given depth and path
+///
+/// Stored nodes are used as they are; missing nodes that cover at least two leaves are calculated and stored.
+fn subtree_hash(store: &mut impl NodeStore, depth: u16, path: &[u8; 32]) -> Result<Hash256> {
+    if let Some(hash) = store.node(depth, path)? {
+        return Ok(hash);
+    }
+    let leaves = store.leaves(depth, path)?;
+    if leaves.len() < 2 || depth == MAX_DEPTH {
+        return Ok(leaves.first().copied().unwrap_or_else(Hash256::zero));
+    }
+    let path = prefix(path, depth);
+    let left = subtree_hash(store, depth + 1, &path)?;
+    let right = subtree_hash(store, depth + 1, &with_bit(&path, depth))?;
+    let hash = node_hash(&left, &right);
+    store.put_node(depth, &path, &hash)?;
+    Ok(hash)
+}
+
+/// State root of a contract at the given version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateRoot {
+    /// Number of actions, that have been applied to the contract
+    pub version: u64,
+    pub root: Hash256,
+}
+
+/// Proof, that a value is part of the state of a contract
+///
+/// The proof contains the hashes of all siblings along the path from the root to the leaf.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateProof {
+    pub contract_id: ContractId,
+    pub base_key: u64,
+    pub sub_key: u64,
+    /// Value of the storage key
+    #[serde(with = "serde_bytes")]
+    pub value: Vec<u8>,
+    /// Sibling hashes, starting at the root
+    pub siblings: Vec<Hash256>,
+}
+
+impl StateProof {
+    /// Verifies the proof against the given state root
+    pub fn verify(&self, root: &Hash256) -> bool {
+        let key = StorageKey::user_key(self.contract_id, self.base_key, self.sub_key);
+        if self.siblings.len() > MAX_DEPTH as usize {
+            return false;
+        }
+        let path = path_of(key.as_ref());
+        let hash = self.siblings.iter().enumerate().rev().fold(
+            leaf_hash(&key, &self.value),
+            |hash, (depth, sibling)| {
+                if bit(&path, depth as u16) {
+                    node_hash(sibling, &hash)
+                } else {
+                    node_hash(&hash, sibling)
+                }
+            },
+        );
+        hash == *root
+    }
+}
+
+/// Merkle tree over the user-space storage of a contract
+///
+/// The root of the tree is a compact commitment to the contract state, which is updated after each transaction
+/// and stored alongside the action record (see [`ActionRecord::state_root`](super::action_log::ActionRecord::state_root)).
+pub struct StateTree<'a, S: Db> {
+    db: &'a S,
+    cid: ContractId,
+}
+
+impl<'a, S: Db> StateTree<'a, S> {
+    pub fn new(db: &'a S, cid: ContractId) -> Self {
+        Self { db, cid }
+    }
+
+    /// Returns the current state root
+    ///
+    /// Returns `None`, if the tree has not been built yet (which happens with the next transaction of the contract).
+    pub fn root(&self) -> Result<Option<Hash256>> {
+        let tree_db = self.db.open_sub_db(STATE_TREE_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        if txn.read(&tree_db, &init_key(&self.cid))?.is_none() {
+            return Ok(None);
+        }
+        let mut store = RoStore::<S> {
+            txn: &txn,
+            tree_db: &tree_db,
+            cid: self.cid,
+        };
+        let root = subtree_hash(&mut store, 0, &[0; 32])?;
+        txn.commit()?;
+        Ok(Some(root))
+    }
+
+    /// Generates a proof for the value behind the given user-space key
+    ///
+    /// Returns `None`, if the key does not exist or the tree has not been built yet.
+    pub fn proof(&self, base_key: u64, sub_key: u64) -> Result<Option<StateProof>> {
+        let key = StorageKey::user_key(self.cid, base_key, sub_key);
+        let db_ptr = self.db.open_sub_db(CONTRACT_SUB_DB)?;
+        let tree_db = self.db.open_sub_db(STATE_TREE_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let path = path_of(key.as_ref());
+        let value = match txn.read(&db_ptr, &key)? {
+            Some(value) => value.to_vec(),
+            None => return Ok(None),
+        };
+        if txn.read(&tree_db, &leaf_key(&self.cid, &path))?.is_none() {
+            return Ok(None);
+        }
+        let mut store = RoStore::<S> {
+            txn: &txn,
+            tree_db: &tree_db,
+            cid: self.cid,
+        };
+        // Only nodes with at least two leaves are stored - the leaf is reached at the first missing node
+        let mut siblings = Vec::new();
+        let mut depth = 0;
+        while store.node(depth, &path)?.is_some() {
+            let sibling = if bit(&path, depth) {
+                prefix(&path, depth)
+            } else {
+                with_bit(&prefix(&path, depth), depth)
+            };
+            siblings.push(subtree_hash(&mut store, depth + 1, &sibling)?);
+            depth += 1;
+        }
+        txn.commit()?;
+        Ok(Some(StateProof {
+            contract_id: self.cid,
+            base_key: key.base_key(),
+            sub_key,
+            value,
+            siblings,
+        }))
+    }
+
+    /// Applies the changes to the tree and returns the new state root
+    ///
+    /// The changes must be recorded before they are applied to the contract state,
+    /// because the tree is built from the current state, if it does not exist yet.
+    pub(crate) fn update(
+        &self,
+        db_ptr: &S::Handle,
+        txn: &mut <S as Db>::RwTx<'_>,
+        ops: &[HistoryOp<'_>],
+    ) -> Result<Hash256> {
+        let tree_db = self.db.open_sub_db(STATE_TREE_SUB_DB)?;
+        let init_key = init_key(&self.cid);
+        if txn.read(&tree_db, &init_key)?.is_none() {
+            let prefix = self.cid.into_bytes();
+            let mut leaves = Vec::new();
+            let mut cursor = txn.rw_cursor(db_ptr)?;
+            for (key, value) in cursor.iter_prefix(&prefix) {
+                let key = StorageKey::try_from(key).expect("Slice length error");
+                if key.is_user_key() {
+                    leaves.push((path_of(key.as_ref()), leaf_hash(&key, value)));
+                }
+            }
+            drop(cursor);
+            debug!("building state-tree from {} keys", leaves.len());
+            for (path, hash) in leaves {
+                txn.write(&tree_db, &leaf_key(&self.cid, &path), &hash)?;
+            }
+            txn.write(&tree_db, &init_key, &[])?;
+        }
+
+        // Invalidate all stored nodes along the paths of the changed keys
+        let mut removed = HashSet::new();
+        for op in ops {
+            let (key, leaf) = match op {
+                HistoryOp::Write(key, value) => (*key, Some(leaf_hash(key, value))),
+                HistoryOp::Remove(key) => (*key, None),
+            };
+            let path = path_of(key.as_ref());
+            for depth in 0..MAX_DEPTH {
+                let node_key = node_key(&self.cid, depth, &path);
+                if txn.read(&tree_db, &node_key)?.is_some() {
+                    txn.delete(&tree_db, &node_key)?;
+                    removed.insert(node_key);
+                } else if !removed.contains(&node_key) {
+                    break;
+                }
+            }
+            let leaf_key = leaf_key(&self.cid, &path);
+            match leaf {
+                Some(hash) => txn.write(&tree_db, &leaf_key, &hash)?,
+                None => txn.delete(&tree_db, &leaf_key)?,
+            }
+        }
+
+        let mut store = RwStore::<S> {
+            txn,
+            tree_db: &tree_db,
+            cid: self.cid,
+        };
+        subtree_hash(&mut store, 0, &[0; 32])
+    }
+}
+
+#[cfg(all(test, feature = "contracts"))]
+mod tests {
+    use super::*;
+    use borderless_kv_store::backend::lmdb::Lmdb;
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+
+    fn open_tmp_lmdb() -> Lmdb {
+        let tmp_dir = tempdir().unwrap();
+        let env = Lmdb::new(tmp_dir.path(), 2).unwrap();
+        env.create_sub_db(CONTRACT_SUB_DB).unwrap();
+        env.create_sub_db(STATE_TREE_SUB_DB).unwrap();
+        env
+    }
+
+    /// Applies the changes like the vm does
+    fn apply(db: &Lmdb, cid: ContractId, ops: &[HistoryOp<'_>]) -> Hash256 {
+        let db_ptr = db.open_sub_db(CONTRACT_SUB_DB).unwrap();
+        let mut txn = db.begin_rw_txn().unwrap();
+        let root = StateTree::new(db, cid)
+            .update(&db_ptr, &mut txn, ops)
+            .unwrap();
+        for op in ops {
+            match op {
+                HistoryOp::Write(key, value) => txn.write(&db_ptr, key, value).unwrap(),
+                HistoryOp::Remove(key) => txn.delete(&db_ptr, key).unwrap(),
+            }
+        }
+        txn.commit().unwrap();
+        root
+    }
+
+    /// Calculates the root from scratch
+    fn full_root(state: &BTreeMap<u64, Vec<u8>>, cid: ContractId) -> Hash256 {
+        fn calc(leaves: &[([u8; 32], Hash256)], depth: u16) -> Hash256 {
+            match leaves {
+                [] => Hash256::zero(),
+                [(_, hash)] => *hash,
+                _ => {
+                    let split = leaves.partition_point(|(path, _)| !bit(path, depth));
+                    node_hash(
+                        &calc(&leaves[..split], depth + 1),
+                        &calc(&leaves[split..], depth + 1),
+                    )
+                }
+            }
+        }
+        let mut leaves: Vec<_> = state
+            .iter()
+            .map(|(sub_key, value)| {
+                let key = StorageKey::user_key(cid, 1, *sub_key);
+                (path_of(key.as_ref()), leaf_hash(&key, value))
+            })
+            .collect();
+        leaves.sort_by_key(|(path, _)| *path);
+        calc(&leaves, 0)
+    }
+
+    #[test]
+    fn incremental_root_matches_full_root() -> Result<()> {
+        let db = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let tree = StateTree::new(&db, cid);
+        assert!(tree.root()?.is_none());
+
+        let mut state = BTreeMap::new();
+        for round in 0..20u64 {
+            let keys: Vec<_> = (0..10)
+                .map(|i| StorageKey::user_key(cid, 1, (round * 7 + i) % 50))
+                .collect();
+            let value = round.to_be_bytes();
+            let ops: Vec<_> = keys
+                .iter()
+                .enumerate()
+                .map(|(i, key)| {
+                    if (i as u64 + round).is_multiple_of(3) {
+                        state.remove(&key.sub_key());
+                        HistoryOp::Remove(key)
+                    } else {
+                        state.insert(key.sub_key(), value.to_vec());
+                        HistoryOp::Write(key, &value)
+                    }
+                })
+                .collect();
+            let root = apply(&db, cid, &ops);
+            assert_eq!(root, full_root(&state, cid));
+            assert_eq!(tree.root()?, Some(root));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn tree_is_built_from_existing_state() -> Result<()> {
+        let db = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let db_ptr = db.open_sub_db(CONTRACT_SUB_DB)?;
+        let mut state = BTreeMap::new();
+        let mut txn = db.begin_rw_txn()?;
+        for sub_key in 0..5 {
+            let value = vec![sub_key as u8; 3];
+            txn.write(&db_ptr, &StorageKey::user_key(cid, 1, sub_key), &value)?;
+            state.insert(sub_key, value);
+        }
+        txn.commit()?;
+
+        let root = apply(&db, cid, &[]);
+        assert_eq!(root, full_root(&state, cid));
+        Ok(())
+    }
+
+    #[test]
+    fn proofs_verify_against_root() -> Result<()> {
+        let db = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let keys: Vec<_> = (0..16).map(|i| StorageKey::user_key(cid, 1, i)).collect();
+        let ops: Vec<_> = keys
+            .iter()
+            .map(|key| HistoryOp::Write(key, b"value"))
+            .collect();
+        let root = apply(&db, cid, &ops);
+
+        let tree = StateTree::new(&db, cid);
+        for sub_key in 0..16 {
+            let proof = tree.proof(1, sub_key)?.expect("key must exist");
+            assert!(proof.verify(&root));
+            let mut forged = proof.clone();
+            forged.value = b"other".to_vec();
+            assert!(!forged.verify(&root));
+        }
+        assert!(tree.proof(1, 100)?.is_none());
+        Ok(())
+    }
+}
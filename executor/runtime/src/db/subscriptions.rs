@@ -32,6 +32,34 @@ fn generate_key(publisher: Id, topic: String, subscriber: Option<AgentId>) -> St
     }
 }
 
+/// Generates the key of the subscriber index from a subscription key
+///
+/// Index keys are 'subscriber | publisher | topic' with a leading delimiter,
+/// so they never collide with the subscription keys, which always start with the publisher.
+fn index_key(key: &[u8]) -> Result<Vec<u8>> {
+    let key = std::str::from_utf8(key).with_context(|| "DB key deserialization failed")?;
+    let mut parts = key.splitn(3, '\n');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(p), Some(topic), Some(s)) => Ok(format!("\n{s}\n{p}\n{topic}").into_bytes()),
+        _ => Err(crate::Error::msg("Malformed key error")),
+    }
+}
+
+/// Restores the subscription key from a key of the subscriber index
+fn subscription_key(index_key: &[u8]) -> Result<Vec<u8>> {
+    let key = std::str::from_utf8(index_key).with_context(|| "DB key deserialization failed")?;
+    let mut parts = key.strip_prefix('\n').unwrap_or(key).splitn(3, '\n');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(s), Some(p), Some(topic)) => Ok(format!("{p}\n{topic}\n{s}").into_bytes()),
+        _ => Err(crate::Error::msg("Malformed key error")),
+    }
+}
+
+/// Generates the look-up key of all index entries of a subscriber
+fn subscriber_prefix(subscriber: AgentId) -> String {
+    format!("\n{}\n", subscriber.to_string().to_ascii_lowercase())
+}
+
 /// Extracts the topic and subscriber from a DB entry
 ///
 /// Returns a tuple, or an error if the deserialization fails
@@ -97,7 +125,18 @@ impl<'a, S: Db> SubscriptionHandler<'a, S> {
         let db_ptr = self.db.open_sub_db(SUBSCRIPTION_REL_SUB_DB)?;
         // Generate DB key
         let key = generate_key(topic.publisher, topic.topic, Some(subscriber));
-        txn.write(&db_ptr, &key, &topic.method)?;
+        Self::write_entry(txn, &db_ptr, key.as_bytes(), topic.method.as_bytes())
+    }
+
+    /// Writes a subscription together with its entry in the subscriber index
+    pub(crate) fn write_entry(
+        txn: &mut <S as Db>::RwTx<'_>,
+        db_ptr: &S::Handle,
+        key: &[u8],
+        method: &[u8],
+    ) -> Result<()> {
+        txn.write(db_ptr, &key, &method)?;
+        txn.write(db_ptr, &index_key(key)?, &method)?;
         Ok(())
     }
 
@@ -123,6 +162,7 @@ impl<'a, S: Db> SubscriptionHandler<'a, S> {
         let db_ptr = self.db.open_sub_db(SUBSCRIPTION_REL_SUB_DB)?;
         // Generate DB key
         let key = generate_key(topic.publisher, topic.topic, Some(subscriber));
+        txn.delete(&db_ptr, &index_key(key.as_bytes())?)?;
         Ok(txn.delete(&db_ptr, &key)?)
     }
 
@@ -142,11 +182,7 @@ impl<'a, S: Db> SubscriptionHandler<'a, S> {
         // Use an efficient look-up key
         let prefix = generate_key(publisher, topic, None);
 
-        for (key, value) in cursor.iter_from(&prefix) {
-            // Stop iterating when prefix no longer matches
-            if !key.starts_with(prefix.as_bytes()) {
-                break;
-            }
+        for (key, value) in cursor.iter_prefix(&prefix) {
             let (topic, subscriber) = extract_entry(key, value)?;
             // Push the tuple
             subscribers.push((subscriber, topic.method));
@@ -164,12 +200,10 @@ impl<'a, S: Db> SubscriptionHandler<'a, S> {
         let mut cursor = txn.ro_cursor(&db_ptr)?;
 
         let mut topics = Vec::new();
-        for (key, value) in cursor.iter() {
-            let (topic, subscriber) = extract_entry(key, value)?;
-            // Ignore subscription not related with target
-            if target != subscriber {
-                continue;
-            }
+        // Use the subscriber index instead of scanning all subscriptions
+        let prefix = subscriber_prefix(target);
+        for (key, value) in cursor.iter_prefix(&prefix) {
+            let (topic, _) = extract_entry(&subscription_key(key)?, value)?;
             // Push the topic
             topics.push(topic);
         }
@@ -178,6 +212,58 @@ impl<'a, S: Db> SubscriptionHandler<'a, S> {
         Ok(topics)
     }
 
+    /// Returns the raw subscriptions, where the entity is either the publisher or the subscriber
+    ///
+    /// The entries of the subscriber index are not part of the result, see [`Self::write_entry`].
+    pub(crate) fn export_entries(&self, id: Id) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let db_ptr = self.db.create_sub_db(SUBSCRIPTION_REL_SUB_DB)?;
+        let txn = self.db.begin_ro_txn()?;
+        let mut cursor = txn.ro_cursor(&db_ptr)?;
+
+        let publisher = generate_key(id, String::new(), None);
+        let mut entries: Vec<_> = cursor
+            .iter_prefix(&publisher)
+            .map(|(k, v)| (k.to_vec(), v.to_vec()))
+            .collect();
+        if let Id::Agent { agent_id } = id {
+            for (key, value) in cursor.iter_prefix(&subscriber_prefix(agent_id)) {
+                let key = subscription_key(key)?;
+                // Subscriptions to the agent itself are already part of the result
+                if !key.starts_with(publisher.as_bytes()) {
+                    entries.push((key, value.to_vec()));
+                }
+            }
+        }
+        drop(cursor);
+        txn.commit()?;
+        Ok(entries)
+    }
+
+    /// Adds all existing subscriptions to the subscriber index
+    ///
+    /// Registered as migration of the [`SUBSCRIPTION_REL_SUB_DB`], see [`super::schema`].
+    pub fn index_subscribers(db: &S) -> Result<()> {
+        let db_ptr = db.open_sub_db(SUBSCRIPTION_REL_SUB_DB)?;
+        let txn = db.begin_ro_txn()?;
+        let mut cursor = txn.ro_cursor(&db_ptr)?;
+        let mut entries = Vec::new();
+        for (key, value) in cursor.iter() {
+            // Skip the entries of the index itself
+            if !key.starts_with(b"\n") {
+                entries.push((index_key(key)?, value.to_vec()));
+            }
+        }
+        drop(cursor);
+        txn.commit()?;
+
+        let mut txn = db.begin_rw_txn()?;
+        for (key, value) in &entries {
+            txn.write(&db_ptr, key, value)?;
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
     pub fn unsubscribe_all(&self, txn: &mut <S as Db>::RwTx<'_>, subscriber: Id) -> Result<()> {
         let subscriber = match subscriber {
             Id::Contract { .. } => return Ok(()), // Not applicable
@@ -355,6 +441,38 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn index_existing_subscriptions() -> Result<()> {
+        use borderless_kv_store::{RawWrite, Tx};
+
+        let lmdb = open_tmp_lmdb();
+        let handler = SubscriptionHandler::new(&lmdb);
+        let subscriber = AgentId::generate();
+        let publisher = Id::contract(ContractId::generate());
+
+        // Subscription, that was written before the subscriber index existed
+        let db_ptr = lmdb.open_sub_db(SUBSCRIPTION_REL_SUB_DB)?;
+        let mut txn = lmdb.begin_rw_txn()?;
+        let key = super::generate_key(publisher, "tennis".to_string(), Some(subscriber));
+        txn.write(&db_ptr, &key, &"method")?;
+        txn.commit()?;
+        assert!(handler.get_subscriptions(subscriber)?.is_empty());
+
+        SubscriptionHandler::index_subscribers(&lmdb)?;
+        // Running the migration again does not duplicate the subscriptions
+        SubscriptionHandler::index_subscribers(&lmdb)?;
+        let expected = Topic::new(publisher, "tennis", "method");
+        assert_eq!(
+            handler.get_subscriptions(subscriber)?,
+            vec![expected.clone()]
+        );
+
+        // Unsubscribing also removes the index entry
+        handler.unsubscribe(subscriber, expected)?;
+        assert!(handler.get_subscriptions(subscriber)?.is_empty());
+        Ok(())
+    }
+
     #[test]
     fn fetch_subscriptions() -> Result<()> {
         // Setup dummy DB
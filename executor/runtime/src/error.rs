@@ -39,6 +39,12 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        ErrorKind::from(value).into()
+    }
+}
+
 impl From<wasmtime::Error> for Error {
     fn from(value: wasmtime::Error) -> Self {
         ErrorKind::from(value).into()
@@ -61,6 +67,10 @@ pub(crate) enum ErrorKind {
     #[error("encoding error (json) - {0}")]
     JsonEncoding(#[from] serde_json::Error),
 
+    /// I/O errors (e.g. while writing exports)
+    #[error("io error - {0}")]
+    Io(#[from] std::io::Error),
+
     /// Wasmtime related errors
     #[error("wasmtime error - {0}")]
     Wasm(#[from] wasmtime::Error),
@@ -120,6 +130,15 @@ pub(crate) enum ErrorKind {
     #[error("VmState is not initialized with async support")]
     NoAsync,
 
+    // --- Schema errors
+    /// The sub-database was written by a newer version of the runtime
+    #[error("schema of sub-db '{sub_db}' has version {version}, but only version {supported} is supported")]
+    UnsupportedSchema {
+        sub_db: String,
+        version: u32,
+        supported: u32,
+    },
+
     /// Generic error message - useful for communicating more complicated errors
     #[error("{0}")]
     Msg(String),
@@ -15,6 +15,9 @@ pub mod agent;
 #[cfg(feature = "contracts")]
 pub mod ledger;
 
+#[cfg(feature = "contracts")]
+pub mod admin;
+
 pub type Request<T = Bytes> = http::Request<T>;
 pub type Response<T = Bytes> = http::Response<T>;
 
@@ -0,0 +1,107 @@
+pub use super::*;
+use crate::db::fx::{FxRates, RateEntry};
+use crate::log_shim::*;
+use borderless_kv_store::{backend::lmdb::Lmdb, Db};
+use http::header::AUTHORIZATION;
+use http::method::Method;
+use std::convert::Infallible;
+use std::future::Future;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Service for administrative routes, that modify the node-local data of the ledgers
+///
+/// Every request must carry the admin token as bearer token in the `Authorization` header.
+/// Web-frameworks that embed this service should mount it separately from the read-only [`LedgerService`].
+///
+/// [`LedgerService`]: super::ledger::LedgerService
+#[derive(Clone)]
+pub struct AdminService<S = Lmdb>
+where
+    S: Db + 'static,
+{
+    db: S,
+    token: String,
+}
+
+impl<S> AdminService<S>
+where
+    S: Db + 'static,
+{
+    pub fn new(db: S, token: String) -> Self {
+        Self { db, token }
+    }
+
+    /// Checks, that the request carries the admin token
+    fn is_authorized(&self, parts: &http::request::Parts) -> bool {
+        parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| !self.token.is_empty() && token == self.token)
+    }
+
+    async fn process_rq(&self, req: Request) -> crate::Result<Response> {
+        let (parts, payload) = req.into_parts();
+        if !self.is_authorized(&parts) {
+            warn!(
+                "Rejected unauthorized admin request. path={}",
+                parts.uri.path()
+            );
+            return Ok(err_response(
+                StatusCode::UNAUTHORIZED,
+                "missing or invalid admin token".to_string(),
+            ));
+        }
+        if parts.method != Method::POST {
+            return Ok(method_not_allowed());
+        }
+        if !check_json_content(&parts) {
+            return Ok(unsupported_media_type());
+        }
+
+        match parts.uri.path() {
+            // POST /fx-rates
+            "/fx-rates" | "fx-rates" => {
+                let rates = match serde_json::from_slice::<Vec<RateEntry>>(&payload) {
+                    Ok(r) => r,
+                    Err(e) => return Ok(bad_request(e.to_string())),
+                };
+                if let Err(e) = FxRates::new(&self.db).set(&rates) {
+                    return Ok(bad_request(e.to_string()));
+                }
+                info!("Updated {} exchange rates", rates.len());
+                Ok(json_response(&rates.len()))
+            }
+            _ => Ok(reject_404()),
+        }
+    }
+}
+
+impl<S> Service<Request> for AdminService<S>
+where
+    S: Db + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let this = self.clone();
+        let fut = async move {
+            let result: Response = match this.process_rq(req).await {
+                Ok(r) => r,
+                Err(e) => into_server_error(e),
+            };
+            Ok(result)
+        };
+        Box::pin(fut)
+    }
+}
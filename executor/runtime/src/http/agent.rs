@@ -1,5 +1,7 @@
 pub use super::*;
+use crate::db::logger::{LogFilter, LogStream};
 use crate::log_shim::*;
+use crate::trace;
 use crate::{db::controller::Controller, rt::agent::Runtime};
 use borderless::events::{Message, Topic, TopicDto};
 use borderless::{
@@ -25,6 +27,9 @@ use tokio::sync::Mutex;
 pub struct ActionResp {
     pub events: Events,
     pub action: CallAction,
+    /// Return value of the action
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
 }
 
 pub trait EventHandler: Clone + Send + Sync {
@@ -131,6 +136,21 @@ where
         }
     }
 
+    /// Returns a stream of live log lines of the given sw-agent
+    ///
+    /// Returns `None`, if the agent does not exist.
+    pub async fn log_stream(
+        &self,
+        aid: AgentId,
+        filter: LogFilter,
+    ) -> crate::Result<Option<LogStream>> {
+        if !Controller::new(&self.db).agent_exists(&aid)? {
+            return Ok(None);
+        }
+        let tail = self.rt.lock().await.log_tail();
+        Ok(Some(tail.subscribe(aid, filter)))
+    }
+
     async fn process_rq(&self, req: Request) -> crate::Result<Response> {
         let start = Instant::now();
         let path = req.uri().path().to_string();
@@ -209,11 +229,20 @@ where
                 }
             }
             "logs" => {
-                // Extract pagination
+                // Extract pagination and filter
                 let pagination = Pagination::from_query(query).unwrap_or_default();
+                let filter = match LogFilter::from_query(query) {
+                    Ok(f) => f,
+                    Err(e) => return Ok(bad_request(e.to_string())),
+                };
 
                 // Get logs
-                let log = controller.logs(agent_id).get_logs_paginated(pagination)?;
+                let logger = controller.logs(agent_id);
+                let log = if filter.is_empty() {
+                    logger.get_logs_paginated(pagination)?
+                } else {
+                    logger.get_logs_filtered(&filter, pagination)?
+                };
 
                 Ok(json_response(&log))
             }
@@ -313,13 +342,16 @@ where
                 if !check_json_content(&parts) {
                     return Ok(unsupported_media_type());
                 }
-                let (events, action) = {
+                let trace_parent = parts.extensions.get::<trace::TraceParent>().copied();
+                let (events, action, output) = {
                     let mut rt = self.rt.lock().await;
                     rt.set_executor(self.writer)?; // For agents the executor and the writer are actually the same
-                    match rt
+                    rt.set_trace_parent(trace_parent);
+                    let result = rt
                         .http_post_action(&agent_id, trunc, payload.into(), &self.writer)
-                        .await?
-                    {
+                        .await;
+                    rt.set_trace_parent(None);
+                    match result? {
                         Ok(out) => out,
                         Err((status, err)) => {
                             return Ok(err_response(status.try_into().unwrap(), err))
@@ -330,7 +362,11 @@ where
                 match self.event_handler.handle_events(events.clone()).await {
                     Ok(_) => {
                         // Build action response
-                        let resp = ActionResp { events, action };
+                        let resp = ActionResp {
+                            events,
+                            action,
+                            output,
+                        };
                         Ok(json_response(&resp))
                     }
                     Err(e) => Ok(into_server_error(e)),
@@ -399,8 +435,11 @@ where
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, req: Request) -> Self::Future {
+    fn call(&mut self, mut req: Request) -> Self::Future {
         let this = self.clone();
+        let _trace_parent = trace::continue_trace(&mut req);
+        #[cfg(feature = "tracing")]
+        let span = trace::request_span("agent", &req, _trace_parent.as_ref());
         let fut = async move {
             let result: Response = match this.process_rq(req).await {
                 Ok(r) => r,
@@ -408,6 +447,8 @@ where
             };
             Ok(result)
         };
+        #[cfg(feature = "tracing")]
+        let fut = tracing::Instrument::instrument(fut, span);
         Box::pin(fut)
     }
 }
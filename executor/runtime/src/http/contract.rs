@@ -16,6 +16,9 @@ use std::{
 };
 
 pub use super::*;
+use crate::db::action_log::ActionQuery;
+use crate::db::logger::{LogFilter, LogStream};
+use crate::db::state_tree::StateRoot;
 use crate::log_shim::*;
 use crate::{db::controller::Controller, rt::contract::Runtime};
 
@@ -55,6 +58,9 @@ pub struct ActionResp {
     pub error: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tx_hash: Option<Hash256>,
+    /// Return value of the action in the dry-run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
 }
 
 /// Simple service around the runtime
@@ -100,6 +106,21 @@ where
         }
     }
 
+    /// Returns a stream of live log lines of the given contract
+    ///
+    /// Returns `None`, if the contract does not exist.
+    pub fn log_stream(
+        &self,
+        cid: ContractId,
+        filter: LogFilter,
+    ) -> crate::Result<Option<LogStream>> {
+        if !Controller::new(&self.db).contract_exists(&cid)? {
+            return Ok(None);
+        }
+        let tail = self.rt.lock().log_tail();
+        Ok(Some(tail.subscribe(cid, filter)))
+    }
+
     async fn process_rq(&self, req: Request) -> crate::Result<Response> {
         let start = Instant::now();
         let path = req.uri().path().to_string();
@@ -161,41 +182,103 @@ where
         if trunc.is_empty() {
             trunc.push('/');
         }
+        let path_len = trunc.len();
         if let Some(query) = query {
             trunc.push('?');
             trunc.push_str(query);
         }
         match route {
             "state" => {
+                // The 'at' parameter selects the version of the state and is not forwarded to the contract
+                trunc.truncate(path_len);
+                let mut at = None;
+                let query: Vec<&str> = query
+                    .unwrap_or_default()
+                    .split('&')
+                    .filter(|piece| match piece.strip_prefix("at=") {
+                        Some(value) => {
+                            at = Some(value);
+                            false
+                        }
+                        None => !piece.is_empty(),
+                    })
+                    .collect();
+                if !query.is_empty() {
+                    trunc.push('?');
+                    trunc.push_str(&query.join("&"));
+                }
+                let version = match at.filter(|v| !v.is_empty()) {
+                    Some(at) => match controller.history(contract_id).resolve(at) {
+                        Ok(version) => Some(version),
+                        Err(e) => return Ok(bad_request(e.to_string())),
+                    },
+                    None => None,
+                };
+
                 // TODO: The contract should also parse query parameters !
                 // TODO: URL-Decode !
                 let mut rt = self.rt.lock();
-                let (status, payload) = rt.http_get_state(&contract_id, trunc)?;
+                let (status, payload) = rt.http_get_state_at(&contract_id, trunc, version)?;
                 if status == 200 {
                     Ok(json_body(payload))
                 } else {
                     Ok(reject_404())
                 }
             }
+            "root" => {
+                let tree = controller.state_tree(contract_id);
+                let pieces: Vec<&str> = trunc[..path_len].split('/').skip(1).collect();
+                match pieces.as_slice() {
+                    [""] => {
+                        let version = controller.actions(contract_id).len()?;
+                        match tree.root()? {
+                            Some(root) => Ok(json_response(&StateRoot { version, root })),
+                            None => Ok(reject_404()),
+                        }
+                    }
+                    ["proof", base_key, sub_key] => {
+                        let (base_key, sub_key) = match (base_key.parse(), sub_key.parse()) {
+                            (Ok(base_key), Ok(sub_key)) => (base_key, sub_key),
+                            _ => return Ok(bad_request("invalid storage key".to_string())),
+                        };
+                        match tree.proof(base_key, sub_key)? {
+                            Some(proof) => Ok(json_response(&proof)),
+                            None => Ok(reject_404()),
+                        }
+                    }
+                    _ => Ok(reject_404()),
+                }
+            }
             "logs" => {
-                // Extract pagination
+                // Extract pagination and filter
                 let pagination = Pagination::from_query(query).unwrap_or_default();
+                let filter = match LogFilter::from_query(query) {
+                    Ok(f) => f,
+                    Err(e) => return Ok(bad_request(e.to_string())),
+                };
 
                 // Get logs
-                let log = controller
-                    .logs(contract_id)
-                    .get_logs_paginated(pagination)?;
+                let logger = controller.logs(contract_id);
+                let log = if filter.is_empty() {
+                    logger.get_logs_paginated(pagination)?
+                } else {
+                    logger.get_logs_filtered(&filter, pagination)?
+                };
 
                 Ok(json_response(&log))
             }
             "txs" => {
-                // Extract pagination
+                // Extract pagination and filter
                 let pagination = Pagination::from_query(query).unwrap_or_default();
+                let action_query = match ActionQuery::from_query(query) {
+                    Ok(q) => q,
+                    Err(e) => return Ok(bad_request(e.to_string())),
+                };
 
                 // Get actions
                 let paginated = controller
                     .actions(contract_id)
-                    .get_tx_action_paginated(pagination)?;
+                    .query(&action_query, pagination)?;
 
                 Ok(json_response(&paginated))
             }
@@ -297,24 +380,25 @@ where
                     return Ok(unsupported_media_type());
                 }
 
-                let action = {
+                let (action, output) = {
                     let mut rt = self.rt.lock();
                     rt.set_executor(self.writer)?; // NOTE: In this case writer and executor are identical
                     match rt.http_post_action(&contract_id, trunc, payload.into(), &self.writer)? {
                         Ok(action) => {
                             // Perform dry-run of action ( and return action resp in case of error )
-                            if let Err(e) = rt.perform_dry_run(&contract_id, &action, &self.writer)
-                            {
-                                let resp = ActionResp {
-                                    success: false,
-                                    action,
-                                    error: Some(e.to_string()),
-                                    tx_hash: None,
-                                };
-                                return Ok(json_response(&resp));
+                            match rt.perform_dry_run(&contract_id, &action, &self.writer) {
+                                Ok(output) => (action, output),
+                                Err(e) => {
+                                    let resp = ActionResp {
+                                        success: false,
+                                        action,
+                                        error: Some(e.to_string()),
+                                        tx_hash: None,
+                                        output: None,
+                                    };
+                                    return Ok(json_response(&resp));
+                                }
                             }
-
-                            action
                         }
                         Err((status, err)) => {
                             return Ok(err_response(status.try_into().unwrap(), err))
@@ -333,6 +417,7 @@ where
                     error: None,
                     action,
                     tx_hash: Some(tx_hash),
+                    output,
                 };
                 Ok(json_response(&resp))
             }
@@ -355,8 +440,11 @@ where
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, req: Request) -> Self::Future {
+    fn call(&mut self, mut req: Request) -> Self::Future {
         let this = self.clone();
+        let _trace_parent = crate::trace::continue_trace(&mut req);
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::request_span("contract", &req, _trace_parent.as_ref());
         let fut = async move {
             let result: Response = match this.process_rq(req).await {
                 Ok(r) => r,
@@ -364,6 +452,8 @@ where
             };
             Ok(result)
         };
+        #[cfg(feature = "tracing")]
+        let fut = tracing::Instrument::instrument(fut, span);
         Box::pin(fut)
     }
 }
@@ -1,9 +1,14 @@
 pub use super::*;
 use crate::db::controller::Controller;
+use crate::db::fx::{parse_currency, FxDate, FxRates};
+use crate::db::ledger::{Checkpoint, EntryFilter, ExportFormat, LedgerMetaDto, NodeKey};
+use crate::db::netting::Netting;
 use crate::log_shim::*;
-use borderless::http::queries::Pagination;
+use borderless::contracts::ledger::Currency;
+use borderless::http::{queries::Pagination, PaginatedElements};
 use borderless::{BorderlessId, ContractId};
 use borderless_kv_store::{backend::lmdb::Lmdb, Db};
+use futures_util::{stream, Stream};
 use http::method::Method;
 use serde::Deserialize;
 use std::convert::Infallible;
@@ -11,9 +16,26 @@ use std::future::Future;
 use std::{
     pin::Pin,
     task::{Context, Poll},
-    time::Instant,
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
+/// Stream of the encoded chunks of a ledger export
+pub type ExportStream = Pin<Box<dyn Stream<Item = crate::Result<Bytes>> + Send>>;
+
+/// Parses the format and the entry filter of an export from the query
+///
+/// The format defaults to csv.
+pub fn parse_export_query(query: Option<&str>) -> crate::Result<(ExportFormat, EntryFilter)> {
+    let format = query
+        .into_iter()
+        .flat_map(|q| form_urlencoded::parse(q.as_bytes()))
+        .find_map(|(key, value)| (key == "format").then_some(value))
+        .unwrap_or("csv".into())
+        .parse()?;
+    let filter = EntryFilter::from_query(query)?;
+    Ok((format, filter))
+}
+
 /// Simple service around the runtime
 #[derive(Clone)]
 pub struct LedgerService<S = Lmdb>
@@ -21,6 +43,8 @@ where
     S: Db + 'static,
 {
     db: S,
+    /// Key, that signs off the checkpoints of verified hash chains
+    node_key: Option<NodeKey>,
 }
 
 impl<S> LedgerService<S>
@@ -28,7 +52,34 @@ where
     S: Db + 'static,
 {
     pub fn new(db: S) -> Self {
-        Self { db }
+        Self { db, node_key: None }
+    }
+
+    /// Creates a new service, that signs off the checkpoints of verified hash chains with the node key
+    pub fn with_node_key(db: S, node_key: NodeKey) -> Self {
+        Self {
+            db,
+            node_key: Some(node_key),
+        }
+    }
+
+    /// Returns the export of a ledger as a stream of chunks
+    ///
+    /// The export is not part of the regular service routes, as the [`Response`] type has no streaming body.
+    /// Web-frameworks that embed this service should expose it as `GET /{ledger-id}/export?format=csv|jsonl`.
+    pub fn export_stream(
+        &self,
+        ledger_id: u64,
+        filter: EntryFilter,
+        format: ExportFormat,
+    ) -> crate::Result<ExportStream> {
+        let pages = Controller::new(&self.db)
+            .ledger()
+            .select(ledger_id)
+            .export_pages(filter, format)?;
+        Ok(Box::pin(stream::iter(
+            pages.map(|page| page.map(Bytes::from)),
+        )))
     }
 
     async fn process_rq(&self, req: Request) -> crate::Result<Response> {
@@ -65,34 +116,141 @@ where
 
         let controller = Controller::new(&self.db);
         let pagination = Pagination::from_query(query).unwrap_or_default();
+        let base = match parse_base_query(query) {
+            Ok(b) => b,
+            Err(e) => return Ok(bad_request(e)),
+        };
         match segs.as_slice() {
-            // GET /
+            // GET /?base={currency}&at={date}
             [] => {
                 let res = controller.ledger().all_paginated(pagination)?;
-                Ok(json_response(&res))
+                Ok(json_response(&self.with_base_totals(res, base)?))
             }
             // GET /ids
             ["ids"] => {
                 let ids = controller.ledger().all_ids_paginated(pagination)?;
                 Ok(json_response(&ids))
             }
+            // GET /participant/{borderless-id}?base={currency}&at={date}
+            ["participant", id_str] => {
+                let participant = match id_str.parse::<BorderlessId>() {
+                    Ok(id) => id,
+                    Err(e) => return Ok(bad_request(e.to_string())),
+                };
+                let ledgers = controller
+                    .ledger()
+                    .participant_ledgers_paginated(participant, pagination)?;
+                Ok(json_response(&self.with_base_totals(ledgers, base)?))
+            }
+            // GET /contract/{contract-id}
+            ["contract", cid_str] => {
+                let cid = match cid_str.parse::<ContractId>() {
+                    Ok(cid) => cid,
+                    Err(e) => return Ok(bad_request(e.to_string())),
+                };
+                let entries = controller
+                    .ledger()
+                    .contract_entries_paginated(cid, pagination)?;
+                Ok(json_response(&entries))
+            }
+            // GET /netting?participants={id},{id},...&currency={currency}
+            ["netting"] => {
+                let (participants, currency) = match parse_netting_query(query) {
+                    Ok(q) => q,
+                    Err(e) => return Ok(bad_request(e)),
+                };
+                let netting = Netting::new(&self.db);
+                match currency {
+                    Some(currency) => Ok(json_response(
+                        &netting.propose_currency(&participants, currency)?,
+                    )),
+                    None => Ok(json_response(&netting.propose(&participants)?)),
+                }
+            }
+            // GET /fx-rates
+            ["fx-rates"] => Ok(json_response(&FxRates::new(&self.db).all()?)),
+            // GET /{ledger-id}?base={currency}&at={date}
             [id_str] => {
                 let ledger_id = match id_str.parse::<u64>() {
                     Ok(id) => id,
                     Err(e) => return Ok(bad_request(e.to_string())),
                 };
                 let ledger = controller.ledger().select(ledger_id);
-                Ok(json_response(&ledger.meta()?.map(|m| m.into_dto())))
+                let meta = ledger.meta()?.map(|m| m.into_dto());
+                Ok(json_response(&self.with_base_total(meta, base)?))
             }
             [id_str, "entries"] => {
                 let ledger_id = match id_str.parse::<u64>() {
                     Ok(id) => id,
                     Err(e) => return Ok(bad_request(e.to_string())),
                 };
+                let filter = match EntryFilter::from_query(query) {
+                    Ok(f) => f,
+                    Err(e) => return Ok(bad_request(e.to_string())),
+                };
                 let ledger = controller.ledger().select(ledger_id);
-                let entries = ledger.get_entries_paginated(pagination)?;
+                let entries = ledger.get_entries_filtered(&filter, pagination)?;
                 Ok(json_response(&entries))
             }
+            // GET /{ledger-id}/open-items?as_of={timestamp}
+            [id_str, "open-items"] => {
+                let ledger_id = match id_str.parse::<u64>() {
+                    Ok(id) => id,
+                    Err(e) => return Ok(bad_request(e.to_string())),
+                };
+                let as_of = match parse_as_of(query) {
+                    Ok(ts) => ts,
+                    Err(e) => return Ok(bad_request(e)),
+                };
+                let ledger = controller.ledger().select(ledger_id);
+                Ok(json_response(&ledger.open_items(as_of)?))
+            }
+            // GET /{ledger-id}/aging?as_of={timestamp}
+            [id_str, "aging"] => {
+                let ledger_id = match id_str.parse::<u64>() {
+                    Ok(id) => id,
+                    Err(e) => return Ok(bad_request(e.to_string())),
+                };
+                let as_of = match parse_as_of(query) {
+                    Ok(ts) => ts,
+                    Err(e) => return Ok(bad_request(e)),
+                };
+                let ledger = controller.ledger().select(ledger_id);
+                Ok(json_response(&ledger.aging_report(as_of)?))
+            }
+            // GET /{ledger-id}/verify?interval={n}&checkpoint={line}:{hash}
+            [id_str, "verify"] => {
+                let ledger_id = match id_str.parse::<u64>() {
+                    Ok(id) => id,
+                    Err(e) => return Ok(bad_request(e.to_string())),
+                };
+                let mut interval = 1000;
+                let mut trusted = Vec::new();
+                for (key, value) in query
+                    .into_iter()
+                    .flat_map(|q| form_urlencoded::parse(q.as_bytes()))
+                {
+                    match key.as_ref() {
+                        "interval" => match value.parse() {
+                            Ok(i) => interval = i,
+                            Err(_) => {
+                                return Ok(bad_request(format!("invalid interval '{value}'")))
+                            }
+                        },
+                        "checkpoint" => match value.parse::<Checkpoint>() {
+                            Ok(c) => trusted.push(c),
+                            Err(e) => return Ok(bad_request(e.to_string())),
+                        },
+                        _ => (),
+                    }
+                }
+                let ledger = controller.ledger().select(ledger_id);
+                let mut report = ledger.verify_chain(interval, &trusted)?;
+                if let Some(key) = &self.node_key {
+                    report.sign_off(key);
+                }
+                Ok(json_response(&report))
+            }
             _ => Ok(reject_404()),
         }
     }
@@ -110,6 +268,10 @@ where
         // Parse pagination
         let query = parts.uri.query();
         let pagination = Pagination::from_query(query).unwrap_or_default();
+        let base = match parse_base_query(query) {
+            Ok(b) => b,
+            Err(e) => return Ok(bad_request(e)),
+        };
 
         let payload = match serde_json::from_slice::<LedgerQuery>(&payload) {
             Ok(p) => p,
@@ -131,10 +293,13 @@ where
         // NOTE: We haven't split the path, so the trailing '/' might be important depending on the
         // web-framework that embeds this service !
         match path {
-            "/" | "" => match payload.contract_id {
-                Some(cid) => Ok(json_response(&ledger.meta_for_contract(cid)?)),
-                None => Ok(json_response(&ledger.meta()?.map(|m| m.into_dto()))),
-            },
+            "/" | "" => {
+                let meta = match payload.contract_id {
+                    Some(cid) => ledger.meta_for_contract(cid)?,
+                    None => ledger.meta()?.map(|m| m.into_dto()),
+                };
+                Ok(json_response(&self.with_base_total(meta, base)?))
+            }
             "/entries" | "entries" => match payload.contract_id {
                 Some(cid) => Ok(json_response(
                     &ledger.get_contract_paginated(cid, pagination)?,
@@ -144,6 +309,110 @@ where
             _ => Ok(reject_404()),
         }
     }
+
+    /// Adds the total in the base currency to the ledger meta (if a base currency was requested)
+    fn with_base_total(
+        &self,
+        meta: Option<LedgerMetaDto>,
+        base: Option<(Currency, FxDate)>,
+    ) -> crate::Result<Option<LedgerMetaDto>> {
+        match (meta, base) {
+            (Some(meta), Some((currency, date))) => Ok(Some(meta.with_base_total(
+                &FxRates::new(&self.db),
+                currency,
+                date,
+            )?)),
+            (meta, _) => Ok(meta),
+        }
+    }
+
+    /// Adds the total in the base currency to all ledgers (if a base currency was requested)
+    fn with_base_totals(
+        &self,
+        mut ledgers: PaginatedElements<LedgerMetaDto>,
+        base: Option<(Currency, FxDate)>,
+    ) -> crate::Result<PaginatedElements<LedgerMetaDto>> {
+        if let Some((currency, date)) = base {
+            let rates = FxRates::new(&self.db);
+            ledgers.elements = ledgers
+                .elements
+                .into_iter()
+                .map(|meta| meta.with_base_total(&rates, currency, date))
+                .collect::<crate::Result<_>>()?;
+        }
+        Ok(ledgers)
+    }
+}
+
+/// Parses the (optional) base currency and the date of the exchange rates from the query
+///
+/// The date defaults to the current day.
+fn parse_base_query(query: Option<&str>) -> Result<Option<(Currency, FxDate)>, String> {
+    let mut base = None;
+    let mut date = None;
+    for (key, value) in query
+        .into_iter()
+        .flat_map(|q| form_urlencoded::parse(q.as_bytes()))
+    {
+        match key.as_ref() {
+            "base" => base = Some(parse_currency(&value).map_err(|e| e.to_string())?),
+            "at" => date = Some(value.parse::<FxDate>().map_err(|e| e.to_string())?),
+            _ => (),
+        }
+    }
+    let date = date.unwrap_or_else(|| FxDate::from_millis(now_millis()));
+    Ok(base.map(|b| (b, date)))
+}
+
+/// Parses the group of participants and the (optional) currency of a netting request
+fn parse_netting_query(
+    query: Option<&str>,
+) -> Result<(Vec<BorderlessId>, Option<Currency>), String> {
+    let mut participants = Vec::new();
+    let mut currency = None;
+    for (key, value) in query
+        .into_iter()
+        .flat_map(|q| form_urlencoded::parse(q.as_bytes()))
+    {
+        match key.as_ref() {
+            "participants" => {
+                for id in value.split(',').filter(|s| !s.is_empty()) {
+                    participants.push(
+                        id.parse()
+                            .map_err(|_| format!("invalid participant '{id}'"))?,
+                    );
+                }
+            }
+            "currency" => currency = Some(parse_currency(&value).map_err(|e| e.to_string())?),
+            _ => (),
+        }
+    }
+    if participants.len() < 2 {
+        return Err("netting requires at least two participants".to_string());
+    }
+    Ok((participants, currency))
+}
+
+/// Parses the reference timestamp of a report from the query (defaults to the current time)
+fn parse_as_of(query: Option<&str>) -> Result<u64, String> {
+    let as_of = query
+        .into_iter()
+        .flat_map(|q| form_urlencoded::parse(q.as_bytes()))
+        .find_map(|(key, value)| (key == "as_of").then_some(value));
+    match as_of {
+        Some(ts) => ts.parse().map_err(|_| format!("invalid as_of '{ts}'")),
+        None => Ok(now_millis()),
+    }
+}
+
+/// Current timestamp (milliseconds since unix-epoch)
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("timestamp < 1970")
+        .as_millis()
+        .try_into()
+        .expect("u64 should fit for 584942417 years")
 }
 
 /// Selects a ledger either by its id or creditor / debitor tuple
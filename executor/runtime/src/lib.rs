@@ -1,5 +1,6 @@
 pub mod db;
 pub mod error;
+pub mod trace;
 
 #[cfg(feature = "http")]
 pub mod http;
@@ -34,12 +35,30 @@ pub const WASM_CODE_SUB_DB: &str = "wasm-code-db";
 /// Sub-Database to store the relationship between an action and a transaction
 pub const ACTION_TX_REL_SUB_DB: &str = "rel-tx-action-db";
 
+/// Sub-Database, where the secondary indices of the action-log are stored
+pub const ACTION_INDEX_SUB_DB: &str = "action-index-db";
+
+/// Sub-Database, where the history of the contract state is stored
+pub const STATE_HISTORY_SUB_DB: &str = "state-history-db";
+
+/// Sub-Database, where the merkle tree over the contract state is stored
+pub const STATE_TREE_SUB_DB: &str = "state-tree-db";
+
 /// Sub-Database to store the relationship between contracts and agents, and vice-versa
 pub const SUBSCRIPTION_REL_SUB_DB: &str = "rel-subscription-db";
 
 /// Sub-Database, where the ledger information is stored
 pub const LEDGER_SUB_DB: &str = "ledger-db";
 
+/// Sub-Database, where the secondary indices of the ledger are stored
+pub const LEDGER_INDEX_SUB_DB: &str = "ledger-index-db";
+
+/// Sub-Database, where the exchange rates for base-currency reporting are stored
+pub const FX_RATES_SUB_DB: &str = "fx-rates-db";
+
+/// Sub-Database, where the schema versions of all other sub-databases are stored
+pub const SCHEMA_SUB_DB: &str = "schema-db";
+
 // TODO: Tracing vs Logging !
 // We should make this toggleable via feature switch.
 
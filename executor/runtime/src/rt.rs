@@ -4,6 +4,9 @@ pub mod contract;
 #[cfg(feature = "agents")]
 pub mod agent;
 
+#[cfg(feature = "contracts")]
+pub mod replay;
+
 #[cfg(any(feature = "contracts", feature = "agents"))]
 mod vm;
 
@@ -111,7 +114,7 @@ pub mod factory {
 #[cfg(feature = "code-store")]
 pub mod code_store {
     use super::vm::VmState;
-    use borderless::{aid_prefix, cid_prefix, AgentId, ContractId};
+    use borderless::{AgentId, ContractId};
     use borderless_kv_store::{Db, RawRead, RawWrite, Tx};
     use lru::LruCache;
     use parking_lot::Mutex;
@@ -119,6 +122,7 @@ pub mod code_store {
     use std::{num::NonZeroUsize, sync::Arc};
     use wasmtime::{Engine, Instance, Linker, Module, Store};
 
+    use crate::db::logger::LogTail;
     use crate::{log_shim::*, AGENT_SUB_DB, CONTRACT_SUB_DB};
     use crate::{Result, WASM_CODE_SUB_DB};
 
@@ -130,6 +134,7 @@ pub mod code_store {
     pub struct CodeStore<S: Db> {
         db: S,
         cache: Arc<Mutex<LruCache<Id, Module, ahash::RandomState>>>,
+        log_tail: LogTail,
     }
 
     impl<S: Db> CodeStore<S> {
@@ -143,18 +148,26 @@ pub mod code_store {
             Ok(Self {
                 db: db.clone(),
                 cache: Arc::new(Mutex::new(cache)),
+                log_tail: LogTail::new(),
             })
         }
 
+        /// Returns the [`LogTail`], that is shared by all stores created from this code-store
+        pub fn log_tail(&self) -> &LogTail {
+            &self.log_tail
+        }
+
         pub fn create_store(&self, engine: &Engine) -> Result<Store<VmState<S>>> {
             // TODO: Select correct sub-db based on entity type
             // ( do we want to use the engine here ? )
-            let db_ptr = if engine.is_async() {
-                self.db.open_sub_db(AGENT_SUB_DB)?
+            let mut state = if engine.is_async() {
+                let db_ptr = self.db.open_sub_db(AGENT_SUB_DB)?;
+                VmState::new_async(self.db.clone(), db_ptr)
             } else {
-                self.db.open_sub_db(CONTRACT_SUB_DB)?
+                let db_ptr = self.db.open_sub_db(CONTRACT_SUB_DB)?;
+                VmState::new(self.db.clone(), db_ptr)
             };
-            let state = VmState::new(self.db.clone(), db_ptr);
+            state.set_log_tail(self.log_tail.clone());
             let store = Store::new(engine, state);
             Ok(store)
         }
@@ -250,8 +263,8 @@ pub mod code_store {
             let db_ptr = self.db.open_sub_db(WASM_CODE_SUB_DB)?;
             let txn = self.db.begin_ro_txn()?;
             let mut cursor = txn.ro_cursor(&db_ptr)?;
-            // NOTE: We have to filter out all keys without the cid prefix
-            for (key, _value) in cursor.iter().filter(|(key, _)| cid_prefix(key)) {
+            // NOTE: All contract-ids start with the nibble 0xc (see `cid_prefix`)
+            for (key, _value) in cursor.iter_range([0xc0]..[0xd0]) {
                 let cid =
                     ContractId::from_bytes(key.try_into().map_err(|_| {
                         crate::Error::msg("failed to parse contract-id from storage")
@@ -270,8 +283,8 @@ pub mod code_store {
             let db_ptr = self.db.open_sub_db(WASM_CODE_SUB_DB)?;
             let txn = self.db.begin_ro_txn()?;
             let mut cursor = txn.ro_cursor(&db_ptr)?;
-            // NOTE: We have to filter out all keys without the aid prefix
-            for (key, _value) in cursor.iter().filter(|(key, _)| aid_prefix(key)) {
+            // NOTE: All agent-ids start with the nibble 0xa (see `aid_prefix`)
+            for (key, _value) in cursor.iter_range([0xa0]..[0xb0]) {
                 let aid = AgentId::from_bytes(
                     key.try_into()
                         .map_err(|_| crate::Error::msg("failed to parse agent-id from storage"))?,
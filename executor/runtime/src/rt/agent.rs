@@ -20,7 +20,10 @@ use super::{
     vm::{self, VmState},
 };
 use crate::db::controller::Controller;
+use crate::db::logger::LogTail;
+use crate::db::schema::Migrator;
 use crate::log_shim::*;
+use crate::trace::TraceParent;
 use crate::{
     error::{ErrorKind, Result},
     AGENT_SUB_DB, SUBSCRIPTION_REL_SUB_DB,
@@ -30,6 +33,9 @@ pub mod tasks;
 
 pub type SharedRuntime<S> = Arc<Mutex<Runtime<S>>>;
 
+/// Events, executed action and return value of an action, that was posted via http
+pub type PostActionOutput = (Events, CallAction, Option<serde_json::Value>);
+
 pub struct Runtime<S = Lmdb>
 where
     S: Db,
@@ -39,6 +45,7 @@ where
     agent_store: CodeStore<S>,
     mutability_lock: MutLock,
     executor: Option<Vec<u8>>,
+    trace_parent: Option<TraceParent>,
 }
 
 impl<S: Db> Runtime<S> {
@@ -48,6 +55,9 @@ impl<S: Db> Runtime<S> {
         let _ = storage.create_sub_db(AGENT_SUB_DB)?;
         let _ = storage.create_sub_db(SUBSCRIPTION_REL_SUB_DB)?;
 
+        // Bring sub-databases from older versions up to date
+        Migrator::new(storage).upgrade()?;
+
         // Generate engine ( with async enabled )
         let mut config = Config::new();
         config.cranelift_opt_level(wasmtime::OptLevel::Speed);
@@ -63,6 +73,13 @@ impl<S: Db> Runtime<S> {
             "print",
             |caller: Caller<'_, VmState<S>>, ptr, len, level| vm::print(caller, ptr, len, level),
         )?;
+        linker.func_wrap(
+            "env",
+            "print_fields",
+            |caller: Caller<'_, VmState<S>>, ptr, len, fields_ptr, fields_len, level| {
+                vm::print_fields(caller, ptr, len, fields_ptr, fields_len, level)
+            },
+        )?;
         linker.func_wrap(
             "env",
             "read_register",
@@ -173,6 +190,7 @@ impl<S: Db> Runtime<S> {
             agent_store,
             mutability_lock: lock,
             executor: None,
+            trace_parent: None,
         })
     }
 
@@ -185,6 +203,11 @@ impl<S: Db> Runtime<S> {
         self.agent_store.get_db()
     }
 
+    /// Returns a handle to the live log output of all executions
+    pub fn log_tail(&self) -> LogTail {
+        self.agent_store.log_tail().clone()
+    }
+
     /// Check whether a sw-agent exists
     pub fn agent_exists(&self, aid: &AgentId) -> Result<bool> {
         let db = self.get_db();
@@ -250,6 +273,15 @@ impl<S: Db> Runtime<S> {
         Ok(())
     }
 
+    /// Sets the trace context for the following executions
+    ///
+    /// The trace context is forwarded as is to all outgoing http-requests of the agent,
+    /// so its `parent_id` must be the id of the span, that handles the execution (see [`TraceParent::child`]).
+    /// Callers should reset it to `None` once the traced request has been handled.
+    pub fn set_trace_parent(&mut self, trace_parent: Option<TraceParent>) {
+        self.trace_parent = trace_parent;
+    }
+
     /// Registers a new websocket client
     #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(agent_id = %aid), err))]
     pub fn register_ws(&mut self, aid: AgentId) -> Result<mpsc::Receiver<Vec<u8>>> {
@@ -270,6 +302,7 @@ impl<S: Db> Runtime<S> {
         store
             .data_mut()
             .set_register(REGISTER_EXECUTOR, self.executor.clone().unwrap_or_default());
+        store.data_mut().set_trace_parent(self.trace_parent)?;
 
         // Call the actual function on the wasm side
         let func = instance.get_typed_func::<(), ()>(&mut store, "on_init")?;
@@ -400,6 +433,7 @@ impl<S: Db> Runtime<S> {
         store
             .data_mut()
             .set_register(REGISTER_EXECUTOR, self.executor.clone().unwrap_or_default());
+        store.data_mut().set_trace_parent(self.trace_parent)?;
 
         // Inject ws-sender (if any)
         if let Some(tx) = state.ws_sender {
@@ -449,6 +483,7 @@ impl<S: Db> Runtime<S> {
         store
             .data_mut()
             .set_register(REGISTER_EXECUTOR, self.executor.clone().unwrap_or_default());
+        store.data_mut().set_trace_parent(self.trace_parent)?;
 
         // Get function
         let func = instance.get_typed_func::<(), ()>(&mut store, "http_get_state")?;
@@ -492,6 +527,7 @@ impl<S: Db> Runtime<S> {
     /// The return type is a nested result. The outer result type should convert to a server error,
     /// as it represents errors in the runtime itself.
     /// The inner error type comes from the wasm code and contains the error status and message.
+    ///
     #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(agent_id = %aid, %path, %writer), err))]
     pub async fn http_post_action(
         &mut self,
@@ -499,7 +535,7 @@ impl<S: Db> Runtime<S> {
         path: String,
         payload: Vec<u8>,
         writer: &BorderlessId, // TODO: I think the writer makes no sense here and is an artifact
-    ) -> Result<std::result::Result<(Events, CallAction), (u16, String)>> {
+    ) -> Result<std::result::Result<PostActionOutput, (u16, String)>> {
         // Check whether agent exists
         let Some((instance, mut store)) = self
             .agent_store
@@ -541,6 +577,7 @@ impl<S: Db> Runtime<S> {
         store
             .data_mut()
             .set_register(REGISTER_EXECUTOR, self.executor.clone().unwrap_or_default());
+        store.data_mut().set_trace_parent(self.trace_parent)?;
 
         // Prepare mutable execution
         store
@@ -557,6 +594,7 @@ impl<S: Db> Runtime<S> {
         let status = store.data().get_register(REGISTER_OUTPUT_HTTP_STATUS);
         let result = store.data().get_register(REGISTER_OUTPUT_HTTP_RESULT);
         let output = store.data().get_register(REGISTER_OUTPUT);
+        let value = store.data().get_register(REGISTER_OUTPUT_VALUE);
 
         // Finish the execution
         // NOTE: This will clear all the registers !
@@ -581,7 +619,10 @@ impl<S: Db> Runtime<S> {
                 None => Events::default(),
             };
             let action = CallAction::from_bytes(&result)?;
-            Ok(Ok((events, action)))
+            let value = value
+                .map(|bytes| serde_json::from_slice(&bytes))
+                .transpose()?;
+            Ok(Ok((events, action, value)))
         } else {
             let error = String::from_utf8(result).map_err(|_| ErrorKind::InvalidRegisterValue {
                 register: "http-result",
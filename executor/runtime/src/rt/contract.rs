@@ -20,12 +20,17 @@ use super::{
     vm::{self, VmState},
 };
 use crate::db::controller::Controller;
+use crate::db::logger::LogTail;
+use crate::db::schema::Migrator;
 use crate::{
     error::{ErrorKind, Result},
     CONTRACT_SUB_DB,
 };
-use crate::{log_shim::*, LEDGER_SUB_DB};
-use crate::{ACTION_TX_REL_SUB_DB, SUBSCRIPTION_REL_SUB_DB};
+use crate::{log_shim::*, FX_RATES_SUB_DB, LEDGER_INDEX_SUB_DB, LEDGER_SUB_DB};
+use crate::{
+    ACTION_INDEX_SUB_DB, ACTION_TX_REL_SUB_DB, STATE_HISTORY_SUB_DB, STATE_TREE_SUB_DB,
+    SUBSCRIPTION_REL_SUB_DB,
+};
 
 pub type SharedRuntime<S> = Arc<Mutex<Runtime<S>>>;
 
@@ -57,8 +62,16 @@ impl<S: Db> Runtime<S> {
         // We create all necessary dub-databases, in case they don't exist
         let _ = storage.create_sub_db(CONTRACT_SUB_DB)?;
         let _ = storage.create_sub_db(ACTION_TX_REL_SUB_DB)?;
+        let _ = storage.create_sub_db(ACTION_INDEX_SUB_DB)?;
+        let _ = storage.create_sub_db(STATE_HISTORY_SUB_DB)?;
+        let _ = storage.create_sub_db(STATE_TREE_SUB_DB)?;
         let _ = storage.create_sub_db(LEDGER_SUB_DB)?;
+        let _ = storage.create_sub_db(FX_RATES_SUB_DB)?;
         let _ = storage.create_sub_db(SUBSCRIPTION_REL_SUB_DB)?;
+        let _ = storage.create_sub_db(LEDGER_INDEX_SUB_DB)?;
+
+        // Bring sub-databases from older versions up to date
+        Migrator::new(storage).upgrade()?;
 
         // Generate engine ( without async support )
         let mut config = Config::new();
@@ -75,6 +88,13 @@ impl<S: Db> Runtime<S> {
             "print",
             |caller: Caller<'_, VmState<S>>, ptr, len, level| vm::print(caller, ptr, len, level),
         )?;
+        linker.func_wrap(
+            "env",
+            "print_fields",
+            |caller: Caller<'_, VmState<S>>, ptr, len, fields_ptr, fields_len, level| {
+                vm::print_fields(caller, ptr, len, fields_ptr, fields_len, level)
+            },
+        )?;
         // -- Register-API
         linker.func_wrap(
             "env",
@@ -255,8 +275,16 @@ impl<S: Db> Runtime<S> {
         tx_ctx: TxCtx,
     ) -> Result<Option<Events>> {
         let input = action.to_bytes()?;
-        let events =
-            self.process_chain_tx(*cid, input, *writer, tx_ctx, Some(Commit::Action(action)))?;
+        let (events, _value) = self.process_chain_tx(
+            *cid,
+            input,
+            *writer,
+            tx_ctx,
+            Some(Commit::Action {
+                action,
+                writer: *writer,
+            }),
+        )?;
         Ok(events)
     }
 
@@ -310,6 +338,7 @@ impl<S: Db> Runtime<S> {
     // TODO: Return Option<Events> to have None or use Events::default() ?
     /// Abstraction over all possible chain transactions
     ///
+    /// Returns the output events and the json-encoded return value of the transaction.
     /// In case of an error, the `VmState` is reset by this function.
     fn process_chain_tx(
         &mut self,
@@ -318,7 +347,7 @@ impl<S: Db> Runtime<S> {
         writer: BorderlessId,
         tx_ctx: TxCtx,
         commit: Option<Commit>,
-    ) -> Result<Option<Events>> {
+    ) -> Result<(Option<Events>, Option<Vec<u8>>)> {
         let tx_ctx_bytes = tx_ctx.to_bytes()?;
         let (instance, mut store) = self
             .contract_store
@@ -333,7 +362,7 @@ impl<S: Db> Runtime<S> {
         let _guard = mtx.lock();
 
         let contract_method = match &commit {
-            Some(Commit::Action(_)) => "process_transaction",
+            Some(Commit::Action { .. }) => "process_transaction",
             Some(Commit::Introduction(_)) => "process_introduction",
             Some(Commit::Revocation(_)) => "process_revocation",
             Some(Commit::Other) => panic!("Commit::Other is reserved for actions"),
@@ -375,23 +404,27 @@ impl<S: Db> Runtime<S> {
             }
         };
         let output = store.data().get_register(REGISTER_OUTPUT);
+        let value = store.data().get_register(REGISTER_OUTPUT_VALUE);
         let _log = store.data_mut().finish_exec(commit);
 
         // Return output events
-        match output {
-            Some(bytes) => Ok(Some(Events::from_bytes(&bytes)?)),
-            None => Ok(None),
-        }
+        let events = match output {
+            Some(bytes) => Some(Events::from_bytes(&bytes)?),
+            None => None,
+        };
+        Ok((events, value))
     }
 
     /// Executes an action without commiting the state
+    ///
+    /// Returns the return value of the action (if any).
     #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(contract_id = %cid, %writer), err))]
     pub fn perform_dry_run(
         &mut self,
         cid: &ContractId,
         action: &CallAction,
         writer: &BorderlessId,
-    ) -> Result<()> {
+    ) -> Result<Option<serde_json::Value>> {
         let input = action.to_bytes()?;
 
         // TODO: Maybe do this a little bit more elaborate,
@@ -400,15 +433,32 @@ impl<S: Db> Runtime<S> {
         let tx_ctx = TxCtx::dummy();
         let block_ctx = BlockCtx::dummy();
         self.set_block(block_ctx.block_id, block_ctx.timestamp)?;
-        let _out = self.process_chain_tx(*cid, input, *writer, tx_ctx, None)?;
-        Ok(())
+        let (_events, value) = self.process_chain_tx(*cid, input, *writer, tx_ctx, None)?;
+        let value = value
+            .map(|bytes| serde_json::from_slice(&bytes))
+            .transpose()?;
+        Ok(value)
     }
 
     // --- NOTE: Maybe we should create a separate runtime for the HTTP handling ?
 
     #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(contract_id = %cid, %path), err))]
     pub fn http_get_state(&mut self, cid: &ContractId, path: String) -> Result<(u16, Vec<u8>)> {
-        let (status, result) = self.process_http_call(cid, path, None, None, "http_get_state")?;
+        self.http_get_state_at(cid, path, None)
+    }
+
+    /// Queries the contract state at the given version (see [`StateHistory`](crate::db::history::StateHistory))
+    ///
+    /// If the version is `None`, the latest state is used.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(contract_id = %cid, %path, ?version), err))]
+    pub fn http_get_state_at(
+        &mut self,
+        cid: &ContractId,
+        path: String,
+        version: Option<u64>,
+    ) -> Result<(u16, Vec<u8>)> {
+        let (status, result) =
+            self.process_http_call(cid, path, None, None, version, "http_get_state")?;
         Ok((status, result))
     }
 
@@ -432,8 +482,14 @@ impl<S: Db> Runtime<S> {
                 ErrorKind::RevokedContract { cid: *cid }.to_string(),
             )));
         }
-        let (status, result) =
-            self.process_http_call(cid, path, Some(payload), Some(writer), "http_post_action")?;
+        let (status, result) = self.process_http_call(
+            cid,
+            path,
+            Some(payload),
+            Some(writer),
+            None,
+            "http_post_action",
+        )?;
         if status == 200 {
             let action =
                 CallAction::from_bytes(&result).map_err(|_| ErrorKind::InvalidRegisterValue {
@@ -456,6 +512,7 @@ impl<S: Db> Runtime<S> {
         path: String,
         payload: Option<Vec<u8>>,
         writer: Option<&BorderlessId>,
+        version: Option<u64>,
         http_method: &'static str,
     ) -> Result<(u16, Vec<u8>)> {
         let (instance, mut store) = self
@@ -467,6 +524,7 @@ impl<S: Db> Runtime<S> {
         store
             .data_mut()
             .prepare_exec(ActiveEntity::contract_http(*cid))?;
+        store.data_mut().set_read_version(version);
 
         store
             .data_mut()
@@ -556,6 +614,11 @@ impl<S: Db> Runtime<S> {
     pub fn get_db(&self) -> S {
         self.contract_store.get_db()
     }
+
+    /// Returns a handle to the live log output of all executions
+    pub fn log_tail(&self) -> LogTail {
+        self.contract_store.log_tail().clone()
+    }
 }
 
 type Lock = Arc<Mutex<()>>;
@@ -618,6 +681,16 @@ fn check_module(engine: &Engine, module: &Module) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::ledger::InvalidReversal;
+    use borderless::__private::storage_keys::StorageKey;
+    use borderless::common::{Description, Metadata, Participant};
+    use borderless::contracts::ledger::{Currency, EntryType, LedgerEntry};
+    use borderless::hash::Hash256;
+    use borderless::pkg::{PkgType, Source, SourceType, WasmPkg};
+    use borderless::prelude::Id;
+    use borderless::TxIdentifier;
+    use borderless_kv_store::{RawRead, Tx};
+    use tempfile::tempdir;
 
     const ALL_EXPORTS: &str = r#"
 (module
@@ -680,4 +753,154 @@ mod tests {
         let err = check_module(&engine, &module.unwrap());
         assert!(err.is_ok());
     }
+
+    /// Contract, that tries to create the given ledger entries in every transaction
+    ///
+    /// The return code of every call is stored under the user key `(1, i)`.
+    fn ledger_contract(entries: &[LedgerEntry]) -> String {
+        let mut data = String::new();
+        let mut calls = String::new();
+        let mut offset = 8;
+        for (i, entry) in entries.iter().enumerate() {
+            let bytes = entry.to_bytes().unwrap();
+            let escaped: String = bytes.iter().map(|b| format!("\\{b:02x}")).collect();
+            data.push_str(&format!("  (data (i32.const {offset}) \"{escaped}\")\n"));
+            calls.push_str(&format!(
+                "    (i64.store (i32.const 0) (call $create (i64.const {offset}) (i64.const {})))\n\
+                 \x20   (call $storage_write (i64.const 0x8000000000000001) (i64.const {i}) (i64.const 0) (i64.const 8))\n",
+                bytes.len()
+            ));
+            offset += bytes.len();
+        }
+        format!(
+            r#"
+(module
+  (import "env" "storage_write" (func $storage_write (param i64 i64 i64 i64)))
+  (import "env" "create_ledger_entry" (func $create (param i64 i64) (result i64)))
+  (memory (export "memory") 1)
+{data}
+  (func $transaction
+{calls}  )
+  (func $placeholder)
+  (export "process_introduction" (func $placeholder))
+  (export "process_transaction" (func $transaction))
+  (export "process_revocation" (func $placeholder))
+  (export "http_get_state" (func $placeholder))
+  (export "http_post_action" (func $placeholder))
+  (export "parse_state" (func $placeholder))
+  (export "get_symbols" (func $placeholder))
+)
+"#
+        )
+    }
+
+    #[test]
+    fn double_reversal_is_rejected() -> Result<()> {
+        let tmp_dir = tempdir().unwrap();
+        let db = Lmdb::new(tmp_dir.path(), 16).unwrap();
+        let cid = ContractId::generate();
+        let [alice, bob] = [(); 2].map(|_| BorderlessId::generate());
+        let entry = |kind, reverses| LedgerEntry {
+            creditor: alice,
+            debitor: bob,
+            amount_milli: 1000,
+            tax_milli: 0,
+            currency: Currency::EUR,
+            kind,
+            tag: String::new(),
+            due_date: None,
+            reference: None,
+            payment_terms: None,
+            reverses,
+        };
+        // Every transaction creates a new line and tries to reverse the first line twice
+        let entries = [
+            entry(EntryType::CREATE, None),
+            entry(EntryType::REVERSAL, Some(0)),
+            entry(EntryType::REVERSAL, Some(0)),
+        ];
+        let mut rt = Runtime::new(&db, CodeStore::new(&db)?, MutLock::default())?;
+        rt.instantiate_contract(cid, ledger_contract(&entries).as_bytes())?;
+
+        let tx_ctx = |number: u64| TxCtx {
+            tx_id: TxIdentifier::new(1, number, Hash256::digest(&number.to_be_bytes())),
+            index: 0,
+        };
+        let participant = |id| Participant {
+            id,
+            alias: String::new(),
+            roles: Vec::new(),
+        };
+        let introduction = Introduction {
+            id: Id::contract(cid),
+            participants: vec![participant(alice), participant(bob)],
+            initial_state: serde_json::json!({}),
+            sinks: Vec::new(),
+            subscriptions: Vec::new(),
+            desc: Description {
+                display_name: "ledger".to_string(),
+                summary: "creates ledger entries".to_string(),
+                legal: None,
+            },
+            meta: Metadata::default(),
+            package: WasmPkg {
+                name: "ledger".to_string(),
+                app_name: None,
+                app_module: None,
+                capabilities: None,
+                pkg_type: PkgType::Contract,
+                meta: Default::default(),
+                source: Source {
+                    version: Default::default(),
+                    digest: Hash256::empty(),
+                    code: SourceType::Wasm {
+                        wasm: Vec::new(),
+                        git_info: None,
+                    },
+                },
+            },
+        };
+        rt.set_block(BlockIdentifier::new(1, 0, Hash256::empty()), 0)?;
+        rt.process_introduction(introduction, &alice, tx_ctx(0))?;
+
+        let codes = || -> Result<Vec<u64>> {
+            let db_ptr = db.open_sub_db(CONTRACT_SUB_DB)?;
+            let txn = db.begin_ro_txn()?;
+            let codes = (0..3)
+                .map(|i| {
+                    let value = txn.read(&db_ptr, &StorageKey::user_key(cid, 1, i))?;
+                    let bytes = value.and_then(|v| v.try_into().ok()).unwrap_or_default();
+                    Ok(u64::from_le_bytes(bytes))
+                })
+                .collect();
+            txn.commit()?;
+            codes
+        };
+        let process = |rt: &mut Runtime<Lmdb>, n: u64| -> Result<Vec<u64>> {
+            let action = CallAction::by_method("ledger", serde_json::json!({}));
+            rt.set_block(BlockIdentifier::new(1, n, Hash256::empty()), n)?;
+            rt.process_transaction(&cid, action, &alice, tx_ctx(n))?;
+            codes()
+        };
+        let (unknown, reversed) = (
+            InvalidReversal::UnknownLine.code(),
+            InvalidReversal::AlreadyReversed.code(),
+        );
+        // The first line only exists after the first transaction has been commited
+        assert_eq!(process(&mut rt, 1)?, [0, unknown, unknown]);
+        // The second reversal is checked against the pending entries of the transaction
+        assert_eq!(process(&mut rt, 2)?, [0, 0, reversed]);
+        // ...and afterwards against the commited ledger
+        assert_eq!(process(&mut rt, 3)?, [0, reversed, reversed]);
+
+        let entries = Controller::new(&db)
+            .ledger()
+            .open(alice, bob)
+            .get_entries_paginated(Default::default())?
+            .elements;
+        let reversals = entries.iter().filter(|e| e.reverses.is_some()).count();
+        assert_eq!(reversals, 1);
+        assert_eq!(entries[0].reversed_by, Some(2));
+        Ok(())
+    }
 }
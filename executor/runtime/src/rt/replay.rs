@@ -0,0 +1,495 @@
+//! Deterministic replay of a contract's history
+//!
+//! The [`verify_replay`] function re-applies the introduction and every logged action of a contract
+//! against its stored wasm module in a scratch database, and compares the resulting user-space storage with the live state.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use borderless::__private::storage_keys::StorageKey;
+use borderless::events::CallAction;
+use borderless::hash::Hash256;
+use borderless::{BlockIdentifier, BorderlessId, ContractId, TxIdentifier};
+use borderless_kv_store::*;
+
+use super::code_store::CodeStore;
+use super::contract::{MutLock, Runtime};
+use crate::db::action_log::ActionLog;
+use crate::db::controller::Controller;
+use crate::db::history::StateHistory;
+#[allow(unused_imports)]
+use crate::log_shim::*;
+use crate::{Error, Result, CONTRACT_SUB_DB, WASM_CODE_SUB_DB};
+
+/// Result of a replay verification
+#[derive(Debug)]
+pub struct ReplayReport {
+    /// Contract, that has been verified
+    pub contract_id: ContractId,
+    /// Number of actions, that have been replayed
+    pub replayed: u64,
+    /// Number of intermediate states, that could be compared with the state history
+    pub compared: u64,
+    /// First divergence between the replayed and the live state (if any)
+    pub divergence: Option<Divergence>,
+}
+
+impl ReplayReport {
+    /// Returns `true` if the replayed state matches the live state
+    pub fn is_ok(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// Describes the first point, where the replayed state diverged from the live state
+#[derive(Debug)]
+pub struct Divergence {
+    /// Index of the divergent action (`None` refers to the introduction)
+    pub action_idx: Option<u64>,
+    /// Transaction, that contained the divergent action
+    pub tx_id: Option<TxIdentifier>,
+    /// Reason for the divergence
+    pub reason: DivergenceReason,
+}
+
+/// Reason for a [`Divergence`]
+#[derive(Debug)]
+pub enum DivergenceReason {
+    /// The action could not be applied during the replay
+    Failed,
+    /// The value of a storage key differs (`None` means, that the key does not exist)
+    Value {
+        key: Vec<u8>,
+        expected: Option<Vec<u8>>,
+        actual: Option<Vec<u8>>,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.action_idx {
+            Some(idx) => write!(f, "action {idx}")?,
+            None => write!(f, "introduction")?,
+        }
+        if let Some(tx_id) = &self.tx_id {
+            write!(f, " (tx {tx_id})")?;
+        }
+        match &self.reason {
+            DivergenceReason::Failed => write!(f, " failed during replay"),
+            DivergenceReason::Value {
+                key,
+                expected,
+                actual,
+            } => {
+                let key = StorageKey::try_from(key.as_slice()).expect("Slice length error");
+                write!(
+                    f,
+                    " diverged at base-key={}, sub-key={}: expected {}, got {}",
+                    key.base_key(),
+                    key.sub_key(),
+                    fmt_value(expected),
+                    fmt_value(actual)
+                )
+            }
+        }
+    }
+}
+
+fn fmt_value(value: &Option<Vec<u8>>) -> String {
+    match value {
+        Some(v) => format!("{} bytes ({})", v.len(), Hash256::digest(v)),
+        None => "no value".to_string(),
+    }
+}
+
+/// Replays the history of a contract and verifies the resulting state
+///
+/// The replay is executed in the given scratch database, which must not contain the contract.
+/// The state after the introduction and each action is compared with the state history of the contract,
+/// as far as it reaches back. The final state is always compared byte-for-byte with the live user-space storage.
+///
+/// Note: The original block context is not recorded. The replay uses the block number of the transaction
+/// together with the commit timestamp of the action, so contracts that depend on the exact block hash may diverge.
+pub fn verify_replay<S: Db, T: Db>(live: &S, scratch: &T, cid: ContractId) -> Result<ReplayReport> {
+    let controller = Controller::new(live);
+    let introduction = controller
+        .contract_introduction(&cid)?
+        .ok_or_else(|| Error::msg(format!("introduction of contract {cid} is not available")))?;
+
+    // Copy the stored wasm module into the scratch database
+    let module = {
+        let db_ptr = live.open_sub_db(WASM_CODE_SUB_DB)?;
+        let txn = live.begin_ro_txn()?;
+        let module = txn.read(&db_ptr, &cid)?.map(|m| m.to_vec());
+        txn.commit()?;
+        module.ok_or_else(|| Error::msg(format!("wasm module of contract {cid} is missing")))?
+    };
+    let code_store = CodeStore::new(scratch)?;
+    {
+        let db_ptr = scratch.open_sub_db(WASM_CODE_SUB_DB)?;
+        let mut txn = scratch.begin_rw_txn()?;
+        txn.write(&db_ptr, &cid, &module)?;
+        txn.commit()?;
+    }
+    let mut rt = Runtime::new(scratch, code_store, MutLock::default())?;
+
+    let history = StateHistory::new(live, cid);
+    let earliest = history.earliest_version()?;
+    let actions = ActionLog::new(live, cid);
+    let replayed_history = StateHistory::new(scratch, cid);
+    let replayed_log = ActionLog::new(scratch, cid);
+
+    let mut last_action = None;
+    let mut report = ReplayReport {
+        contract_id: cid,
+        replayed: 0,
+        compared: 0,
+        divergence: None,
+    };
+
+    // The writer of the introduction is not recorded
+    let writer = BorderlessId::from_bytes([0; 16]);
+    let tx_ctx = introduction.meta.tx_ctx_introduction.clone();
+    let intro_tx_ctx = tx_ctx
+        .clone()
+        .unwrap_or_else(borderless::contracts::TxCtx::dummy);
+    set_block(&mut rt, &intro_tx_ctx.tx_id, introduction.meta.active_since)?;
+    let introduced = rt.process_introduction(introduction, &writer, intro_tx_ctx);
+    if introduced.is_err() || !Controller::new(scratch).contract_exists(&cid)? {
+        report.divergence = Some(Divergence {
+            action_idx: None,
+            tx_id: tx_ctx.map(|t| t.tx_id),
+            reason: DivergenceReason::Failed,
+        });
+        return Ok(report);
+    }
+    if earliest == 0 {
+        let expected = history.state_at(0)?;
+        report.compared += 1;
+        if let Some(reason) = compare(&expected, &replayed_history.state_at(0)?) {
+            report.divergence = Some(Divergence {
+                action_idx: None,
+                tx_id: tx_ctx.map(|t| t.tx_id),
+                reason,
+            });
+            return Ok(report);
+        }
+    }
+
+    for (idx, record) in actions.iter().enumerate() {
+        let record = record?;
+        let idx = idx as u64;
+        let version = idx + 1;
+        let action = CallAction::from_bytes(&record.value)?;
+        // The writer is not known for actions, that were commited by older versions
+        let writer = record.writer.unwrap_or(writer);
+        let tx_id = record.tx_ctx.tx_id.clone();
+
+        last_action = Some((idx, tx_id.clone()));
+        set_block(&mut rt, &tx_id, record.commited)?;
+        let result = rt.process_transaction(&cid, action, &writer, record.tx_ctx);
+        report.replayed += 1;
+        if result.is_err() || replayed_log.len()? != version {
+            report.divergence = Some(Divergence {
+                action_idx: Some(idx),
+                tx_id: Some(tx_id),
+                reason: DivergenceReason::Failed,
+            });
+            return Ok(report);
+        }
+
+        // Compare intermediate states, as long as they are covered by the history
+        if version >= earliest {
+            let expected = history.state_at(version)?;
+            report.compared += 1;
+            if let Some(reason) = compare(&expected, &replayed_history.state_at(version)?) {
+                report.divergence = Some(Divergence {
+                    action_idx: Some(idx),
+                    tx_id: Some(tx_id),
+                    reason,
+                });
+                return Ok(report);
+            }
+        }
+    }
+
+    // The history only mirrors the live state, so the final state is compared with the live storage itself
+    if let Some(reason) = compare(&live_state(live, cid)?, &live_state(scratch, cid)?) {
+        let (action_idx, tx_id) = match last_action {
+            Some((idx, tx_id)) => (Some(idx), Some(tx_id)),
+            None => (None, tx_ctx.map(|t| t.tx_id)),
+        };
+        report.divergence = Some(Divergence {
+            action_idx,
+            tx_id,
+            reason,
+        });
+        return Ok(report);
+    }
+    debug!(
+        "replayed {} actions of contract {cid}, compared {} states",
+        report.replayed, report.compared
+    );
+    Ok(report)
+}
+
+/// Sets the block context for the replayed transaction
+fn set_block<T: Db>(rt: &mut Runtime<T>, tx_id: &TxIdentifier, timestamp: u64) -> Result<()> {
+    let block_id = BlockIdentifier::new(tx_id.chain_id, tx_id.number, Hash256::empty());
+    rt.set_block(block_id, timestamp)
+}
+
+/// Reads the current user-space storage of the contract
+fn live_state<S: Db>(db: &S, cid: ContractId) -> Result<BTreeMap<Vec<u8>, Vec<u8>>> {
+    let db_ptr = db.open_sub_db(CONTRACT_SUB_DB)?;
+    let txn = db.begin_ro_txn()?;
+    let mut cursor = txn.ro_cursor(&db_ptr)?;
+    let state = cursor
+        .iter_prefix(&cid.into_bytes())
+        .filter(|(k, _)| {
+            StorageKey::try_from(*k)
+                .expect("Slice length error")
+                .is_user_key()
+        })
+        .map(|(k, v)| (k.to_vec(), v.to_vec()))
+        .collect();
+    drop(cursor);
+    txn.commit()?;
+    Ok(state)
+}
+
+/// Compares two states and returns the first divergent key
+fn compare(
+    expected: &BTreeMap<Vec<u8>, Vec<u8>>,
+    actual: &BTreeMap<Vec<u8>, Vec<u8>>,
+) -> Option<DivergenceReason> {
+    let keys = expected.keys().chain(actual.keys());
+    let first = keys.filter(|k| expected.get(*k) != actual.get(*k)).min()?;
+    Some(DivergenceReason::Value {
+        key: first.clone(),
+        expected: expected.get(first).cloned(),
+        actual: actual.get(first).cloned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::STATE_HISTORY_SUB_DB;
+    use borderless::common::{Description, Introduction, Metadata};
+    use borderless::contracts::TxCtx;
+    use borderless::pkg::{PkgType, Source, SourceType, WasmPkg};
+    use borderless::prelude::Id;
+    use borderless_kv_store::backend::lmdb::Lmdb;
+    use tempfile::{tempdir, TempDir};
+
+    /// Minimal contract, that stores its input (initial state or action) under a fixed key
+    const CONTRACT: &str = r#"
+(module
+  (import "env" "storage_write" (func $storage_write (param i64 i64 i64 i64)))
+  (import "env" "register_len" (func $register_len (param i64) (result i64)))
+  (import "env" "read_register" (func $read_register (param i64 i64)))
+  (memory (export "memory") 1)
+  (func $store_input (param $sub_key i64)
+    (call $read_register (i64.const 0) (i64.const 0))
+    (call $storage_write (i64.const 0x8000000000000001) (local.get $sub_key) (i64.const 0) (call $register_len (i64.const 0))))
+  (func $introduction (call $store_input (i64.const 0)))
+  (func $transaction (call $store_input (i64.const 1)))
+  (func $placeholder)
+  (export "process_introduction" (func $introduction))
+  (export "process_transaction" (func $transaction))
+  (export "process_revocation" (func $placeholder))
+  (export "http_get_state" (func $placeholder))
+  (export "http_post_action" (func $placeholder))
+  (export "parse_state" (func $placeholder))
+  (export "get_symbols" (func $placeholder))
+)
+"#;
+
+    fn open_tmp_lmdb() -> (Lmdb, TempDir) {
+        let tmp_dir = tempdir().unwrap();
+        let env = Lmdb::new(tmp_dir.path(), 16).unwrap();
+        (env, tmp_dir)
+    }
+
+    fn tx_ctx(number: u64) -> TxCtx {
+        TxCtx {
+            tx_id: TxIdentifier::new(1, number, Hash256::digest(&number.to_be_bytes())),
+            index: 0,
+        }
+    }
+
+    fn introduction(cid: ContractId) -> Introduction {
+        Introduction {
+            id: Id::contract(cid),
+            participants: Vec::new(),
+            initial_state: serde_json::json!({ "switch": false }),
+            sinks: Vec::new(),
+            subscriptions: Vec::new(),
+            desc: Description {
+                display_name: "replay".to_string(),
+                summary: "stores its input".to_string(),
+                legal: None,
+            },
+            meta: Metadata {
+                tx_ctx_introduction: Some(tx_ctx(0)),
+                ..Default::default()
+            },
+            package: WasmPkg {
+                name: "replay".to_string(),
+                app_name: None,
+                app_module: None,
+                capabilities: None,
+                pkg_type: PkgType::Contract,
+                meta: Default::default(),
+                source: Source {
+                    version: Default::default(),
+                    digest: Hash256::empty(),
+                    code: SourceType::Wasm {
+                        wasm: Vec::new(),
+                        git_info: None,
+                    },
+                },
+            },
+        }
+    }
+
+    /// Introduces the contract in the live database and applies the given number of actions
+    fn setup(actions: u64) -> (Lmdb, TempDir, ContractId) {
+        let (db, dir) = open_tmp_lmdb();
+        let cid = ContractId::generate();
+        let mut rt = Runtime::new(&db, CodeStore::new(&db).unwrap(), MutLock::default()).unwrap();
+        rt.instantiate_contract(cid, CONTRACT.as_bytes()).unwrap();
+        let writer = BorderlessId::generate();
+        set_block(&mut rt, &tx_ctx(0).tx_id, 0).unwrap();
+        rt.process_introduction(introduction(cid), &writer, tx_ctx(0))
+            .unwrap();
+        for n in 1..=actions {
+            let action = CallAction::by_method("set", serde_json::json!({ "n": n }));
+            set_block(&mut rt, &tx_ctx(n).tx_id, n).unwrap();
+            rt.process_transaction(&cid, action, &writer, tx_ctx(n))
+                .unwrap();
+        }
+        (db, dir, cid)
+    }
+
+    #[test]
+    fn matching_replay() -> Result<()> {
+        let (live, _live_dir, cid) = setup(3);
+        let db_ptr = live.open_sub_db(CONTRACT_SUB_DB)?;
+        let txn = live.begin_ro_txn()?;
+        assert!(txn
+            .read(&db_ptr, &StorageKey::user_key(cid, 1, 1))?
+            .is_some());
+        txn.commit()?;
+        let (scratch, _scratch_dir) = open_tmp_lmdb();
+        let report = verify_replay(&live, &scratch, cid)?;
+        assert!(report.is_ok(), "{:?}", report.divergence);
+        assert_eq!(report.replayed, 3);
+        // The introduction and all three actions are covered by the history
+        assert_eq!(report.compared, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn divergence_in_intermediate_action() -> Result<()> {
+        let (live, _live_dir, cid) = setup(3);
+
+        // Tamper with the recorded state after the second action
+        let key = StorageKey::user_key(cid, 1, 1);
+        let mut hist_key = key.as_bytes().to_vec();
+        hist_key.extend_from_slice(&2u64.to_be_bytes());
+        let hist_db = live.open_sub_db(STATE_HISTORY_SUB_DB)?;
+        let mut txn = live.begin_rw_txn()?;
+        txn.write(&hist_db, &hist_key, &[1, b'x'])?;
+        txn.commit()?;
+
+        let (scratch, _scratch_dir) = open_tmp_lmdb();
+        let report = verify_replay(&live, &scratch, cid)?;
+        let divergence = report.divergence.expect("replay must diverge");
+        assert_eq!(divergence.action_idx, Some(1));
+        assert_eq!(divergence.tx_id, Some(tx_ctx(2).tx_id));
+        match divergence.reason {
+            DivergenceReason::Value {
+                key: k,
+                expected,
+                actual,
+            } => {
+                assert_eq!(k, key.as_bytes().to_vec());
+                assert_eq!(expected, Some(b"x".to_vec()));
+                assert!(actual.is_some_and(|v| v != b"x"));
+            }
+            DivergenceReason::Failed => panic!("expected a value divergence"),
+        }
+        assert_eq!(report.replayed, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn divergence_in_live_state() -> Result<()> {
+        let (live, _live_dir, cid) = setup(3);
+
+        // Tamper with the live storage, while the history stays intact
+        let key = StorageKey::user_key(cid, 1, 1);
+        let db_ptr = live.open_sub_db(CONTRACT_SUB_DB)?;
+        let mut txn = live.begin_rw_txn()?;
+        txn.write(&db_ptr, &key, b"tampered")?;
+        txn.commit()?;
+
+        let (scratch, _scratch_dir) = open_tmp_lmdb();
+        let report = verify_replay(&live, &scratch, cid)?;
+        let divergence = report.divergence.expect("replay must diverge");
+        assert_eq!(divergence.action_idx, Some(2));
+        assert_eq!(divergence.tx_id, Some(tx_ctx(3).tx_id));
+        match divergence.reason {
+            DivergenceReason::Value {
+                key: k,
+                expected,
+                actual,
+            } => {
+                assert_eq!(k, key.as_bytes().to_vec());
+                assert_eq!(expected, Some(b"tampered".to_vec()));
+                assert!(actual.is_some_and(|v| v != b"tampered"));
+            }
+            DivergenceReason::Failed => panic!("expected a value divergence"),
+        }
+        assert_eq!(report.replayed, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn failing_introduction() -> Result<()> {
+        let (live, _live_dir, cid) = setup(1);
+
+        // Replace the stored module with one, whose introduction traps
+        let failing = CONTRACT.replace(
+            "(func $introduction (call $store_input (i64.const 0)))",
+            "(func $introduction unreachable)",
+        );
+        let mut rt = Runtime::new(&live, CodeStore::new(&live)?, MutLock::default())?;
+        rt.instantiate_contract(cid, failing.as_bytes())?;
+
+        let (scratch, _scratch_dir) = open_tmp_lmdb();
+        let report = verify_replay(&live, &scratch, cid)?;
+        let divergence = report.divergence.expect("replay must diverge");
+        assert_eq!(divergence.action_idx, None);
+        assert_eq!(divergence.tx_id, Some(tx_ctx(0).tx_id));
+        assert!(matches!(divergence.reason, DivergenceReason::Failed));
+        assert_eq!(report.replayed, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn replay_with_pruned_history() -> Result<()> {
+        let (live, _live_dir, cid) = setup(4);
+        StateHistory::new(&live, cid).set_retention(Some(2))?;
+
+        let (scratch, _scratch_dir) = open_tmp_lmdb();
+        let report = verify_replay(&live, &scratch, cid)?;
+        assert!(report.is_ok(), "{:?}", report.divergence);
+        assert_eq!(report.replayed, 4);
+        // Only the versions 2 to 4 are still covered by the history
+        assert_eq!(report.compared, 3);
+        Ok(())
+    }
+}
@@ -9,12 +9,12 @@ use borderless::contracts::BlockCtx;
 use borderless::prelude::ledger::LedgerEntry;
 use borderless::Context;
 use borderless::{
-    __private::storage_keys::StorageKey,
+    __private::storage_keys::{StorageKey, BASE_KEY_ACTION_LOG},
     common::{Introduction, Revocation},
     contracts::TxCtx,
     events::CallAction,
     log::LogLine,
-    AgentId, ContractId,
+    AgentId, BorderlessId, ContractId,
 };
 use borderless_kv_store::*;
 use nohash::IntMap;
@@ -26,14 +26,18 @@ use std::{
 use wasmtime::{Caller, Extern, Memory};
 
 use crate::db::controller::Controller;
-use crate::db::ledger::Ledger;
+use crate::db::ledger::{InvalidReversal, Ledger};
+#[cfg(feature = "agents")]
+use crate::trace::TraceParent;
 use crate::{
-    db::action_log::{ActionLog, ActionRecord},
-    db::controller::{write_introduction, write_revocation},
-    db::logger::Logger,
+    db::action_log::{ActionLog, ActionRecord, SUB_KEY_LOG_LEN},
+    db::controller::{read_system_value, write_introduction, write_revocation},
+    db::history::{HistoryOp, StateHistory},
+    db::logger::{LogTail, Logger},
+    db::state_tree::StateTree,
     error::ErrorKind,
     log_shim::*,
-    Error, Result,
+    Error, Result, LEDGER_SUB_DB,
 };
 use borderless::events::Topic;
 #[cfg(feature = "agents")]
@@ -66,6 +70,12 @@ pub struct VmState<S: Db> {
     /// Currently active contract or sw-agent
     active: ActiveEntity,
 
+    /// Receives the log output after each commit
+    log_tail: Option<LogTail>,
+
+    /// Version of the contract state, that is read by the current execution (`None` reads the latest state)
+    read_version: Option<u64>,
+
     _async: Option<AsyncState>,
 }
 
@@ -78,6 +88,8 @@ impl<S: Db> VmState<S> {
             last_timer: None,
             log_buffer: Vec::new(),
             active: ActiveEntity::None,
+            log_tail: None,
+            read_version: None,
             _async: None,
         }
     }
@@ -90,10 +102,25 @@ impl<S: Db> VmState<S> {
             last_timer: None,
             log_buffer: Vec::new(),
             active: ActiveEntity::None,
+            log_tail: None,
+            read_version: None,
             _async: Some(AsyncState::default()),
         }
     }
 
+    /// Sets the [`LogTail`], that receives the log output of all committed executions
+    pub fn set_log_tail(&mut self, log_tail: LogTail) {
+        self.log_tail = Some(log_tail);
+    }
+
+    /// Sets the version of the contract state, that is read by the next execution
+    ///
+    /// This only affects reads of immutable contract executions and is reset by `finish_exec`.
+    /// See [`StateHistory`] for details about the versions.
+    pub fn set_read_version(&mut self, version: Option<u64>) {
+        self.read_version = version;
+    }
+
     /// Marks the beginning of a new execution
     ///
     /// Sets the active entity and removes output artifacts from previous executions.
@@ -112,6 +139,7 @@ impl<S: Db> VmState<S> {
 
         // Check output registers in debug mode
         debug_assert!(self.registers.remove(&REGISTER_OUTPUT).is_none());
+        debug_assert!(self.registers.remove(&REGISTER_OUTPUT_VALUE).is_none());
         debug_assert!(self
             .registers
             .remove(&REGISTER_OUTPUT_HTTP_RESULT)
@@ -139,6 +167,7 @@ impl<S: Db> VmState<S> {
     ///
     /// Note: This function resets all output registers and the log-buffer,
     /// so be sure to copy the content from the output buffers *before* you call it.
+    /// The return value of a committed action is stored alongside the action in the [`ActionLog`].
     ///
     /// # Errors
     ///
@@ -148,10 +177,15 @@ impl<S: Db> VmState<S> {
         // Take and reset log-output and active-entity
         let log_output = std::mem::take(&mut self.log_buffer);
         let active = std::mem::replace(&mut self.active, ActiveEntity::None);
+        self.read_version = None;
         self.clear_cursor_registers()?;
 
         // Clear output registers, just in case
         self.registers.remove(&REGISTER_OUTPUT);
+        let output_value = self
+            .registers
+            .remove(&REGISTER_OUTPUT_VALUE)
+            .map(RefCell::into_inner);
         self.registers.remove(&REGISTER_OUTPUT_HTTP_RESULT);
         self.registers.remove(&REGISTER_OUTPUT_HTTP_STATUS);
 
@@ -181,7 +215,40 @@ impl<S: Db> VmState<S> {
         logger.flush_lines(&log_output, &self.db_ptr, &mut txn)?;
 
         // If db_txns is none (immutable execution), we won't iterate here
-        for op in db_txns.unwrap_or_default() {
+        let db_txns = db_txns.unwrap_or_default();
+
+        // Record the changes of the contract state, before they are applied
+        let mut state_root = None;
+        if let Some(cid) = id.as_cid() {
+            let ops: Vec<_> = db_txns
+                .iter()
+                .filter(|op| op.is_userspace())
+                .map(|op| match op {
+                    StorageOp::Write { key, value } => HistoryOp::Write(key, value),
+                    StorageOp::Remove { key } => HistoryOp::Remove(key),
+                })
+                .collect();
+            // The introduction starts the history, even if it does not write any state
+            if !ops.is_empty() || matches!(commit, Commit::Introduction(_)) {
+                let len_actions: u64 = read_system_value::<S, _, _>(
+                    &self.db_ptr,
+                    &txn,
+                    &cid,
+                    BASE_KEY_ACTION_LOG,
+                    SUB_KEY_LOG_LEN,
+                )?
+                .unwrap_or_default();
+                let version = match commit {
+                    Commit::Action { .. } => len_actions + 1,
+                    _ => len_actions,
+                };
+                StateHistory::new(&self.db, cid).record(&self.db_ptr, &mut txn, version, &ops)?;
+            }
+            let root = StateTree::new(&self.db, cid).update(&self.db_ptr, &mut txn, &ops)?;
+            state_root = Some(root);
+        }
+
+        for op in db_txns {
             // Check, that all keys are user-keys - ignore system-keys.
             if !op.is_userspace() {
                 warn!("Tried to write or remove a value with a storage-key that is not in user-space, id={id}");
@@ -193,6 +260,14 @@ impl<S: Db> VmState<S> {
             }
         }
 
+        // Current timestamp ( milliseconds since epoch )
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("timestamp < 1970")
+            .as_millis()
+            .try_into()
+            .expect("u64 should fit for 584942417 years");
+
         // Update ledger for each ledger entry
         // NOTE: We assume that the check, if the creditor or debitor are actually participants has been done
         let ledger = Ledger::new(&self.db);
@@ -202,24 +277,25 @@ impl<S: Db> VmState<S> {
                 &entry,
                 id.as_cid().expect("ledgers only exist in contracts"),
                 tx_ctx.as_ref().expect("ledgers are only modified by txs"),
+                Some(timestamp),
             )?;
         }
 
-        // Current timestamp ( milliseconds since epoch )
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("timestamp < 1970")
-            .as_millis()
-            .try_into()
-            .expect("u64 should fit for 584942417 years");
-
         // Commit external item (introduction, action or revocation)
         match commit {
-            Commit::Action(action) => {
+            Commit::Action { action, writer } => {
                 let cid = id.as_cid().expect("actions are only commited in contracts");
                 let tx_ctx = tx_ctx.expect("actions are only commited in contracts");
                 let action_log = ActionLog::new(&self.db, cid);
-                action_log.commit(&self.db_ptr, &mut txn, &action, tx_ctx)?;
+                action_log.commit(
+                    &self.db_ptr,
+                    &mut txn,
+                    &action,
+                    writer,
+                    tx_ctx,
+                    state_root,
+                    output_value.as_deref(),
+                )?;
             }
             Commit::Introduction(mut introduction) => {
                 assert_eq!(introduction.id, id);
@@ -247,6 +323,11 @@ impl<S: Db> VmState<S> {
         let elapsed = now.elapsed();
         debug!("storage commit: {elapsed:?}");
 
+        // Forward the (now persisted) log output to live subscribers
+        if let Some(tail) = &self.log_tail {
+            tail.publish(id, &log_output);
+        }
+
         // Everything should be reset now
         debug_assert!(self.active.is_none());
         debug_assert!(self.log_buffer.is_empty());
@@ -261,6 +342,13 @@ impl<S: Db> VmState<S> {
         Ok(key)
     }
 
+    /// Returns the [`StateHistory`] and version, if the active contract reads a historic state
+    fn historic_state(&self) -> Option<(StateHistory<'_, S>, u64)> {
+        let version = self.read_version?;
+        let cid = self.active.is_contract()?;
+        Some((StateHistory::new(&self.db, cid), version))
+    }
+
     /// Writes the given value into the register.
     pub fn set_register(&mut self, register_id: u64, value: Vec<u8>) {
         self.registers.insert(register_id, value.into());
@@ -299,6 +387,14 @@ impl<S: Db> VmState<S> {
         state.ws_sender = Some(ch);
         Ok(())
     }
+
+    /// Sets the trace context, that is forwarded to outgoing http-requests
+    #[cfg(feature = "agents")]
+    pub fn set_trace_parent(&mut self, trace_parent: Option<TraceParent>) -> Result<()> {
+        let state = self._async.as_mut().ok_or_else(|| ErrorKind::NoAsync)?;
+        state.trace_parent = trace_parent;
+        Ok(())
+    }
 }
 
 /// Parts of `VmState` that are only relevant for async execution
@@ -306,6 +402,10 @@ impl<S: Db> VmState<S> {
 struct AsyncState {
     #[cfg(feature = "agents")]
     ws_sender: Option<mpsc::Sender<Vec<u8>>>,
+
+    /// Trace context of the request span, that triggered the current execution
+    #[cfg(feature = "agents")]
+    trace_parent: Option<TraceParent>,
 }
 
 /// Helper function to get the linear memory of the wasm module
@@ -386,26 +486,93 @@ pub fn print(
     // Get timestamp as early as possible
     let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
 
-    // Read string from WASM memory and print it
-    // (Implementation details omitted for brevity)
-    // let s = String::from_raw_parts(, length, capacity)
     let memory = get_memory(&mut caller)?;
+    let msg = read_log_msg(&memory, &caller, ptr, len)?;
+
+    // Buffer log line
+    let line = LogLine::new(timestamp, level, msg);
+    push_log_line(&mut caller, line);
+    Ok(())
+}
 
-    // Read memory
+/// Host function that logs a string with a log-level and structured key-value fields.
+///
+/// The fields are a postcard encoded list of key-value pairs.
+///
+/// This is the host implementation of `borderless_abi::print_fields` and must be linked by the runtime.
+pub fn print_fields(
+    mut caller: Caller<'_, VmState<impl Db>>,
+    ptr: u64,
+    len: u64,
+    fields_ptr: u64,
+    fields_len: u64,
+    level: u32,
+) -> wasmtime::Result<()> {
+    // Get timestamp as early as possible
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+
+    let memory = get_memory(&mut caller)?;
+    let msg = read_log_msg(&memory, &caller, ptr, len)?;
     let data = memory
-        .data(&mut caller)
-        .get(ptr as usize..(ptr + len) as usize)
+        .data(&caller)
+        .get(fields_ptr as usize..(fields_ptr + fields_len) as usize)
         .ok_or_else(|| wasmtime::Error::msg("Memory access out of bounds"))?;
 
-    // Construct message
+    // Invalid fields should not prevent the log line from being written
+    let fields: Vec<(String, String)> = postcard::from_bytes(data).unwrap_or_else(|e| {
+        warn!("failed to decode log fields: {e}");
+        Vec::new()
+    });
+
+    // Buffer log line
+    let line = LogLine::new(timestamp, level, msg).with_fields(fields);
+    push_log_line(&mut caller, line);
+    Ok(())
+}
+
+/// Reads the message of a log line from the wasm memory
+fn read_log_msg(
+    memory: &Memory,
+    caller: &Caller<'_, VmState<impl Db>>,
+    ptr: u64,
+    len: u64,
+) -> wasmtime::Result<String> {
+    let data = memory
+        .data(caller)
+        .get(ptr as usize..(ptr + len) as usize)
+        .ok_or_else(|| wasmtime::Error::msg("Memory access out of bounds"))?;
     let msg =
         String::from_utf8(data.to_vec()).unwrap_or_else(|e| format!("Invalid UTF-8 sequence: {e}"));
+    Ok(msg)
+}
+
+fn push_log_line(caller: &mut Caller<'_, VmState<impl Db>>, line: LogLine) {
+    // Attach the log line as event to the current execution span
+    #[cfg(feature = "tracing")]
+    emit_span_event(&line);
 
-    // Buffer log line
-    let line = LogLine::new(timestamp, level, msg);
     caller.data_mut().log_buffer.push(line);
+}
 
-    Ok(())
+/// Emits a guest log line as event of the currently active span
+///
+/// Since tracing requires field names to be known at compile time,
+/// the structured fields of the guest are recorded as a single `fields` value, that contains a JSON object.
+/// Each guest field is a separate key of that object, so subscribers can index and filter on them.
+#[cfg(feature = "tracing")]
+fn emit_span_event(line: &LogLine) {
+    use borderless::log::LogLevel;
+    let msg = &line.msg;
+    let fields = (!line.fields.is_empty())
+        .then(|| serde_json::to_string(&line.fields).expect("string map is valid json"));
+    let fields = fields.as_deref();
+    match line.level {
+        LogLevel::Trace => tracing::trace!(target: "guest", fields, "{msg}"),
+        LogLevel::Debug => tracing::debug!(target: "guest", fields, "{msg}"),
+        LogLevel::Info => tracing::info!(target: "guest", fields, "{msg}"),
+        LogLevel::Warn => tracing::warn!(target: "guest", fields, "{msg}"),
+        LogLevel::Error => tracing::error!(target: "guest", fields, "{msg}"),
+    }
 }
 
 /// Host function that reads bytes from some register.
@@ -540,10 +707,16 @@ pub fn storage_read(
 
     // Check, if there is an acid txn, and if so, commit the changes to that:
     let caller_data = &mut caller.data_mut();
-    // If not, create a new transaction and instantly commit the changes
-    let txn = caller_data.db.begin_ro_txn()?;
-    let value = txn.read(&caller_data.db_ptr, &key)?.map(|v| v.to_vec());
-    txn.commit()?;
+    let value = match caller_data.historic_state() {
+        Some((history, version)) => history.read_at(&key, version)?,
+        None => {
+            // If not, create a new transaction and instantly commit the changes
+            let txn = caller_data.db.begin_ro_txn()?;
+            let value = txn.read(&caller_data.db_ptr, &key)?.map(|v| v.to_vec());
+            txn.commit()?;
+            value
+        }
+    };
     if let Some(value) = value {
         // Write to register
         caller.data_mut().set_register(register_id, value);
@@ -598,28 +771,34 @@ pub fn storage_cursor(
     let key = caller.data().get_storage_key(base_key, 1)?;
     let tgt_prefix = key.get_prefix();
 
-    // Set up DB access
-    let db = &caller.data().db;
-    let db_ptr = &caller.data().db_ptr;
-    let txn = db.begin_ro_txn()?;
-
-    // 1 - Move cursor at target key
-    // 2 - Convert DB keys into StorageKey
-    // 3 - Fetch all the keys matching the target prefix
-    // 4 - For each resulting key, extract its sub-key
-    let mut cursor = txn.ro_cursor(db_ptr)?;
-    let keys: Vec<u64> = cursor
-        .iter_from(&key)
-        .map(|(key, _)| StorageKey::try_from(key).expect("Slice length error"))
-        .take_while(|key| {
-            let key_prefix = key.get_prefix();
-            key_prefix.starts_with(&tgt_prefix)
-        })
-        .map(|key| key.sub_key())
-        .collect();
-
-    drop(cursor);
-    drop(txn);
+    let keys: Vec<u64> = match caller.data().historic_state() {
+        Some((history, version)) => history.sub_keys_at(&key, version)?,
+        None => {
+            // Set up DB access
+            let db = &caller.data().db;
+            let db_ptr = &caller.data().db_ptr;
+            let txn = db.begin_ro_txn()?;
+
+            // 1 - Move cursor at target key
+            // 2 - Convert DB keys into StorageKey
+            // 3 - Fetch all the keys matching the target prefix
+            // 4 - For each resulting key, extract its sub-key
+            let mut cursor = txn.ro_cursor(db_ptr)?;
+            let keys = cursor
+                .iter_from(&key)
+                .map(|(key, _)| StorageKey::try_from(key).expect("Slice length error"))
+                .take_while(|key| {
+                    let key_prefix = key.get_prefix();
+                    key_prefix.starts_with(&tgt_prefix)
+                })
+                .map(|key| key.sub_key())
+                .collect();
+
+            drop(cursor);
+            drop(txn);
+            keys
+        }
+    };
 
     let caller_data = &mut caller.data_mut();
 
@@ -647,6 +826,9 @@ pub fn storage_has_key(
 
     // Check, if there is an acid txn, and if so, commit the changes to that:
     let caller_data = &mut caller.data_mut();
+    if let Some((history, version)) = caller_data.historic_state() {
+        return Ok(history.read_at(&key, version)?.is_some() as u64);
+    }
     // If not, create a new transaction
     let txn = caller_data.db.begin_ro_txn()?;
     let result = txn.read(&caller_data.db_ptr, &key)?.is_some();
@@ -667,6 +849,9 @@ pub fn storage_gen_sub_key() -> wasmtime::Result<u64> {
 
 /// Host function to create a ledger entry
 ///
+/// Returns `1` to `3`, if creditor and/or debitor are no participants of the contract,
+/// and the [`InvalidReversal::code`] of an invalid reversal.
+///
 /// This is the host implementation of `borderless_abi::create_ledger_entry` and must be linked by the runtime.
 pub fn create_ledger_entry(
     mut caller: Caller<'_, VmState<impl Db>>,
@@ -697,6 +882,26 @@ pub fn create_ledger_entry(
     let creditor = participants.iter().any(|p| p.id == entry.creditor);
     let debitor = participants.iter().any(|p| p.id == entry.debitor);
     if creditor && debitor {
+        // Reversals are checked against the commited ledger and the pending entries of this execution
+        if let Some(line) = entry.reverses {
+            let ledger_id = entry.creditor.merge_compact(&entry.debitor);
+            let pending = caller.data().active.ledger_entries().iter().any(|e| {
+                e.reverses == Some(line) && e.creditor.merge_compact(&e.debitor) == ledger_id
+            });
+            if pending {
+                return Ok(InvalidReversal::AlreadyReversed.code());
+            }
+        }
+        let db = &caller.data().db;
+        let db_ptr = db.open_sub_db(LEDGER_SUB_DB)?;
+        let txn = db.begin_ro_txn()?;
+        let checked = Ledger::new(db)
+            .select(entry.creditor.merge_compact(&entry.debitor))
+            .check_reversal(&txn, &db_ptr, &entry, cid)?;
+        txn.commit()?;
+        if let Err(e) = checked {
+            return Ok(e.code());
+        }
         caller.data_mut().active.push_ledger(entry)?;
         Ok(0)
     } else if !creditor && debitor {
@@ -819,6 +1024,7 @@ pub mod async_abi {
 
     use super::*;
 
+    use crate::trace::TRACEPARENT_HEADER;
     use reqwest::{
         header::{HeaderMap, HeaderName, HeaderValue},
         Client, Method as ReqwestMethod, Request, Response,
@@ -913,7 +1119,21 @@ pub mod async_abi {
         // We can use the `register_failure` to return the error message back to the caller on the wasm side.
         let client = Client::new();
         let rq = match parse_reqwest_request_from_parts(&client, &head, body) {
-            Ok(rq) => rq,
+            Ok(mut rq) => {
+                // Forward the trace context of the current span, unless the guest has set the header itself
+                let trace_parent = caller
+                    .data()
+                    ._async
+                    .as_ref()
+                    .and_then(|state| state.trace_parent);
+                if let Some(tp) = trace_parent {
+                    if !rq.headers().contains_key(TRACEPARENT_HEADER) {
+                        let value = HeaderValue::from_str(&tp.to_string())?;
+                        rq.headers_mut().insert(TRACEPARENT_HEADER, value);
+                    }
+                }
+                rq
+            }
             Err(e) => {
                 caller
                     .data_mut()
@@ -1153,7 +1373,10 @@ pub mod async_abi {
 /// while introductions generally have their own behaviour, regardless of the package type.
 pub enum Commit {
     /// commit a contract action
-    Action(CallAction),
+    Action {
+        action: CallAction,
+        writer: BorderlessId,
+    },
     /// commit a contract or agent introduction
     Introduction(Introduction),
     /// commit a contract or agent revocation
@@ -1277,6 +1500,17 @@ impl ActiveEntity {
         }
     }
 
+    /// Returns the ledger entries, that have been created during the execution
+    fn ledger_entries(&self) -> &[LedgerEntry] {
+        match self {
+            ActiveEntity::Contract {
+                ledger_entries: Some(entries),
+                ..
+            } => entries,
+            _ => &[],
+        }
+    }
+
     /// Pushes an entry to a ledger
     ///
     /// Returns an error if the active entity is not a contract or immutable.
@@ -1326,24 +1560,21 @@ impl StorageOp {
 
 #[cfg(test)]
 mod tests {
-    use backend::lmdb::Lmdb;
-    use tempfile::{tempdir, TempDir};
+    use backend::memdb::MemDb;
 
     use super::*;
 
-    fn dummy_vm_state() -> (VmState<Lmdb>, TempDir) {
-        let tmp_dir = tempdir().expect("failed to create tmp directory for testing");
-        let db = Lmdb::new(tmp_dir.path(), 2).expect("failed to create lmdb");
+    fn dummy_vm_state() -> VmState<MemDb> {
+        let db = MemDb::new();
         let db_ptr = db
             .create_sub_db("dummy-sub-db")
             .expect("failed to create sub-db");
-        let state = VmState::new(db, db_ptr);
-        (state, tmp_dir)
+        VmState::new(db, db_ptr)
     }
 
     #[test]
     fn finish_none() {
-        let (mut state, _tmp_dir) = dummy_vm_state();
+        let mut state = dummy_vm_state();
         let res = state.finish_exec(None);
         assert!(res.is_ok());
         assert!(res.unwrap().is_empty(), "no logs should have been written");
@@ -1351,9 +1582,10 @@ mod tests {
 
     #[test]
     fn finish_resets_everything() {
-        let (mut state, _tmp_dir) = dummy_vm_state();
+        let mut state = dummy_vm_state();
         let registers = [
             REGISTER_OUTPUT,
+            REGISTER_OUTPUT_VALUE,
             REGISTER_CURSOR + 1,
             REGISTER_OUTPUT_HTTP_RESULT,
             REGISTER_OUTPUT_HTTP_STATUS,
@@ -1371,16 +1603,81 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn span_event_fields() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+
+        type Captured = Arc<Mutex<Vec<(&'static str, String)>>>;
+
+        /// Captures the fields of all events
+        struct Capture(Captured);
+
+        struct Visitor<'a>(&'a mut Vec<(&'static str, String)>);
+
+        impl Visit for Visitor<'_> {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                self.0.push((field.name(), value.to_string()));
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0.push((field.name(), format!("{value:?}")));
+            }
+        }
+
+        impl tracing::Subscriber for Capture {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _span: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _span: &Id, _values: &Record<'_>) {}
+            fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                event.record(&mut Visitor(&mut self.0.lock().unwrap()));
+            }
+            fn enter(&self, _span: &Id) {}
+            fn exit(&self, _span: &Id) {}
+        }
+
+        let captured = Captured::default();
+        let line = LogLine::new(0, 2, "payment received".to_string()).with_fields([
+            ("amount".to_string(), "12.50".to_string()),
+            ("payer".to_string(), "alice".to_string()),
+        ]);
+        tracing::subscriber::with_default(Capture(captured.clone()), || {
+            emit_span_event(&line);
+            emit_span_event(&LogLine::new(0, 2, "no fields".to_string()));
+        });
+
+        let captured = captured.lock().unwrap();
+        let fields: Vec<_> = captured.iter().filter(|(k, _)| *k == "fields").collect();
+        // Events without guest fields do not carry the field at all
+        assert_eq!(fields.len(), 1);
+        let fields: serde_json::Value = serde_json::from_str(&fields[0].1).unwrap();
+        assert_eq!(
+            fields,
+            serde_json::json!({ "amount": "12.50", "payer": "alice" })
+        );
+        assert!(captured
+            .iter()
+            .any(|(k, v)| *k == "message" && v == "payment received"));
+    }
+
     #[test]
     fn no_storage_key_on_inactive() {
-        let (state, _tmp_dir) = dummy_vm_state();
+        let state = dummy_vm_state();
         let res = state.get_storage_key(0, 0);
         assert!(res.is_err());
     }
 
     #[test]
     fn storage_key_prefix_matches_active() {
-        let (mut state, _tmp_dir) = dummy_vm_state();
+        let mut state = dummy_vm_state();
         // Check that storage-key-prefix matches contract-id
         let cid = ContractId::generate();
         state.active = ActiveEntity::contract_http(cid);
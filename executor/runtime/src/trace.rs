@@ -0,0 +1,244 @@
+//! W3C trace-context propagation
+//!
+//! The runtime does not depend on a specific opentelemetry implementation.
+//! Instead, we parse the `traceparent` header (see <https://www.w3.org/TR/trace-context/>) at the http boundary,
+//! attach its values to the execution span and forward it to outgoing http-requests of sw-agents.
+//!
+//! A tracing subscriber (e.g. `tracing-opentelemetry`) can then pick up the `trace_id` and `parent_id` fields
+//! to stitch the spans of the node, the agent and any remote api together.
+
+use rand::Rng;
+use std::{fmt::Display, str::FromStr};
+
+/// Name of the http-header that carries the trace context
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Parsed value of a W3C `traceparent` header
+///
+/// Only version `00` of the specification is supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceParent {
+    /// Id of the whole trace
+    pub trace_id: [u8; 16],
+    /// Id of the span, that issued the request
+    pub parent_id: [u8; 8],
+    /// Trace-flags (only the `sampled` flag is defined)
+    pub flags: u8,
+}
+
+impl TraceParent {
+    /// Returns `true` if the `sampled` flag is set
+    pub fn sampled(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+
+    /// Hex-encoded trace-id
+    pub fn trace_id_hex(&self) -> String {
+        to_hex(&self.trace_id)
+    }
+
+    /// Hex-encoded parent-id
+    pub fn parent_id_hex(&self) -> String {
+        to_hex(&self.parent_id)
+    }
+
+    /// Generates the trace context of a new child span with a random span-id.
+    ///
+    /// The `parent_id` of the result is the id of the new span,
+    /// so the result can be forwarded to remote apis, that are called from within that span.
+    pub fn child(&self) -> Self {
+        let mut rng = rand::rng();
+        let mut parent_id = [0u8; 8];
+        // An all-zero span-id is invalid
+        while parent_id == [0u8; 8] {
+            rng.fill(&mut parent_id);
+        }
+        Self {
+            trace_id: self.trace_id,
+            parent_id,
+            flags: self.flags,
+        }
+    }
+}
+
+impl FromStr for TraceParent {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || crate::Error::msg(format!("invalid traceparent header '{s}'"));
+        let mut parts = s.trim().split('-');
+        let (version, trace_id, parent_id, flags) =
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(v), Some(t), Some(p), Some(f)) => (v, t, p, f),
+                _ => return Err(err()),
+            };
+        // NOTE: Future versions may append more fields, but version "00" must have exactly four
+        if version != "00" || parts.next().is_some() {
+            return Err(err());
+        }
+        let mut out = TraceParent {
+            trace_id: [0; 16],
+            parent_id: [0; 8],
+            flags: 0,
+        };
+        let mut flag_buf = [0u8; 1];
+        from_hex(trace_id, &mut out.trace_id).ok_or_else(err)?;
+        from_hex(parent_id, &mut out.parent_id).ok_or_else(err)?;
+        from_hex(flags, &mut flag_buf).ok_or_else(err)?;
+        out.flags = flag_buf[0];
+        // All-zero ids are invalid according to the specification
+        if out.trace_id == [0; 16] || out.parent_id == [0; 8] {
+            return Err(err());
+        }
+        Ok(out)
+    }
+}
+
+impl Display for TraceParent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id_hex(),
+            self.parent_id_hex(),
+            self.flags
+        )
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Decodes lowercase hex into the output buffer. The input must match the buffer length exactly.
+fn from_hex(s: &str, out: &mut [u8]) -> Option<()> {
+    if s.len() != out.len() * 2 {
+        return None;
+    }
+    for (i, chunk) in s.as_bytes().chunks(2).enumerate() {
+        let hi = hex_val(chunk[0])?;
+        let lo = hex_val(chunk[1])?;
+        out[i] = (hi << 4) | lo;
+    }
+    Some(())
+}
+
+fn hex_val(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        _ => None,
+    }
+}
+
+/// Extracts the [`TraceParent`] from the http-headers of a request
+///
+/// Invalid headers are ignored, as the specification demands that the receiver starts a new trace in that case.
+#[cfg(feature = "http")]
+pub fn from_headers(headers: &http::HeaderMap) -> Option<TraceParent> {
+    headers
+        .get(TRACEPARENT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse().ok())
+}
+
+/// Continues the trace of an incoming request in a new span
+///
+/// The id of the new span is generated once per request. The resulting trace context
+/// is stored in the request extensions, so it can be forwarded to all outgoing requests, that are issued while handling the request.
+/// Returns the trace context of the caller.
+#[cfg(feature = "http")]
+pub(crate) fn continue_trace(req: &mut http::Request<bytes::Bytes>) -> Option<TraceParent> {
+    let incoming = from_headers(req.headers())?;
+    req.extensions_mut().insert(incoming.child());
+    Some(incoming)
+}
+
+/// Creates the span, that wraps the handling of a single http-request
+///
+/// If the request carries a trace context, its ids are recorded as span fields.
+/// The `span_id` is the id of the request span itself, which is forwarded to remote apis (see [`continue_trace`]).
+#[cfg(all(feature = "http", feature = "tracing"))]
+pub(crate) fn request_span(
+    service: &'static str,
+    req: &http::Request<bytes::Bytes>,
+    trace_parent: Option<&TraceParent>,
+) -> tracing::Span {
+    let span = tracing::info_span!(
+        "request",
+        service,
+        method = %req.method(),
+        path = %req.uri().path(),
+        trace_id = tracing::field::Empty,
+        parent_id = tracing::field::Empty,
+        span_id = tracing::field::Empty,
+    );
+    if let Some(tp) = trace_parent {
+        span.record("trace_id", tp.trace_id_hex());
+        span.record("parent_id", tp.parent_id_hex());
+    }
+    if let Some(local) = req.extensions().get::<TraceParent>() {
+        span.record("span_id", local.parent_id_hex());
+    }
+    span
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn parse_roundtrip() {
+        let tp: TraceParent = EXAMPLE.parse().unwrap();
+        assert!(tp.sampled());
+        assert_eq!(tp.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(tp.parent_id_hex(), "00f067aa0ba902b7");
+        assert_eq!(tp.to_string(), EXAMPLE);
+    }
+
+    #[test]
+    fn reject_invalid() {
+        let invalid = [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-00",
+            "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+        ];
+        for s in invalid {
+            assert!(s.parse::<TraceParent>().is_err(), "{s} should be invalid");
+        }
+    }
+
+    #[test]
+    fn child_keeps_trace_id() {
+        let tp: TraceParent = EXAMPLE.parse().unwrap();
+        let child = tp.child();
+        assert_eq!(child.trace_id, tp.trace_id);
+        assert_eq!(child.flags, tp.flags);
+        assert_ne!(child.parent_id, [0; 8]);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn continue_trace_once_per_request() {
+        let mut req = http::Request::builder()
+            .header(TRACEPARENT_HEADER, EXAMPLE)
+            .body(bytes::Bytes::new())
+            .unwrap();
+        let incoming = continue_trace(&mut req).unwrap();
+        assert_eq!(incoming.to_string(), EXAMPLE);
+        let local = *req.extensions().get::<TraceParent>().unwrap();
+        assert_eq!(local.trace_id, incoming.trace_id);
+        assert_ne!(local.parent_id, incoming.parent_id);
+
+        let mut req = http::Request::new(bytes::Bytes::new());
+        assert!(continue_trace(&mut req).is_none());
+        assert!(req.extensions().get::<TraceParent>().is_none());
+    }
+}
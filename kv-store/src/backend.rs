@@ -1 +1,9 @@
+pub mod encrypted;
+pub mod hooks;
 pub mod lmdb;
+pub mod memdb;
+#[cfg(feature = "rocksdb")]
+pub mod rocksdb;
+
+#[cfg(test)]
+mod test_suite;
@@ -0,0 +1,1132 @@
+//! Transparent encryption at rest for any [`Db`] backend
+//!
+//! [`EncryptedDb`] wraps another backend and encrypts every value with AES-256-GCM-SIV before it is written.
+//! Keys are stored in plaintext by default, so their ordering - and thereby range and prefix iteration - is preserved.
+//!
+//! Sub-databases that are only accessed by point lookups can encrypt their keys as well.
+//! The key encryption is deterministic (the same key always yields the same ciphertext), which reveals
+//! whether two keys are equal, but neither their content nor their order. Iterating over such a sub-database
+//! yields all items in an arbitrary order, so range and prefix queries are meaningless there.
+//!
+//! Stored values have the format `key-id (4 bytes) | nonce (12 bytes) | ciphertext | tag (16 bytes)`,
+//! encrypted keys the format `key-id | ciphertext | tag`. The name of the sub-database (and for values also the plaintext key)
+//! is authenticated, so entries cannot be moved between keys or sub-databases without being detected.
+//!
+//! Since the transactions hand out borrowed slices, decrypted items are buffered inside the transaction until it ends.
+//! Transactions that read a lot of data therefore use more memory than with the plain backend.
+use crate::{CursorOp, Error, KvDatabase, KvHandle, RoCursor, RoTx, RwCursor, RwTx, ToCursorOp};
+use aes_gcm_siv::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256GcmSiv, Nonce,
+};
+use rand::RngCore;
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::BTreeSet,
+    fmt,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use crate::{Db, RawRead, RawWrite, Tx};
+
+/// Identifies an [`EncryptionKey`]. It is stored alongside every encrypted item.
+pub type KeyId = u32;
+
+const KEY_ID_LEN: usize = 4;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Cursor operations of the encrypted backend - they are translated into the operations of the inner backend
+const OP_FIRST: u32 = 0;
+const OP_LAST: u32 = 1;
+const OP_NEXT: u32 = 2;
+const OP_PREV: u32 = 3;
+const OP_CURRENT: u32 = 4;
+
+impl<DB: KvDatabase> ToCursorOp<EncHandle<DB>> for CursorOp {
+    fn to_op(&self) -> u32 {
+        match self {
+            CursorOp::First => OP_FIRST,
+            CursorOp::Last => OP_LAST,
+            CursorOp::Next => OP_NEXT,
+            CursorOp::Prev => OP_PREV,
+            CursorOp::Current => OP_CURRENT,
+        }
+    }
+}
+
+fn cursor_op(op: u32) -> Result<CursorOp, Error> {
+    match op {
+        OP_FIRST => Ok(CursorOp::First),
+        OP_LAST => Ok(CursorOp::Last),
+        OP_NEXT => Ok(CursorOp::Next),
+        OP_PREV => Ok(CursorOp::Prev),
+        OP_CURRENT => Ok(CursorOp::Current),
+        _ => Err(Error::InvalidArgument),
+    }
+}
+
+/// 256-bit key, that is used to encrypt the data
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    /// Creates a key from raw bytes
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        EncryptionKey(bytes)
+    }
+
+    /// Generates a new random key
+    pub fn generate() -> Self {
+        let mut bytes = [0; 32];
+        rand::rng().fill_bytes(&mut bytes);
+        EncryptionKey(bytes)
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// Provides the keys of an [`EncryptedDb`]
+pub trait KeyProvider: Send + Sync {
+    /// Returns all keys, that may be required to decrypt existing data.
+    ///
+    /// The first key is the current key, which is used to encrypt new data.
+    fn keys(&self) -> Result<Vec<(KeyId, EncryptionKey)>, Error>;
+}
+
+/// A fixed set of keys (the first one being the current key)
+impl KeyProvider for Vec<(KeyId, EncryptionKey)> {
+    fn keys(&self) -> Result<Vec<(KeyId, EncryptionKey)>, Error> {
+        Ok(self.clone())
+    }
+}
+
+/// Loaded ciphers for all keys of the provider
+struct Keyring {
+    /// The first entry is the current key
+    ciphers: Vec<(KeyId, Aes256GcmSiv)>,
+}
+
+impl Keyring {
+    fn load(provider: &dyn KeyProvider) -> Result<Self, Error> {
+        let keys = provider.keys()?;
+        if keys.is_empty() {
+            return Err(Error::Other("no encryption key available".to_string()));
+        }
+        let mut ids = BTreeSet::new();
+        let mut ciphers = Vec::with_capacity(keys.len());
+        for (id, key) in keys {
+            if !ids.insert(id) {
+                return Err(Error::Other(format!("duplicate encryption key {id}")));
+            }
+            ciphers.push((id, Aes256GcmSiv::new(&key.0.into())));
+        }
+        Ok(Keyring { ciphers })
+    }
+
+    fn current_id(&self) -> KeyId {
+        self.ciphers[0].0
+    }
+
+    fn cipher(&self, id: KeyId) -> Result<&Aes256GcmSiv, Error> {
+        self.ciphers
+            .iter()
+            .find(|(key_id, _)| *key_id == id)
+            .map(|(_, cipher)| cipher)
+            .ok_or_else(|| Error::Other(format!("unknown encryption key {id}")))
+    }
+
+    fn encrypt_value(&self, db_name: &str, key: &[u8], value: &[u8]) -> Result<Vec<u8>, Error> {
+        let (id, cipher) = &self.ciphers[0];
+        let mut nonce = [0; NONCE_LEN];
+        rand::rng().fill_bytes(&mut nonce);
+        let aad = value_aad(db_name, key);
+        let payload = Payload {
+            msg: value,
+            aad: &aad,
+        };
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| Error::Other("failed to encrypt value".to_string()))?;
+
+        let mut out = Vec::with_capacity(KEY_ID_LEN + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(&id.to_be_bytes());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    fn decrypt_value(&self, db_name: &str, key: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+        if data.len() < KEY_ID_LEN + NONCE_LEN + TAG_LEN {
+            return Err(Error::Corrupted);
+        }
+        let (id, rest) = split_key_id(data);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let aad = value_aad(db_name, key);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: &aad,
+        };
+        self.cipher(id)?
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| Error::Corrupted)
+    }
+
+    /// Encrypts a key with the key at the given index of the keyring
+    ///
+    /// The nonce is fixed, so the same key always results in the same ciphertext.
+    fn encrypt_key(&self, index: usize, db_name: &str, key: &[u8]) -> Vec<u8> {
+        let (id, cipher) = &self.ciphers[index];
+        let payload = Payload {
+            msg: key,
+            aad: db_name.as_bytes(),
+        };
+        let ciphertext = cipher
+            .encrypt(&Nonce::default(), payload)
+            .expect("keys are always shorter than the maximum message length");
+        let mut out = Vec::with_capacity(KEY_ID_LEN + ciphertext.len());
+        out.extend_from_slice(&id.to_be_bytes());
+        out.extend_from_slice(&ciphertext);
+        out
+    }
+
+    fn decrypt_key(&self, db_name: &str, data: &[u8]) -> Result<Vec<u8>, Error> {
+        if data.len() < KEY_ID_LEN + TAG_LEN {
+            return Err(Error::Corrupted);
+        }
+        let (id, ciphertext) = split_key_id(data);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: db_name.as_bytes(),
+        };
+        self.cipher(id)?
+            .decrypt(&Nonce::default(), payload)
+            .map_err(|_| Error::Corrupted)
+    }
+
+    /// Returns the key, under which new data is stored in the given sub-database
+    fn storage_key<'a>(&self, db_name: &str, encrypt_keys: bool, key: &'a [u8]) -> Cow<'a, [u8]> {
+        if encrypt_keys {
+            Cow::Owned(self.encrypt_key(0, db_name, key))
+        } else {
+            Cow::Borrowed(key)
+        }
+    }
+
+    /// Returns all keys, under which the given key may be stored (the current one first)
+    ///
+    /// With encrypted keys, entries that have not been re-encrypted yet are still stored under an older key.
+    fn storage_keys<'a, DB>(&self, handle: &EncHandle<DB>, key: &'a [u8]) -> Vec<Cow<'a, [u8]>> {
+        if handle.encrypt_keys {
+            (0..self.ciphers.len())
+                .map(|index| Cow::Owned(self.encrypt_key(index, &handle.name, key)))
+                .collect()
+        } else {
+            vec![Cow::Borrowed(key)]
+        }
+    }
+}
+
+/// Values are bound to the sub-database and the key, under which they are stored
+fn value_aad(db_name: &str, key: &[u8]) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(db_name.len() + 1 + key.len());
+    aad.extend_from_slice(db_name.as_bytes());
+    aad.push(0);
+    aad.extend_from_slice(key);
+    aad
+}
+
+/// Splits the key-id from encrypted data - the caller has to check the length
+fn split_key_id(data: &[u8]) -> (KeyId, &[u8]) {
+    let (id, rest) = data.split_at(KEY_ID_LEN);
+    (KeyId::from_be_bytes(id.try_into().unwrap()), rest)
+}
+
+/// Returns the id of the key, that was used to encrypt the data
+fn key_id(data: &[u8]) -> Option<KeyId> {
+    data.get(..KEY_ID_LEN)
+        .map(|id| KeyId::from_be_bytes(id.try_into().unwrap()))
+}
+
+/// Handle to a sub-database of an [`EncryptedDb`]
+pub struct EncHandle<DB> {
+    inner: DB,
+    name: Arc<str>,
+    encrypt_keys: bool,
+}
+
+impl<DB: KvDatabase> KvDatabase for EncHandle<DB> {}
+
+impl<DB: KvHandle<DB> + KvDatabase> KvHandle<EncHandle<DB>> for EncHandle<DB> {
+    fn db(&self) -> &EncHandle<DB> {
+        self
+    }
+}
+
+/// Encrypting adapter around another [`Db`] backend
+///
+/// See the [module documentation](self) for details.
+#[derive(Clone)]
+pub struct EncryptedDb<D> {
+    inner: D,
+    provider: Arc<dyn KeyProvider>,
+    keyring: Arc<RwLock<Arc<Keyring>>>,
+    /// Names of the sub-databases, whose keys are encrypted as well
+    encrypted_keys: Arc<BTreeSet<String>>,
+}
+
+impl<D> EncryptedDb<D>
+where
+    D: Db<Handle = <D as Db>::DB>,
+    D::DB: KvHandle<D::DB>,
+{
+    /// Wraps the given backend - all values are encrypted with the keys of the provider.
+    pub fn new(inner: D, provider: impl KeyProvider + 'static) -> Result<Self, Error> {
+        let keyring = Keyring::load(&provider)?;
+        Ok(EncryptedDb {
+            inner,
+            provider: Arc::new(provider),
+            keyring: Arc::new(RwLock::new(Arc::new(keyring))),
+            encrypted_keys: Arc::new(BTreeSet::new()),
+        })
+    }
+
+    /// Encrypts the keys of the given sub-databases as well.
+    ///
+    /// Only use this for sub-databases, that do not rely on the ordering of their keys.
+    pub fn with_encrypted_keys<S: Into<String>>(
+        mut self,
+        names: impl IntoIterator<Item = S>,
+    ) -> Self {
+        Arc::make_mut(&mut self.encrypted_keys).extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Returns the wrapped backend (which only sees the encrypted data)
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Reloads the keys from the key provider.
+    ///
+    /// Transactions that are already running keep using the previous keys.
+    pub fn reload_keys(&self) -> Result<(), Error> {
+        let keyring = Keyring::load(&*self.provider)?;
+        *self.keyring.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(keyring);
+        Ok(())
+    }
+
+    /// Re-encrypts all entries of the given sub-databases, that are not encrypted with the current key.
+    ///
+    /// The keys are reloaded from the provider first, so the first key it returns becomes the current key.
+    /// Every sub-database is re-encrypted in a transaction of its own. Since the provider still returns the previous keys,
+    /// all data stays readable during the rotation. Afterwards, the previous keys can be removed from the provider.
+    ///
+    /// Returns the number of re-encrypted entries.
+    pub fn rotate_keys(&self, sub_dbs: &[&str]) -> Result<usize, Error> {
+        self.reload_keys()?;
+        let mut rotated = 0;
+        for name in sub_dbs {
+            let handle = self.handle(self.inner.open_sub_db(name)?, name);
+            let mut txn = self.inner.begin_rw_txn()?;
+            let keys = self.keyring();
+            let current = Some(keys.current_id());
+
+            // Collect the stale entries first, as we cannot modify the sub-database while iterating over it
+            let mut stale = Vec::new();
+            {
+                let mut cursor = txn.rw_cursor(&handle.inner)?;
+                for (raw_key, raw_value) in cursor.iter_start() {
+                    let stale_key = handle.encrypt_keys && key_id(raw_key) != current;
+                    if !stale_key && key_id(raw_value) == current {
+                        continue;
+                    }
+                    let key = if handle.encrypt_keys {
+                        keys.decrypt_key(name, raw_key)?
+                    } else {
+                        raw_key.to_vec()
+                    };
+                    let value = keys.decrypt_value(name, &key, raw_value)?;
+                    stale.push((stale_key.then(|| raw_key.to_vec()), key, value));
+                }
+            }
+
+            for (old_key, key, value) in stale {
+                if let Some(old_key) = old_key {
+                    txn.delete(&handle.inner, &old_key)?;
+                }
+                let storage_key = keys.storage_key(name, handle.encrypt_keys, &key);
+                let value = keys.encrypt_value(name, &key, &value)?;
+                txn.write(&handle.inner, &storage_key, &value)?;
+                rotated += 1;
+            }
+            txn.commit()?;
+        }
+        Ok(rotated)
+    }
+
+    fn keyring(&self) -> Arc<Keyring> {
+        self.keyring
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn handle(&self, inner: D::DB, name: &str) -> EncHandle<D::DB> {
+        EncHandle {
+            inner,
+            name: Arc::from(name),
+            encrypt_keys: self.encrypted_keys.contains(name),
+        }
+    }
+}
+
+impl<D> Db for EncryptedDb<D>
+where
+    D: Db<Handle = <D as Db>::DB>,
+    D::DB: KvHandle<D::DB>,
+    CursorOp: ToCursorOp<D::DB>,
+{
+    type DB = EncHandle<D::DB>;
+    type Handle = EncHandle<D::DB>;
+    type RoTx<'env>
+        = EncRoTx<D::RoTx<'env>>
+    where
+        Self: 'env;
+    type RwTx<'env>
+        = EncRwTx<D::RwTx<'env>>
+    where
+        Self: 'env;
+
+    fn open_sub_db(&self, name: &str) -> Result<Self::Handle, Error> {
+        let inner = self.inner.open_sub_db(name)?;
+        Ok(self.handle(inner, name))
+    }
+
+    fn create_sub_db(&self, name: &str) -> Result<Self::Handle, Error> {
+        let inner = self.inner.create_sub_db(name)?;
+        Ok(self.handle(inner, name))
+    }
+
+    fn begin_ro_txn(&self) -> Result<Self::RoTx<'_>, Error> {
+        let inner = self.inner.begin_ro_txn()?;
+        Ok(EncRoTx {
+            inner,
+            keys: self.keyring(),
+            arena: Arena::default(),
+        })
+    }
+
+    fn begin_rw_txn(&self) -> Result<Self::RwTx<'_>, Error> {
+        // NOTE: The keys are taken after the transaction has begun,
+        // so a concurrent key rotation is either completely visible or not at all
+        let inner = self.inner.begin_rw_txn()?;
+        Ok(EncRwTx {
+            inner,
+            keys: self.keyring(),
+            arena: Arena::default(),
+        })
+    }
+}
+
+/// Buffer for decrypted items, that lives as long as the transaction
+#[derive(Default)]
+struct Arena(RefCell<Vec<*mut [u8]>>);
+
+impl Arena {
+    fn alloc(&self, data: Vec<u8>) -> &[u8] {
+        let ptr = Box::into_raw(data.into_boxed_slice());
+        self.0.borrow_mut().push(ptr);
+        // SAFETY: The allocation never moves and is only freed, when the arena is dropped -
+        // which requires that the returned reference is gone.
+        unsafe { &*ptr }
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        for ptr in self.0.get_mut().drain(..) {
+            // SAFETY: The pointer was created by `Box::into_raw` and is freed exactly once
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+    }
+}
+
+/// Everything a cursor needs to decrypt the items of a sub-database
+#[derive(Clone)]
+struct Ctx<'txn> {
+    arena: &'txn Arena,
+    keys: &'txn Keyring,
+    name: Arc<str>,
+    encrypt_keys: bool,
+}
+
+impl<'txn> Ctx<'txn> {
+    fn new<DB>(arena: &'txn Arena, keys: &'txn Keyring, handle: &EncHandle<DB>) -> Self {
+        Ctx {
+            arena,
+            keys,
+            name: handle.name.clone(),
+            encrypt_keys: handle.encrypt_keys,
+        }
+    }
+
+    fn storage_key<'a>(&self, key: &'a [u8]) -> Cow<'a, [u8]> {
+        self.keys.storage_key(&self.name, self.encrypt_keys, key)
+    }
+
+    fn decrypt_key(&self, raw_key: &'txn [u8]) -> Result<&'txn [u8], Error> {
+        if self.encrypt_keys {
+            Ok(self
+                .arena
+                .alloc(self.keys.decrypt_key(&self.name, raw_key)?))
+        } else {
+            Ok(raw_key)
+        }
+    }
+
+    fn decrypt_value(&self, key: &[u8], raw_value: &[u8]) -> Result<&'txn [u8], Error> {
+        let value = self.keys.decrypt_value(&self.name, key, raw_value)?;
+        Ok(self.arena.alloc(value))
+    }
+
+    fn decrypt(
+        &self,
+        raw_key: &'txn [u8],
+        raw_value: &[u8],
+    ) -> Result<(&'txn [u8], &'txn [u8]), Error> {
+        let key = self.decrypt_key(raw_key)?;
+        Ok((key, self.decrypt_value(key, raw_value)?))
+    }
+
+    /// Decrypts the result of a cursor operation - if the operation did not return the key, the requested key is used
+    fn decrypt_get(
+        &self,
+        requested: Option<&[u8]>,
+        (raw_key, raw_value): (Option<&'txn [u8]>, &'txn [u8]),
+    ) -> Result<(Option<&'txn [u8]>, &'txn [u8]), Error> {
+        match (raw_key, requested) {
+            (Some(raw_key), _) => {
+                let (key, value) = self.decrypt(raw_key, raw_value)?;
+                Ok((Some(key), value))
+            }
+            (None, Some(key)) => Ok((None, self.decrypt_value(key, raw_value)?)),
+            (None, None) => Err(Error::InvalidArgument),
+        }
+    }
+}
+
+/// Reads and decrypts a value - shared by read-only and read-write transactions
+fn read<'a, 'env, DB, T>(
+    txn: &'a T,
+    keys: &Keyring,
+    arena: &'a Arena,
+    handle: &EncHandle<DB>,
+    key: &[u8],
+) -> Result<Option<&'a [u8]>, Error>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    T: RawRead<'env, DB>,
+{
+    for storage_key in keys.storage_keys(handle, key) {
+        if let Some(raw_value) = txn.read(&handle.inner, &storage_key)? {
+            let value = keys.decrypt_value(&handle.name, key, raw_value)?;
+            return Ok(Some(arena.alloc(value)));
+        }
+    }
+    Ok(None)
+}
+
+/// Read-only transaction of an [`EncryptedDb`]
+pub struct EncRoTx<T> {
+    inner: T,
+    keys: Arc<Keyring>,
+    arena: Arena,
+}
+
+impl<T: Tx> Tx for EncRoTx<T> {
+    fn commit(self) -> Result<(), Error> {
+        self.inner.commit()
+    }
+
+    fn abort(self) {
+        self.inner.abort()
+    }
+}
+
+impl<'env, DB, T> RawRead<'env, EncHandle<DB>> for EncRoTx<T>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    T: RawRead<'env, DB>,
+{
+    fn read(
+        &self,
+        db: &impl KvHandle<EncHandle<DB>>,
+        key: &impl AsRef<[u8]>,
+    ) -> Result<Option<&[u8]>, Error> {
+        read(&self.inner, &self.keys, &self.arena, db.db(), key.as_ref())
+    }
+}
+
+impl<'env, DB, T> RoTx<'env, EncHandle<DB>> for EncRoTx<T>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    T: RoTx<'env, DB>,
+    CursorOp: ToCursorOp<DB>,
+{
+    type Cursor<'txn>
+        = EncCursor<'txn, T::Cursor<'txn>>
+    where
+        Self: 'txn;
+
+    fn ro_cursor<'txn>(
+        &'txn self,
+        db: &impl KvHandle<EncHandle<DB>>,
+    ) -> Result<Self::Cursor<'txn>, Error> {
+        let handle = db.db();
+        Ok(EncCursor {
+            inner: self.inner.ro_cursor(&handle.inner)?,
+            ctx: Ctx::new(&self.arena, &self.keys, handle),
+        })
+    }
+}
+
+/// Read-write transaction of an [`EncryptedDb`]
+pub struct EncRwTx<T> {
+    inner: T,
+    keys: Arc<Keyring>,
+    arena: Arena,
+}
+
+impl<T: Tx> Tx for EncRwTx<T> {
+    fn commit(self) -> Result<(), Error> {
+        self.inner.commit()
+    }
+
+    fn abort(self) {
+        self.inner.abort()
+    }
+}
+
+impl<'env, DB, T> RawRead<'env, EncHandle<DB>> for EncRwTx<T>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    T: RawRead<'env, DB>,
+{
+    fn read(
+        &self,
+        db: &impl KvHandle<EncHandle<DB>>,
+        key: &impl AsRef<[u8]>,
+    ) -> Result<Option<&[u8]>, Error> {
+        read(&self.inner, &self.keys, &self.arena, db.db(), key.as_ref())
+    }
+}
+
+impl<'env, DB, T> RawWrite<'env, EncHandle<DB>> for EncRwTx<T>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    T: RawWrite<'env, DB>,
+{
+    fn write(
+        &mut self,
+        db: &impl KvHandle<EncHandle<DB>>,
+        key: &impl AsRef<[u8]>,
+        data: &impl AsRef<[u8]>,
+    ) -> Result<(), Error> {
+        let handle = db.db();
+        let key = key.as_ref();
+        let mut storage_keys = self.keys.storage_keys(handle, key).into_iter();
+        let storage_key = storage_keys.next().expect("there is always a current key");
+        // Remove the entry, if it is still stored under an older key
+        for old_key in storage_keys {
+            self.inner.delete(&handle.inner, &old_key)?;
+        }
+        let value = self.keys.encrypt_value(&handle.name, key, data.as_ref())?;
+        self.inner.write(&handle.inner, &storage_key, &value)
+    }
+
+    fn delete(
+        &mut self,
+        db: &impl KvHandle<EncHandle<DB>>,
+        key: &impl AsRef<[u8]>,
+    ) -> Result<(), Error> {
+        let handle = db.db();
+        for storage_key in self.keys.storage_keys(handle, key.as_ref()) {
+            self.inner.delete(&handle.inner, &storage_key)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'env, DB, T> RwTx<'env, EncHandle<DB>> for EncRwTx<T>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    T: RwTx<'env, DB>,
+    CursorOp: ToCursorOp<DB>,
+{
+    type Cursor<'txn>
+        = EncCursor<'txn, T::Cursor<'txn>>
+    where
+        Self: 'txn;
+
+    type RwTx<'txn>
+        = EncRwTx<T::RwTx<'txn>>
+    where
+        Self: 'txn;
+
+    fn rw_cursor<'txn>(
+        &'txn mut self,
+        db: &impl KvHandle<EncHandle<DB>>,
+    ) -> Result<Self::Cursor<'txn>, Error> {
+        let handle = db.db();
+        Ok(EncCursor {
+            inner: self.inner.rw_cursor(&handle.inner)?,
+            ctx: Ctx::new(&self.arena, &self.keys, handle),
+        })
+    }
+
+    fn nested_txn(&mut self) -> Result<Self::RwTx<'_>, Error> {
+        Ok(EncRwTx {
+            inner: self.inner.nested_txn()?,
+            keys: self.keys.clone(),
+            arena: Arena::default(),
+        })
+    }
+}
+
+/// Cursor of an [`EncryptedDb`] - wraps a read-only or read-write cursor of the inner backend
+///
+/// Cursors position themselves by the key, that is encrypted with the current key.
+/// In sub-databases with encrypted keys, entries that are still encrypted with an older key
+/// are therefore only found by iteration, until they have been re-encrypted.
+/// The same applies to [`RwCursor::put`], which does not replace such entries.
+pub struct EncCursor<'txn, C> {
+    inner: C,
+    ctx: Ctx<'txn>,
+}
+
+impl<'txn, DB, C> RoCursor<'txn, EncHandle<DB>> for EncCursor<'txn, C>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    C: RoCursor<'txn, DB>,
+    CursorOp: ToCursorOp<DB>,
+{
+    type Iter = Iter<'txn, C::Iter>;
+
+    /// The value is ignored, as values are encrypted with a random nonce
+    fn get<K, V>(
+        &self,
+        key: Option<&K>,
+        _value: Option<&V>,
+        op: impl ToCursorOp<EncHandle<DB>>,
+    ) -> Result<(Option<&'txn [u8]>, &'txn [u8]), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let op = cursor_op(op.to_op())?;
+        let requested = key.map(|k| k.as_ref());
+        let storage_key = requested.map(|k| self.ctx.storage_key(k));
+        let res = self.inner.get(storage_key.as_ref(), None::<&&[u8]>, op)?;
+        self.ctx.decrypt_get(requested, res)
+    }
+
+    fn iter(&mut self) -> Self::Iter {
+        Iter::new(self.inner.iter(), self.ctx.clone())
+    }
+
+    fn iter_start(&mut self) -> Self::Iter {
+        Iter::new(self.inner.iter_start(), self.ctx.clone())
+    }
+
+    fn iter_from<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = self.ctx.storage_key(key.as_ref());
+        Iter::new(self.inner.iter_from(&key), self.ctx.clone())
+    }
+
+    fn iter_rev(&mut self) -> Self::Iter {
+        Iter::new(self.inner.iter_rev(), self.ctx.clone())
+    }
+
+    fn iter_from_rev<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = self.ctx.storage_key(key.as_ref());
+        Iter::new(self.inner.iter_from_rev(&key), self.ctx.clone())
+    }
+}
+
+impl<'txn, DB, C> RwCursor<'txn, EncHandle<DB>> for EncCursor<'txn, C>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    C: RwCursor<'txn, DB>,
+    CursorOp: ToCursorOp<DB>,
+{
+    type Iter = Iter<'txn, C::Iter>;
+
+    /// The value is ignored, as values are encrypted with a random nonce
+    fn get<K, V>(
+        &self,
+        key: Option<&K>,
+        _value: Option<&V>,
+        op: impl ToCursorOp<EncHandle<DB>>,
+    ) -> Result<(Option<&'txn [u8]>, &'txn [u8]), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let op = cursor_op(op.to_op())?;
+        let requested = key.map(|k| k.as_ref());
+        let storage_key = requested.map(|k| self.ctx.storage_key(k));
+        let res = self.inner.get(storage_key.as_ref(), None::<&&[u8]>, op)?;
+        self.ctx.decrypt_get(requested, res)
+    }
+
+    fn put<K, V>(&mut self, key: &K, value: &V) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        let storage_key = self.ctx.storage_key(key);
+        let value = self
+            .ctx
+            .keys
+            .encrypt_value(&self.ctx.name, key, value.as_ref())?;
+        self.inner.put(&storage_key, &value)
+    }
+
+    fn del(&mut self) -> Result<(), Error> {
+        self.inner.del()
+    }
+
+    fn iter(&mut self) -> Self::Iter {
+        Iter::new(self.inner.iter(), self.ctx.clone())
+    }
+
+    fn iter_start(&mut self) -> Self::Iter {
+        Iter::new(self.inner.iter_start(), self.ctx.clone())
+    }
+
+    fn iter_from<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = self.ctx.storage_key(key.as_ref());
+        Iter::new(self.inner.iter_from(&key), self.ctx.clone())
+    }
+
+    fn iter_rev(&mut self) -> Self::Iter {
+        Iter::new(self.inner.iter_rev(), self.ctx.clone())
+    }
+
+    fn iter_from_rev<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = self.ctx.storage_key(key.as_ref());
+        Iter::new(self.inner.iter_from_rev(&key), self.ctx.clone())
+    }
+}
+
+/// Iterator over the decrypted items of a cursor
+pub struct Iter<'txn, I> {
+    inner: I,
+    ctx: Ctx<'txn>,
+    done: bool,
+}
+
+impl<'txn, I> Iter<'txn, I> {
+    fn new(inner: I, ctx: Ctx<'txn>) -> Self {
+        Iter {
+            inner,
+            ctx,
+            done: false,
+        }
+    }
+}
+
+impl<'txn, I> Iterator for Iter<'txn, I>
+where
+    I: Iterator<Item = (&'txn [u8], &'txn [u8])>,
+{
+    type Item = (&'txn [u8], &'txn [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let (raw_key, raw_value) = self.inner.next()?;
+        // NOTE: Like the LMDB iterator, we stop at the first error, as the interface has no way to report it.
+        // The error itself resurfaces with the next direct read of the item.
+        match self.ctx.decrypt(raw_key, raw_value) {
+            Ok(item) => Some(item),
+            Err(_) => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{lmdb::Lmdb, memdb::MemDb, test_suite};
+    use std::sync::Mutex;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    fn key(n: u8) -> EncryptionKey {
+        EncryptionKey::from_bytes([n; 32])
+    }
+
+    fn open_encrypted() -> EncryptedDb<MemDb> {
+        EncryptedDb::new(MemDb::new(), vec![(1, key(1))]).unwrap()
+    }
+
+    fn open_encrypted_keys() -> EncryptedDb<MemDb> {
+        open_encrypted().with_encrypted_keys(["test", "first", "second"])
+    }
+
+    #[test]
+    fn read_write_delete() -> TestResult {
+        test_suite::read_write_delete(&open_encrypted())?;
+        test_suite::read_write_delete(&open_encrypted_keys())
+    }
+
+    #[test]
+    fn not_found_is_none() -> TestResult {
+        test_suite::not_found_is_none(&open_encrypted())?;
+        test_suite::not_found_is_none(&open_encrypted_keys())
+    }
+
+    #[test]
+    fn non_existing_db() {
+        test_suite::non_existing_db(&open_encrypted());
+    }
+
+    #[test]
+    fn abort_discards_changes() -> TestResult {
+        test_suite::abort_discards_changes(&open_encrypted())?;
+        test_suite::abort_discards_changes(&open_encrypted_keys())
+    }
+
+    #[test]
+    fn sub_dbs_are_isolated() -> TestResult {
+        test_suite::sub_dbs_are_isolated(&open_encrypted())?;
+        test_suite::sub_dbs_are_isolated(&open_encrypted_keys())
+    }
+
+    #[test]
+    fn cursor_iteration() -> TestResult {
+        test_suite::cursor_iteration(&open_encrypted())
+    }
+
+    #[test]
+    fn rw_cursor_sees_own_writes() -> TestResult {
+        test_suite::rw_cursor_sees_own_writes(&open_encrypted())
+    }
+
+    #[test]
+    fn ro_txn_reads_snapshot() -> TestResult {
+        test_suite::ro_txn_reads_snapshot(&open_encrypted())?;
+        test_suite::ro_txn_reads_snapshot(&open_encrypted_keys())
+    }
+
+    #[test]
+    fn cursor_ranges() -> TestResult {
+        test_suite::cursor_ranges(&open_encrypted())
+    }
+
+    #[test]
+    fn lmdb_backend() -> TestResult {
+        let temp_dir = tempfile::tempdir()?;
+        let env = EncryptedDb::new(Lmdb::new(temp_dir.path(), 4)?, vec![(1, key(1))])?;
+        test_suite::cursor_ranges(&env)?;
+        test_suite::abort_discards_changes(&env)
+    }
+
+    /// Returns all raw items of the sub-database, as they are stored in the inner backend
+    fn raw_items(db: &MemDb, name: &str) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let handle = db.open_sub_db(name).unwrap();
+        let txn = db.begin_ro_txn().unwrap();
+        let mut cursor = txn.ro_cursor(&handle).unwrap();
+        cursor
+            .iter_start()
+            .map(|(k, v)| (k.to_vec(), v.to_vec()))
+            .collect()
+    }
+
+    fn write(env: &impl Db, name: &str, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        let handle = env.create_sub_db(name)?;
+        let mut txn = env.begin_rw_txn()?;
+        txn.write(&handle, &key, &value)?;
+        txn.commit()
+    }
+
+    fn read(env: &impl Db, name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        let handle = env.open_sub_db(name)?;
+        let txn = env.begin_ro_txn()?;
+        let value = txn.read(&handle, &key)?.map(|v| v.to_vec());
+        Ok(value)
+    }
+
+    #[test]
+    fn data_is_encrypted_at_rest() -> TestResult {
+        let env = open_encrypted().with_encrypted_keys(["secret-keys"]);
+        write(&env, "plain-keys", b"key", b"some value")?;
+        write(&env, "secret-keys", b"key", b"some value")?;
+        write(&env, "secret-keys", b"other", b"some value")?;
+
+        let plain = raw_items(env.inner(), "plain-keys");
+        assert_eq!(plain.len(), 1);
+        assert_eq!(plain[0].0, b"key");
+        assert_eq!(plain[0].1.len(), KEY_ID_LEN + NONCE_LEN + 10 + TAG_LEN);
+        assert!(!plain[0].1.windows(10).any(|w| w == b"some value"));
+
+        let secret = raw_items(env.inner(), "secret-keys");
+        assert_eq!(secret.len(), 2);
+        assert!(secret
+            .iter()
+            .all(|(k, _)| k.len() > TAG_LEN && k != b"key" && k != b"other"));
+        // Same value, but different ciphertext due to the random nonce
+        assert_ne!(secret[0].1[KEY_ID_LEN..], secret[1].1[KEY_ID_LEN..]);
+
+        // Key encryption is deterministic, so an overwrite replaces the entry
+        write(&env, "secret-keys", b"key", b"new value")?;
+        assert_eq!(raw_items(env.inner(), "secret-keys").len(), 2);
+        assert_eq!(read(&env, "secret-keys", b"key")?.unwrap(), b"new value");
+
+        let handle = env.open_sub_db("secret-keys")?;
+        let txn = env.begin_ro_txn()?;
+        let mut cursor = txn.ro_cursor(&handle)?;
+        let mut keys: Vec<&[u8]> = cursor.iter_start().map(|(k, _)| k).collect();
+        keys.sort();
+        assert_eq!(keys, [&b"key"[..], b"other"]);
+        Ok(())
+    }
+
+    #[test]
+    fn tampering_is_detected() -> TestResult {
+        let env = open_encrypted();
+        write(&env, "test", b"a", b"value of a")?;
+        write(&env, "test", b"b", b"value of b")?;
+
+        // Flip a bit of the ciphertext
+        let (_, mut value) = raw_items(env.inner(), "test").remove(0);
+        *value.last_mut().unwrap() ^= 1;
+        write(env.inner(), "test", b"a", &value)?;
+        assert!(matches!(read(&env, "test", b"a"), Err(Error::Corrupted)));
+
+        // Values are bound to their key
+        let (_, value) = raw_items(env.inner(), "test").remove(1);
+        write(env.inner(), "test", b"a", &value)?;
+        assert!(matches!(read(&env, "test", b"a"), Err(Error::Corrupted)));
+
+        // ... and to their sub-database
+        write(env.inner(), "other", b"b", &value)?;
+        assert!(matches!(read(&env, "other", b"b"), Err(Error::Corrupted)));
+        assert_eq!(read(&env, "test", b"b")?.unwrap(), b"value of b");
+
+        // A different key cannot decrypt the data
+        let other = EncryptedDb::new(env.inner().clone(), vec![(1, key(2))])?;
+        assert!(matches!(read(&other, "test", b"b"), Err(Error::Corrupted)));
+        let other = EncryptedDb::new(env.inner().clone(), vec![(2, key(2))])?;
+        assert!(matches!(read(&other, "test", b"b"), Err(Error::Other(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_key_sets_are_rejected() {
+        assert!(EncryptedDb::new(MemDb::new(), Vec::new()).is_err());
+        assert!(EncryptedDb::new(MemDb::new(), vec![(1, key(1)), (1, key(2))]).is_err());
+    }
+
+    /// Key provider, whose keys can be changed at runtime
+    #[derive(Clone, Default)]
+    struct SharedKeys(Arc<Mutex<Vec<(KeyId, EncryptionKey)>>>);
+
+    impl KeyProvider for SharedKeys {
+        fn keys(&self) -> Result<Vec<(KeyId, EncryptionKey)>, Error> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    #[test]
+    fn key_rotation() -> TestResult {
+        let keys = SharedKeys::default();
+        keys.0.lock().unwrap().push((1, EncryptionKey::generate()));
+        let env = EncryptedDb::new(MemDb::new(), keys.clone())?.with_encrypted_keys(["secret"]);
+        for n in 0u8..10 {
+            write(&env, "plain", &[n], &[n; 8])?;
+            write(&env, "secret", &[n], &[n; 8])?;
+        }
+
+        // Add a new current key - the existing data stays readable
+        keys.0
+            .lock()
+            .unwrap()
+            .insert(0, (2, EncryptionKey::generate()));
+        env.reload_keys()?;
+        write(&env, "plain", &[0], b"new")?;
+        write(&env, "secret", &[1], b"new")?;
+        assert_eq!(read(&env, "secret", &[1])?.unwrap(), b"new");
+        assert_eq!(read(&env, "secret", &[2])?.unwrap(), [2; 8]);
+        assert_eq!(raw_items(env.inner(), "secret").len(), 10);
+
+        assert_eq!(env.rotate_keys(&["plain", "secret"])?, 18);
+        assert_eq!(env.rotate_keys(&["plain", "secret"])?, 0);
+        for name in ["plain", "secret"] {
+            let items = raw_items(env.inner(), name);
+            assert_eq!(items.len(), 10);
+            assert!(items.iter().all(|(_, v)| key_id(v) == Some(2)));
+        }
+        assert!(raw_items(env.inner(), "secret")
+            .iter()
+            .all(|(k, _)| key_id(k) == Some(2)));
+
+        // The old key is no longer required
+        keys.0.lock().unwrap().truncate(1);
+        env.reload_keys()?;
+        for n in 2u8..10 {
+            assert_eq!(read(&env, "plain", &[n])?.unwrap(), [n; 8]);
+            assert_eq!(read(&env, "secret", &[n])?.unwrap(), [n; 8]);
+        }
+        assert_eq!(read(&env, "plain", &[0])?.unwrap(), b"new");
+        assert_eq!(read(&env, "secret", &[1])?.unwrap(), b"new");
+        Ok(())
+    }
+
+    #[test]
+    fn nested_transactions() -> TestResult {
+        let env = open_encrypted();
+        let handle = env.create_sub_db("test")?;
+        let mut txn = env.begin_rw_txn()?;
+        txn.write(&handle, b"outer", b"1")?;
+        {
+            let mut nested = txn.nested_txn()?;
+            assert_eq!(nested.read(&handle, b"outer")?, Some(&b"1"[..]));
+            nested.write(&handle, b"inner", b"2")?;
+            nested.commit()?;
+        }
+        {
+            let mut nested = txn.nested_txn()?;
+            nested.write(&handle, b"aborted", b"3")?;
+            nested.abort();
+        }
+        txn.commit()?;
+        assert_eq!(read(&env, "test", b"inner")?.unwrap(), b"2");
+        assert!(read(&env, "test", b"aborted")?.is_none());
+        Ok(())
+    }
+}
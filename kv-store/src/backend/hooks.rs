@@ -0,0 +1,711 @@
+//! Post-commit hooks for any [`Db`] backend
+//!
+//! [`HookedDb`] wraps another backend and records all writes and deletes of a read-write transaction.
+//! Once the transaction has been committed successfully, the recorded [`ChangeSet`] is passed to every registered [`CommitHook`].
+//! Aborted transactions never reach the hooks.
+//!
+//! Nested transactions hand their changes to the parent transaction when they are committed,
+//! so the hooks always receive the changes of the outermost transaction as a whole.
+//!
+//! Changes are only recorded, if at least one hook was registered when the transaction began.
+use crate::{CursorOp, Error, KvDatabase, KvHandle, RoCursor, RoTx, RwCursor, RwTx, ToCursorOp};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use crate::{Db, RawRead, RawWrite, Tx};
+
+impl<DB: KvDatabase> ToCursorOp<HookHandle<DB>> for CursorOp
+where
+    CursorOp: ToCursorOp<DB>,
+{
+    fn to_op(&self) -> u32 {
+        <CursorOp as ToCursorOp<DB>>::to_op(self)
+    }
+}
+
+/// Cursor operation, that is passed through to the inner backend unchanged
+struct RawOp(u32);
+
+impl<DB: KvDatabase> ToCursorOp<DB> for RawOp {
+    fn to_op(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Change of a single key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The key was written with the given value
+    Put { key: Vec<u8>, value: Vec<u8> },
+    /// The key was deleted
+    Delete { key: Vec<u8> },
+}
+
+impl Change {
+    /// Returns the key, that was changed
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Change::Put { key, .. } | Change::Delete { key } => key,
+        }
+    }
+
+    /// Returns the written value (or `None`, if the key was deleted)
+    pub fn value(&self) -> Option<&[u8]> {
+        match self {
+            Change::Put { value, .. } => Some(value),
+            Change::Delete { .. } => None,
+        }
+    }
+}
+
+/// All changes of a committed transaction, grouped by sub-database
+///
+/// The changes of every sub-database are kept in the order, in which they were made.
+/// If a key was changed multiple times, only the last change of the key describes its final state.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeSet {
+    sub_dbs: BTreeMap<Arc<str>, Vec<Change>>,
+}
+
+impl ChangeSet {
+    /// Returns `true` if the transaction did not change anything
+    pub fn is_empty(&self) -> bool {
+        self.sub_dbs.is_empty()
+    }
+
+    /// Returns the total number of changes
+    pub fn len(&self) -> usize {
+        self.sub_dbs.values().map(Vec::len).sum()
+    }
+
+    /// Returns the changes of the given sub-database
+    pub fn sub_db(&self, name: &str) -> &[Change] {
+        self.sub_dbs
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Iterates over all changed sub-databases and their changes
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[Change])> {
+        self.sub_dbs
+            .iter()
+            .map(|(name, changes)| (&**name, changes.as_slice()))
+    }
+
+    fn push(&mut self, sub_db: &Arc<str>, change: Change) {
+        self.sub_dbs.entry(sub_db.clone()).or_default().push(change);
+    }
+
+    fn append(&mut self, other: ChangeSet) {
+        for (name, mut changes) in other.sub_dbs {
+            self.sub_dbs.entry(name).or_default().append(&mut changes);
+        }
+    }
+}
+
+/// Receives the changes of every committed transaction
+///
+/// Hooks are called synchronously on the committing thread, after the commit has succeeded.
+/// They should therefore hand the changes off quickly (e.g. into a channel) instead of doing heavy work.
+pub trait CommitHook: Send + Sync {
+    fn on_commit(&self, changes: &ChangeSet);
+}
+
+impl<F> CommitHook for F
+where
+    F: Fn(&ChangeSet) + Send + Sync,
+{
+    fn on_commit(&self, changes: &ChangeSet) {
+        self(changes)
+    }
+}
+
+type Hooks = Arc<RwLock<Vec<Arc<dyn CommitHook>>>>;
+
+/// Handle to a sub-database of a [`HookedDb`]
+pub struct HookHandle<DB> {
+    inner: DB,
+    name: Arc<str>,
+}
+
+impl<DB: KvDatabase> KvDatabase for HookHandle<DB> {}
+
+impl<DB: KvHandle<DB> + KvDatabase> KvHandle<HookHandle<DB>> for HookHandle<DB> {
+    fn db(&self) -> &HookHandle<DB> {
+        self
+    }
+}
+
+/// Adapter around another [`Db`] backend, that calls hooks after every commit
+///
+/// See the [module documentation](self) for details.
+#[derive(Clone)]
+pub struct HookedDb<D> {
+    inner: D,
+    hooks: Hooks,
+}
+
+impl<D> HookedDb<D>
+where
+    D: Db<Handle = <D as Db>::DB>,
+    D::DB: KvHandle<D::DB>,
+{
+    /// Wraps the given backend - initially without any hooks
+    pub fn new(inner: D) -> Self {
+        HookedDb {
+            inner,
+            hooks: Hooks::default(),
+        }
+    }
+
+    /// Registers a hook, that is called after every commit.
+    ///
+    /// The hook is shared by all clones of this database. Transactions that are already running are not affected.
+    pub fn add_hook(&self, hook: impl CommitHook + 'static) {
+        self.hooks
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::new(hook));
+    }
+
+    /// Returns the wrapped backend
+    ///
+    /// Changes made directly through the inner backend are not passed to the hooks.
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+}
+
+impl<D> Db for HookedDb<D>
+where
+    D: Db<Handle = <D as Db>::DB>,
+    D::DB: KvHandle<D::DB>,
+    CursorOp: ToCursorOp<D::DB>,
+{
+    type DB = HookHandle<D::DB>;
+    type Handle = HookHandle<D::DB>;
+    type RoTx<'env>
+        = HookRoTx<D::RoTx<'env>>
+    where
+        Self: 'env;
+    type RwTx<'env>
+        = HookRwTx<'env, D::RwTx<'env>>
+    where
+        Self: 'env;
+
+    fn open_sub_db(&self, name: &str) -> Result<Self::Handle, Error> {
+        let inner = self.inner.open_sub_db(name)?;
+        Ok(HookHandle {
+            inner,
+            name: Arc::from(name),
+        })
+    }
+
+    fn create_sub_db(&self, name: &str) -> Result<Self::Handle, Error> {
+        let inner = self.inner.create_sub_db(name)?;
+        Ok(HookHandle {
+            inner,
+            name: Arc::from(name),
+        })
+    }
+
+    fn begin_ro_txn(&self) -> Result<Self::RoTx<'_>, Error> {
+        Ok(HookRoTx {
+            inner: self.inner.begin_ro_txn()?,
+        })
+    }
+
+    fn begin_rw_txn(&self) -> Result<Self::RwTx<'_>, Error> {
+        let inner = self.inner.begin_rw_txn()?;
+        let hooks = self
+            .hooks
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let recording = !hooks.is_empty();
+        Ok(HookRwTx {
+            inner,
+            changes: ChangeSet::default(),
+            recording,
+            target: Target::Hooks(hooks),
+        })
+    }
+}
+
+/// Read-only transaction of a [`HookedDb`]
+pub struct HookRoTx<T> {
+    inner: T,
+}
+
+impl<T: Tx> Tx for HookRoTx<T> {
+    fn commit(self) -> Result<(), Error> {
+        self.inner.commit()
+    }
+
+    fn abort(self) {
+        self.inner.abort()
+    }
+}
+
+impl<'env, DB, T> RawRead<'env, HookHandle<DB>> for HookRoTx<T>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    T: RawRead<'env, DB>,
+{
+    fn read(
+        &self,
+        db: &impl KvHandle<HookHandle<DB>>,
+        key: &impl AsRef<[u8]>,
+    ) -> Result<Option<&[u8]>, Error> {
+        self.inner.read(&db.db().inner, key)
+    }
+}
+
+impl<'env, DB, T> RoTx<'env, HookHandle<DB>> for HookRoTx<T>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    T: RoTx<'env, DB>,
+    CursorOp: ToCursorOp<DB>,
+{
+    type Cursor<'txn>
+        = HookCursor<'txn, T::Cursor<'txn>>
+    where
+        Self: 'txn;
+
+    fn ro_cursor<'txn>(
+        &'txn self,
+        db: &impl KvHandle<HookHandle<DB>>,
+    ) -> Result<Self::Cursor<'txn>, Error> {
+        let handle = db.db();
+        Ok(HookCursor {
+            inner: self.inner.ro_cursor(&handle.inner)?,
+            name: handle.name.clone(),
+            changes: None,
+        })
+    }
+}
+
+/// Receiver of the changes, once a read-write transaction is committed
+enum Target<'a> {
+    /// Root transaction, which passes the changes to the hooks
+    Hooks(Vec<Arc<dyn CommitHook>>),
+    /// Nested transaction, which passes the changes to its parent
+    Parent(&'a mut ChangeSet),
+}
+
+/// Read-write transaction of a [`HookedDb`]
+pub struct HookRwTx<'a, T> {
+    inner: T,
+    changes: ChangeSet,
+    recording: bool,
+    target: Target<'a>,
+}
+
+impl<T> HookRwTx<'_, T> {
+    fn record(&mut self, handle: &Arc<str>, change: impl FnOnce() -> Change) {
+        if self.recording {
+            self.changes.push(handle, change());
+        }
+    }
+}
+
+impl<T: Tx> Tx for HookRwTx<'_, T> {
+    fn commit(self) -> Result<(), Error> {
+        self.inner.commit()?;
+        match self.target {
+            Target::Hooks(hooks) if !self.changes.is_empty() => {
+                for hook in hooks {
+                    hook.on_commit(&self.changes);
+                }
+            }
+            Target::Hooks(_) => (),
+            Target::Parent(parent) => parent.append(self.changes),
+        }
+        Ok(())
+    }
+
+    fn abort(self) {
+        self.inner.abort()
+    }
+}
+
+impl<'env, DB, T> RawRead<'env, HookHandle<DB>> for HookRwTx<'_, T>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    T: RawRead<'env, DB>,
+{
+    fn read(
+        &self,
+        db: &impl KvHandle<HookHandle<DB>>,
+        key: &impl AsRef<[u8]>,
+    ) -> Result<Option<&[u8]>, Error> {
+        self.inner.read(&db.db().inner, key)
+    }
+}
+
+impl<'env, DB, T> RawWrite<'env, HookHandle<DB>> for HookRwTx<'_, T>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    T: RawWrite<'env, DB>,
+{
+    fn write(
+        &mut self,
+        db: &impl KvHandle<HookHandle<DB>>,
+        key: &impl AsRef<[u8]>,
+        data: &impl AsRef<[u8]>,
+    ) -> Result<(), Error> {
+        let handle = db.db();
+        self.inner.write(&handle.inner, key, data)?;
+        self.record(&handle.name, || Change::Put {
+            key: key.as_ref().to_vec(),
+            value: data.as_ref().to_vec(),
+        });
+        Ok(())
+    }
+
+    fn delete(
+        &mut self,
+        db: &impl KvHandle<HookHandle<DB>>,
+        key: &impl AsRef<[u8]>,
+    ) -> Result<(), Error> {
+        let handle = db.db();
+        self.inner.delete(&handle.inner, key)?;
+        self.record(&handle.name, || Change::Delete {
+            key: key.as_ref().to_vec(),
+        });
+        Ok(())
+    }
+}
+
+impl<'env, DB, T> RwTx<'env, HookHandle<DB>> for HookRwTx<'_, T>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    T: RwTx<'env, DB>,
+    CursorOp: ToCursorOp<DB>,
+{
+    type Cursor<'txn>
+        = HookCursor<'txn, T::Cursor<'txn>>
+    where
+        Self: 'txn;
+
+    type RwTx<'txn>
+        = HookRwTx<'txn, T::RwTx<'txn>>
+    where
+        Self: 'txn;
+
+    fn rw_cursor<'txn>(
+        &'txn mut self,
+        db: &impl KvHandle<HookHandle<DB>>,
+    ) -> Result<Self::Cursor<'txn>, Error> {
+        let handle = db.db();
+        Ok(HookCursor {
+            inner: self.inner.rw_cursor(&handle.inner)?,
+            name: handle.name.clone(),
+            changes: self.recording.then_some(&mut self.changes),
+        })
+    }
+
+    fn nested_txn(&mut self) -> Result<Self::RwTx<'_>, Error> {
+        Ok(HookRwTx {
+            inner: self.inner.nested_txn()?,
+            changes: ChangeSet::default(),
+            recording: self.recording,
+            target: Target::Parent(&mut self.changes),
+        })
+    }
+}
+
+/// Cursor of a [`HookedDb`] - wraps a read-only or read-write cursor of the inner backend
+///
+/// Read-write cursors record their changes directly in the transaction.
+pub struct HookCursor<'txn, C> {
+    inner: C,
+    name: Arc<str>,
+    changes: Option<&'txn mut ChangeSet>,
+}
+
+impl<'txn, DB, C> RoCursor<'txn, HookHandle<DB>> for HookCursor<'_, C>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    C: RoCursor<'txn, DB>,
+{
+    type Iter = C::Iter;
+
+    fn get<K, V>(
+        &self,
+        key: Option<&K>,
+        value: Option<&V>,
+        op: impl ToCursorOp<HookHandle<DB>>,
+    ) -> Result<(Option<&'txn [u8]>, &'txn [u8]), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.inner.get(key, value, RawOp(op.to_op()))
+    }
+
+    fn iter(&mut self) -> Self::Iter {
+        self.inner.iter()
+    }
+
+    fn iter_start(&mut self) -> Self::Iter {
+        self.inner.iter_start()
+    }
+
+    fn iter_from<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        self.inner.iter_from(key)
+    }
+
+    fn iter_rev(&mut self) -> Self::Iter {
+        self.inner.iter_rev()
+    }
+
+    fn iter_from_rev<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        self.inner.iter_from_rev(key)
+    }
+}
+
+impl<'txn, DB, C> RwCursor<'txn, HookHandle<DB>> for HookCursor<'_, C>
+where
+    DB: KvHandle<DB> + KvDatabase,
+    C: RwCursor<'txn, DB>,
+    CursorOp: ToCursorOp<DB>,
+{
+    type Iter = C::Iter;
+
+    fn get<K, V>(
+        &self,
+        key: Option<&K>,
+        value: Option<&V>,
+        op: impl ToCursorOp<HookHandle<DB>>,
+    ) -> Result<(Option<&'txn [u8]>, &'txn [u8]), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.inner.get(key, value, RawOp(op.to_op()))
+    }
+
+    fn put<K, V>(&mut self, key: &K, value: &V) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.inner.put(key, value)?;
+        if let Some(changes) = &mut self.changes {
+            let change = Change::Put {
+                key: key.as_ref().to_vec(),
+                value: value.as_ref().to_vec(),
+            };
+            changes.push(&self.name, change);
+        }
+        Ok(())
+    }
+
+    fn del(&mut self) -> Result<(), Error> {
+        // The cursor only knows its position, so we have to look up the key before it is deleted
+        let key = match self.changes {
+            Some(_) => {
+                let current = ToCursorOp::<DB>::to_op(&CursorOp::Current);
+                let (key, _) = self
+                    .inner
+                    .get(None::<&&[u8]>, None::<&&[u8]>, RawOp(current))?;
+                key.map(<[u8]>::to_vec)
+            }
+            None => None,
+        };
+        self.inner.del()?;
+        if let (Some(changes), Some(key)) = (&mut self.changes, key) {
+            changes.push(&self.name, Change::Delete { key });
+        }
+        Ok(())
+    }
+
+    fn iter(&mut self) -> Self::Iter {
+        self.inner.iter()
+    }
+
+    fn iter_start(&mut self) -> Self::Iter {
+        self.inner.iter_start()
+    }
+
+    fn iter_from<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        self.inner.iter_from(key)
+    }
+
+    fn iter_rev(&mut self) -> Self::Iter {
+        self.inner.iter_rev()
+    }
+
+    fn iter_from_rev<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        self.inner.iter_from_rev(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{lmdb::Lmdb, memdb::MemDb, test_suite};
+    use std::sync::Mutex;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    /// Wraps the backend and collects all change-sets
+    fn open_hooked<D>(inner: D) -> (HookedDb<D>, Arc<Mutex<Vec<ChangeSet>>>)
+    where
+        D: Db<Handle = <D as Db>::DB>,
+        D::DB: KvHandle<D::DB>,
+    {
+        let db = HookedDb::new(inner);
+        let commits = Arc::new(Mutex::new(Vec::new()));
+        let sink = commits.clone();
+        db.add_hook(move |changes: &ChangeSet| sink.lock().unwrap().push(changes.clone()));
+        (db, commits)
+    }
+
+    #[test]
+    fn test_suite() -> TestResult {
+        let fresh = || open_hooked(MemDb::new()).0;
+        test_suite::read_write_delete(&fresh())?;
+        test_suite::not_found_is_none(&fresh())?;
+        test_suite::non_existing_db(&fresh());
+        test_suite::abort_discards_changes(&fresh())?;
+        test_suite::sub_dbs_are_isolated(&fresh())?;
+        test_suite::cursor_iteration(&fresh())?;
+        test_suite::rw_cursor_sees_own_writes(&fresh())?;
+        test_suite::ro_txn_reads_snapshot(&fresh())?;
+        test_suite::cursor_ranges(&fresh())
+    }
+
+    #[test]
+    fn lmdb_backend() -> TestResult {
+        let temp_dir = tempfile::tempdir()?;
+        let (db, _) = open_hooked(Lmdb::new(temp_dir.path(), 4)?);
+        test_suite::cursor_ranges(&db)?;
+        test_suite::abort_discards_changes(&db)
+    }
+
+    fn check_changes<D>(inner: D) -> TestResult
+    where
+        D: Db<Handle = <D as Db>::DB>,
+        D::DB: KvHandle<D::DB>,
+        CursorOp: ToCursorOp<D::DB>,
+    {
+        let (db, commits) = open_hooked(inner);
+        let first = db.create_sub_db("first")?;
+        let second = db.create_sub_db("second")?;
+
+        let mut txn = db.begin_rw_txn()?;
+        txn.write(&first, b"a", b"1")?;
+        txn.write(&first, b"b", b"2")?;
+        txn.write(&second, b"c", b"3")?;
+        txn.commit()?;
+
+        // Aborted and empty transactions do not reach the hooks
+        let mut txn = db.begin_rw_txn()?;
+        txn.write(&first, b"x", b"aborted")?;
+        txn.abort();
+        db.begin_rw_txn()?.commit()?;
+        db.begin_ro_txn()?.commit()?;
+
+        let mut txn = db.begin_rw_txn()?;
+        txn.delete(&first, b"a")?;
+        {
+            let mut cursor = txn.rw_cursor(&second)?;
+            cursor.put(b"d", b"4")?;
+            cursor.get(None::<&&[u8]>, None::<&&[u8]>, CursorOp::First)?;
+            cursor.del()?;
+        }
+        txn.commit()?;
+
+        let commits = commits.lock().unwrap();
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].len(), 3);
+        assert_eq!(
+            commits[0].sub_db("first"),
+            [
+                Change::Put {
+                    key: b"a".to_vec(),
+                    value: b"1".to_vec()
+                },
+                Change::Put {
+                    key: b"b".to_vec(),
+                    value: b"2".to_vec()
+                },
+            ]
+        );
+        let changed: Vec<_> = commits[1]
+            .iter()
+            .flat_map(|(name, changes)| changes.iter().map(move |c| (name, c.key(), c.value())))
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                ("first", &b"a"[..], None),
+                ("second", b"d", Some(&b"4"[..])),
+                ("second", b"c", None),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn hooks_receive_changes() -> TestResult {
+        check_changes(MemDb::new())?;
+        let temp_dir = tempfile::tempdir()?;
+        check_changes(Lmdb::new(temp_dir.path(), 4)?)
+    }
+
+    #[test]
+    fn nested_transactions() -> TestResult {
+        let (db, commits) = open_hooked(MemDb::new());
+        let handle = db.create_sub_db("test")?;
+        let mut txn = db.begin_rw_txn()?;
+        txn.write(&handle, b"outer", b"1")?;
+        {
+            let mut nested = txn.nested_txn()?;
+            nested.write(&handle, b"aborted", b"2")?;
+            nested.abort();
+        }
+        {
+            let mut nested = txn.nested_txn()?;
+            nested.write(&handle, b"nested", b"3")?;
+            nested.commit()?;
+        }
+        // Nothing is published before the outer transaction is committed
+        assert!(commits.lock().unwrap().is_empty());
+        txn.commit()?;
+
+        let commits = commits.lock().unwrap();
+        assert_eq!(commits.len(), 1);
+        let keys: Vec<_> = commits[0].sub_db("test").iter().map(Change::key).collect();
+        assert_eq!(keys, [&b"outer"[..], b"nested"]);
+        Ok(())
+    }
+
+    #[test]
+    fn without_hooks_nothing_is_recorded() -> TestResult {
+        let db = HookedDb::new(MemDb::new());
+        let handle = db.create_sub_db("test")?;
+        let mut txn = db.begin_rw_txn()?;
+        txn.write(&handle, b"key", b"value")?;
+        assert!(txn.changes.is_empty());
+        txn.commit()?;
+        Ok(())
+    }
+}
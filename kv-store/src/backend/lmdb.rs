@@ -1,7 +1,13 @@
 use crate::{CursorOp, Error, KvDatabase, KvHandle, RoCursor, RoTx, RwCursor, RwTx, ToCursorOp};
 use lmdb::EnvironmentFlags;
-use lmdb_sys::{MDB_FIRST, MDB_GET_CURRENT, MDB_LAST, MDB_NEXT, MDB_PREV};
-use std::{path::Path, sync::Arc};
+use lmdb_sys::{mdb_env_copy2, MDB_CP_COMPACT};
+use lmdb_sys::{MDB_FIRST, MDB_GET_CURRENT, MDB_LAST, MDB_NEXT, MDB_PREV, MDB_SET_RANGE};
+use std::{
+    ffi::CString,
+    marker::PhantomData,
+    path::Path,
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard},
+};
 
 use crate::{Db, RawRead, RawWrite, Tx};
 
@@ -78,11 +84,16 @@ impl KvHandle<lmdb::Database> for lmdb::Database {
 #[derive(Clone)]
 pub struct Lmdb {
     env: Arc<lmdb::Environment>,
+    /// Every transaction holds a read-lock on this, so the map is only resized while no transaction is active
+    resize: Arc<RwLock<()>>,
+    grow_step: Option<usize>,
 }
 
 impl Lmdb {
     /// Initializes a new LMDB environment at the specified path with a given maximum number of databases.
     ///
+    /// This uses the default [`LmdbOptions`]; use them directly to tweak the map size, flags etc.
+    ///
     /// ### Parameters:
     /// - `path`: The filesystem path where the LMDB environment will be created or accessed.
     /// - `max_dbs`: The maximum number of named databases that can be created within this environment.
@@ -91,25 +102,294 @@ impl Lmdb {
     /// - `Ok(Lmdb)` if the environment was successfully initialized.
     /// - `Err(Error)` if an error occurred during initialization.
     pub fn new(path: &Path, max_dbs: u32) -> Result<Lmdb, Error> {
-        // We can do some further optimizations, if we want to increase the performance:
-        let flags = EnvironmentFlags::default()
-            | EnvironmentFlags::WRITE_MAP     // Faster writes, backed by OS memory mapping. Safe on modern OSes. (incompatible with nested transactions !)
-            | EnvironmentFlags::NO_META_SYNC; // Skips metadata sync — tiny risk on power loss, big write speed boost.
+        LmdbOptions::new(max_dbs).open(path)
+    }
+
+    /// Copies the environment into the directory at `path`, while it is in use.
+    ///
+    /// The copy is a consistent snapshot of the last committed transaction. With `compact` set,
+    /// free pages are omitted and the pages are renumbered sequentially, which makes the copy
+    /// smaller but slower to create. The directory is created if it does not exist,
+    /// but it must not already contain a database.
+    ///
+    /// Like every other read, this must not be called while the calling thread has an open transaction.
+    pub fn backup(&self, path: &Path, compact: bool) -> Result<(), Error> {
+        if path.join("data.mdb").exists() {
+            return Err(Error::Other(format!(
+                "backup target {} already contains a database",
+                path.display()
+            )));
+        }
+        std::fs::create_dir_all(path)
+            .map_err(|e| Error::Other(format!("failed to create {}: {e}", path.display())))?;
+        let path = path
+            .to_str()
+            .and_then(|p| CString::new(p).ok())
+            .ok_or(Error::InvalidArgument)?;
+        let flags = if compact { MDB_CP_COMPACT } else { 0 };
+
+        let _guard = read_lock(&self.resize);
+        // SAFETY: The environment is open for the lifetime of `self.env` and the path is a valid c-string
+        let rc = unsafe { mdb_env_copy2(self.env.env(), path.as_ptr(), flags) };
+        if rc != 0 {
+            return Err(lmdb::Error::from_err_code(rc).into());
+        }
+        Ok(())
+    }
+
+    /// Returns statistics about the environment and all of its sub-databases.
+    ///
+    /// Must not be called while the calling thread has an open transaction,
+    /// as opening the sub-databases requires a transaction of its own.
+    pub fn stats(&self) -> Result<EnvStats, Error> {
+        let _guard = read_lock(&self.resize);
+        let info = self.env.info()?;
+        let stat = self.env.stat()?;
+        let free_pages = self.env.freelist()?;
+
+        // The names of all sub-databases are stored as keys in the unnamed main database
+        let main = self.env.open_db(None)?;
+        let names: Vec<String> = {
+            let txn = self.env.begin_ro_txn()?;
+            let mut cursor = lmdb::Transaction::open_ro_cursor(&txn, main)?;
+            lmdb::Cursor::iter_start(&mut cursor)
+                .filter_map(Result::ok)
+                .filter_map(|(key, _)| std::str::from_utf8(key).ok().map(String::from))
+                .collect()
+        };
+        let mut dbs = Vec::with_capacity(names.len());
+        for name in names {
+            match self.env.open_db(Some(&name)) {
+                Ok(db) => dbs.push((name, db)),
+                // Regular keys, that were written into the main database
+                Err(lmdb::Error::Incompatible) | Err(lmdb::Error::NotFound) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let txn = self.env.begin_ro_txn()?;
+        let mut sub_dbs = Vec::with_capacity(dbs.len());
+        for (name, db) in dbs {
+            let stat = lmdb::Transaction::stat(&txn, db)?;
+            sub_dbs.push(DbStats {
+                name,
+                entries: stat.entries(),
+                depth: stat.depth(),
+                branch_pages: stat.branch_pages(),
+                leaf_pages: stat.leaf_pages(),
+                overflow_pages: stat.overflow_pages(),
+            });
+        }
+
+        Ok(EnvStats {
+            map_size: info.map_size(),
+            page_size: stat.page_size(),
+            pages: info.last_pgno() + 1,
+            free_pages,
+            max_readers: info.max_readers(),
+            num_readers: info.num_readers(),
+            sub_dbs,
+        })
+    }
+
+    /// Grows the map by `step` bytes, if less than `step` bytes are left.
+    ///
+    /// LMDB only allows resizing while there are no active transactions in this process.
+    /// If there are any, we skip the resize and try again with the next write transaction.
+    fn grow(&self, step: usize) -> Result<(), Error> {
+        let Ok(_lock) = self.resize.try_write() else {
+            return Ok(());
+        };
+        let info = self.env.info()?;
+        let used = (info.last_pgno() + 1) * self.env.stat()?.page_size() as usize;
+        if info.map_size().saturating_sub(used) < step {
+            self.env.set_map_size(info.map_size() + step)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options to open an LMDB environment with.
+///
+/// The defaults match [`Lmdb::new`]: a map size of 1 TB, 2048 readers and the
+/// `WRITE_MAP` and `NO_META_SYNC` flags.
+///
+/// ```no_run
+/// # use borderless_kv_store::backend::lmdb::LmdbOptions;
+/// let db = LmdbOptions::new(16)
+///     .map_size(1 << 30)
+///     .auto_grow(1 << 30)
+///     .open(std::path::Path::new("/var/lib/node/db"))?;
+/// # Ok::<(), borderless_kv_store::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct LmdbOptions {
+    max_dbs: u32,
+    map_size: usize,
+    max_readers: u32,
+    flags: EnvironmentFlags,
+    grow_step: Option<usize>,
+}
+
+impl LmdbOptions {
+    /// Creates the default options for an environment with up to `max_dbs` named databases.
+    pub fn new(max_dbs: u32) -> Self {
+        LmdbOptions {
+            max_dbs,
+            // NOTE: This is the maximum size of the database.
+            // Use `auto_grow` if you want to start smaller.
+            map_size: 1_099_511_627_776, // 1 TB
+            max_readers: 2048,           // allow more concurrent readers
+            // Faster writes, backed by OS memory mapping. Safe on modern OSes. (incompatible with nested transactions !)
+            // and skipping the metadata sync — tiny risk on power loss, big write speed boost.
+            flags: EnvironmentFlags::WRITE_MAP | EnvironmentFlags::NO_META_SYNC,
+            grow_step: None,
+        }
+    }
+
+    /// Sets the size of the memory map (and thereby the maximum size of the database) in bytes.
+    pub fn map_size(mut self, bytes: usize) -> Self {
+        self.map_size = bytes;
+        self
+    }
+
+    /// Sets the maximum number of concurrent read transactions.
+    pub fn max_readers(mut self, readers: u32) -> Self {
+        self.max_readers = readers;
+        self
+    }
+
+    /// Don't flush the buffers to disk on commit.
+    ///
+    /// The database stays consistent, but the last transactions may be lost on a system crash.
+    pub fn no_sync(mut self, enabled: bool) -> Self {
+        self.flags.set(EnvironmentFlags::NO_SYNC, enabled);
+        self
+    }
+
+    /// Don't flush the meta page on commit (enabled by default).
+    ///
+    /// A system crash may undo the last transaction, but the database stays consistent.
+    pub fn no_meta_sync(mut self, enabled: bool) -> Self {
+        self.flags.set(EnvironmentFlags::NO_META_SYNC, enabled);
+        self
+    }
+
+    /// Use a writeable memory map (enabled by default).
+    ///
+    /// This is faster, but nested transactions are not supported with a writeable map.
+    pub fn write_map(mut self, enabled: bool) -> Self {
+        self.flags.set(EnvironmentFlags::WRITE_MAP, enabled);
+        self
+    }
+
+    /// Opens the environment read-only. Write transactions will fail.
+    pub fn read_only(mut self, enabled: bool) -> Self {
+        self.flags.set(EnvironmentFlags::READ_ONLY, enabled);
+        self
+    }
+
+    /// Grows the map by `step` bytes, whenever less than `step` bytes are left.
+    ///
+    /// The check happens when a write transaction begins and is skipped while other transactions
+    /// of this process are still active. A single transaction that writes more than `step` bytes
+    /// can therefore still fail with a full map.
+    pub fn auto_grow(mut self, step: usize) -> Self {
+        self.grow_step = Some(step);
+        self
+    }
 
+    /// Opens (or creates) the environment at the given path.
+    pub fn open(&self, path: &Path) -> Result<Lmdb, Error> {
         let env = lmdb::Environment::new()
-            .set_max_dbs(max_dbs)
-            // NOTE: we have to maintain the map size
-            // in future. A mechanism to increase this size
-            // is a good idea.
-            .set_map_size(1_099_511_627_776) // 1 TB
-            .set_max_readers(2048) // allow more concurrent readers
-            .set_flags(flags)
+            .set_max_dbs(self.max_dbs)
+            .set_map_size(self.map_size)
+            .set_max_readers(self.max_readers)
+            .set_flags(self.flags)
             .open(path)?;
 
-        Ok(Lmdb { env: Arc::new(env) })
+        let read_only = self.flags.contains(EnvironmentFlags::READ_ONLY);
+        Ok(Lmdb {
+            env: Arc::new(env),
+            resize: Arc::new(RwLock::new(())),
+            grow_step: self.grow_step.filter(|_| !read_only),
+        })
+    }
+}
+
+/// Statistics about an LMDB environment
+#[derive(Debug, Clone)]
+pub struct EnvStats {
+    /// Size of the memory map in bytes
+    pub map_size: usize,
+    /// Size of a database page in bytes
+    pub page_size: u32,
+    /// Number of pages in the data file (including free pages)
+    pub pages: usize,
+    /// Number of free pages, that are reused by later transactions
+    pub free_pages: usize,
+    /// Maximum number of reader slots
+    pub max_readers: u32,
+    /// Number of reader slots, that were used so far
+    pub num_readers: u32,
+    /// Statistics for each named sub-database
+    pub sub_dbs: Vec<DbStats>,
+}
+
+impl EnvStats {
+    /// Size of the data file in bytes
+    pub fn file_bytes(&self) -> usize {
+        self.pages * self.page_size as usize
+    }
+
+    /// Bytes that are occupied by live data
+    pub fn used_bytes(&self) -> usize {
+        self.pages.saturating_sub(self.free_pages) * self.page_size as usize
+    }
+}
+
+/// Statistics about a single sub-database
+#[derive(Debug, Clone)]
+pub struct DbStats {
+    /// Name of the sub-database
+    pub name: String,
+    /// Number of key-value pairs
+    pub entries: usize,
+    /// Depth of the b-tree
+    pub depth: u32,
+    /// Number of internal (non-leaf) pages
+    pub branch_pages: usize,
+    /// Number of leaf pages
+    pub leaf_pages: usize,
+    /// Number of overflow pages (used for large values)
+    pub overflow_pages: usize,
+}
+
+impl DbStats {
+    /// Total number of pages used by this sub-database
+    pub fn pages(&self) -> usize {
+        self.branch_pages + self.leaf_pages + self.overflow_pages
     }
 }
 
+/// Locks the resize-lock for reading - the lock does not protect any data, so poisoning is irrelevant.
+fn read_lock(lock: &RwLock<()>) -> RwLockReadGuard<'_, ()> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Read-only transaction of an [`Lmdb`] environment
+pub struct LmdbRoTx<'env> {
+    txn: lmdb::RoTransaction<'env>,
+    _guard: RwLockReadGuard<'env, ()>,
+}
+
+/// Read-write transaction of an [`Lmdb`] environment
+pub struct LmdbRwTx<'env> {
+    txn: lmdb::RwTransaction<'env>,
+    /// Nested transactions are covered by the guard of their parent
+    _guard: Option<RwLockReadGuard<'env, ()>>,
+}
+
 /// Implements the `Db` trait for `Lmdb`.
 ///
 /// This allows `Lmdb` to serve as a fully functional key-value environment
@@ -117,8 +397,8 @@ impl Lmdb {
 impl Db for Lmdb {
     type DB = lmdb::Database;
     type Handle = lmdb::Database;
-    type RoTx<'env> = lmdb::RoTransaction<'env>;
-    type RwTx<'env> = lmdb::RwTransaction<'env>;
+    type RoTx<'env> = LmdbRoTx<'env>;
+    type RwTx<'env> = LmdbRwTx<'env>;
 
     /// Opens a database by name or the default database if the name is "default".
     ///
@@ -170,8 +450,9 @@ impl Db for Lmdb {
     /// - `Ok(RoTx<'env>)` containing the transaction object.
     /// - `Err(Error)` if the transaction could not be started.
     fn begin_ro_txn(&self) -> Result<Self::RoTx<'_>, Error> {
+        let guard = read_lock(&self.resize);
         let txn = self.env.begin_ro_txn()?;
-        Ok(txn)
+        Ok(LmdbRoTx { txn, _guard: guard })
     }
 
     /// Begins a new read-write transaction within the environment.
@@ -180,23 +461,30 @@ impl Db for Lmdb {
     /// - `Ok(RwTx<'env>)` containing the transaction object.
     /// - `Err(Error)` if the transaction could not be started.
     fn begin_rw_txn(&self) -> Result<Self::RwTx<'_>, Error> {
+        if let Some(step) = self.grow_step {
+            self.grow(step)?;
+        }
+        let guard = read_lock(&self.resize);
         let txn = self.env.begin_rw_txn()?;
-        Ok(txn)
+        Ok(LmdbRwTx {
+            txn,
+            _guard: Some(guard),
+        })
     }
 }
 
-/// Implements the `Tx` trait for LMDB's read-only transactions (`RoTransaction`).
+/// Implements the `Tx` trait for LMDB's read-only transactions (`LmdbRoTx`).
 ///
 /// This trait provides methods to manage the lifecycle of read-only transactions,
 /// including committing and aborting.
-impl<'env> Tx for lmdb::RoTransaction<'env> {
+impl<'env> Tx for LmdbRoTx<'env> {
     /// Commits the transaction, making all changes visible to other transactions.
     ///
     /// ### Returns:
     /// - `Ok(())` if the transaction was successfully committed.
     /// - `Err(Error)` if an error occurred during the commit operation.
     fn commit(self) -> Result<(), Error> {
-        <Self as lmdb::Transaction>::commit(self)?;
+        lmdb::Transaction::commit(self.txn)?;
         Ok(())
     }
 
@@ -205,18 +493,18 @@ impl<'env> Tx for lmdb::RoTransaction<'env> {
     /// ### Notes:
     /// - This method ensures that the transaction is cleanly terminated without affecting the database.
     fn abort(self) {
-        <Self as lmdb::Transaction>::abort(self);
+        lmdb::Transaction::abort(self.txn);
     }
 }
 
-/// Implements the `RawRead` trait for LMDB's read-only transaction (`RoTransaction`).
+/// Implements the `RawRead` trait for LMDB's read-only transaction (`LmdbRoTx`).
 ///
 /// This allows performing read operations within the context of a read-only transaction.
 /// It retrieves data from the database based on a specified key.
 ///
 /// ### Methods:
 /// - `read`: Fetches the value associated with a given key in the specified database.
-impl<'env> RawRead<'env, lmdb::Database> for lmdb::RoTransaction<'env> {
+impl<'env> RawRead<'env, lmdb::Database> for LmdbRoTx<'env> {
     /// Reads a value from the database associated with the provided key.
     ///
     /// ### Parameters:
@@ -232,7 +520,7 @@ impl<'env> RawRead<'env, lmdb::Database> for lmdb::RoTransaction<'env> {
         db: &impl KvHandle<lmdb::Database>,
         key: &impl AsRef<[u8]>,
     ) -> Result<Option<&[u8]>, Error> {
-        let res = <Self as lmdb::Transaction>::get(self, *db.db(), key);
+        let res = lmdb::Transaction::get(&self.txn, *db.db(), key);
 
         let data = match res {
             Ok(buf) => Some(buf),
@@ -247,9 +535,10 @@ impl<'env> RawRead<'env, lmdb::Database> for lmdb::RoTransaction<'env> {
 /// Implements the `RoTx` trait for LMDB's read-only transactions.
 ///
 /// This provides additional methods specific to read-only transactions, such as creating cursors.
-impl<'env> RoTx<'env, lmdb::Database> for lmdb::RoTransaction<'env> {
+impl<'env> RoTx<'env, lmdb::Database> for LmdbRoTx<'env> {
     /// Type definition for the cursor used in read-only transactions.
-    type Cursor<'txn> = lmdb::RoCursor<'txn>
+    type Cursor<'txn>
+        = lmdb::RoCursor<'txn>
     where
         Self: 'txn;
 
@@ -265,7 +554,7 @@ impl<'env> RoTx<'env, lmdb::Database> for lmdb::RoTransaction<'env> {
         &'txn self,
         db: &impl KvHandle<lmdb::Database>,
     ) -> Result<Self::Cursor<'txn>, Error> {
-        let cursor = <Self as lmdb::Transaction>::open_ro_cursor(self, *db.db())?;
+        let cursor = lmdb::Transaction::open_ro_cursor(&self.txn, *db.db())?;
         Ok(cursor)
     }
 }
@@ -315,19 +604,30 @@ impl<'txn> RoCursor<'txn, lmdb::Database> for lmdb::RoCursor<'txn> {
     {
         Iter(<Self as lmdb::Cursor>::iter_from(self, key))
     }
+
+    fn iter_rev(&mut self) -> Self::Iter {
+        Iter::rev(self)
+    }
+
+    fn iter_from_rev<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        Iter::from_rev(self, key.as_ref())
+    }
 }
 
 /// Implements the `Tx` trait for LMDB's read-write transactions.
 ///
 /// Provides methods to manage the lifecycle of read-write transactions, such as committing or aborting.
-impl<'env> Tx for lmdb::RwTransaction<'env> {
+impl<'env> Tx for LmdbRwTx<'env> {
     /// Commits the transaction, applying all changes to the database.
     ///
     /// ### Returns:
     /// - `Ok(())`: If the transaction was successfully committed.
     /// - `Err(Error)`: If an error occurs during the commit operation.
     fn commit(self) -> Result<(), Error> {
-        <Self as lmdb::Transaction>::commit(self)?;
+        lmdb::Transaction::commit(self.txn)?;
         Ok(())
     }
 
@@ -336,14 +636,14 @@ impl<'env> Tx for lmdb::RwTransaction<'env> {
     /// ### Notes:
     /// - This ensures no changes from the transaction are persisted in the database.
     fn abort(self) {
-        <Self as lmdb::Transaction>::abort(self);
+        lmdb::Transaction::abort(self.txn);
     }
 }
 
-/// Implements the `RawRead` trait for LMDB's read-write transaction (`RwTransaction`).
+/// Implements the `RawRead` trait for LMDB's read-write transaction (`LmdbRwTx`).
 ///
 /// Enables reading data within the context of a read-write transaction.
-impl<'env> RawRead<'env, lmdb::Database> for lmdb::RwTransaction<'env> {
+impl<'env> RawRead<'env, lmdb::Database> for LmdbRwTx<'env> {
     /// Reads a value from the database associated with the provided key.
     ///
     /// ### Parameters:
@@ -359,7 +659,7 @@ impl<'env> RawRead<'env, lmdb::Database> for lmdb::RwTransaction<'env> {
         db: &impl KvHandle<lmdb::Database>,
         key: &impl AsRef<[u8]>,
     ) -> Result<Option<&[u8]>, Error> {
-        let res = <Self as lmdb::Transaction>::get(self, *db.db(), key);
+        let res = lmdb::Transaction::get(&self.txn, *db.db(), key);
 
         let data = match res {
             Ok(buf) => Some(buf),
@@ -371,10 +671,10 @@ impl<'env> RawRead<'env, lmdb::Database> for lmdb::RwTransaction<'env> {
     }
 }
 
-/// Implements the `RawWrite` trait for LMDB's read-write transaction (`RwTransaction`).
+/// Implements the `RawWrite` trait for LMDB's read-write transaction (`LmdbRwTx`).
 ///
 /// Enables writing and deleting data within the context of a read-write transaction.
-impl<'env> RawWrite<'env, lmdb::Database> for lmdb::RwTransaction<'env> {
+impl<'env> RawWrite<'env, lmdb::Database> for LmdbRwTx<'env> {
     /// Writes a key-value pair to the database.
     ///
     /// ### Parameters:
@@ -391,7 +691,8 @@ impl<'env> RawWrite<'env, lmdb::Database> for lmdb::RwTransaction<'env> {
         key: &impl AsRef<[u8]>,
         data: &impl AsRef<[u8]>,
     ) -> Result<(), Error> {
-        self.put(*db.db(), key, &data, lmdb::WriteFlags::empty())?;
+        self.txn
+            .put(*db.db(), key, &data, lmdb::WriteFlags::empty())?;
         Ok(())
     }
 
@@ -409,7 +710,7 @@ impl<'env> RawWrite<'env, lmdb::Database> for lmdb::RwTransaction<'env> {
         db: &impl KvHandle<lmdb::Database>,
         key: &impl AsRef<[u8]>,
     ) -> Result<(), Error> {
-        let res = self.del(*db.db(), key, None);
+        let res = self.txn.del(*db.db(), key, None);
 
         match res {
             Ok(_) => Ok(()),
@@ -422,14 +723,16 @@ impl<'env> RawWrite<'env, lmdb::Database> for lmdb::RwTransaction<'env> {
 /// Implements the `RwTx` trait for LMDB's read-write transactions.
 ///
 /// Provides methods to create cursors and nested transactions within the context of a read-write transaction.
-impl<'env> RwTx<'env, lmdb::Database> for lmdb::RwTransaction<'env> {
+impl<'env> RwTx<'env, lmdb::Database> for LmdbRwTx<'env> {
     /// Type definition for cursors used in read-write transactions.
-    type Cursor<'txn> = lmdb::RwCursor<'txn>
+    type Cursor<'txn>
+        = lmdb::RwCursor<'txn>
     where
         Self: 'txn;
 
     /// Type definition for nested read-write transactions.
-    type RwTx<'txn> = lmdb::RwTransaction<'txn>
+    type RwTx<'txn>
+        = LmdbRwTx<'txn>
     where
         Self: 'txn;
 
@@ -445,7 +748,7 @@ impl<'env> RwTx<'env, lmdb::Database> for lmdb::RwTransaction<'env> {
         &'txn mut self,
         db: &impl KvHandle<lmdb::Database>,
     ) -> Result<Self::Cursor<'txn>, Error> {
-        let cursor = self.open_rw_cursor(*db.db())?;
+        let cursor = self.txn.open_rw_cursor(*db.db())?;
         Ok(cursor)
     }
 
@@ -455,8 +758,8 @@ impl<'env> RwTx<'env, lmdb::Database> for lmdb::RwTransaction<'env> {
     /// - `Ok(RwTx<'txn>)`: If the nested transaction is successfully started.
     /// - `Err(Error)`: If an error occurs during the operation.
     fn nested_txn(&mut self) -> Result<Self::RwTx<'_>, Error> {
-        let ntx = self.begin_nested_txn()?;
-        Ok(ntx)
+        let txn = self.txn.begin_nested_txn()?;
+        Ok(LmdbRwTx { txn, _guard: None })
     }
 }
 
@@ -519,10 +822,50 @@ impl<'txn> RwCursor<'txn, lmdb::Database> for lmdb::RwCursor<'txn> {
     {
         Iter(<Self as lmdb::Cursor>::iter_from(self, key))
     }
+
+    fn iter_rev(&mut self) -> Self::Iter {
+        Iter::rev(self)
+    }
+
+    fn iter_from_rev<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        Iter::from_rev(self, key.as_ref())
+    }
 }
 
 pub struct Iter<'txn>(lmdb::Iter<'txn>);
 
+impl<'txn> Iter<'txn> {
+    /// Iterates backwards, starting at the last item of the database
+    fn rev(cursor: &impl lmdb::Cursor<'txn>) -> Self {
+        Iter(lmdb::Iter::Ok {
+            cursor: cursor.cursor(),
+            op: MDB_LAST,
+            next_op: MDB_PREV,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Iterates backwards, starting at the given key (or the closest key before it)
+    fn from_rev(cursor: &impl lmdb::Cursor<'txn>, key: &[u8]) -> Self {
+        // MDB_SET_RANGE positions the cursor at the first key, that is greater or equal to the given key
+        let op = match cursor.get(Some(key), None, MDB_SET_RANGE) {
+            Ok((found, _)) if found.is_none_or(|found| found == key) => MDB_GET_CURRENT,
+            Ok(_) => MDB_PREV,
+            Err(lmdb::Error::NotFound) => MDB_LAST,
+            Err(e) => return Iter(lmdb::Iter::Err(e)),
+        };
+        Iter(lmdb::Iter::Ok {
+            cursor: cursor.cursor(),
+            op,
+            next_op: MDB_PREV,
+            _marker: PhantomData,
+        })
+    }
+}
+
 impl<'txn> Iterator for Iter<'txn> {
     type Item = (&'txn [u8], &'txn [u8]);
 
@@ -558,116 +901,201 @@ fn flatten_opt_result<T, E>(opt_res: Option<Result<T, E>>) -> Option<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rand::Rng;
+    use crate::backend::test_suite;
     use tempfile::tempdir;
 
-    const TEST_REPEATS: usize = 4;
-
     fn open_tmp_lmdb() -> Lmdb {
         let temp_dir = tempdir().unwrap();
-        let env = Lmdb::new(temp_dir.path(), 1).unwrap();
+        let env = Lmdb::new(temp_dir.path(), 4).unwrap();
         env
     }
 
-    fn create_test_handle<DB: KvDatabase, Env: Db>(env: &Env) -> impl KvHandle<DB>
-    where
-        <Env as Db>::Handle: KvHandle<DB>,
-    {
-        env.create_sub_db("test").unwrap()
+    #[test]
+    fn read_write_delete() -> Result<(), Box<dyn std::error::Error>> {
+        let env = open_tmp_lmdb();
+        test_suite::read_write_delete(&env)
     }
 
-    fn write_with_rw_txn<'env, DB: KvDatabase, Txn: RwTx<'env, DB>>(
-        txn: &mut Txn,
-        handle: &impl KvHandle<DB>,
-        key: &impl AsRef<[u8]>,
-        data: &impl AsRef<[u8]>,
-    ) -> Result<(), Error> {
-        txn.write(handle, &key, &data)?;
-        Ok(())
+    #[test]
+    fn not_found_is_none() -> Result<(), Box<dyn std::error::Error>> {
+        let env = open_tmp_lmdb();
+        test_suite::not_found_is_none(&env)
     }
 
-    fn read_with_ro_txn<'env, DB: KvDatabase, Txn: RoTx<'env, DB>>(
-        txn: &Txn,
-        handle: &impl KvHandle<DB>,
-        key: &impl AsRef<[u8]>,
-    ) -> Result<Option<Vec<u8>>, Error> {
-        let buf = txn.read(handle, &key)?;
-        let out: Option<Vec<u8>> = buf.map(|v| v.to_vec());
-        Ok(out)
+    #[test]
+    fn non_existing_db() {
+        let env = open_tmp_lmdb();
+        test_suite::non_existing_db(&env);
     }
 
-    fn delete_with_rw_txn<'env, DB: KvDatabase, Txn: RwTx<'env, DB>>(
-        txn: &mut Txn,
-        handle: &impl KvHandle<DB>,
-        key: &impl AsRef<[u8]>,
-    ) -> Result<(), Error> {
-        txn.delete(handle, &key)?;
-        Ok(())
+    #[test]
+    fn abort_discards_changes() -> Result<(), Box<dyn std::error::Error>> {
+        let env = open_tmp_lmdb();
+        test_suite::abort_discards_changes(&env)
     }
 
     #[test]
-    fn read_write_delete() -> Result<(), Box<dyn std::error::Error>> {
+    fn sub_dbs_are_isolated() -> Result<(), Box<dyn std::error::Error>> {
         let env = open_tmp_lmdb();
-        let handle = create_test_handle(&env);
-
-        for _ in 0..TEST_REPEATS {
-            let mut rng = rand::rng();
-            let test_key: Vec<u8> = (0..256).map(|_| rng.random()).collect(); // 32 byte random key
-            let test_data: Vec<u8> = (0..1048576).map(|_| rng.random()).collect(); // 1 MiB random data
-            {
-                // write test value to db
-                let mut txn = env.begin_rw_txn()?;
-                write_with_rw_txn(&mut txn, &handle, &test_key, &test_data)?;
-                Tx::commit(txn)?;
-            }
+        test_suite::sub_dbs_are_isolated(&env)
+    }
 
-            {
-                // read test value from db
-                let txn = env.begin_ro_txn()?;
-                let res = read_with_ro_txn(&txn, &handle, &test_key)?;
-                assert!(res.is_some(), "failed to read value from db");
-                assert_eq!(test_data, res.unwrap(), "data read is corrupted");
-            }
+    #[test]
+    fn cursor_iteration() -> Result<(), Box<dyn std::error::Error>> {
+        let env = open_tmp_lmdb();
+        test_suite::cursor_iteration(&env)
+    }
 
-            {
-                // delete test value from db
-                let mut txn = env.begin_rw_txn()?;
-                delete_with_rw_txn(&mut txn, &handle, &test_key)?;
-                Tx::commit(txn)?;
+    #[test]
+    fn rw_cursor_sees_own_writes() -> Result<(), Box<dyn std::error::Error>> {
+        let env = open_tmp_lmdb();
+        test_suite::rw_cursor_sees_own_writes(&env)
+    }
 
-                // try to read deleted value
-                let txn = env.begin_ro_txn()?;
-                let res = read_with_ro_txn(&txn, &handle, &test_key)?;
-                assert!(res.is_none(), "could read deleted value");
-            }
-        }
-        Ok(())
+    #[test]
+    fn ro_txn_reads_snapshot() -> Result<(), Box<dyn std::error::Error>> {
+        let env = open_tmp_lmdb();
+        test_suite::ro_txn_reads_snapshot(&env)
     }
 
     #[test]
-    fn not_found_is_none() -> Result<(), Box<dyn std::error::Error>> {
+    fn cursor_ranges() -> Result<(), Box<dyn std::error::Error>> {
         let env = open_tmp_lmdb();
-        let handle = create_test_handle(&env);
+        test_suite::cursor_ranges(&env)
+    }
+
+    #[test]
+    fn options_are_applied() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let env = LmdbOptions::new(4)
+            .map_size(1 << 20)
+            .max_readers(16)
+            .no_sync(true)
+            .open(temp_dir.path())?;
+        let stats = env.stats()?;
+        assert_eq!(stats.map_size, 1 << 20);
+        assert_eq!(stats.max_readers, 16);
+        Ok(())
+    }
 
+    #[test]
+    fn read_only_rejects_writes() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        {
+            let env = Lmdb::new(temp_dir.path(), 4)?;
+            let handle = env.create_sub_db("test")?;
+            let mut txn = env.begin_rw_txn()?;
+            txn.write(&handle, b"key", b"value")?;
+            Tx::commit(txn)?;
+        }
+        let env = LmdbOptions::new(4).read_only(true).open(temp_dir.path())?;
+        let handle = env.open_sub_db("test")?;
         let txn = env.begin_ro_txn()?;
-        let not_found = txn.read(&handle, &[0, 0, 0, 0])?;
-        assert!(not_found.is_none());
+        assert_eq!(txn.read(&handle, b"key")?, Some(&b"value"[..]));
+        Tx::commit(txn)?;
+        assert!(env.begin_rw_txn().is_err());
+        Ok(())
+    }
+
+    /// Writes 8 MiB of data in chunks of 256 KiB
+    fn fill(env: &Lmdb) -> Result<(), Error> {
+        let handle = env.create_sub_db("test")?;
+        for i in 0u32..32 {
+            let mut txn = env.begin_rw_txn()?;
+            txn.write(&handle, &i.to_be_bytes(), &vec![0xab; 256 * 1024])?;
+            Tx::commit(txn)?;
+        }
         Ok(())
     }
 
     #[test]
-    fn non_existing_db() {
-        let env = open_tmp_lmdb();
-        let db_name = "does-not-exist";
-        let res = env.open_sub_db(db_name);
-        assert!(res.is_err());
-        if let Err(e) = res {
-            match e {
-                Error::DbNotFound(name) => {
-                    assert_eq!(name, db_name, "error should include db-name")
-                }
-                _ => panic!("expected error 'DbNotFound'"),
+    fn auto_grow_extends_map() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let env = LmdbOptions::new(4)
+            .map_size(1 << 20)
+            .open(temp_dir.path())?;
+        assert!(fill(&env).is_err(), "map should be full");
+        drop(env);
+
+        let temp_dir = tempdir()?;
+        let env = LmdbOptions::new(4)
+            .map_size(1 << 20)
+            .auto_grow(1 << 20)
+            .open(temp_dir.path())?;
+        fill(&env)?;
+        let stats = env.stats()?;
+        assert!(stats.map_size > 8 << 20);
+        assert!(stats.used_bytes() <= stats.file_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn auto_grow_waits_for_active_txns() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let env = LmdbOptions::new(4)
+            .map_size(1 << 20)
+            .auto_grow(1 << 20)
+            .open(temp_dir.path())?;
+        let handle = env.create_sub_db("test")?;
+        let ro_txn = env.begin_ro_txn()?;
+        let mut txn = env.begin_rw_txn()?;
+        txn.write(&handle, b"key", b"value")?;
+        Tx::commit(txn)?;
+        // The read transaction is still active, so the map must not have been resized
+        assert_eq!(env.env.info()?.map_size(), 1 << 20);
+        Tx::commit(ro_txn)?;
+        Ok(())
+    }
+
+    #[test]
+    fn backup_and_stats() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let env = Lmdb::new(temp_dir.path(), 4)?;
+        let first = env.create_sub_db("first")?;
+        let second = env.create_sub_db("second")?;
+        let mut txn = env.begin_rw_txn()?;
+        for i in 0u32..100 {
+            txn.write(&first, &i.to_be_bytes(), b"first")?;
+        }
+        txn.write(&second, b"key", b"second")?;
+        Tx::commit(txn)?;
+        // Leave some free pages behind
+        let mut txn = env.begin_rw_txn()?;
+        for i in 0u32..50 {
+            txn.delete(&first, &i.to_be_bytes())?;
+        }
+        Tx::commit(txn)?;
+
+        let stats = env.stats()?;
+        let entries: Vec<(&str, usize)> = stats
+            .sub_dbs
+            .iter()
+            .map(|db| (db.name.as_str(), db.entries))
+            .collect();
+        assert_eq!(entries, [("first", 50), ("second", 1)]);
+        assert!(stats.sub_dbs.iter().all(|db| db.pages() > 0));
+
+        for compact in [false, true] {
+            let backup_dir = tempdir()?;
+            let target = backup_dir.path().join("backup");
+            env.backup(&target, compact)?;
+            assert!(env.backup(&target, compact).is_err(), "must not overwrite");
+
+            let backup = Lmdb::new(&target, 4)?;
+            let first = backup.open_sub_db("first")?;
+            let second = backup.open_sub_db("second")?;
+            let txn = backup.begin_ro_txn()?;
+            assert!(txn.read(&first, &0u32.to_be_bytes())?.is_none());
+            assert_eq!(txn.read(&first, &99u32.to_be_bytes())?, Some(&b"first"[..]));
+            assert_eq!(txn.read(&second, b"key")?, Some(&b"second"[..]));
+            Tx::commit(txn)?;
+            let backup_stats = backup.stats()?;
+            assert_eq!(backup_stats.sub_dbs.len(), 2);
+            if compact {
+                assert_eq!(backup_stats.free_pages, 0);
+                assert!(backup_stats.pages <= stats.pages);
             }
         }
+        Ok(())
     }
 }
@@ -0,0 +1,736 @@
+use crate::{CursorOp, Error, KvDatabase, KvHandle, RoCursor, RoTx, RwCursor, RwTx, ToCursorOp};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    marker::PhantomData,
+    ops::Bound,
+    sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock},
+};
+
+use crate::{Db, RawRead, RawWrite, Tx};
+
+/// Keys and values are immutable, reference counted byte slices
+type Bytes = Arc<[u8]>;
+
+/// Content of a single sub-database
+type Table = BTreeMap<Bytes, Bytes>;
+
+/// Content of all sub-databases
+///
+/// Every table is reference counted, so a snapshot can be taken by cloning the outer map.
+/// A table is only copied, when it is modified while it is shared with another snapshot (copy-on-write).
+type Tables = BTreeMap<Arc<str>, Arc<Table>>;
+
+/// Cursor operations of the in-memory backend
+const OP_FIRST: u32 = 0;
+const OP_LAST: u32 = 1;
+const OP_NEXT: u32 = 2;
+const OP_PREV: u32 = 3;
+const OP_CURRENT: u32 = 4;
+
+impl ToCursorOp<MemHandle> for CursorOp {
+    fn to_op(&self) -> u32 {
+        match self {
+            CursorOp::First => OP_FIRST,
+            CursorOp::Last => OP_LAST,
+            CursorOp::Next => OP_NEXT,
+            CursorOp::Prev => OP_PREV,
+            CursorOp::Current => OP_CURRENT,
+        }
+    }
+}
+
+/// Handle to a sub-database of the in-memory backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemHandle {
+    name: Arc<str>,
+}
+
+impl KvDatabase for MemHandle {}
+
+impl KvHandle<MemHandle> for MemHandle {
+    fn db(&self) -> &MemHandle {
+        self
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Names of all sub-databases, that have been created
+    sub_dbs: RwLock<BTreeSet<Arc<str>>>,
+    /// Last committed state
+    committed: RwLock<Arc<Tables>>,
+    /// Held by the active read-write transaction
+    writer: Mutex<()>,
+}
+
+/// In-memory database, which is mainly intended for tests and ephemeral runtimes.
+///
+/// The semantics follow the LMDB backend:
+/// - There is at most one read-write transaction at a time - beginning a second one blocks until the first one has finished
+/// - Read-only transactions work on the snapshot, that was committed when they were started
+/// - Nested transactions can be committed or aborted independently of their parent
+///
+/// Nothing is persisted; all data is lost, once the last clone of the database is dropped.
+#[derive(Clone, Default)]
+pub struct MemDb {
+    inner: Arc<Inner>,
+}
+
+impl MemDb {
+    /// Creates a new, empty in-memory database
+    pub fn new() -> MemDb {
+        MemDb::default()
+    }
+}
+
+/// Locks are only held for short, non-panicking operations (or by a transaction, which never leaves
+/// the committed state half-written) - so a poisoned lock still protects a consistent state.
+fn ignore_poison<G>(result: Result<G, PoisonError<G>>) -> G {
+    result.unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Db for MemDb {
+    type DB = MemHandle;
+    type Handle = MemHandle;
+    type RoTx<'env> = MemRoTx<'env>;
+    type RwTx<'env> = MemRwTx<'env>;
+
+    /// Opens a sub-database by name.
+    ///
+    /// ### Returns:
+    /// - `Ok(MemHandle)` containing a handle to the sub-database.
+    /// - `Err(Error::DbNotFound)` if the sub-database was never created.
+    fn open_sub_db(&self, name: &str) -> Result<Self::Handle, Error> {
+        let sub_dbs = ignore_poison(self.inner.sub_dbs.read());
+        match sub_dbs.get(name) {
+            Some(name) => Ok(MemHandle { name: name.clone() }),
+            None => Err(Error::DbNotFound(name.to_string())),
+        }
+    }
+
+    /// Creates a sub-database by name, or opens it if it already exists.
+    ///
+    /// The sub-database is empty until the first write to it is committed.
+    fn create_sub_db(&self, name: &str) -> Result<Self::Handle, Error> {
+        let mut sub_dbs = ignore_poison(self.inner.sub_dbs.write());
+        if let Some(name) = sub_dbs.get(name) {
+            return Ok(MemHandle { name: name.clone() });
+        }
+        let name: Arc<str> = name.into();
+        sub_dbs.insert(name.clone());
+        Ok(MemHandle { name })
+    }
+
+    /// Begins a new read-only transaction on the last committed state.
+    fn begin_ro_txn(&self) -> Result<Self::RoTx<'_>, Error> {
+        let tables = ignore_poison(self.inner.committed.read()).clone();
+        Ok(MemRoTx {
+            tables,
+            _env: PhantomData,
+        })
+    }
+
+    /// Begins a new read-write transaction. Blocks, until the current writer has finished.
+    fn begin_rw_txn(&self) -> Result<Self::RwTx<'_>, Error> {
+        let writer = ignore_poison(self.inner.writer.lock());
+        let tables = Tables::clone(&*ignore_poison(self.inner.committed.read()));
+        Ok(MemRwTx {
+            tables,
+            parent: Parent::Env {
+                committed: &self.inner.committed,
+                _writer: writer,
+            },
+            retired: Vec::new(),
+        })
+    }
+}
+
+/// Returns a slice of the given bytes, that lives as long as the transaction.
+///
+/// ### Safety
+///
+/// Keys and values are never mutated, so the slice stays valid as long as one reference to the allocation exists.
+/// The caller has to guarantee, that this is the case for the lifetime `'txn`:
+/// - Read-only transactions hold the snapshot, which contains all items, for their entire lifetime.
+/// - Read-write transactions keep every item, that is replaced or deleted through a cursor, until they are dropped
+///   (see [`MemRwTx`]). The snapshot, from which the transaction was started, is kept alive by the database.
+unsafe fn detach<'txn>(bytes: &Bytes) -> &'txn [u8] {
+    &*Arc::as_ptr(bytes)
+}
+
+/// Positions the cursor according to the given operation and returns the item at the new position
+fn seek<'a>(
+    table: &'a Table,
+    position: Option<&Bytes>,
+    op: u32,
+) -> Result<Option<(&'a Bytes, &'a Bytes)>, Error> {
+    let after = |key: &Bytes| {
+        table
+            .range::<[u8], _>((Bound::Excluded(&**key), Bound::Unbounded))
+            .next()
+    };
+    let before = |key: &Bytes| {
+        table
+            .range::<[u8], _>((Bound::Unbounded, Bound::Excluded(&**key)))
+            .next_back()
+    };
+    let item = match (op, position) {
+        (OP_FIRST, _) => table.first_key_value(),
+        (OP_LAST, _) => table.last_key_value(),
+        (OP_NEXT, Some(key)) => after(key),
+        // Like LMDB, an unpositioned cursor starts at the first item
+        (OP_NEXT, None) => table.first_key_value(),
+        (OP_PREV, Some(key)) => before(key),
+        (OP_PREV, None) => table.last_key_value(),
+        (OP_CURRENT, Some(key)) => table.get_key_value(&**key),
+        (OP_CURRENT, None) => None,
+        _ => return Err(Error::InvalidArgument),
+    };
+    Ok(item)
+}
+
+/// Positioned cursor over a table - shared between read-only and read-write cursors
+#[derive(Default)]
+struct Cursor {
+    position: RefCell<Option<Bytes>>,
+}
+
+impl Cursor {
+    /// Moves the cursor on the given table and returns the new item.
+    ///
+    /// The caller is responsible for the lifetime of the returned slices (see [`detach`]).
+    unsafe fn get<'txn>(
+        &self,
+        table: &Table,
+        op: u32,
+    ) -> Result<(Option<&'txn [u8]>, &'txn [u8]), Error> {
+        let mut position = self.position.borrow_mut();
+        match seek(table, position.as_ref(), op)? {
+            Some((key, value)) => {
+                *position = Some(key.clone());
+                Ok((Some(detach(key)), detach(value)))
+            }
+            None => Err(Error::Other(
+                "cursor is not positioned on an item".to_string(),
+            )),
+        }
+    }
+
+    /// Iterates from the item after the current position (or from the start, if the cursor is not positioned)
+    fn iter<'txn>(&self, table: Arc<Table>) -> Iter<'txn> {
+        let from = match &*self.position.borrow() {
+            Some(key) => Bound::Excluded(key.clone()),
+            None => Bound::Unbounded,
+        };
+        Iter::new(table, from)
+    }
+}
+
+/// Iterator over the key-value pairs of a table
+///
+/// The iterator works on the table as it was, when the iterator was created.
+/// It does not borrow the table, but looks up the next key on every step, so the table can be
+/// modified through a read-write cursor while the iterator is alive (which then copies the table).
+pub struct Iter<'txn> {
+    table: Arc<Table>,
+    /// Bound of the remaining items - lower bound for forward iteration, upper bound for reverse iteration
+    from: Bound<Bytes>,
+    reverse: bool,
+    _txn: PhantomData<&'txn [u8]>,
+}
+
+impl Iter<'_> {
+    fn new(table: Arc<Table>, from: Bound<Bytes>) -> Self {
+        Iter {
+            table,
+            from,
+            reverse: false,
+            _txn: PhantomData,
+        }
+    }
+
+    fn rev(table: Arc<Table>, from: Bound<Bytes>) -> Self {
+        Iter {
+            table,
+            from,
+            reverse: true,
+            _txn: PhantomData,
+        }
+    }
+}
+
+impl<'txn> Iterator for Iter<'txn> {
+    type Item = (&'txn [u8], &'txn [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let from = self.from.as_ref().map(|key| &**key);
+        let (key, value) = if self.reverse {
+            self.table
+                .range::<[u8], _>((Bound::Unbounded, from))
+                .next_back()?
+        } else {
+            self.table
+                .range::<[u8], _>((from, Bound::Unbounded))
+                .next()?
+        };
+        self.from = Bound::Excluded(key.clone());
+        // SAFETY: The items of the table are kept alive by the transaction (see `detach`)
+        unsafe { Some((detach(key), detach(value))) }
+    }
+}
+
+/// Read-only transaction of the in-memory backend, which holds a snapshot of the committed state
+pub struct MemRoTx<'env> {
+    tables: Arc<Tables>,
+    _env: PhantomData<&'env MemDb>,
+}
+
+impl MemRoTx<'_> {
+    fn table(&self, db: &impl KvHandle<MemHandle>) -> Arc<Table> {
+        self.tables.get(&db.db().name).cloned().unwrap_or_default()
+    }
+}
+
+impl Tx for MemRoTx<'_> {
+    /// Releases the snapshot (there is nothing to commit for read-only transactions).
+    fn commit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Releases the snapshot.
+    fn abort(self) {}
+}
+
+impl<'env> RawRead<'env, MemHandle> for MemRoTx<'env> {
+    fn read(
+        &self,
+        db: &impl KvHandle<MemHandle>,
+        key: &impl AsRef<[u8]>,
+    ) -> Result<Option<&[u8]>, Error> {
+        Ok(read(&self.tables, db, key))
+    }
+}
+
+fn read<'a>(
+    tables: &'a Tables,
+    db: &impl KvHandle<MemHandle>,
+    key: &impl AsRef<[u8]>,
+) -> Option<&'a [u8]> {
+    tables
+        .get(&db.db().name)
+        .and_then(|table| table.get(key.as_ref()))
+        .map(|value| &**value)
+}
+
+impl<'env> RoTx<'env, MemHandle> for MemRoTx<'env> {
+    type Cursor<'txn>
+        = MemRoCursor<'txn>
+    where
+        Self: 'txn;
+
+    fn ro_cursor<'txn>(
+        &'txn self,
+        db: &impl KvHandle<MemHandle>,
+    ) -> Result<Self::Cursor<'txn>, Error> {
+        Ok(MemRoCursor {
+            table: self.table(db),
+            cursor: Cursor::default(),
+            _txn: PhantomData,
+        })
+    }
+}
+
+/// Read-only cursor of the in-memory backend
+pub struct MemRoCursor<'txn> {
+    table: Arc<Table>,
+    cursor: Cursor,
+    _txn: PhantomData<&'txn MemRoTx<'txn>>,
+}
+
+impl<'txn> RoCursor<'txn, MemHandle> for MemRoCursor<'txn> {
+    type Iter = Iter<'txn>;
+
+    fn get<K, V>(
+        &self,
+        _key: Option<&K>,
+        _value: Option<&V>,
+        op: impl ToCursorOp<MemHandle>,
+    ) -> Result<(Option<&'txn [u8]>, &'txn [u8]), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        // SAFETY: The table is part of the snapshot, which is held by the transaction
+        unsafe { self.cursor.get(&self.table, op.to_op()) }
+    }
+
+    fn iter(&mut self) -> Self::Iter {
+        self.cursor.iter(self.table.clone())
+    }
+
+    fn iter_start(&mut self) -> Self::Iter {
+        Iter::new(self.table.clone(), Bound::Unbounded)
+    }
+
+    fn iter_from<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        Iter::new(self.table.clone(), Bound::Included(key.as_ref().into()))
+    }
+
+    fn iter_rev(&mut self) -> Self::Iter {
+        Iter::rev(self.table.clone(), Bound::Unbounded)
+    }
+
+    fn iter_from_rev<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        Iter::rev(self.table.clone(), Bound::Included(key.as_ref().into()))
+    }
+}
+
+/// Target of a read-write transaction, into which the changes are written on commit
+enum Parent<'env> {
+    /// Root transaction, which holds the writer lock of the database
+    Env {
+        committed: &'env RwLock<Arc<Tables>>,
+        _writer: MutexGuard<'env, ()>,
+    },
+    /// Nested transaction
+    Txn(&'env mut Tables),
+}
+
+/// Read-write transaction of the in-memory backend
+///
+/// The transaction works on its own copy of the state, which replaces the state of its parent on commit.
+/// Dropping the transaction discards all changes.
+pub struct MemRwTx<'env> {
+    tables: Tables,
+    parent: Parent<'env>,
+    /// Items, that were replaced or deleted through a cursor.
+    ///
+    /// Slices returned by a read-write cursor live as long as the transaction, so the underlying
+    /// items must not be freed before the transaction ends.
+    retired: Vec<Bytes>,
+}
+
+impl Tx for MemRwTx<'_> {
+    /// Commits the transaction. For nested transactions, the changes become part of the parent transaction.
+    fn commit(self) -> Result<(), Error> {
+        match self.parent {
+            Parent::Env { committed, _writer } => {
+                *ignore_poison(committed.write()) = Arc::new(self.tables);
+            }
+            Parent::Txn(parent) => *parent = self.tables,
+        }
+        Ok(())
+    }
+
+    /// Aborts the transaction, discarding all changes made within it.
+    fn abort(self) {}
+}
+
+impl<'env> RawRead<'env, MemHandle> for MemRwTx<'env> {
+    fn read(
+        &self,
+        db: &impl KvHandle<MemHandle>,
+        key: &impl AsRef<[u8]>,
+    ) -> Result<Option<&[u8]>, Error> {
+        Ok(read(&self.tables, db, key))
+    }
+}
+
+impl MemRwTx<'_> {
+    fn table_mut(&mut self, db: &impl KvHandle<MemHandle>) -> &mut Arc<Table> {
+        self.tables.entry(db.db().name.clone()).or_default()
+    }
+}
+
+impl<'env> RawWrite<'env, MemHandle> for MemRwTx<'env> {
+    fn write(
+        &mut self,
+        db: &impl KvHandle<MemHandle>,
+        key: &impl AsRef<[u8]>,
+        data: &impl AsRef<[u8]>,
+    ) -> Result<(), Error> {
+        let table = Arc::make_mut(self.table_mut(db));
+        table.insert(key.as_ref().into(), data.as_ref().into());
+        Ok(())
+    }
+
+    fn delete(
+        &mut self,
+        db: &impl KvHandle<MemHandle>,
+        key: &impl AsRef<[u8]>,
+    ) -> Result<(), Error> {
+        let table = self.table_mut(db);
+        if table.contains_key(key.as_ref()) {
+            Arc::make_mut(table).remove(key.as_ref());
+        }
+        Ok(())
+    }
+}
+
+impl<'env> RwTx<'env, MemHandle> for MemRwTx<'env> {
+    type Cursor<'txn>
+        = MemRwCursor<'txn>
+    where
+        Self: 'txn;
+
+    type RwTx<'txn>
+        = MemRwTx<'txn>
+    where
+        Self: 'txn;
+
+    fn rw_cursor<'txn>(
+        &'txn mut self,
+        db: &impl KvHandle<MemHandle>,
+    ) -> Result<Self::Cursor<'txn>, Error> {
+        let name = db.db().name.clone();
+        let table = self.tables.entry(name).or_default();
+        Ok(MemRwCursor {
+            table,
+            retired: &mut self.retired,
+            cursor: Cursor::default(),
+        })
+    }
+
+    /// Begins a nested transaction, which starts with a (copy-on-write) copy of the current state.
+    fn nested_txn(&mut self) -> Result<Self::RwTx<'_>, Error> {
+        Ok(MemRwTx {
+            tables: self.tables.clone(),
+            parent: Parent::Txn(&mut self.tables),
+            retired: Vec::new(),
+        })
+    }
+}
+
+/// Read-write cursor of the in-memory backend
+///
+/// The cursor sees all changes of its transaction, including the ones that are made through the cursor.
+pub struct MemRwCursor<'txn> {
+    table: &'txn mut Arc<Table>,
+    retired: &'txn mut Vec<Bytes>,
+    cursor: Cursor,
+}
+
+impl<'txn> RwCursor<'txn, MemHandle> for MemRwCursor<'txn> {
+    type Iter = Iter<'txn>;
+
+    fn get<K, V>(
+        &self,
+        _key: Option<&K>,
+        _value: Option<&V>,
+        op: impl ToCursorOp<MemHandle>,
+    ) -> Result<(Option<&'txn [u8]>, &'txn [u8]), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        // SAFETY: Items are retired instead of dropped, when they are replaced through the cursor
+        unsafe { self.cursor.get(self.table, op.to_op()) }
+    }
+
+    /// Puts a key/data pair into the database and positions the cursor at the new item.
+    fn put<K, V>(&mut self, key: &K, value: &V) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let table = Arc::make_mut(self.table);
+        if let Some((old_key, old_value)) = table.remove_entry(key.as_ref()) {
+            self.retired.push(old_key);
+            self.retired.push(old_value);
+        }
+        let key: Bytes = key.as_ref().into();
+        table.insert(key.clone(), value.as_ref().into());
+        *self.cursor.position.get_mut() = Some(key);
+        Ok(())
+    }
+
+    /// Deletes the current key/data pair. A following `Next` operation moves the cursor to the next item.
+    fn del(&mut self) -> Result<(), Error> {
+        let Some(key) = self.cursor.position.get_mut().clone() else {
+            return Err(Error::InvalidArgument);
+        };
+        if !self.table.contains_key(&key) {
+            return Err(Error::InvalidArgument);
+        }
+        let table = Arc::make_mut(self.table);
+        if let Some((old_key, old_value)) = table.remove_entry(&key) {
+            self.retired.push(old_key);
+            self.retired.push(old_value);
+        }
+        Ok(())
+    }
+
+    fn iter(&mut self) -> Self::Iter {
+        self.cursor.iter(self.table.clone())
+    }
+
+    fn iter_start(&mut self) -> Self::Iter {
+        Iter::new(self.table.clone(), Bound::Unbounded)
+    }
+
+    fn iter_from<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        Iter::new(self.table.clone(), Bound::Included(key.as_ref().into()))
+    }
+
+    fn iter_rev(&mut self) -> Self::Iter {
+        Iter::rev(self.table.clone(), Bound::Unbounded)
+    }
+
+    fn iter_from_rev<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        Iter::rev(self.table.clone(), Bound::Included(key.as_ref().into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::test_suite;
+
+    #[test]
+    fn read_write_delete() -> Result<(), Box<dyn std::error::Error>> {
+        test_suite::read_write_delete(&MemDb::new())
+    }
+
+    #[test]
+    fn not_found_is_none() -> Result<(), Box<dyn std::error::Error>> {
+        test_suite::not_found_is_none(&MemDb::new())
+    }
+
+    #[test]
+    fn non_existing_db() {
+        test_suite::non_existing_db(&MemDb::new());
+    }
+
+    #[test]
+    fn abort_discards_changes() -> Result<(), Box<dyn std::error::Error>> {
+        test_suite::abort_discards_changes(&MemDb::new())
+    }
+
+    #[test]
+    fn sub_dbs_are_isolated() -> Result<(), Box<dyn std::error::Error>> {
+        test_suite::sub_dbs_are_isolated(&MemDb::new())
+    }
+
+    #[test]
+    fn cursor_iteration() -> Result<(), Box<dyn std::error::Error>> {
+        test_suite::cursor_iteration(&MemDb::new())
+    }
+
+    #[test]
+    fn rw_cursor_sees_own_writes() -> Result<(), Box<dyn std::error::Error>> {
+        test_suite::rw_cursor_sees_own_writes(&MemDb::new())
+    }
+
+    #[test]
+    fn ro_txn_reads_snapshot() -> Result<(), Box<dyn std::error::Error>> {
+        test_suite::ro_txn_reads_snapshot(&MemDb::new())
+    }
+
+    #[test]
+    fn cursor_ranges() -> Result<(), Box<dyn std::error::Error>> {
+        test_suite::cursor_ranges(&MemDb::new())
+    }
+
+    #[test]
+    fn nested_transactions() -> Result<(), Box<dyn std::error::Error>> {
+        let env = MemDb::new();
+        let handle = env.create_sub_db("test")?;
+        let mut txn = env.begin_rw_txn()?;
+        txn.write(&handle, b"outer", b"1")?;
+        {
+            let mut nested = txn.nested_txn()?;
+            nested.write(&handle, b"committed", b"2")?;
+            {
+                let mut inner = nested.nested_txn()?;
+                inner.write(&handle, b"inner", b"3")?;
+                inner.commit()?;
+            }
+            nested.commit()?;
+        }
+        {
+            let mut nested = txn.nested_txn()?;
+            nested.write(&handle, b"aborted", b"4")?;
+            {
+                // Changes of committed children are reverted together with their parent
+                let mut inner = nested.nested_txn()?;
+                inner.write(&handle, b"aborted-inner", b"5")?;
+                inner.commit()?;
+            }
+            nested.abort();
+        }
+        {
+            // Dropping a nested transaction aborts it
+            let mut nested = txn.nested_txn()?;
+            nested.write(&handle, b"dropped", b"6")?;
+        }
+        for key in ["outer", "committed", "inner"] {
+            assert!(txn.read(&handle, &key)?.is_some(), "missing key {key}");
+        }
+        for key in ["aborted", "aborted-inner", "dropped"] {
+            assert!(txn.read(&handle, &key)?.is_none(), "unexpected key {key}");
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    #[test]
+    fn cursor_slices_outlive_modifications() -> Result<(), Box<dyn std::error::Error>> {
+        let env = MemDb::new();
+        let handle = env.create_sub_db("test")?;
+        let mut txn = env.begin_rw_txn()?;
+        txn.write(&handle, b"a", b"1")?;
+        txn.write(&handle, b"b", b"2")?;
+        let mut cursor = txn.rw_cursor(&handle)?;
+        let (key, value) = cursor.get(None::<&&[u8]>, None::<&&[u8]>, CursorOp::First)?;
+        let mut iter = cursor.iter_start();
+        cursor.put(b"a", b"replaced")?;
+        cursor.del()?;
+        // Slices and iterators still point to the data before the modification
+        assert_eq!((key, value), (Some(&b"a"[..]), &b"1"[..]));
+        assert_eq!(iter.next(), Some((&b"a"[..], &b"1"[..])));
+        assert_eq!(iter.next(), Some((&b"b"[..], &b"2"[..])));
+        let (key, _) = cursor.get(None::<&&[u8]>, None::<&&[u8]>, CursorOp::Next)?;
+        assert_eq!(key, Some(&b"b"[..]));
+        drop(cursor);
+        assert!(txn.read(&handle, b"a")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn writer_blocks_until_commit() -> Result<(), Box<dyn std::error::Error>> {
+        let env = MemDb::new();
+        let handle = env.create_sub_db("test")?;
+        let mut txn = env.begin_rw_txn()?;
+        txn.write(&handle, b"key", b"first")?;
+        let second = std::thread::spawn({
+            let env = env.clone();
+            let handle = handle.clone();
+            move || -> Result<(), Error> {
+                let mut txn = env.begin_rw_txn()?;
+                // The first writer has committed, before we get the lock
+                assert_eq!(txn.read(&handle, b"key")?, Some(&b"first"[..]));
+                txn.write(&handle, b"key", b"second")?;
+                txn.commit()
+            }
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        txn.commit()?;
+        second.join().expect("writer thread panicked")?;
+        let txn = env.begin_ro_txn()?;
+        assert_eq!(txn.read(&handle, b"key")?, Some(&b"second"[..]));
+        Ok(())
+    }
+}
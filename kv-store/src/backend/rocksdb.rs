@@ -1 +1,817 @@
+use crate::{CursorOp, Error, KvDatabase, KvHandle, RoCursor, RoTx, RwCursor, RwTx, ToCursorOp};
+use rocksdb::{
+    BoundColumnFamily, DBAccess, DBRawIteratorWithThreadMode, MultiThreaded, Options,
+    SnapshotWithThreadMode, Transaction, TransactionDB, TransactionDBOptions,
+};
+use std::{
+    cell::{Cell, RefCell},
+    path::Path,
+    sync::Arc,
+};
 
+use crate::{Db, RawRead, RawWrite, Tx};
+
+type TxnDb = TransactionDB<MultiThreaded>;
+
+/// Converts RocksDB errors (`rocksdb::Error`) into the database interface `Error` type.
+///
+/// ### Notes:
+/// - RocksDB reports missing keys as `Ok(None)`, so `NotFound` only occurs for missing files or column families.
+/// - Transaction conflicts (`Busy`, `TryAgain`, `TimedOut`) are reported as such, so callers can retry.
+impl From<rocksdb::Error> for Error {
+    fn from(value: rocksdb::Error) -> Error {
+        use rocksdb::ErrorKind;
+        match value.kind() {
+            ErrorKind::Corruption => Error::Corrupted,
+            ErrorKind::InvalidArgument => Error::InvalidArgument,
+            ErrorKind::IOError => Error::IOError,
+            ErrorKind::TimedOut => Error::TimedOut,
+            ErrorKind::Busy | ErrorKind::TryAgain => Error::Busy(value.into_string()),
+            ErrorKind::Unknown => Error::Unknown,
+            _ => Error::Other(value.into_string()),
+        }
+    }
+}
+
+/// Cursor operations of the RocksDB backend
+///
+/// RocksDB has no native cursor operation codes, so we simply enumerate the supported operations.
+const OP_FIRST: u32 = 0;
+const OP_LAST: u32 = 1;
+const OP_NEXT: u32 = 2;
+const OP_PREV: u32 = 3;
+const OP_CURRENT: u32 = 4;
+
+impl ToCursorOp<CfHandle> for CursorOp {
+    fn to_op(&self) -> u32 {
+        match self {
+            CursorOp::First => OP_FIRST,
+            CursorOp::Last => OP_LAST,
+            CursorOp::Next => OP_NEXT,
+            CursorOp::Prev => OP_PREV,
+            CursorOp::Current => OP_CURRENT,
+        }
+    }
+}
+
+/// Handle to a sub-database, which is mapped to a RocksDB column family.
+///
+/// The handle only stores the name of the column family;
+/// the native handle is resolved for every operation, as it is bound to the lifetime of the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfHandle {
+    name: Arc<str>,
+}
+
+impl KvDatabase for CfHandle {}
+
+impl KvHandle<CfHandle> for CfHandle {
+    fn db(&self) -> &CfHandle {
+        self
+    }
+}
+
+/// Represents a RocksDB database for managing sub-databases (column families) and transactions.
+///
+/// In contrast to LMDB, RocksDB allows multiple concurrent writers and has no fixed map size,
+/// which makes it better suited for write-heavy workloads.
+/// Transactions are pessimistic (`TransactionDB`), so concurrent writes to the same key
+/// either wait for the lock or fail with [`Error::Busy`] or [`Error::TimedOut`].
+#[derive(Clone)]
+pub struct RocksDb {
+    db: Arc<TxnDb>,
+}
+
+impl RocksDb {
+    /// Opens (or creates) a RocksDB database at the specified path.
+    ///
+    /// All existing column families are opened, so every sub-database that was created before is available.
+    ///
+    /// ### Parameters:
+    /// - `path`: The filesystem path where the database will be created or accessed.
+    ///
+    /// ### Returns:
+    /// - `Ok(RocksDb)` if the database was successfully opened.
+    /// - `Err(Error)` if an error occurred during initialization.
+    pub fn new(path: &Path) -> Result<RocksDb, Error> {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+
+        // NOTE: Listing fails, if the database does not exist yet
+        let cfs = TxnDb::list_cf(&opts, path).unwrap_or_default();
+        let db = TxnDb::open_cf(&opts, &TransactionDBOptions::default(), path, cfs)?;
+        Ok(RocksDb { db: Arc::new(db) })
+    }
+}
+
+/// Resolves the native column family of the given handle
+fn column_family<'db>(
+    db: &'db TxnDb,
+    handle: &impl KvHandle<CfHandle>,
+) -> Result<Arc<BoundColumnFamily<'db>>, Error> {
+    let name = &handle.db().name;
+    db.cf_handle(name)
+        .ok_or_else(|| Error::DbNotFound(name.to_string()))
+}
+
+impl Db for RocksDb {
+    type DB = CfHandle;
+    type Handle = CfHandle;
+    type RoTx<'env> = RocksRoTx<'env>;
+    type RwTx<'env> = RocksRwTx<'env>;
+
+    /// Opens a sub-database (column family) by name.
+    ///
+    /// ### Returns:
+    /// - `Ok(CfHandle)` containing a handle to the column family.
+    /// - `Err(Error::DbNotFound)` if the column family does not exist.
+    fn open_sub_db(&self, name: &str) -> Result<Self::Handle, Error> {
+        let name = normalize_name(name);
+        if self.db.cf_handle(name).is_none() {
+            return Err(Error::DbNotFound(name.to_string()));
+        }
+        Ok(CfHandle { name: name.into() })
+    }
+
+    /// Creates a sub-database (column family) by name, or opens it if it already exists.
+    fn create_sub_db(&self, name: &str) -> Result<Self::Handle, Error> {
+        let name = normalize_name(name);
+        if self.db.cf_handle(name).is_none() {
+            if let Err(e) = self.db.create_cf(name, &Options::default()) {
+                // Another thread might have created the column family in the meantime
+                if self.db.cf_handle(name).is_none() {
+                    return Err(e.into());
+                }
+            }
+        }
+        Ok(CfHandle { name: name.into() })
+    }
+
+    /// Begins a new read-only transaction, which reads from a consistent snapshot of the database.
+    fn begin_ro_txn(&self) -> Result<Self::RoTx<'_>, Error> {
+        Ok(RocksRoTx {
+            db: &self.db,
+            snapshot: self.db.snapshot(),
+            arena: Arena::default(),
+        })
+    }
+
+    /// Begins a new read-write transaction.
+    fn begin_rw_txn(&self) -> Result<Self::RwTx<'_>, Error> {
+        Ok(RocksRwTx {
+            db: &self.db,
+            txn: TxnRef::Root {
+                txn: Some(self.db.transaction()),
+                savepoints: Cell::new(0),
+            },
+            arena: Arena::default(),
+        })
+    }
+}
+
+/// The default column family of RocksDB is called "default" - same as the unnamed database of LMDB
+fn normalize_name(name: &str) -> &str {
+    if name.eq_ignore_ascii_case("default") {
+        rocksdb::DEFAULT_COLUMN_FAMILY_NAME
+    } else {
+        name
+    }
+}
+
+/// Append-only storage for values, that have been read within a transaction.
+///
+/// The traits hand out slices that live as long as the transaction,
+/// but RocksDB only guarantees the validity of a value until the next operation.
+/// Therefore, every value is copied into the arena and is released together with the transaction.
+#[derive(Default)]
+struct Arena(RefCell<Vec<*mut [u8]>>);
+
+impl Arena {
+    fn alloc(&self, data: &[u8]) -> &[u8] {
+        let ptr = Box::into_raw(Box::<[u8]>::from(data));
+        self.0.borrow_mut().push(ptr);
+        // SAFETY: The allocation is owned by the arena and is neither moved, mutated nor freed
+        // before the arena is dropped, which cannot happen while the returned borrow is alive.
+        unsafe { &*ptr }
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        for ptr in self.0.get_mut().drain(..) {
+            // SAFETY: The pointer was created by `Box::into_raw` and is only freed once.
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+    }
+}
+
+/// Read-only transaction of the RocksDB backend, which is based on a snapshot.
+pub struct RocksRoTx<'env> {
+    db: &'env TxnDb,
+    snapshot: SnapshotWithThreadMode<'env, TxnDb>,
+    arena: Arena,
+}
+
+impl Tx for RocksRoTx<'_> {
+    /// Releases the snapshot (there is nothing to commit for read-only transactions).
+    fn commit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Releases the snapshot.
+    fn abort(self) {}
+}
+
+impl<'env> RawRead<'env, CfHandle> for RocksRoTx<'env> {
+    fn read(
+        &self,
+        db: &impl KvHandle<CfHandle>,
+        key: &impl AsRef<[u8]>,
+    ) -> Result<Option<&[u8]>, Error> {
+        let cf = column_family(self.db, db)?;
+        let value = self.snapshot.get_pinned_cf(&cf, key)?;
+        Ok(value.map(|v| self.arena.alloc(&v)))
+    }
+}
+
+impl<'env> RoTx<'env, CfHandle> for RocksRoTx<'env> {
+    type Cursor<'txn>
+        = RocksRoCursor<'txn>
+    where
+        Self: 'txn;
+
+    fn ro_cursor<'txn>(
+        &'txn self,
+        db: &impl KvHandle<CfHandle>,
+    ) -> Result<Self::Cursor<'txn>, Error> {
+        let cf = column_family(self.db, db)?;
+        Ok(RocksRoCursor {
+            cursor: Cursor::new(self.snapshot.raw_iterator_cf(&cf), &self.arena),
+            snapshot: &self.snapshot,
+            cf,
+        })
+    }
+}
+
+/// Read-only cursor of the RocksDB backend
+pub struct RocksRoCursor<'txn> {
+    cursor: Cursor<'txn, TxnDb>,
+    snapshot: &'txn SnapshotWithThreadMode<'txn, TxnDb>,
+    cf: Arc<BoundColumnFamily<'txn>>,
+}
+
+impl<'txn> RocksRoCursor<'txn> {
+    fn raw_iter(&self) -> DBRawIteratorWithThreadMode<'txn, TxnDb> {
+        self.snapshot.raw_iterator_cf(&self.cf)
+    }
+}
+
+impl<'txn> RoCursor<'txn, CfHandle> for RocksRoCursor<'txn> {
+    type Iter = Iter<'txn, TxnDb>;
+
+    fn get<K, V>(
+        &self,
+        _key: Option<&K>,
+        _value: Option<&V>,
+        op: impl ToCursorOp<CfHandle>,
+    ) -> Result<(Option<&'txn [u8]>, &'txn [u8]), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.cursor.get(op.to_op())
+    }
+
+    fn iter(&mut self) -> Self::Iter {
+        self.cursor.iter(self.raw_iter())
+    }
+
+    fn iter_start(&mut self) -> Self::Iter {
+        Iter::start(self.raw_iter(), self.cursor.arena)
+    }
+
+    fn iter_from<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        Iter::from_key(self.raw_iter(), self.cursor.arena, key)
+    }
+
+    fn iter_rev(&mut self) -> Self::Iter {
+        Iter::rev(self.raw_iter(), self.cursor.arena)
+    }
+
+    fn iter_from_rev<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        Iter::from_key_rev(self.raw_iter(), self.cursor.arena, key)
+    }
+}
+
+/// Transaction of a read-write transaction - either the transaction itself or a nested transaction
+///
+/// RocksDB has no nested transactions, so they are emulated with savepoints of the root transaction.
+/// As savepoints cannot be released without rolling them back, a committed nested transaction
+/// leaves its savepoint on the stack. This is fine: aborting an outer nested transaction
+/// rolls back all savepoints down to its own, which also reverts the changes of its committed children.
+enum TxnRef<'env> {
+    Root {
+        txn: Option<Transaction<'env, TxnDb>>,
+        /// Number of savepoints on the stack of the transaction
+        savepoints: Cell<usize>,
+    },
+    Nested {
+        txn: &'env Transaction<'env, TxnDb>,
+        savepoints: &'env Cell<usize>,
+        /// Number of savepoints on the stack before this transaction was started
+        depth: usize,
+        done: bool,
+    },
+}
+
+/// Read-write transaction of the RocksDB backend
+pub struct RocksRwTx<'env> {
+    db: &'env TxnDb,
+    txn: TxnRef<'env>,
+    arena: Arena,
+}
+
+impl<'env> RocksRwTx<'env> {
+    fn txn(&self) -> &Transaction<'env, TxnDb> {
+        match &self.txn {
+            TxnRef::Root { txn, .. } => txn.as_ref().expect("transaction is only taken on commit"),
+            TxnRef::Nested { txn, .. } => txn,
+        }
+    }
+
+    fn savepoints(&self) -> &Cell<usize> {
+        match &self.txn {
+            TxnRef::Root { savepoints, .. } => savepoints,
+            TxnRef::Nested { savepoints, .. } => savepoints,
+        }
+    }
+}
+
+impl Drop for RocksRwTx<'_> {
+    /// Nested transactions, that were not committed, are aborted (like nested transactions in LMDB).
+    ///
+    /// Root transactions are rolled back by RocksDB itself, when they are dropped.
+    fn drop(&mut self) {
+        if let TxnRef::Nested {
+            txn,
+            savepoints,
+            depth,
+            done: false,
+        } = &self.txn
+        {
+            while savepoints.get() > *depth {
+                // NOTE: This can only fail, if there is no savepoint - which we keep track of
+                let _ = txn.rollback_to_savepoint();
+                savepoints.set(savepoints.get() - 1);
+            }
+        }
+    }
+}
+
+impl Tx for RocksRwTx<'_> {
+    /// Commits the transaction. For nested transactions, the changes become part of the parent transaction.
+    fn commit(mut self) -> Result<(), Error> {
+        match &mut self.txn {
+            TxnRef::Root { txn, .. } => {
+                let txn = txn.take().expect("transaction is only taken on commit");
+                txn.commit()?;
+            }
+            TxnRef::Nested { done, .. } => *done = true,
+        }
+        Ok(())
+    }
+
+    /// Aborts the transaction, discarding all changes made within it.
+    fn abort(self) {
+        // Dropping the transaction rolls back all changes
+    }
+}
+
+impl<'env> RawRead<'env, CfHandle> for RocksRwTx<'env> {
+    fn read(
+        &self,
+        db: &impl KvHandle<CfHandle>,
+        key: &impl AsRef<[u8]>,
+    ) -> Result<Option<&[u8]>, Error> {
+        let cf = column_family(self.db, db)?;
+        let value = self.txn().get_pinned_cf(&cf, key)?;
+        Ok(value.map(|v| self.arena.alloc(&v)))
+    }
+}
+
+impl<'env> RawWrite<'env, CfHandle> for RocksRwTx<'env> {
+    fn write(
+        &mut self,
+        db: &impl KvHandle<CfHandle>,
+        key: &impl AsRef<[u8]>,
+        data: &impl AsRef<[u8]>,
+    ) -> Result<(), Error> {
+        let cf = column_family(self.db, db)?;
+        self.txn().put_cf(&cf, key, data)?;
+        Ok(())
+    }
+
+    fn delete(
+        &mut self,
+        db: &impl KvHandle<CfHandle>,
+        key: &impl AsRef<[u8]>,
+    ) -> Result<(), Error> {
+        let cf = column_family(self.db, db)?;
+        self.txn().delete_cf(&cf, key)?;
+        Ok(())
+    }
+}
+
+impl<'env> RwTx<'env, CfHandle> for RocksRwTx<'env> {
+    type Cursor<'txn>
+        = RocksRwCursor<'txn>
+    where
+        Self: 'txn;
+
+    type RwTx<'txn>
+        = RocksRwTx<'txn>
+    where
+        Self: 'txn;
+
+    fn rw_cursor<'txn>(
+        &'txn mut self,
+        db: &impl KvHandle<CfHandle>,
+    ) -> Result<Self::Cursor<'txn>, Error> {
+        let cf = column_family(self.db, db)?;
+        let txn = self.txn();
+        Ok(RocksRwCursor {
+            cursor: Cursor::new(txn.raw_iterator_cf(&cf), &self.arena),
+            txn,
+            cf,
+        })
+    }
+
+    /// Begins a nested transaction, which is backed by a savepoint of the root transaction.
+    fn nested_txn(&mut self) -> Result<Self::RwTx<'_>, Error> {
+        let txn = self.txn();
+        let savepoints = self.savepoints();
+        let depth = savepoints.get();
+        txn.set_savepoint();
+        savepoints.set(depth + 1);
+        Ok(RocksRwTx {
+            db: self.db,
+            txn: TxnRef::Nested {
+                txn,
+                savepoints,
+                depth,
+                done: false,
+            },
+            arena: Arena::default(),
+        })
+    }
+}
+
+/// Read-write cursor of the RocksDB backend
+///
+/// The cursor sees all changes of its transaction, including the ones that are made through the cursor.
+pub struct RocksRwCursor<'txn> {
+    cursor: Cursor<'txn, Transaction<'txn, TxnDb>>,
+    txn: &'txn Transaction<'txn, TxnDb>,
+    cf: Arc<BoundColumnFamily<'txn>>,
+}
+
+impl<'txn> RocksRwCursor<'txn> {
+    fn raw_iter(&self) -> DBRawIteratorWithThreadMode<'txn, Transaction<'txn, TxnDb>> {
+        self.txn.raw_iterator_cf(&self.cf)
+    }
+}
+
+impl<'txn> RwCursor<'txn, CfHandle> for RocksRwCursor<'txn> {
+    type Iter = Iter<'txn, Transaction<'txn, TxnDb>>;
+
+    fn get<K, V>(
+        &self,
+        _key: Option<&K>,
+        _value: Option<&V>,
+        op: impl ToCursorOp<CfHandle>,
+    ) -> Result<(Option<&'txn [u8]>, &'txn [u8]), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.cursor.get(op.to_op())
+    }
+
+    /// Puts a key/data pair into the database and positions the cursor at the new item.
+    fn put<K, V>(&mut self, key: &K, value: &V) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.txn.put_cf(&self.cf, key, value)?;
+        // Modifying the transaction invalidates the current position of the iterator
+        self.cursor.raw.get_mut().seek(key);
+        Ok(())
+    }
+
+    /// Deletes the current key/data pair. The cursor is positioned at the next item afterwards.
+    fn del(&mut self) -> Result<(), Error> {
+        let raw = self.cursor.raw.get_mut();
+        let key = match raw.key() {
+            Some(key) => key.to_vec(),
+            None => return Err(Error::InvalidArgument),
+        };
+        self.txn.delete_cf(&self.cf, &key)?;
+        raw.seek(&key);
+        Ok(())
+    }
+
+    fn iter(&mut self) -> Self::Iter {
+        self.cursor.iter(self.raw_iter())
+    }
+
+    fn iter_start(&mut self) -> Self::Iter {
+        Iter::start(self.raw_iter(), self.cursor.arena)
+    }
+
+    fn iter_from<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        Iter::from_key(self.raw_iter(), self.cursor.arena, key)
+    }
+
+    fn iter_rev(&mut self) -> Self::Iter {
+        Iter::rev(self.raw_iter(), self.cursor.arena)
+    }
+
+    fn iter_from_rev<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>,
+    {
+        Iter::from_key_rev(self.raw_iter(), self.cursor.arena, key)
+    }
+}
+
+/// Positioned cursor over a column family - shared between read-only and read-write cursors
+struct Cursor<'txn, D: DBAccess> {
+    raw: RefCell<DBRawIteratorWithThreadMode<'txn, D>>,
+    arena: &'txn Arena,
+}
+
+impl<'txn, D: DBAccess> Cursor<'txn, D> {
+    fn new(raw: DBRawIteratorWithThreadMode<'txn, D>, arena: &'txn Arena) -> Self {
+        Self {
+            raw: RefCell::new(raw),
+            arena,
+        }
+    }
+
+    fn get(&self, op: u32) -> Result<(Option<&'txn [u8]>, &'txn [u8]), Error> {
+        let mut raw = self.raw.borrow_mut();
+        match op {
+            OP_FIRST => raw.seek_to_first(),
+            OP_LAST => raw.seek_to_last(),
+            OP_NEXT if raw.valid() => raw.next(),
+            // Like LMDB, an unpositioned cursor starts at the first item
+            OP_NEXT => raw.seek_to_first(),
+            OP_PREV if raw.valid() => raw.prev(),
+            OP_PREV => raw.seek_to_last(),
+            OP_CURRENT => (),
+            _ => return Err(Error::InvalidArgument),
+        }
+        raw.status()?;
+        match raw.item() {
+            Some((key, value)) => Ok((Some(self.arena.alloc(key)), self.arena.alloc(value))),
+            None => Err(Error::Other(
+                "cursor is not positioned on an item".to_string(),
+            )),
+        }
+    }
+
+    /// Iterates from the item after the current position (or from the start, if the cursor is not positioned)
+    fn iter(&self, mut raw: DBRawIteratorWithThreadMode<'txn, D>) -> Iter<'txn, D> {
+        let current = self.raw.borrow();
+        match current.key() {
+            Some(key) => {
+                raw.seek(key);
+                if raw.key() == Some(key) {
+                    raw.next();
+                }
+            }
+            None => raw.seek_to_first(),
+        }
+        Iter {
+            raw,
+            arena: self.arena,
+            advance: false,
+            reverse: false,
+        }
+    }
+}
+
+/// Iterator over the key-value pairs of a column family
+pub struct Iter<'txn, D: DBAccess> {
+    raw: DBRawIteratorWithThreadMode<'txn, D>,
+    arena: &'txn Arena,
+    /// Whether the iterator has to be advanced before the next item is returned
+    advance: bool,
+    reverse: bool,
+}
+
+impl<'txn, D: DBAccess> Iter<'txn, D> {
+    fn start(mut raw: DBRawIteratorWithThreadMode<'txn, D>, arena: &'txn Arena) -> Self {
+        raw.seek_to_first();
+        Iter {
+            raw,
+            arena,
+            advance: false,
+            reverse: false,
+        }
+    }
+
+    fn from_key<K: AsRef<[u8]>>(
+        mut raw: DBRawIteratorWithThreadMode<'txn, D>,
+        arena: &'txn Arena,
+        key: &K,
+    ) -> Self {
+        raw.seek(key);
+        Iter {
+            raw,
+            arena,
+            advance: false,
+            reverse: false,
+        }
+    }
+
+    fn rev(mut raw: DBRawIteratorWithThreadMode<'txn, D>, arena: &'txn Arena) -> Self {
+        raw.seek_to_last();
+        Iter {
+            raw,
+            arena,
+            advance: false,
+            reverse: true,
+        }
+    }
+
+    fn from_key_rev<K: AsRef<[u8]>>(
+        mut raw: DBRawIteratorWithThreadMode<'txn, D>,
+        arena: &'txn Arena,
+        key: &K,
+    ) -> Self {
+        // Positions the iterator at the last key, that is less or equal to the given key
+        raw.seek_for_prev(key);
+        Iter {
+            raw,
+            arena,
+            advance: false,
+            reverse: true,
+        }
+    }
+}
+
+impl<'txn, D: DBAccess> Iterator for Iter<'txn, D> {
+    type Item = (&'txn [u8], &'txn [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // NOTE: Like the LMDB backend, errors of the iterator simply end the iteration.
+        if self.advance {
+            if self.reverse {
+                self.raw.prev();
+            } else {
+                self.raw.next();
+            }
+        }
+        self.advance = true;
+        let (key, value) = self.raw.item()?;
+        Some((self.arena.alloc(key), self.arena.alloc(value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::test_suite;
+    use tempfile::tempdir;
+
+    fn open_tmp_rocksdb() -> (tempfile::TempDir, RocksDb) {
+        let temp_dir = tempdir().unwrap();
+        let env = RocksDb::new(temp_dir.path()).unwrap();
+        (temp_dir, env)
+    }
+
+    #[test]
+    fn read_write_delete() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, env) = open_tmp_rocksdb();
+        test_suite::read_write_delete(&env)
+    }
+
+    #[test]
+    fn not_found_is_none() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, env) = open_tmp_rocksdb();
+        test_suite::not_found_is_none(&env)
+    }
+
+    #[test]
+    fn non_existing_db() {
+        let (_dir, env) = open_tmp_rocksdb();
+        test_suite::non_existing_db(&env);
+    }
+
+    #[test]
+    fn abort_discards_changes() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, env) = open_tmp_rocksdb();
+        test_suite::abort_discards_changes(&env)
+    }
+
+    #[test]
+    fn sub_dbs_are_isolated() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, env) = open_tmp_rocksdb();
+        test_suite::sub_dbs_are_isolated(&env)
+    }
+
+    #[test]
+    fn cursor_iteration() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, env) = open_tmp_rocksdb();
+        test_suite::cursor_iteration(&env)
+    }
+
+    #[test]
+    fn rw_cursor_sees_own_writes() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, env) = open_tmp_rocksdb();
+        test_suite::rw_cursor_sees_own_writes(&env)
+    }
+
+    #[test]
+    fn ro_txn_reads_snapshot() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, env) = open_tmp_rocksdb();
+        test_suite::ro_txn_reads_snapshot(&env)
+    }
+
+    #[test]
+    fn cursor_ranges() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, env) = open_tmp_rocksdb();
+        test_suite::cursor_ranges(&env)
+    }
+
+    #[test]
+    fn nested_transactions() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, env) = open_tmp_rocksdb();
+        let handle = env.create_sub_db("test")?;
+        let mut txn = env.begin_rw_txn()?;
+        txn.write(&handle, b"outer", b"1")?;
+        {
+            let mut nested = txn.nested_txn()?;
+            nested.write(&handle, b"committed", b"2")?;
+            {
+                let mut inner = nested.nested_txn()?;
+                inner.write(&handle, b"inner", b"3")?;
+                inner.commit()?;
+            }
+            nested.commit()?;
+        }
+        {
+            let mut nested = txn.nested_txn()?;
+            nested.write(&handle, b"aborted", b"4")?;
+            {
+                // Changes of committed children are reverted together with their parent
+                let mut inner = nested.nested_txn()?;
+                inner.write(&handle, b"aborted-inner", b"5")?;
+                inner.commit()?;
+            }
+            nested.abort();
+        }
+        {
+            // Dropping a nested transaction aborts it
+            let mut nested = txn.nested_txn()?;
+            nested.write(&handle, b"dropped", b"6")?;
+        }
+        for key in ["outer", "committed", "inner"] {
+            assert!(txn.read(&handle, &key)?.is_some(), "missing key {key}");
+        }
+        for key in ["aborted", "aborted-inner", "dropped"] {
+            assert!(txn.read(&handle, &key)?.is_none(), "unexpected key {key}");
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    #[test]
+    fn reopen_keeps_sub_dbs() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        {
+            let env = RocksDb::new(temp_dir.path())?;
+            let handle = env.create_sub_db("persistent")?;
+            let mut txn = env.begin_rw_txn()?;
+            txn.write(&handle, b"key", b"value")?;
+            txn.commit()?;
+        }
+        let env = RocksDb::new(temp_dir.path())?;
+        let handle = env.open_sub_db("persistent")?;
+        let txn = env.begin_ro_txn()?;
+        assert_eq!(txn.read(&handle, b"key")?, Some(&b"value"[..]));
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+//! Generic test suite, that every backend has to pass
+//!
+//! The tests are written against the traits only. Each backend calls them from its own test module.
+
+use crate::{
+    Db, Error, KvDatabase, KvHandle, RawRead, RawWrite, RoCursor, RoTx, RwCursor, RwTx, Tx,
+};
+use rand::Rng;
+use std::ops::Bound;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+const TEST_REPEATS: usize = 4;
+
+fn create_test_handle<DB: KvDatabase, Env: Db>(env: &Env) -> impl KvHandle<DB>
+where
+    <Env as Db>::Handle: KvHandle<DB>,
+{
+    env.create_sub_db("test").unwrap()
+}
+
+fn write_with_rw_txn<'env, DB: KvDatabase, Txn: RwTx<'env, DB>>(
+    txn: &mut Txn,
+    handle: &impl KvHandle<DB>,
+    key: &impl AsRef<[u8]>,
+    data: &impl AsRef<[u8]>,
+) -> Result<(), Error> {
+    txn.write(handle, &key, &data)?;
+    Ok(())
+}
+
+fn read_with_ro_txn<'env, DB: KvDatabase, Txn: RoTx<'env, DB>>(
+    txn: &Txn,
+    handle: &impl KvHandle<DB>,
+    key: &impl AsRef<[u8]>,
+) -> Result<Option<Vec<u8>>, Error> {
+    let buf = txn.read(handle, &key)?;
+    let out: Option<Vec<u8>> = buf.map(|v| v.to_vec());
+    Ok(out)
+}
+
+fn delete_with_rw_txn<'env, DB: KvDatabase, Txn: RwTx<'env, DB>>(
+    txn: &mut Txn,
+    handle: &impl KvHandle<DB>,
+    key: &impl AsRef<[u8]>,
+) -> Result<(), Error> {
+    txn.delete(handle, &key)?;
+    Ok(())
+}
+
+/// Writes the given key-value pairs in a single transaction
+fn write_all<E: Db>(env: &E, handle: &E::Handle, pairs: &[(&[u8], &[u8])]) -> TestResult {
+    let mut txn = env.begin_rw_txn()?;
+    for (key, value) in pairs {
+        txn.write(handle, key, value)?;
+    }
+    Tx::commit(txn)?;
+    Ok(())
+}
+
+pub fn read_write_delete<E: Db>(env: &E) -> TestResult {
+    let handle = create_test_handle(env);
+
+    for _ in 0..TEST_REPEATS {
+        let mut rng = rand::rng();
+        let test_key: Vec<u8> = (0..256).map(|_| rng.random()).collect(); // 32 byte random key
+        let test_data: Vec<u8> = (0..1048576).map(|_| rng.random()).collect(); // 1 MiB random data
+        {
+            // write test value to db
+            let mut txn = env.begin_rw_txn()?;
+            write_with_rw_txn(&mut txn, &handle, &test_key, &test_data)?;
+            Tx::commit(txn)?;
+        }
+
+        {
+            // read test value from db
+            let txn = env.begin_ro_txn()?;
+            let res = read_with_ro_txn(&txn, &handle, &test_key)?;
+            assert!(res.is_some(), "failed to read value from db");
+            assert_eq!(test_data, res.unwrap(), "data read is corrupted");
+        }
+
+        {
+            // delete test value from db
+            let mut txn = env.begin_rw_txn()?;
+            delete_with_rw_txn(&mut txn, &handle, &test_key)?;
+            Tx::commit(txn)?;
+
+            // try to read deleted value
+            let txn = env.begin_ro_txn()?;
+            let res = read_with_ro_txn(&txn, &handle, &test_key)?;
+            assert!(res.is_none(), "could read deleted value");
+        }
+    }
+    Ok(())
+}
+
+pub fn not_found_is_none<E: Db>(env: &E) -> TestResult {
+    let handle = create_test_handle(env);
+
+    let txn = env.begin_ro_txn()?;
+    let not_found = txn.read(&handle, &[0, 0, 0, 0])?;
+    assert!(not_found.is_none());
+    Ok(())
+}
+
+pub fn non_existing_db<E: Db>(env: &E) {
+    let db_name = "does-not-exist";
+    let res = env.open_sub_db(db_name);
+    assert!(res.is_err());
+    if let Err(e) = res {
+        match e {
+            Error::DbNotFound(name) => {
+                assert_eq!(name, db_name, "error should include db-name")
+            }
+            _ => panic!("expected error 'DbNotFound'"),
+        }
+    }
+}
+
+pub fn abort_discards_changes<E: Db>(env: &E) -> TestResult {
+    let handle = env.create_sub_db("test")?;
+    write_all(env, &handle, &[(b"kept", b"1")])?;
+
+    let mut txn = env.begin_rw_txn()?;
+    txn.write(&handle, b"discarded", b"2")?;
+    txn.delete(&handle, b"kept")?;
+    // Changes are visible within the transaction
+    assert!(txn.read(&handle, b"discarded")?.is_some());
+    assert!(txn.read(&handle, b"kept")?.is_none());
+    txn.abort();
+
+    let txn = env.begin_ro_txn()?;
+    assert!(txn.read(&handle, b"discarded")?.is_none());
+    assert_eq!(txn.read(&handle, b"kept")?, Some(&b"1"[..]));
+    Ok(())
+}
+
+pub fn sub_dbs_are_isolated<E: Db>(env: &E) -> TestResult {
+    let first = env.create_sub_db("first")?;
+    let second = env.create_sub_db("second")?;
+    write_all(env, &first, &[(b"key", b"first")])?;
+    write_all(env, &second, &[(b"key", b"second"), (b"other", b"second")])?;
+
+    // Creating an existing sub-db simply opens it
+    let first = env.create_sub_db("first")?;
+    let txn = env.begin_ro_txn()?;
+    assert_eq!(txn.read(&first, b"key")?, Some(&b"first"[..]));
+    assert_eq!(txn.read(&second, b"key")?, Some(&b"second"[..]));
+    assert!(txn.read(&first, b"other")?.is_none());
+    let mut cursor = txn.ro_cursor(&first)?;
+    assert_eq!(cursor.iter_start().count(), 1);
+    Ok(())
+}
+
+pub fn cursor_iteration<E: Db>(env: &E) -> TestResult {
+    let handle = env.create_sub_db("test")?;
+    // Insert the keys out of order - iteration must be sorted by key
+    let keys: [&[u8]; 5] = [b"c", b"a", b"e", b"b", b"d"];
+    let pairs: Vec<(&[u8], &[u8])> = keys.iter().map(|k| (*k, *k)).collect();
+    write_all(env, &handle, &pairs)?;
+
+    let txn = env.begin_ro_txn()?;
+    let mut cursor = txn.ro_cursor(&handle)?;
+    let all: Vec<&[u8]> = cursor.iter_start().map(|(k, _)| k).collect();
+    assert_eq!(all, [b"a", b"b", b"c", b"d", b"e"]);
+
+    // Values stay valid, while the cursor moves on
+    let pairs: Vec<(&[u8], &[u8])> = cursor.iter_start().collect();
+    assert!(pairs.iter().all(|(k, v)| k == v));
+
+    let from: Vec<&[u8]> = cursor.iter_from(b"bb").map(|(k, _)| k).collect();
+    assert_eq!(from, [b"c", b"d", b"e"]);
+    let from: Vec<&[u8]> = cursor.iter_from(b"d").map(|(k, _)| k).collect();
+    assert_eq!(from, [b"d", b"e"]);
+    assert_eq!(cursor.iter_from(b"f").count(), 0);
+    drop(cursor);
+
+    // A new cursor starts at the beginning
+    let mut cursor = txn.ro_cursor(&handle)?;
+    assert_eq!(cursor.iter().count(), 5);
+    Ok(())
+}
+
+pub fn rw_cursor_sees_own_writes<E: Db>(env: &E) -> TestResult {
+    let handle = env.create_sub_db("test")?;
+    write_all(env, &handle, &[(b"a", b"1"), (b"c", b"3")])?;
+
+    let mut txn = env.begin_rw_txn()?;
+    txn.write(&handle, b"b", b"2")?;
+    txn.delete(&handle, b"c")?;
+    let mut cursor = txn.rw_cursor(&handle)?;
+    let all: Vec<(&[u8], &[u8])> = cursor.iter_start().collect();
+    assert_eq!(all, [(&b"a"[..], &b"1"[..]), (&b"b"[..], &b"2"[..])]);
+    cursor.put(b"d", b"4")?;
+    drop(cursor);
+    Tx::commit(txn)?;
+
+    let txn = env.begin_ro_txn()?;
+    let mut cursor = txn.ro_cursor(&handle)?;
+    let keys: Vec<&[u8]> = cursor.iter_start().map(|(k, _)| k).collect();
+    assert_eq!(keys, [b"a", b"b", b"d"]);
+    Ok(())
+}
+
+pub fn ro_txn_reads_snapshot<E: Db>(env: &E) -> TestResult {
+    let handle = env.create_sub_db("test")?;
+    write_all(env, &handle, &[(b"key", b"old")])?;
+
+    let ro_txn = env.begin_ro_txn()?;
+    write_all(env, &handle, &[(b"key", b"new"), (b"added", b"new")])?;
+
+    // The read-only transaction still sees the state at its beginning
+    assert_eq!(ro_txn.read(&handle, b"key")?, Some(&b"old"[..]));
+    assert!(ro_txn.read(&handle, b"added")?.is_none());
+    Tx::commit(ro_txn)?;
+
+    let ro_txn = env.begin_ro_txn()?;
+    assert_eq!(ro_txn.read(&handle, b"key")?, Some(&b"new"[..]));
+    Ok(())
+}
+
+pub fn cursor_ranges<E: Db>(env: &E) -> TestResult {
+    let handle = env.create_sub_db("test")?;
+    let keys: [&[u8]; 7] = [b"a", b"b\x00", b"b\x01", b"b\xff", b"b\xff\xff", b"c", b"d"];
+    let pairs: Vec<(&[u8], &[u8])> = keys.iter().map(|k| (*k, *k)).collect();
+    write_all(env, &handle, &pairs)?;
+
+    let txn = env.begin_ro_txn()?;
+    let mut cursor = txn.ro_cursor(&handle)?;
+    let prefix: Vec<&[u8]> = cursor.iter_prefix(b"b").map(|(k, _)| k).collect();
+    assert_eq!(prefix, keys[1..5]);
+    let prefix: Vec<&[u8]> = cursor.iter_prefix(b"b\xff").map(|(k, _)| k).collect();
+    assert_eq!(prefix, keys[3..5]);
+    assert_eq!(cursor.iter_prefix(b"x").count(), 0);
+    assert_eq!(cursor.iter_prefix(b"").count(), keys.len());
+
+    let range: Vec<&[u8]> = cursor.iter_range(&b"b"[..]..b"c").map(|(k, _)| k).collect();
+    assert_eq!(range, keys[1..5]);
+    let range: Vec<&[u8]> = cursor
+        .iter_range(&b"b\x01"[..]..=b"c")
+        .map(|(k, _)| k)
+        .collect();
+    assert_eq!(range, keys[2..6]);
+    let range: Vec<&[u8]> = cursor
+        .iter_range((
+            Bound::Excluded(b"a".to_vec()),
+            Bound::Excluded(b"b\xff".to_vec()),
+        ))
+        .map(|(k, _)| k)
+        .collect();
+    assert_eq!(range, keys[1..3]);
+    assert_eq!(cursor.iter_range(..&b"b"[..]).count(), 1);
+    assert_eq!(cursor.iter_range(&b"c"[..]..).count(), 2);
+
+    let rev: Vec<&[u8]> = cursor.iter_rev().map(|(k, _)| k).collect();
+    let mut expected = keys.to_vec();
+    expected.reverse();
+    assert_eq!(rev, expected);
+    // Starts at the key itself, if it exists - otherwise at the closest key before it
+    let rev: Vec<&[u8]> = cursor.iter_from_rev(b"c").map(|(k, _)| k).collect();
+    assert_eq!(rev, expected[1..]);
+    let rev: Vec<&[u8]> = cursor.iter_from_rev(b"b\x02").map(|(k, _)| k).collect();
+    assert_eq!(rev, expected[4..]);
+    assert_eq!(cursor.iter_from_rev(b"z").count(), keys.len());
+    assert_eq!(cursor.iter_from_rev(b"0").count(), 0);
+    drop(cursor);
+
+    // Read-write cursors see their own changes
+    let mut txn = env.begin_rw_txn()?;
+    txn.write(&handle, b"b\x02", b"new")?;
+    let mut cursor = txn.rw_cursor(&handle)?;
+    assert_eq!(cursor.iter_prefix(b"b").count(), 5);
+    let rev: Vec<&[u8]> = cursor.iter_from_rev(b"b\x03").map(|(k, _)| k).collect();
+    assert_eq!(rev, [&b"b\x02"[..], b"b\x01", b"b\x00", b"a"]);
+    Ok(())
+}
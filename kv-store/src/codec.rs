@@ -0,0 +1,443 @@
+//! Key and value codecs for typed [`Table`]s
+//!
+//! Keys are encoded with [`KeyCodec`], which preserves the ordering of the original type -
+//! if `a < b`, then the encoding of `a` is byte-wise smaller than the encoding of `b`.
+//! This is what makes range and prefix queries over a [`Table`] meaningful.
+//!
+//! The encodings are:
+//! - unsigned integers are stored in big-endian
+//! - signed integers are stored in big-endian with the sign bit flipped
+//! - fixed size byte arrays (and ids) are stored as they are
+//! - byte vectors and strings are escaped (`0x00` becomes `0x00 0xff`) and terminated with `0x00 0x01`
+//! - tuples are the concatenation of their elements
+//!
+//! Values are encoded with [`ValueCodec`]. Besides the primitive types, arbitrary serde types
+//! can be stored by wrapping them in [`Json`] or [`Postcard`].
+//!
+//! [`Table`]: crate::table::Table
+
+use std::ops::{Deref, DerefMut};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Error;
+
+/// Order preserving encoding of table keys
+pub trait KeyCodec: Sized {
+    /// Appends the encoded key to the buffer
+    fn encode_key(&self, out: &mut Vec<u8>);
+
+    /// Decodes a key from the start of the input and returns the remaining input
+    fn decode_key(input: &[u8]) -> Result<(Self, &[u8]), Error>;
+
+    /// Encodes the key into a new buffer
+    fn to_key_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_key(&mut out);
+        out
+    }
+
+    /// Decodes a key, which must span the entire input
+    fn from_key_bytes(input: &[u8]) -> Result<Self, Error> {
+        let (key, rest) = Self::decode_key(input)?;
+        if !rest.is_empty() {
+            return Err(Error::Codec(format!(
+                "{} trailing bytes after key",
+                rest.len()
+            )));
+        }
+        Ok(key)
+    }
+}
+
+/// Encoding of table values
+pub trait ValueCodec: Sized {
+    /// Encodes the value
+    fn encode_value(&self) -> Result<Vec<u8>, Error>;
+
+    /// Decodes the value from its encoded bytes
+    fn decode_value(bytes: &[u8]) -> Result<Self, Error>;
+}
+
+/// Splits off the first `N` bytes of the input
+fn split_array<const N: usize>(input: &[u8]) -> Result<([u8; N], &[u8]), Error> {
+    if input.len() < N {
+        return Err(Error::Codec(format!(
+            "expected {N} bytes, got {}",
+            input.len()
+        )));
+    }
+    let (head, rest) = input.split_at(N);
+    Ok((head.try_into().expect("length was checked"), rest))
+}
+
+/// Reads a value of exactly `N` bytes
+fn exact_array<const N: usize>(bytes: &[u8]) -> Result<[u8; N], Error> {
+    bytes
+        .try_into()
+        .map_err(|_| Error::Codec(format!("expected {N} bytes, got {}", bytes.len())))
+}
+
+macro_rules! impl_unsigned {
+    ($($t:ty),*) => {
+        $(
+            impl KeyCodec for $t {
+                fn encode_key(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn decode_key(input: &[u8]) -> Result<(Self, &[u8]), Error> {
+                    let (bytes, rest) = split_array(input)?;
+                    Ok((<$t>::from_be_bytes(bytes), rest))
+                }
+            }
+
+            impl ValueCodec for $t {
+                fn encode_value(&self) -> Result<Vec<u8>, Error> {
+                    Ok(self.to_be_bytes().to_vec())
+                }
+
+                fn decode_value(bytes: &[u8]) -> Result<Self, Error> {
+                    Ok(<$t>::from_be_bytes(exact_array(bytes)?))
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_signed {
+    ($($t:ty => $u:ty),*) => {
+        $(
+            // NOTE: Flipping the sign bit moves negative numbers in front of positive ones
+            impl KeyCodec for $t {
+                fn encode_key(&self, out: &mut Vec<u8>) {
+                    let flipped = (*self as $u) ^ (1 << (<$u>::BITS - 1));
+                    out.extend_from_slice(&flipped.to_be_bytes());
+                }
+
+                fn decode_key(input: &[u8]) -> Result<(Self, &[u8]), Error> {
+                    let (bytes, rest) = split_array(input)?;
+                    let flipped = <$u>::from_be_bytes(bytes) ^ (1 << (<$u>::BITS - 1));
+                    Ok((flipped as $t, rest))
+                }
+            }
+
+            impl ValueCodec for $t {
+                fn encode_value(&self) -> Result<Vec<u8>, Error> {
+                    Ok(self.to_be_bytes().to_vec())
+                }
+
+                fn decode_value(bytes: &[u8]) -> Result<Self, Error> {
+                    Ok(<$t>::from_be_bytes(exact_array(bytes)?))
+                }
+            }
+        )*
+    };
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128);
+impl_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128);
+
+impl<const N: usize> KeyCodec for [u8; N] {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+
+    fn decode_key(input: &[u8]) -> Result<(Self, &[u8]), Error> {
+        split_array(input)
+    }
+}
+
+impl<const N: usize> ValueCodec for [u8; N] {
+    fn encode_value(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.to_vec())
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, Error> {
+        exact_array(bytes)
+    }
+}
+
+/// Escapes all zero bytes and terminates the output with `0x00 0x01`
+///
+/// This keeps the ordering of variable length keys intact, even if they are followed by other keys.
+/// The terminator is two bytes long, so it cannot be confused with an escaped zero byte,
+/// regardless of the bytes that follow it.
+fn encode_escaped(bytes: &[u8], out: &mut Vec<u8>) {
+    for &b in bytes {
+        out.push(b);
+        if b == 0x00 {
+            out.push(0xff);
+        }
+    }
+    out.extend_from_slice(&[0x00, 0x01]);
+}
+
+fn decode_escaped(input: &[u8]) -> Result<(Vec<u8>, &[u8]), Error> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < input.len() {
+        match (input[pos], input.get(pos + 1)) {
+            (0x00, Some(0xff)) => {
+                out.push(0x00);
+                pos += 2;
+            }
+            (0x00, Some(0x01)) => return Ok((out, &input[pos + 2..])),
+            (0x00, _) => return Err(Error::Codec("invalid escape sequence".to_string())),
+            (b, _) => {
+                out.push(b);
+                pos += 1;
+            }
+        }
+    }
+    Err(Error::Codec("unterminated byte string".to_string()))
+}
+
+impl KeyCodec for Vec<u8> {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        encode_escaped(self, out);
+    }
+
+    fn decode_key(input: &[u8]) -> Result<(Self, &[u8]), Error> {
+        decode_escaped(input)
+    }
+}
+
+impl ValueCodec for Vec<u8> {
+    fn encode_value(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.clone())
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl KeyCodec for String {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        encode_escaped(self.as_bytes(), out);
+    }
+
+    fn decode_key(input: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let (bytes, rest) = decode_escaped(input)?;
+        let s = String::from_utf8(bytes).map_err(|e| Error::Codec(e.to_string()))?;
+        Ok((s, rest))
+    }
+}
+
+impl ValueCodec for String {
+    fn encode_value(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.as_bytes().to_vec())
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, Error> {
+        String::from_utf8(bytes.to_vec()).map_err(|e| Error::Codec(e.to_string()))
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: KeyCodec),+> KeyCodec for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn encode_key(&self, out: &mut Vec<u8>) {
+                let ($($name,)+) = self;
+                $($name.encode_key(out);)+
+            }
+
+            #[allow(non_snake_case)]
+            fn decode_key(input: &[u8]) -> Result<(Self, &[u8]), Error> {
+                $(let ($name, input) = $name::decode_key(input)?;)+
+                Ok((($($name,)+), input))
+            }
+        }
+    };
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+
+#[cfg(feature = "ids")]
+mod ids {
+    use super::*;
+    use borderless_id_types::{AgentId, BorderlessId, ContractId, Did, ExternalId, FlowId};
+
+    macro_rules! impl_id {
+        ($($t:ty),*) => {
+            $(
+                impl KeyCodec for $t {
+                    fn encode_key(&self, out: &mut Vec<u8>) {
+                        out.extend_from_slice(self.as_bytes());
+                    }
+
+                    fn decode_key(input: &[u8]) -> Result<(Self, &[u8]), Error> {
+                        let (bytes, rest) = split_array(input)?;
+                        Ok((<$t>::from_bytes(bytes), rest))
+                    }
+                }
+
+                impl ValueCodec for $t {
+                    fn encode_value(&self) -> Result<Vec<u8>, Error> {
+                        Ok(self.as_bytes().to_vec())
+                    }
+
+                    fn decode_value(bytes: &[u8]) -> Result<Self, Error> {
+                        Ok(<$t>::from_bytes(exact_array(bytes)?))
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_id!(AgentId, BorderlessId, ContractId, Did, ExternalId, FlowId);
+}
+
+/// Stores a serde value as json
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Json<T>(pub T);
+
+/// Stores a serde value in the binary postcard format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Postcard<T>(pub T);
+
+macro_rules! impl_wrapper {
+    ($($wrapper:ident),*) => {
+        $(
+            impl<T> $wrapper<T> {
+                pub fn into_inner(self) -> T {
+                    self.0
+                }
+            }
+
+            impl<T> Deref for $wrapper<T> {
+                type Target = T;
+
+                fn deref(&self) -> &T {
+                    &self.0
+                }
+            }
+
+            impl<T> DerefMut for $wrapper<T> {
+                fn deref_mut(&mut self) -> &mut T {
+                    &mut self.0
+                }
+            }
+
+            impl<T> From<T> for $wrapper<T> {
+                fn from(value: T) -> Self {
+                    $wrapper(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_wrapper!(Json, Postcard);
+
+impl<T: Serialize + DeserializeOwned> ValueCodec for Json<T> {
+    fn encode_value(&self) -> Result<Vec<u8>, Error> {
+        serde_json::to_vec(&self.0).map_err(|e| Error::Codec(e.to_string()))
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, Error> {
+        serde_json::from_slice(bytes)
+            .map(Json)
+            .map_err(|e| Error::Codec(e.to_string()))
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> ValueCodec for Postcard<T> {
+    fn encode_value(&self) -> Result<Vec<u8>, Error> {
+        postcard::to_allocvec(&self.0).map_err(|e| Error::Codec(e.to_string()))
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, Error> {
+        postcard::from_bytes(bytes)
+            .map(Postcard)
+            .map_err(|e| Error::Codec(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_ordered<K: KeyCodec + Ord + Clone + std::fmt::Debug>(mut keys: Vec<K>) {
+        keys.sort();
+        let encoded: Vec<_> = keys.iter().map(|k| k.to_key_bytes()).collect();
+        for (key, bytes) in keys.iter().zip(&encoded) {
+            assert_eq!(&K::from_key_bytes(bytes).unwrap(), key);
+        }
+        for pair in encoded.windows(2) {
+            assert!(pair[0] < pair[1], "{:?} >= {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn integers_are_ordered() {
+        assert_ordered(vec![0u64, 1, 255, 256, u64::MAX, 1 << 40]);
+        assert_ordered(vec![i64::MIN, -256, -1, 0, 1, 255, i64::MAX]);
+        assert_ordered(vec![i8::MIN, -1, 0, 1, i8::MAX]);
+        assert_ordered(vec![i128::MIN, -1, 0, i128::MAX]);
+    }
+
+    #[test]
+    fn strings_are_ordered() {
+        assert_ordered(
+            ["", "a", "a\0", "a\0\0", "a\u{1}", "ab", "b", "\u{ff}"]
+                .map(String::from)
+                .to_vec(),
+        );
+        assert_ordered(vec![vec![], vec![0u8], vec![0, 0xff], vec![1], vec![0xff]]);
+    }
+
+    #[test]
+    fn tuples_are_ordered() {
+        assert_ordered(vec![
+            ("a".to_string(), 5u32),
+            ("a".to_string(), 10),
+            ("a\0".to_string(), 0),
+            ("a\0".to_string(), u32::MAX),
+            ("ab".to_string(), 0),
+            ("b".to_string(), 0),
+        ]);
+        assert_ordered(vec![
+            (-1i32, 0u8, [1u8; 2]),
+            (-1, 1, [0; 2]),
+            (0, 0, [0; 2]),
+        ]);
+    }
+
+    #[test]
+    fn invalid_keys_are_rejected() {
+        assert!(u32::from_key_bytes(&[0; 3]).is_err());
+        assert!(u32::from_key_bytes(&[0; 5]).is_err());
+        assert!(String::from_key_bytes(b"abc").is_err());
+        assert!(String::from_key_bytes(&[0xff, 0xfe, 0x00, 0x01]).is_err());
+        assert!(String::from_key_bytes(&[b'a', 0x00, 0x02]).is_err());
+        assert!(<(u8, String)>::from_key_bytes(&[1]).is_err());
+    }
+
+    #[test]
+    fn values_roundtrip() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Record {
+            name: String,
+            amount: i64,
+        }
+        let record = Record {
+            name: "test".to_string(),
+            amount: -42,
+        };
+        let json = Json(record).encode_value().unwrap();
+        let decoded = Json::<Record>::decode_value(&json).unwrap();
+        assert_eq!(decoded.amount, -42);
+        let bin = Postcard(decoded.into_inner()).encode_value().unwrap();
+        assert_eq!(Postcard::<Record>::decode_value(&bin).unwrap().name, "test");
+
+        assert_eq!(u64::decode_value(&7u64.encode_value().unwrap()).unwrap(), 7);
+        assert!(u64::decode_value(&[0; 4]).is_err());
+        assert!(Json::<Record>::decode_value(b"{}").is_err());
+    }
+}
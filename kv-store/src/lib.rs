@@ -1,6 +1,9 @@
+use std::ops::{Bound, RangeBounds};
 use thiserror::Error;
 
 pub mod backend;
+pub mod codec;
+pub mod table;
 
 /// Prelude module to automatically include all necessary traits
 pub mod prelude {
@@ -27,6 +30,8 @@ pub enum Error {
     Busy(String),
     #[error("An unknown error occurred.")]
     Unknown,
+    #[error("Invalid encoding - {0}")]
+    Codec(String),
     #[error("Other error: {0}")]
     Other(String),
 }
@@ -169,6 +174,44 @@ pub trait RwCursor<'txn, DB: KvDatabase> {
     fn iter_from<K>(&mut self, key: &K) -> Self::Iter
     where
         K: AsRef<[u8]>;
+
+    /// Iterate over database items in reverse order, starting from the end of the database.
+    fn iter_rev(&mut self) -> Self::Iter;
+
+    /// Iterate over database items in reverse order, starting from the given key
+    /// (or the closest key before it, if the key does not exist).
+    fn iter_from_rev<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>;
+
+    /// Iterate over all database items, whose key starts with the given prefix.
+    fn iter_prefix<K>(&mut self, prefix: &K) -> RangeIter<Self::Iter>
+    where
+        K: AsRef<[u8]>,
+    {
+        // NOTE: LMDB rejects empty keys, so we cannot position the cursor on them
+        let iter = if prefix.as_ref().is_empty() {
+            self.iter_start()
+        } else {
+            self.iter_from(prefix)
+        };
+        RangeIter::prefix(iter, prefix.as_ref())
+    }
+
+    /// Iterate over all database items, whose key lies within the given range.
+    fn iter_range<K, R>(&mut self, range: R) -> RangeIter<Self::Iter>
+    where
+        K: AsRef<[u8]>,
+        R: RangeBounds<K>,
+    {
+        let iter = match range.start_bound() {
+            Bound::Included(start) | Bound::Excluded(start) if !start.as_ref().is_empty() => {
+                self.iter_from(start)
+            }
+            _ => self.iter_start(),
+        };
+        RangeIter::new(iter, range)
+    }
 }
 
 /// Trait representing a read-only cursor over the database within a transaction.
@@ -204,6 +247,122 @@ pub trait RoCursor<'txn, DB: KvDatabase> {
     fn iter_from<K>(&mut self, key: &K) -> Self::Iter
     where
         K: AsRef<[u8]>;
+
+    /// Iterate over database items in reverse order, starting from the end of the database.
+    fn iter_rev(&mut self) -> Self::Iter;
+
+    /// Iterate over database items in reverse order, starting from the given key
+    /// (or the closest key before it, if the key does not exist).
+    fn iter_from_rev<K>(&mut self, key: &K) -> Self::Iter
+    where
+        K: AsRef<[u8]>;
+
+    /// Iterate over all database items, whose key starts with the given prefix.
+    fn iter_prefix<K>(&mut self, prefix: &K) -> RangeIter<Self::Iter>
+    where
+        K: AsRef<[u8]>,
+    {
+        // NOTE: LMDB rejects empty keys, so we cannot position the cursor on them
+        let iter = if prefix.as_ref().is_empty() {
+            self.iter_start()
+        } else {
+            self.iter_from(prefix)
+        };
+        RangeIter::prefix(iter, prefix.as_ref())
+    }
+
+    /// Iterate over all database items, whose key lies within the given range.
+    fn iter_range<K, R>(&mut self, range: R) -> RangeIter<Self::Iter>
+    where
+        K: AsRef<[u8]>,
+        R: RangeBounds<K>,
+    {
+        let iter = match range.start_bound() {
+            Bound::Included(start) | Bound::Excluded(start) if !start.as_ref().is_empty() => {
+                self.iter_from(start)
+            }
+            _ => self.iter_start(),
+        };
+        RangeIter::new(iter, range)
+    }
+}
+
+/// Iterator over the database items within a key range.
+///
+/// Wraps the iterator of a cursor, that was positioned at the start of the range,
+/// and stops as soon as a key lies behind the end of the range.
+pub struct RangeIter<I> {
+    iter: I,
+    /// Key, that is skipped if it is returned first (excluded start of the range)
+    skip: Option<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+    done: bool,
+}
+
+impl<I> RangeIter<I> {
+    fn new<K: AsRef<[u8]>>(iter: I, range: impl RangeBounds<K>) -> Self {
+        let skip = match range.start_bound() {
+            Bound::Excluded(start) => Some(start.as_ref().to_vec()),
+            _ => None,
+        };
+        RangeIter {
+            iter,
+            skip,
+            end: range.end_bound().map(|end| end.as_ref().to_vec()),
+            done: false,
+        }
+    }
+
+    fn prefix(iter: I, prefix: &[u8]) -> Self {
+        // All keys with the prefix are smaller than the prefix with its last (non 0xff) byte incremented
+        let mut end = prefix.to_vec();
+        while let Some(last) = end.pop() {
+            if last < u8::MAX {
+                end.push(last + 1);
+                break;
+            }
+        }
+        RangeIter {
+            iter,
+            skip: None,
+            end: if end.is_empty() {
+                Bound::Unbounded
+            } else {
+                Bound::Excluded(end)
+            },
+            done: false,
+        }
+    }
+}
+
+impl<'txn, I> Iterator for RangeIter<I>
+where
+    I: Iterator<Item = (&'txn [u8], &'txn [u8])>,
+{
+    type Item = (&'txn [u8], &'txn [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut item = self.iter.next();
+        if let Some(skip) = self.skip.take() {
+            if item.is_some_and(|(key, _)| key == skip) {
+                item = self.iter.next();
+            }
+        }
+        let (key, value) = item?;
+        let in_range = match &self.end {
+            Bound::Included(end) => key <= end.as_slice(),
+            Bound::Excluded(end) => key < end.as_slice(),
+            Bound::Unbounded => true,
+        };
+        if !in_range {
+            self.done = true;
+            return None;
+        }
+        Some((key, value))
+    }
 }
 
 /// Trait representing a read-only transaction.
@@ -0,0 +1,388 @@
+//! Typed tables on top of a sub-database
+//!
+//! A [`Table`] binds a sub-database to a key and a value type, so the callers do not have to
+//! hand-roll the byte layout of their keys and values. See the [`codec`](crate::codec) module
+//! for the available encodings.
+//!
+//! The table does not own any data and does not enforce, that all entries of the sub-database
+//! share the same layout - existing stores can therefore be migrated one access at a time,
+//! as long as the chosen codecs reproduce the existing byte layout.
+
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+
+use crate::codec::{KeyCodec, ValueCodec};
+use crate::{Db, Error, RawRead, RawWrite, RoCursor, RoTx};
+
+/// Sub-database with typed keys and values
+pub struct Table<S: Db, K, V> {
+    handle: S::Handle,
+    _types: PhantomData<fn() -> (K, V)>,
+}
+
+impl<S: Db, K, V> Clone for Table<S, K, V>
+where
+    S::Handle: Clone,
+{
+    fn clone(&self) -> Self {
+        Table {
+            handle: self.handle.clone(),
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<S: Db, K: KeyCodec, V: ValueCodec> Table<S, K, V> {
+    /// Opens the table in an existing sub-database
+    pub fn open(db: &S, name: &str) -> Result<Self, Error> {
+        Ok(Self::from_handle(db.open_sub_db(name)?))
+    }
+
+    /// Opens the table and creates the sub-database, if it does not exist yet
+    pub fn create(db: &S, name: &str) -> Result<Self, Error> {
+        Ok(Self::from_handle(db.create_sub_db(name)?))
+    }
+
+    /// Uses an already opened sub-database handle
+    pub fn from_handle(handle: S::Handle) -> Self {
+        Table {
+            handle,
+            _types: PhantomData,
+        }
+    }
+
+    /// Returns the underlying sub-database handle
+    pub fn handle(&self) -> &S::Handle {
+        &self.handle
+    }
+
+    /// Reads the value of the given key
+    pub fn get<'env>(&self, txn: &impl RawRead<'env, S::DB>, key: &K) -> Result<Option<V>, Error> {
+        txn.read(&self.handle, &key.to_key_bytes())?
+            .map(V::decode_value)
+            .transpose()
+    }
+
+    /// Returns `true` if the table contains the given key
+    pub fn contains_key<'env>(
+        &self,
+        txn: &impl RawRead<'env, S::DB>,
+        key: &K,
+    ) -> Result<bool, Error> {
+        Ok(txn.read(&self.handle, &key.to_key_bytes())?.is_some())
+    }
+
+    /// Writes the value of the given key (and overwrites existing values)
+    pub fn put<'env>(
+        &self,
+        txn: &mut impl RawWrite<'env, S::DB>,
+        key: &K,
+        value: &V,
+    ) -> Result<(), Error> {
+        txn.write(&self.handle, &key.to_key_bytes(), &value.encode_value()?)
+    }
+
+    /// Deletes the given key
+    pub fn delete<'env>(&self, txn: &mut impl RawWrite<'env, S::DB>, key: &K) -> Result<(), Error> {
+        txn.delete(&self.handle, &key.to_key_bytes())
+    }
+
+    /// Iterates over all entries of the table in ascending order
+    pub fn iter<'env: 'txn, 'txn, T: RoTx<'env, S::DB>>(
+        &self,
+        txn: &'txn T,
+    ) -> Result<impl Iterator<Item = Result<(K, V), Error>> + use<'env, 'txn, S, K, V, T>, Error>
+    where
+        S::DB: 'txn,
+        K: 'txn,
+        V: 'txn,
+    {
+        self.range::<T, _>(txn, ..)
+    }
+
+    /// Iterates over all entries, whose key lies within the given range, in ascending order
+    pub fn range<'env: 'txn, 'txn, T: RoTx<'env, S::DB>, R: RangeBounds<K>>(
+        &self,
+        txn: &'txn T,
+        range: R,
+    ) -> Result<impl Iterator<Item = Result<(K, V), Error>> + use<'env, 'txn, S, K, V, T, R>, Error>
+    where
+        S::DB: 'txn,
+        K: 'txn,
+        V: 'txn,
+    {
+        let range = encode_bounds(&range);
+        let mut cursor = txn.ro_cursor(&self.handle)?;
+        let iter = cursor.iter_range(range);
+        Ok(TableIter::new(cursor, iter))
+    }
+
+    /// Iterates over all entries, whose key lies within the given range, in descending order
+    pub fn range_rev<'env: 'txn, 'txn, T: RoTx<'env, S::DB>, R: RangeBounds<K>>(
+        &self,
+        txn: &'txn T,
+        range: R,
+    ) -> Result<impl Iterator<Item = Result<(K, V), Error>> + use<'env, 'txn, S, K, V, T, R>, Error>
+    where
+        S::DB: 'txn,
+        K: 'txn,
+        V: 'txn,
+    {
+        let (start, end) = encode_bounds(&range);
+        let mut cursor = txn.ro_cursor(&self.handle)?;
+        let iter = match &end {
+            Bound::Included(end) | Bound::Excluded(end) => cursor.iter_from_rev(end),
+            Bound::Unbounded => cursor.iter_rev(),
+        };
+        Ok(TableIter::new(cursor, RevRangeIter::new(iter, start, end)))
+    }
+
+    /// Iterates over all entries, whose key starts with the given prefix, in ascending order
+    ///
+    /// The prefix is typically the first element(s) of a tuple key,
+    /// e.g. an `u32` for a table with `(u32, String)` keys.
+    pub fn prefix<'env: 'txn, 'txn, T: RoTx<'env, S::DB>, P: KeyCodec>(
+        &self,
+        txn: &'txn T,
+        prefix: &P,
+    ) -> Result<impl Iterator<Item = Result<(K, V), Error>> + use<'env, 'txn, S, K, V, T, P>, Error>
+    where
+        S::DB: 'txn,
+        K: 'txn,
+        V: 'txn,
+    {
+        let mut cursor = txn.ro_cursor(&self.handle)?;
+        let iter = cursor.iter_prefix(&prefix.to_key_bytes());
+        Ok(TableIter::new(cursor, iter))
+    }
+}
+
+fn encode_bounds<K: KeyCodec>(range: &impl RangeBounds<K>) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    (
+        range.start_bound().map(K::to_key_bytes),
+        range.end_bound().map(K::to_key_bytes),
+    )
+}
+
+/// Iterator over decoded table entries
+///
+/// The iterator of a cursor must not outlive the cursor, so both are kept together.
+struct TableIter<C, I, K, V> {
+    // NOTE: Fields are dropped in declaration order, so the iterator is dropped before the cursor
+    iter: I,
+    _cursor: C,
+    _types: PhantomData<fn() -> (K, V)>,
+}
+
+impl<C, I, K, V> TableIter<C, I, K, V> {
+    fn new(cursor: C, iter: I) -> Self {
+        TableIter {
+            iter,
+            _cursor: cursor,
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<'txn, C, I, K, V> Iterator for TableIter<C, I, K, V>
+where
+    I: Iterator<Item = (&'txn [u8], &'txn [u8])>,
+    K: KeyCodec,
+    V: ValueCodec,
+{
+    type Item = Result<(K, V), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.iter.next()?;
+        Some(K::from_key_bytes(key).and_then(|k| Ok((k, V::decode_value(value)?))))
+    }
+}
+
+/// Reverse counterpart of [`RangeIter`](crate::RangeIter)
+///
+/// The iterator of the cursor is positioned at the end of the range,
+/// so only an excluded end has to be skipped.
+struct RevRangeIter<I> {
+    iter: I,
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+    done: bool,
+}
+
+impl<I> RevRangeIter<I> {
+    fn new(iter: I, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> Self {
+        RevRangeIter {
+            iter,
+            start,
+            end,
+            done: false,
+        }
+    }
+}
+
+impl<'txn, I> Iterator for RevRangeIter<I>
+where
+    I: Iterator<Item = (&'txn [u8], &'txn [u8])>,
+{
+    type Item = (&'txn [u8], &'txn [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let (key, value) = loop {
+            let (key, value) = self.iter.next()?;
+            match &self.end {
+                Bound::Excluded(end) if key >= end.as_slice() => continue,
+                Bound::Included(end) if key > end.as_slice() => continue,
+                _ => break (key, value),
+            }
+        };
+        let in_range = match &self.start {
+            Bound::Included(start) => key >= start.as_slice(),
+            Bound::Excluded(start) => key > start.as_slice(),
+            Bound::Unbounded => true,
+        };
+        if !in_range {
+            self.done = true;
+            return None;
+        }
+        Some((key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::lmdb::Lmdb;
+    use crate::backend::memdb::MemDb;
+    use crate::codec::Json;
+    use crate::Tx;
+    use tempfile::tempdir;
+
+    type Scores<S> = Table<S, (String, i64), u32>;
+
+    fn fill<S: Db>(db: &S) -> Scores<S> {
+        let table = Scores::create(db, "scores").unwrap();
+        let mut txn = db.begin_rw_txn().unwrap();
+        for (name, round, score) in [
+            ("bob", -1, 3),
+            ("alice", 2, 10),
+            ("alice", -5, 7),
+            ("bob", 4, 1),
+            ("alice\0", 0, 5),
+            ("carol", i64::MAX, 0),
+        ] {
+            table
+                .put(&mut txn, &(name.to_string(), round), &score)
+                .unwrap();
+        }
+        txn.commit().unwrap();
+        table
+    }
+
+    fn keys<I: Iterator<Item = Result<((String, i64), u32), Error>>>(
+        iter: I,
+    ) -> Vec<(String, i64)> {
+        iter.map(|e| e.unwrap().0).collect()
+    }
+
+    fn key(name: &str, round: i64) -> (String, i64) {
+        (name.to_string(), round)
+    }
+
+    fn check_table<S: Db>(db: &S) {
+        let table = fill(db);
+        let txn = db.begin_ro_txn().unwrap();
+        assert_eq!(table.get(&txn, &key("alice", 2)).unwrap(), Some(10));
+        assert_eq!(table.get(&txn, &key("alice", 3)).unwrap(), None);
+        assert!(table.contains_key(&txn, &key("alice\0", 0)).unwrap());
+
+        let all = keys(table.iter(&txn).unwrap());
+        assert_eq!(
+            all,
+            vec![
+                key("alice", -5),
+                key("alice", 2),
+                key("alice\0", 0),
+                key("bob", -1),
+                key("bob", 4),
+                key("carol", i64::MAX),
+            ]
+        );
+
+        // Prefix of the first tuple element
+        let alice = keys(table.prefix(&txn, &"alice".to_string()).unwrap());
+        assert_eq!(alice, vec![key("alice", -5), key("alice", 2)]);
+
+        let range = keys(table.range(&txn, key("alice", 0)..key("bob", 4)).unwrap());
+        assert_eq!(
+            range,
+            vec![key("alice", 2), key("alice\0", 0), key("bob", -1)]
+        );
+
+        let rev = keys(
+            table
+                .range_rev(&txn, key("alice", 0)..=key("bob", 4))
+                .unwrap(),
+        );
+        assert_eq!(
+            rev,
+            vec![
+                key("bob", 4),
+                key("bob", -1),
+                key("alice\0", 0),
+                key("alice", 2)
+            ]
+        );
+        let rev = keys(table.range_rev(&txn, ..key("bob", 4)).unwrap());
+        assert_eq!(rev.len(), 4);
+        assert_eq!(rev[0], key("bob", -1));
+        let last = table.range_rev(&txn, ..).unwrap().next().unwrap().unwrap();
+        assert_eq!(last, (key("carol", i64::MAX), 0));
+        txn.commit().unwrap();
+
+        let mut txn = db.begin_rw_txn().unwrap();
+        table.delete(&mut txn, &key("bob", -1)).unwrap();
+        txn.commit().unwrap();
+        let txn = db.begin_ro_txn().unwrap();
+        assert_eq!(
+            keys(table.prefix(&txn, &"bob".to_string()).unwrap()).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn table_memdb() {
+        check_table(&MemDb::new());
+    }
+
+    #[test]
+    fn table_lmdb() {
+        let tmp_dir = tempdir().unwrap();
+        check_table(&Lmdb::new(tmp_dir.path(), 1).unwrap());
+    }
+
+    #[test]
+    fn corrupted_entries() {
+        let db = MemDb::new();
+        let raw = db.create_sub_db("raw").unwrap();
+        let table: Table<MemDb, u32, Json<Vec<String>>> = Table::from_handle(raw.clone());
+        let mut txn = db.begin_rw_txn().unwrap();
+        table
+            .put(&mut txn, &1, &Json(vec!["a".to_string()]))
+            .unwrap();
+        txn.write(&raw, &[0u8; 2], &b"[]").unwrap();
+        txn.write(&raw, &2u32.to_be_bytes(), &b"{").unwrap();
+        txn.commit().unwrap();
+
+        let txn = db.begin_ro_txn().unwrap();
+        assert!(table.get(&txn, &2).is_err());
+        let entries: Vec<_> = table.iter(&txn).unwrap().collect();
+        assert_eq!(entries.len(), 3);
+        // The two byte key sorts first and has an invalid length
+        assert!(matches!(entries[0], Err(Error::Codec(_))));
+        assert_eq!(entries[1].as_ref().unwrap().1 .0, vec!["a".to_string()]);
+        assert!(entries[2].is_err());
+    }
+}
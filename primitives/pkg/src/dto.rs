@@ -80,6 +80,10 @@ pub struct PkgMetaDto {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub repository: Option<String>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_buffer_size: Option<u64>,
 }
 
 impl From<PkgMetaDto> for PkgMeta {
@@ -90,6 +94,7 @@ impl From<PkgMetaDto> for PkgMeta {
             documentation: value.documentation,
             license: value.license,
             repository: value.repository,
+            log_buffer_size: value.log_buffer_size,
         }
     }
 }
@@ -102,6 +107,7 @@ impl From<PkgMeta> for PkgMetaDto {
             documentation: value.documentation,
             license: value.license,
             repository: value.repository,
+            log_buffer_size: value.log_buffer_size,
         }
     }
 }
@@ -114,6 +120,7 @@ impl PkgMetaDto {
             && self.documentation.is_none()
             && self.license.is_none()
             && self.repository.is_none()
+            && self.log_buffer_size.is_none()
     }
 }
 
@@ -210,6 +210,12 @@ pub struct PkgMeta {
     /// URL of the package source repository
     #[serde(default)]
     pub repository: Option<String>,
+
+    /// Number of log lines, that the runtime keeps for this package
+    ///
+    /// Applied once, when the contract or agent is introduced. Uses the default of the runtime, if not set.
+    #[serde(default)]
+    pub log_buffer_size: Option<u64>,
 }
 
 impl PkgMeta {
@@ -5,6 +5,7 @@
 #![no_std]
 extern "C" {
     pub fn print(ptr: u64, len: u64, level: u32);
+    pub fn print_fields(ptr: u64, len: u64, fields_ptr: u64, fields_len: u64, level: u32);
 
     // --- Register functions
     pub fn read_register(register_id: u64, wasm_ptr: u64);
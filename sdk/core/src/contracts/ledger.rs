@@ -3,7 +3,7 @@ use core::fmt;
 use borderless_id_types::BorderlessId;
 use serde::{Deserialize, Serialize};
 
-use crate::{Error, Participant, Result, __private::create_ledger_entry};
+use crate::{__private::create_ledger_entry, Error, Participant, Result};
 
 pub use currency_4217::{Currency, Money};
 
@@ -26,6 +26,24 @@ pub struct LedgerEntry {
     pub currency: Currency,
     pub kind: EntryType,
     pub tag: String,
+    /// Timestamp (as milliseconds since unix-epoch), when the debt is due
+    #[serde(default)]
+    pub due_date: Option<u64>,
+    /// External reference (e.g. an invoice number)
+    ///
+    /// Settlements and cancellations are matched against debts with the same reference.
+    #[serde(default)]
+    pub reference: Option<String>,
+    /// Payment terms in days (e.g. `30` for "net 30")
+    ///
+    /// If no explicit due date is given, the debt is due this many days after it was commited.
+    #[serde(default)]
+    pub payment_terms: Option<u32>,
+    /// Line of the original entry (in the same ledger), that is reversed by this entry
+    ///
+    /// This is only set for entries of type [`EntryType::REVERSAL`].
+    #[serde(default)]
+    pub reverses: Option<u64>,
 }
 
 impl fmt::Display for LedgerEntry {
@@ -40,7 +58,11 @@ impl fmt::Display for LedgerEntry {
             self.tax_milli,
             self.currency,
             self.tag
-        )
+        )?;
+        if let Some(reference) = &self.reference {
+            write!(f, ", ref={reference}")?;
+        }
+        Ok(())
     }
 }
 
@@ -74,6 +96,8 @@ pub enum EntryType {
     SETTLE = 1,
     /// Cancels / removes an existing debt
     CANCEL = 2,
+    /// Reverses the effect of an earlier ledger line (e.g. to correct a wrong entry)
+    REVERSAL = 3,
 }
 
 impl EntryType {
@@ -96,6 +120,7 @@ impl TryFrom<u32> for EntryType {
             0 => Ok(EntryType::CREATE),
             1 => Ok(EntryType::SETTLE),
             2 => Ok(EntryType::CANCEL),
+            3 => Ok(EntryType::REVERSAL),
             _ => Err(EntryTypeErr),
         }
     }
@@ -107,6 +132,7 @@ impl fmt::Display for EntryType {
             EntryType::CREATE => f.write_str("CREATE"),
             EntryType::SETTLE => f.write_str("SETTLE"),
             EntryType::CANCEL => f.write_str("CANCEL"),
+            EntryType::REVERSAL => f.write_str("REVERSAL"),
         }
     }
 }
@@ -118,6 +144,10 @@ pub struct EntryBuilder<C, D> {
     tax: Option<Money>,
     kind: EntryType,
     tag: Option<String>,
+    due_date: Option<u64>,
+    reference: Option<String>,
+    payment_terms: Option<u32>,
+    reverses: Option<u64>,
 }
 
 impl<C, D> EntryBuilder<C, D>
@@ -127,47 +157,56 @@ where
 {
     pub fn with_amount(self, money: Money) -> Self {
         Self {
-            creditor: self.creditor,
-            debitor: self.debitor,
             amount: Some(money),
-            tax: self.tax,
-            kind: self.kind,
-            tag: self.tag,
+            ..self
         }
     }
 
     pub fn with_tax(self, tax: Money) -> Self {
         Self {
-            creditor: self.creditor,
-            debitor: self.debitor,
-            amount: self.amount,
             tax: Some(tax),
-            kind: self.kind,
-            tag: self.tag,
+            ..self
         }
     }
 
     pub fn with_tax_opt(self, maybe_tax: Option<Money>) -> Self {
         Self {
-            creditor: self.creditor,
-            debitor: self.debitor,
-            amount: self.amount,
             tax: maybe_tax,
-            kind: self.kind,
-            tag: self.tag,
+            ..self
         }
     }
 
     pub fn with_tag(self, tag: impl AsRef<str>) -> Self {
         Self {
-            creditor: self.creditor,
-            debitor: self.debitor,
-            amount: self.amount,
-            tax: self.tax,
-            kind: self.kind,
             tag: Some(tag.as_ref().to_string()),
+            ..self
+        }
+    }
+
+    /// Sets the due date (as milliseconds since unix-epoch)
+    pub fn with_due_date(self, due_date: u64) -> Self {
+        Self {
+            due_date: Some(due_date),
+            ..self
+        }
+    }
+
+    /// Sets an external reference (e.g. the invoice number)
+    pub fn with_reference(self, reference: impl AsRef<str>) -> Self {
+        Self {
+            reference: Some(reference.as_ref().to_string()),
+            ..self
+        }
+    }
+
+    /// Sets the payment terms in days, after which the debt is due
+    pub fn with_payment_terms(self, days: u32) -> Self {
+        Self {
+            payment_terms: Some(days),
+            ..self
         }
     }
+
     pub fn build(self) -> Result<LedgerEntry> {
         let creditor = C::get_participant(self.creditor)?;
         let debitor = D::get_participant(self.debitor)?;
@@ -192,6 +231,10 @@ where
             currency: amount.currency,
             kind: self.kind,
             tag,
+            due_date: self.due_date,
+            reference: self.reference,
+            payment_terms: self.payment_terms,
+            reverses: self.reverses,
         };
         Ok(ledger_entry)
     }
@@ -214,6 +257,10 @@ where
         tax: None,
         kind: EntryType::CREATE,
         tag: None,
+        due_date: None,
+        reference: None,
+        payment_terms: None,
+        reverses: None,
     }
 }
 
@@ -229,6 +276,10 @@ where
         tax: None,
         kind: EntryType::SETTLE,
         tag: None,
+        due_date: None,
+        reference: None,
+        payment_terms: None,
+        reverses: None,
     }
 }
 
@@ -244,6 +295,33 @@ where
         tax: None,
         kind: EntryType::CANCEL,
         tag: None,
+        due_date: None,
+        reference: None,
+        payment_terms: None,
+        reverses: None,
+    }
+}
+
+/// Reverses the ledger line `line` between the two participants
+///
+/// The participants, amount and currency must match the original entry, and each line can only be reversed once.
+/// Lines of other contracts cannot be reversed.
+pub fn reversal<C, D>(from: D, to: C, line: u64) -> EntryBuilder<C, D>
+where
+    C: Participant,
+    D: Participant,
+{
+    EntryBuilder {
+        creditor: to,
+        debitor: from,
+        amount: None,
+        tax: None,
+        kind: EntryType::REVERSAL,
+        tag: None,
+        due_date: None,
+        reference: None,
+        payment_terms: None,
+        reverses: Some(line),
     }
 }
 
@@ -333,4 +411,40 @@ mod tests {
         assert_eq!(decoded.tag, "test-transfer");
         Ok(())
     }
+
+    #[test]
+    fn payment_terms() -> Result<()> {
+        let (creditor, debitor) = prepare_participants();
+        let entry = transfer(debitor, creditor)
+            .with_amount("100 €".parse()?)
+            .with_reference("INV-2024-001")
+            .with_due_date(1_700_000_000_000)
+            .with_payment_terms(30)
+            .build()?;
+        let decoded = LedgerEntry::from_bytes(&entry.to_bytes()?)?;
+        assert_eq!(decoded.reference.as_deref(), Some("INV-2024-001"));
+        assert_eq!(decoded.due_date, Some(1_700_000_000_000));
+        assert_eq!(decoded.payment_terms, Some(30));
+
+        // Entries without the new fields can still be decoded
+        let legacy = br#"{"creditor":"00000000-0000-0000-0000-000000000000","debitor":"00000000-0000-0000-0000-000000000000","amount_milli":1000,"tax_milli":0,"currency":"EUR","kind":"CREATE","tag":""}"#;
+        let decoded = LedgerEntry::from_bytes(legacy)?;
+        assert!(decoded.reference.is_none() && decoded.due_date.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn reversal_entry() -> Result<()> {
+        let (creditor, debitor) = prepare_participants();
+        let entry = reversal(debitor, creditor, 3)
+            .with_amount("100 €".parse()?)
+            .build()?;
+        assert_eq!(entry.kind, EntryType::REVERSAL);
+        assert_eq!(entry.reverses, Some(3));
+        assert_eq!(
+            EntryType::from_be_bytes(&entry.kind.to_be_bytes()),
+            Some(EntryType::REVERSAL)
+        );
+        Ok(())
+    }
 }
@@ -382,12 +382,58 @@ impl From<Vec<Message>> for Events {
     }
 }
 
+/// Return value of an action, that is delivered to the caller together with the events of the action
+///
+/// Actions, that return a plain serializable value (or a `Result` of it), are wrapped into an output by the `#[action]` macro.
+/// An explicit output is only required to return a value together with events - e.g. an order number, that was generated by the action:
+/// ```ignore
+/// #[action]
+/// fn place_order(&mut self, item: String) -> Result<Output<u64>> {
+///     let order_no = self.next_order(item)?;
+///     let msg = message("orders").with_content(&order_no)?;
+///     Ok(Output::new(order_no).with_events(msg))
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Output<T> {
+    pub value: T,
+    pub events: Events,
+}
+
+impl<T: Serialize> Output<T> {
+    /// Creates a new output without any events
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            events: Events::default(),
+        }
+    }
+
+    /// Adds events (like a [`ContractCall`] or [`Message`]) to the output
+    pub fn with_events(mut self, events: impl Into<Events>) -> Self {
+        let events = events.into();
+        self.events.contracts.extend(events.contracts);
+        self.events.local.extend(events.local);
+        self
+    }
+}
+
 /// Trait that indicates that a return type can be used as an output of an action function.
 ///
 /// Note: This trait converts `()`, `ActionOutput`, `Result<(), E>` and `Result<ActionOutput, E>` into [`Events`].
 /// The implementation of `ActionOutput` also checks, if the writer actually has access to a sink.
 pub trait ActionOutput: Sealed {
     fn convert_out_events(self) -> crate::Result<Events>;
+
+    /// Splits the output into its events and the json-encoded return value
+    ///
+    /// Only an [`Output`] has a return value.
+    fn split_output(self) -> crate::Result<(Events, Option<Vec<u8>>)>
+    where
+        Self: Sized,
+    {
+        Ok((self.convert_out_events()?, None))
+    }
 }
 
 mod private {
@@ -501,6 +547,41 @@ where
     }
 }
 
+impl<T: Serialize> Sealed for Output<T> {}
+impl<T: Serialize> ActionOutput for Output<T> {
+    fn convert_out_events(self) -> crate::Result<Events> {
+        Ok(self.events)
+    }
+
+    fn split_output(self) -> crate::Result<(Events, Option<Vec<u8>>)> {
+        let value = serde_json::to_vec(&self.value)
+            .map_err(|e| crate::Error::msg(format!("failed to encode action output: {e}")))?;
+        Ok((self.events, Some(value)))
+    }
+}
+
+impl<T, E> Sealed for Result<Output<T>, E>
+where
+    T: Serialize,
+    E: Display + Debug + Send + Sync + 'static,
+{
+}
+impl<T, E> ActionOutput for Result<Output<T>, E>
+where
+    T: Serialize,
+    E: Display + Debug + Send + Sync + 'static,
+{
+    fn convert_out_events(self) -> crate::Result<Events> {
+        let inner = self.map_err(|e| crate::Error::msg(e))?;
+        inner.convert_out_events()
+    }
+
+    fn split_output(self) -> crate::Result<(Events, Option<Vec<u8>>)> {
+        let inner = self.map_err(|e| crate::Error::msg(e))?;
+        inner.split_output()
+    }
+}
+
 /// An event Sink for a smart-contract
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sink {
@@ -613,3 +694,43 @@ impl TopicDto {
         serde_json::from_slice(bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call() -> ContractCall {
+        ContractCall {
+            contract_id: ContractId::generate(),
+            action: CallAction::by_method("set_switch", serde_json::json!({ "switch": true })),
+            writer: BorderlessId::generate(),
+        }
+    }
+
+    #[test]
+    fn split_output_value() -> crate::Result<()> {
+        let output = Output::new(42u64).with_events(call()).with_events(call());
+        let (events, value) = output.split_output()?;
+        assert_eq!(events.contracts.len(), 2);
+        assert_eq!(value.as_deref(), Some(&b"42"[..]));
+
+        let result: Result<Output<&str>, String> = Ok(Output::new("order-1"));
+        let (events, value) = result.split_output()?;
+        assert!(events.is_empty());
+        assert_eq!(value.as_deref(), Some(&b"\"order-1\""[..]));
+
+        let result: Result<Output<&str>, String> = Err("failed".to_string());
+        assert!(result.split_output().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn split_output_without_value() -> crate::Result<()> {
+        let (events, value) = call().split_output()?;
+        assert_eq!(events.contracts.len(), 1);
+        assert!(value.is_none());
+        let (events, value) = ().split_output()?;
+        assert!(events.is_empty() && value.is_none());
+        Ok(())
+    }
+}
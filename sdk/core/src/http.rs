@@ -2,7 +2,8 @@
 
 use std::str::FromStr;
 
-use borderless_id_types::{AgentId, TxIdentifier};
+use borderless_hash::Hash256;
+use borderless_id_types::{AgentId, BorderlessId, TxIdentifier};
 use http::header::CONTENT_TYPE;
 use queries::Pagination;
 use serde::de::DeserializeOwned;
@@ -273,6 +274,15 @@ pub struct TxAction {
     /// Serializable action object
     pub action: CallAction,
     pub commited: u64,
+    /// Writer of the transaction ( not available for actions, that were recorded before the writer was tracked )
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub writer: Option<BorderlessId>,
+    /// State root of the contract after the action ( not available for actions, that were recorded before the state root was tracked )
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_root: Option<Hash256>,
+    /// Return value of the action ( not available for actions without a return value )
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
 }
 
 /// Json description of a contract
@@ -1,11 +1,16 @@
-pub use crate::__private::print;
+pub use crate::__private::{print, print_fields};
 pub use borderless_abi::LogLevel as Level;
+use std::collections::BTreeMap;
 
 /// Internal type to represent the log-level
 ///
 /// As the abi only allows integer types (and integers would look bad in e.g. json),
 /// we wrap the type into this representation.
-#[derive(serde::Serialize, serde::Deserialize)]
+///
+/// The levels are ordered by their severity, so `LogLevel::Trace < LogLevel::Error`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
 pub enum LogLevel {
     Trace,
     Debug,
@@ -14,7 +19,22 @@ pub enum LogLevel {
     Error,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+impl std::str::FromStr for LogLevel {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trace" => Ok(LogLevel::Trace),
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            _ => Err(crate::new_error!("invalid log level '{s}'")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct LogLine {
     /// Timestamp
     ///
@@ -26,6 +46,11 @@ pub struct LogLine {
 
     /// Log-Message
     pub msg: String,
+
+    /// Structured key-value fields of the log line
+    ///
+    /// Values are stored in their formatted representation.
+    pub fields: BTreeMap<String, String>,
 }
 
 impl LogLine {
@@ -43,12 +68,58 @@ impl LogLine {
             timestamp,
             level,
             msg,
+            fields: BTreeMap::new(),
         }
     }
+
+    /// Attaches structured fields to the log line
+    pub fn with_fields(mut self, fields: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.fields.extend(fields);
+        self
+    }
 }
 
+/// Logs a message with the given level
+///
+/// Structured fields can be prepended to the message and are separated from it by a semicolon:
+/// ```
+/// # use borderless::info;
+/// # let (id, amount) = (42, Some(5));
+/// info!(order_id = %id, amount = ?amount; "bid placed");
+/// ```
+/// Similar to `tracing`, `%` formats the value with its `Display` and `?` with its `Debug` implementation.
+/// Values without a sigil are formatted with `Display`.
 #[macro_export]
 macro_rules! log {
+    // Collect all tokens in front of the semicolon
+    (@split $lvl:expr; [$($kv:tt)*] ; $($arg:tt)+) => {
+        {
+            let mut fields: ::std::vec::Vec<(::std::string::String, ::std::string::String)> = ::std::vec::Vec::new();
+            $crate::log!(@kv fields; $($kv)*);
+            let buf = ::std::format!($($arg)+);
+            $crate::log::print_fields($lvl, buf, &fields);
+        }
+    };
+    (@split $lvl:expr; [$($kv:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::log!(@split $lvl; [$($kv)* $next] $($rest)*)
+    };
+    // Parse the key-value pairs
+    (@kv $fields:ident; $key:ident = % $val:expr $(, $($rest:tt)*)?) => {
+        $fields.push((::std::stringify!($key).into(), ::std::format!("{}", $val)));
+        $( $crate::log!(@kv $fields; $($rest)*); )?
+    };
+    (@kv $fields:ident; $key:ident = ? $val:expr $(, $($rest:tt)*)?) => {
+        $fields.push((::std::stringify!($key).into(), ::std::format!("{:?}", $val)));
+        $( $crate::log!(@kv $fields; $($rest)*); )?
+    };
+    (@kv $fields:ident; $key:ident = $val:expr $(, $($rest:tt)*)?) => {
+        $fields.push((::std::stringify!($key).into(), ::std::format!("{}", $val)));
+        $( $crate::log!(@kv $fields; $($rest)*); )?
+    };
+    (@kv $fields:ident;) => {};
+    ($lvl:expr, $key:ident = $($rest:tt)+) => {
+        $crate::log!(@split $lvl; [$key =] $($rest)+)
+    };
     ($lvl:expr, $($arg:tt)+) => {
         {
             let buf = ::std::format!($($arg)+);
@@ -101,3 +172,38 @@ macro_rules! trace {
         $crate::log!($crate::log::Level::Trace, $($arg)+)
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Amount(u32);
+
+    #[test]
+    fn log_macros_accept_fields() {
+        let id = 42;
+        let amount = Amount(5);
+        crate::info!("plain message {id}");
+        crate::info!(order_id = %id, amount = ?amount; "bid placed");
+        crate::warn!(order_id = id; "bid placed for {:?}", amount);
+        crate::error!(order_id = %id,; "trailing comma");
+        assert_eq!(amount.0, 5);
+    }
+
+    #[test]
+    fn parse_log_level() {
+        assert_eq!("WARNING".parse::<LogLevel>().unwrap(), LogLevel::Warn);
+        assert!("loud".parse::<LogLevel>().is_err());
+        assert!(LogLevel::Trace < LogLevel::Error);
+    }
+
+    #[test]
+    fn line_fields_to_json() {
+        let line = LogLine::new(1, 2, "msg".to_string())
+            .with_fields([("order_id".to_string(), "42".to_string())]);
+        let json = serde_json::to_value(&line).unwrap();
+        assert_eq!(json["fields"]["order_id"], "42");
+        assert_eq!(json["level"], "Info");
+    }
+}
@@ -121,6 +121,18 @@ pub fn print(level: abi::LogLevel, msg: impl AsRef<str>) {
     }
 }
 
+pub fn print_fields(level: abi::LogLevel, msg: impl AsRef<str>, fields: &[(String, String)]) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        env::on_chain::print_fields(level, msg, fields)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        env::off_chain::print_fields(level, msg, fields)
+    }
+}
+
 pub fn read_register(register_id: u64) -> Option<Vec<u8>> {
     #[cfg(target_arch = "wasm32")]
     {
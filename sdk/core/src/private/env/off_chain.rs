@@ -32,6 +32,14 @@ pub fn print(level: LogLevel, msg: impl AsRef<str>) {
     println!("[{:?}] {}", level, msg.as_ref())
 }
 
+pub fn print_fields(level: LogLevel, msg: impl AsRef<str>, fields: &[(String, String)]) {
+    let mut line = msg.as_ref().to_string();
+    for (key, value) in fields {
+        line.push_str(&format!(" {key}={value}"));
+    }
+    print(level, line)
+}
+
 pub fn storage_remove(base_key: u64, sub_key: u64) {
     let key = calc_storage_key(base_key, sub_key);
     DATABASE.with(|db| {
@@ -203,6 +211,10 @@ mod tests {
             currency: Currency::EUR,
             kind: EntryType::CREATE,
             tag: "some-tag".to_string(),
+            due_date: None,
+            reference: None,
+            payment_terms: None,
+            reverses: None,
         };
         let key = entry.creditor.merge_compact(&entry.debitor);
         create_ledger_entry(entry.clone())?;
@@ -16,6 +16,20 @@ pub fn print(level: abi::LogLevel, msg: impl AsRef<str>) {
     }
 }
 
+pub fn print_fields(level: abi::LogLevel, msg: impl AsRef<str>, fields: &[(String, String)]) {
+    // NOTE: Serializing a list of strings cannot fail
+    let fields = postcard::to_allocvec(fields).unwrap_or_default();
+    unsafe {
+        abi::print_fields(
+            msg.as_ref().as_ptr() as _,
+            msg.as_ref().len() as _,
+            fields.as_ptr() as _,
+            fields.len() as _,
+            level as u32,
+        );
+    }
+}
+
 pub fn storage_remove(base_key: u64, sub_key: u64) {
     unsafe {
         abi::storage_remove(base_key, sub_key);
@@ -126,6 +140,16 @@ pub fn create_ledger_entry(entry: LedgerEntry) -> crate::Result<()> {
             3 => Err(crate::Error::msg(
                 "creditor and debitor not in participants",
             )),
+            4 => Err(crate::Error::msg("cannot reverse unknown line")),
+            5 => Err(crate::Error::msg("cannot reverse line of another contract")),
+            6 => Err(crate::Error::msg("line is already reversed")),
+            7 => Err(crate::Error::msg(
+                "reversal does not match the original entry",
+            )),
+            8 => Err(crate::Error::msg(
+                "reversal must reference the original line",
+            )),
+            9 => Err(crate::Error::msg("only reversals can reference a line")),
             _ => Err(crate::Error::msg("failed to create ledger entry")),
         }
     }
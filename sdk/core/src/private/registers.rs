@@ -29,6 +29,9 @@ pub const REGISTER_TX_CTX: u64 = 4;
 /// Register used to feed the block context (block-id + block-timestamp) into the contract
 pub const REGISTER_BLOCK_CTX: u64 = 5;
 
+/// Register used to return the (json-encoded) return value of an action back to the caller
+pub const REGISTER_OUTPUT_VALUE: u64 = 6;
+
 // --- State and action http requests
 /// Register to feed http requests into the contract
 pub const REGISTER_INPUT_HTTP_PATH: u64 = 1024;
@@ -85,6 +85,11 @@ pub const BASE_KEY_LOGS: u64 = 2;
 /// These work similar to the logs by using a ring-buffer that is stored in sub-keys.
 pub const BASE_KEY_METRICS: u64 = 3;
 
+/// Base-Key for the configuration of the state-history of a contract
+///
+/// The history itself is not stored in the contract key-space (see the runtime for details).
+pub const BASE_KEY_STATE_HISTORY: u64 = 4;
+
 /// Reserved Base-Key - indicating the maximum possible system-key
 ///
 /// Everything between `0` and `BASE_KEY_RESERVED` can be used to store special
@@ -117,7 +122,7 @@ pub const META_SUB_KEY_META: u64 = 5;
 
 /// Sub-Key to store the initial state of the contract
 ///
-/// Expected data-model: `serde_json::Value`
+/// Expected data-model: `String` (containing the json-encoded `serde_json::Value`)
 pub const META_SUB_KEY_INIT_STATE: u64 = 6;
 
 /// Sub-Key to store the timestamp, when the contract was revoked.
@@ -91,15 +91,65 @@ impl ActionFn {
         }
     }
 
+    /// Generates a typed client function, that builds a call of the action for another contract
+    ///
+    /// An action like `fn set_switch(&mut self, switch: bool)` would generate:
+    /// ```no_compile
+    /// pub fn set_switch(target: impl CallMethod, switch: bool) -> Result<ContractCall> {
+    ///     let args = __derived::__SetSwitchArgs { switch };
+    ///     target.call_method("set_switch").with_args(args)?.build()
+    /// }
+    /// ```
+    pub fn gen_client_fn(&self) -> TokenStream2 {
+        let fn_ident = &self.ident;
+        let args_ident = self.args_ident();
+        let method_name = self.method_name();
+        let fields: Vec<_> = self.args.iter().map(|a| a.0.clone()).collect();
+        let types = self.args.iter().map(|a| a.1.clone());
+        // The target must not shadow one of the arguments
+        let target = if fields.iter().any(|f| f == "target") {
+            format_ident!("__target")
+        } else {
+            format_ident!("target")
+        };
+        let doc = format!(" Builds a call of the action `{method_name}` of the `target` contract");
+        quote! {
+            #[doc = #doc]
+            #[allow(private_interfaces)]
+            pub fn #fn_ident(
+                #target: impl ::borderless::CallMethod,
+                #( #fields: #types ),*
+            ) -> ::borderless::Result<::borderless::events::ContractCall> {
+                let args = super::__derived::#args_ident { #( #fields ),* };
+                ::borderless::CallMethod::call_method(&#target, #method_name)
+                    .with_args(args)?
+                    .build()
+            }
+        }
+    }
+
     /// Generates the parsing of the function arguments + calling of the associated state function.
     ///
     /// References 'action' and 'state' in generated tokens
     pub fn gen_call_tokens(&self, state_ident: &Ident) -> TokenStream2 {
         let args_ident = self.args_ident();
         let fn_ident = &self.ident;
-        let return_type = match &self.output {
-            ReturnType::Default => quote! { () },
-            ReturnType::Type(_, ty) => quote! { #ty },
+        let split_output = match &self.output {
+            ReturnType::Default => quote! {
+                <() as ::borderless::events::ActionOutput>::split_output(result)
+            },
+            ReturnType::Type(_, ty) => match return_kind(ty) {
+                ReturnKind::Events => quote! {
+                    <#ty as ::borderless::events::ActionOutput>::split_output(result)
+                },
+                // Plain values are returned to the caller as output without events
+                ReturnKind::Value => quote! {
+                    ::borderless::events::ActionOutput::split_output(::borderless::events::Output::new(result))
+                },
+                ReturnKind::ResultValue => quote! {
+                    ::borderless::events::ActionOutput::split_output(result.map(::borderless::events::Output::new))
+                },
+            },
         };
         let mut_state = if self.mut_self {
             quote! { &mut state }
@@ -111,7 +161,7 @@ impl ActionFn {
             quote! {
                 #access_check
                 let result = #state_ident::#fn_ident(#mut_state);
-                <#return_type as ::borderless::events::ActionOutput>::convert_out_events(result)
+                #split_output
             }
         } else {
             let arg_idents = self.args.iter().map(|a| a.0.clone());
@@ -119,7 +169,7 @@ impl ActionFn {
                 #access_check
                 let args: __derived::#args_ident = ::borderless::serialize::from_value(action.params)?;
                 let result = #state_ident::#fn_ident(#mut_state, #(args.#arg_idents),*);
-                <#return_type as ::borderless::events::ActionOutput>::convert_out_events(result)
+                #split_output
             }
         }
     }
@@ -361,6 +411,66 @@ fn get_actions_from_impl(state_ident: &Ident, impl_block: &ItemImpl) -> Result<V
     }
 }
 
+/// How the return value of an action is converted into its events and output
+enum ReturnKind {
+    /// Types, that implement `ActionOutput` (like `()`, `Events`, `Message` or `Output<T>`)
+    Events,
+    /// Any other serializable value
+    Value,
+    /// A `Result` of any other serializable value
+    ResultValue,
+}
+
+/// Determines the [`ReturnKind`] of an action by the name of its return type
+///
+/// Since the macro only sees the tokens, the event types are recognized by the last segment of their path.
+fn return_kind(ty: &Type) -> ReturnKind {
+    /// Returns the last path segment of a type
+    fn last_segment(ty: &Type) -> Option<&syn::PathSegment> {
+        match ty {
+            Type::Path(p) if p.qself.is_none() => p.path.segments.last(),
+            _ => None,
+        }
+    }
+    /// Returns the first generic type argument of a path segment
+    fn first_arg(segment: &syn::PathSegment) -> Option<&Type> {
+        match &segment.arguments {
+            syn::PathArguments::AngleBracketed(args) => args.args.iter().find_map(|a| match a {
+                syn::GenericArgument::Type(t) => Some(t),
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+    fn is_event_type(ty: &Type) -> bool {
+        if matches!(ty, Type::Tuple(t) if t.elems.is_empty()) {
+            return true;
+        }
+        let segment = match last_segment(ty) {
+            Some(s) => s,
+            None => return false,
+        };
+        match segment.ident.to_string().as_str() {
+            "Events" | "ContractCall" | "Message" | "Output" => true,
+            "Vec" => first_arg(segment)
+                .and_then(last_segment)
+                .is_some_and(|s| s.ident == "ContractCall" || s.ident == "Message"),
+            _ => false,
+        }
+    }
+
+    if is_event_type(ty) {
+        return ReturnKind::Events;
+    }
+    match last_segment(ty) {
+        Some(segment) if segment.ident == "Result" => match first_arg(segment) {
+            Some(inner) if is_event_type(inner) => ReturnKind::Events,
+            _ => ReturnKind::ResultValue,
+        },
+        _ => ReturnKind::Value,
+    }
+}
+
 /// Calculate the method-id based on the state and action name.
 pub fn calc_method_id(state_ident: &Ident, action_ident: &Ident, rename: Option<String>) -> u32 {
     let action_name = rename.unwrap_or_else(|| action_ident.to_string());
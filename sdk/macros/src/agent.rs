@@ -120,11 +120,14 @@ pub fn parse_module_content(
             let s = action.pretty_print()?;
             let mut state = #as_state::load()?;
             #match_and_call_action
-            let events = _match_result?;
+            let (events, value) = _match_result?;
             if !events.is_empty() {
                 let bytes = events.to_bytes()?;
                 write_register(REGISTER_OUTPUT, &bytes);
             }
+            if let Some(value) = value {
+                write_register(REGISTER_OUTPUT_VALUE, &value);
+            }
             #as_state::commit(state);
             Ok(())
         }
@@ -212,11 +215,14 @@ pub fn parse_module_content(
                     let action_bytes = action.to_bytes()?;
                     let mut state = #as_state::load()?;
                     #match_and_call_action
-                    let events = _match_result?;
+                    let (events, value) = _match_result?;
                     if !events.is_empty() {
                         let bytes = events.to_bytes()?;
                         write_register(REGISTER_OUTPUT, &bytes);
                     }
+                    if let Some(value) = value {
+                        write_register(REGISTER_OUTPUT_VALUE, &value);
+                    }
                     #as_state::commit(state);
                     write_register(REGISTER_OUTPUT_HTTP_STATUS, 200u16.to_be_bytes());
                     write_register(REGISTER_OUTPUT_HTTP_RESULT, action_bytes);
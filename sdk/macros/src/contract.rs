@@ -1,6 +1,7 @@
 use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
-use syn::{Ident, Item, Result};
+use syn::parse::{Parse, ParseStream};
+use syn::{Ident, Item, LitStr, Result, Token};
 
 use crate::{
     action::{get_actions, match_action, ActionFn},
@@ -13,6 +14,7 @@ pub fn parse_module_content(
     mod_span: Span,
     mod_items: &[Item],
     _mod_ident: &Ident,
+    client_feature: Option<&LitStr>,
 ) -> Result<TokenStream2> {
     let read_input = quote! {
         let input = read_register(REGISTER_INPUT).context("missing input register")?;
@@ -24,6 +26,10 @@ pub fn parse_module_content(
     let call_action: Vec<_> = actions.iter().map(|a| a.gen_call_tokens(&state)).collect();
     let action_names: Vec<_> = actions.iter().map(ActionFn::method_name).collect();
     let action_ids: Vec<_> = actions.iter().map(ActionFn::method_id).collect();
+    let client_fns = actions.iter().map(ActionFn::gen_client_fn);
+    // Without the wasm exports, the dispatch functions (and thereby the actions) are never called
+    let allow_unused =
+        client_feature.map(|feature| quote! { #[cfg_attr(feature = #feature, allow(dead_code))] });
 
     // let _args: __ArgsType = ::borderless::serialize::from_value(action.params.clone())?;
     let check_action: Vec<_> = actions
@@ -107,11 +113,14 @@ pub fn parse_module_content(
             let action = CallAction::from_bytes(&input)?;
             let mut state = #as_state::load()?;
             #match_and_call_action
-            let events = _match_result?;
+            let (events, value) = _match_result?;
             if !events.is_empty() {
                 let bytes = events.to_bytes()?;
                 write_register(REGISTER_OUTPUT, &bytes);
             }
+            if let Some(value) = value {
+                write_register(REGISTER_OUTPUT_VALUE, &value);
+            }
             #as_state::commit(state);
             Ok(())
         }
@@ -173,6 +182,7 @@ pub fn parse_module_content(
     let derived = quote! {
         #[doc(hidden)]
         #[automatically_derived]
+        #allow_unused
         pub(super) mod __derived {
             use super::*;
             use ::borderless::prelude::*;
@@ -188,6 +198,13 @@ pub fn parse_module_content(
             #exec_http
         }
 
+        /// Typed client to call the actions of this contract from other contracts and agents
+        #[automatically_derived]
+        pub mod client {
+            #[allow(unused_imports)]
+            use super::*;
+            #(#client_fns)*
+        }
     };
     /*
     * // DEPRECATED - we do not require the actions enum anymore
@@ -201,10 +218,17 @@ pub fn parse_module_content(
     Ok(derived)
 }
 
-pub fn generate_wasm_exports(mod_ident: &Ident) -> TokenStream2 {
+/// Generates the exported functions of the wasm module
+///
+/// If a client feature is given, the exports are omitted when the crate is compiled with that feature,
+/// so other contracts and agents can depend on the crate to use its typed client.
+pub fn generate_wasm_exports(mod_ident: &Ident, client_feature: Option<&LitStr>) -> TokenStream2 {
     let derived = quote! { #mod_ident::__derived };
+    let cfg_exports = client_feature.map(|feature| quote! { #[cfg(not(feature = #feature))] });
 
     quote! {
+    #cfg_exports
+    const _: () = {
     #[no_mangle]
     #[automatically_derived]
     pub extern "C" fn process_transaction() {
@@ -282,5 +306,33 @@ pub fn generate_wasm_exports(mod_ident: &Ident) -> TokenStream2 {
             ::borderless::error!("get-symbols failed: {e:?}");
         }
     }
+    };
+    }
+}
+
+#[derive(Default)]
+pub struct ContractArgs {
+    pub client_feature: Option<LitStr>, // Feature, that omits the wasm exports
+}
+
+impl Parse for ContractArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut args = ContractArgs::default();
+
+        while !input.is_empty() {
+            // If we find the 'client_feature' argument
+            if input.peek(Ident) && input.peek2(Token![=]) {
+                let ident: Ident = input.parse()?; // Parse the 'client_feature'
+                if ident != "client_feature" {
+                    return Err(input.error("Expected 'client_feature' argument"));
+                }
+                let _eq_token: Token![=] = input.parse()?; // Parse the '=' token
+                args.client_feature = Some(input.parse()?); // Parse the name of the feature
+            } else {
+                break; // If we encounter anything else, stop parsing
+            }
+        }
+
+        Ok(args)
     }
 }
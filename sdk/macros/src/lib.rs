@@ -1,4 +1,5 @@
 use agent::AgentArgs;
+use contract::ContractArgs;
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::{parse_macro_input, Item, ItemMod};
@@ -14,9 +15,25 @@ mod utils;
 // - [ ] Check existence of serde crate
 // - [ ] Check re-naming of borderless crate
 
+/// Turns a module into a contract
+///
+/// Besides the wasm exports, the macro generates a `client` module inside the contract module,
+/// with one function per action, that builds a typed `ContractCall` for another contract:
+/// ```ignore
+/// let call = flipper::client::set_switch(target, true)?;
+/// ```
+/// To use the client from another crate, the contract crate must be a library (`crate-type = ["cdylib", "rlib"]`)
+/// and name a feature, that omits the wasm exports, which would otherwise collide with the exports of the dependent crate:
+/// ```ignore
+/// #[borderless::contract(client_feature = "client")]
+/// pub mod flipper { /* ... */ }
+/// ```
+/// The feature must be declared in the manifest of the contract crate and enabled by the dependent crate.
+/// Contracts, that are not used as a library, don't need the argument or the feature.
 #[proc_macro_attribute]
-pub fn contract(_attrs: TokenStream, input: TokenStream) -> TokenStream {
+pub fn contract(attrs: TokenStream, input: TokenStream) -> TokenStream {
     let module = parse_macro_input!(input as ItemMod);
+    let parsed_attrs = syn::parse_macro_input!(attrs as ContractArgs);
 
     // Check if module has some content
     if module.content.is_none() {
@@ -30,8 +47,12 @@ pub fn contract(_attrs: TokenStream, input: TokenStream) -> TokenStream {
     let (brace, mut items) = module.content.unwrap();
 
     // Generate new tokens based on the module's content
-    let new_tokens = match contract::parse_module_content(brace.span.join(), &items, &module.ident)
-    {
+    let new_tokens = match contract::parse_module_content(
+        brace.span.join(),
+        &items,
+        &module.ident,
+        parsed_attrs.client_feature.as_ref(),
+    ) {
         Ok(tokens) => tokens,
         Err(e) => return e.to_compile_error().into(),
     };
@@ -50,7 +71,8 @@ pub fn contract(_attrs: TokenStream, input: TokenStream) -> TokenStream {
         }
     }
 
-    let wasm_exports = contract::generate_wasm_exports(&module.ident);
+    let wasm_exports =
+        contract::generate_wasm_exports(&module.ident, parsed_attrs.client_feature.as_ref());
 
     // Generate a new module from the content of the original module
     let new_module = ItemMod {